@@ -0,0 +1,300 @@
+use crate::datasets::handlers::{data_stamps, error};
+use crate::datasets::models::DatasetRecord;
+use axum::{
+    body::Bytes,
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use sqlx::{Pool, Sqlite};
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+type ApiResult = (StatusCode, Json<Value>);
+
+pub const KIND_PIVOT: &str = "pivot";
+pub const KIND_CHART: &str = "chart";
+pub const KIND_REPORT: &str = "report";
+pub const KIND_STATS: &str = "stats";
+const KINDS: [&str; 4] = [KIND_PIVOT, KIND_CHART, KIND_REPORT, KIND_STATS];
+
+/// Объём кеша по умолчанию, МБ; `QUERY_CACHE_MB=0` отключает кеш
+const DEFAULT_CAPACITY_MB: usize = 64;
+/// Результат крупнее этой доли объёма не кешируется, чтобы не вытеснять всё остальное
+const MAX_ENTRY_SHARE: usize = 4;
+
+/// Ключ результата: вид запроса, версии данных набора и нормализованная
+/// конфигурация. Хранит и наборы, изменение которых делает результат устаревшим
+pub struct CacheKey {
+    kind: &'static str,
+    hash: String,
+    datasets: Vec<String>,
+}
+
+struct Entry {
+    body: Bytes,
+    datasets: Vec<String>,
+    /// Момент последнего обращения — порядок вытеснения
+    used: u64,
+}
+
+#[derive(Default, Clone, Copy, Serialize)]
+pub struct KindStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// Счётчики кеша для администратора
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheStats {
+    pub enabled: bool,
+    pub capacity_bytes: usize,
+    pub size_bytes: usize,
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+    /// Доля попаданий среди всех обращений, 0–1
+    pub hit_rate: f64,
+    pub evictions: u64,
+    pub invalidations: u64,
+    /// Результаты, не попавшие в кеш из-за размера
+    pub oversized: u64,
+    pub by_kind: BTreeMap<&'static str, KindStats>,
+}
+
+/// LRU по объёму: записи в `HashMap`, порядок обращений — в `BTreeMap`
+struct QueryCache {
+    capacity: usize,
+    size: usize,
+    clock: u64,
+    entries: HashMap<String, Entry>,
+    order: BTreeMap<u64, String>,
+    stats: HashMap<&'static str, KindStats>,
+    evictions: u64,
+    invalidations: u64,
+    oversized: u64,
+}
+
+impl QueryCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            size: 0,
+            clock: 0,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            stats: HashMap::new(),
+            evictions: 0,
+            invalidations: 0,
+            oversized: 0,
+        }
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    fn get(&mut self, kind: &'static str, hash: &str) -> Option<Bytes> {
+        let used = self.tick();
+        let stats = self.stats.entry(kind).or_default();
+        let Some(entry) = self.entries.get_mut(hash) else {
+            stats.misses += 1;
+            return None;
+        };
+        stats.hits += 1;
+        self.order.remove(&entry.used);
+        self.order.insert(used, hash.to_string());
+        entry.used = used;
+        Some(entry.body.clone())
+    }
+
+    fn remove(&mut self, hash: &str) {
+        if let Some(entry) = self.entries.remove(hash) {
+            self.order.remove(&entry.used);
+            self.size -= entry.body.len();
+        }
+    }
+
+    fn insert(&mut self, key: CacheKey, body: Bytes) {
+        if body.len() > self.capacity / MAX_ENTRY_SHARE {
+            self.oversized += 1;
+            return;
+        }
+        self.remove(&key.hash);
+        while self.size + body.len() > self.capacity {
+            let Some((_, oldest)) = self.order.pop_first() else {
+                break;
+            };
+            if let Some(entry) = self.entries.remove(&oldest) {
+                self.size -= entry.body.len();
+                self.evictions += 1;
+            }
+        }
+        let used = self.tick();
+        self.size += body.len();
+        self.order.insert(used, key.hash.clone());
+        self.entries.insert(
+            key.hash,
+            Entry {
+                body,
+                datasets: key.datasets,
+                used,
+            },
+        );
+    }
+
+    fn invalidate(&mut self, dataset_id: &str) {
+        let stale: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.datasets.iter().any(|id| id == dataset_id))
+            .map(|(hash, _)| hash.clone())
+            .collect();
+        self.invalidations += stale.len() as u64;
+        for hash in stale {
+            self.remove(&hash);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.invalidations += self.entries.len() as u64;
+        self.entries.clear();
+        self.order.clear();
+        self.size = 0;
+    }
+
+    fn stats(&self) -> CacheStats {
+        let by_kind: BTreeMap<&'static str, KindStats> = KINDS
+            .iter()
+            .map(|kind| (*kind, self.stats.get(kind).copied().unwrap_or_default()))
+            .collect();
+        let hits = by_kind.values().map(|s| s.hits).sum::<u64>();
+        let misses = by_kind.values().map(|s| s.misses).sum::<u64>();
+        let total = hits + misses;
+        CacheStats {
+            enabled: self.capacity > 0,
+            capacity_bytes: self.capacity,
+            size_bytes: self.size,
+            entries: self.entries.len(),
+            hits,
+            misses,
+            hit_rate: if total == 0 {
+                0.0
+            } else {
+                hits as f64 / total as f64
+            },
+            evictions: self.evictions,
+            invalidations: self.invalidations,
+            oversized: self.oversized,
+            by_kind,
+        }
+    }
+}
+
+static CACHE: OnceLock<Mutex<QueryCache>> = OnceLock::new();
+
+fn capacity() -> usize {
+    env::var("QUERY_CACHE_MB")
+        .ok()
+        .and_then(|value| value.trim().parse::<usize>().ok())
+        .unwrap_or(DEFAULT_CAPACITY_MB)
+        .saturating_mul(1024 * 1024)
+}
+
+fn cache() -> MutexGuard<'static, QueryCache> {
+    CACHE
+        .get_or_init(|| Mutex::new(QueryCache::new(capacity())))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+/// Ключ запроса к набору; `None` — кеш отключён или результат не кешируется.
+/// Конфигурация нормализуется повторной сериализацией разобранной структуры:
+/// поля по умолчанию заполнены, порядок ключей JSON не важен
+pub async fn key(
+    pool: &Pool<Sqlite>,
+    kind: &'static str,
+    record: &DatasetRecord,
+    config: &impl Serialize,
+) -> Result<Option<CacheKey>, ApiResult> {
+    if cache().capacity == 0 {
+        return Ok(None);
+    }
+    let Some(stamps) = data_stamps(pool, record).await? else {
+        return Ok(None);
+    };
+    let config = serde_json::to_value(config)
+        .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let mut hasher = Sha256::new();
+    hasher.update(kind.as_bytes());
+    for (id, stamp) in &stamps {
+        hasher.update(id.as_bytes());
+        hasher.update(b"\0");
+        hasher.update(stamp.as_bytes());
+        hasher.update(b"\0");
+    }
+    hasher.update(config.to_string().as_bytes());
+    let mut datasets: Vec<String> = stamps.into_iter().map(|(id, _)| id).collect();
+    datasets.dedup();
+    Ok(Some(CacheKey {
+        kind,
+        hash: format!("{:x}", hasher.finalize()),
+        datasets,
+    }))
+}
+
+/// Готовый ответ из кеша; обращение учитывается в статистике
+pub fn lookup(key: Option<&CacheKey>) -> Option<Response> {
+    let key = key?;
+    let body = cache().get(key.kind, &key.hash)?;
+    Some(json_response(body, "HIT"))
+}
+
+/// Ответ с результатом запроса; с ключом результат сохраняется в кеш
+pub fn respond(key: Option<CacheKey>, result: &impl Serialize) -> Result<Response, ApiResult> {
+    let body = Bytes::from(
+        serde_json::to_vec(result)
+            .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?,
+    );
+    let Some(key) = key else {
+        return Ok(json_response(body, "BYPASS"));
+    };
+    cache().insert(key, body.clone());
+    Ok(json_response(body, "MISS"))
+}
+
+fn json_response(body: Bytes, status: &'static str) -> Response {
+    let mut response = body.into_response();
+    let headers = response.headers_mut();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    headers.insert("x-cache", HeaderValue::from_static(status));
+    response
+}
+
+/// Сброс результатов, посчитанных по набору или по производным от него
+pub fn invalidate_dataset(dataset_id: &str) {
+    if let Some(cache) = CACHE.get() {
+        cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .invalidate(dataset_id);
+    }
+}
+
+pub fn stats() -> CacheStats {
+    cache().stats()
+}
+
+pub fn clear() {
+    cache().clear();
+}
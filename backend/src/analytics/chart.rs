@@ -0,0 +1,567 @@
+use crate::analytics::columnar::{Column, ColumnarTable};
+use crate::analytics::filter::FilterOperator;
+use crate::analytics::pivot::{Accumulator, Aggregation};
+use crate::datasets::schema::parse_date;
+use anyhow::{anyhow, bail, Result};
+use chrono::{Datelike, Days, Months, NaiveDate};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap};
+
+const DEFAULT_OTHER_LABEL: &str = "Другое";
+
+/// Предел числа точек оси дат после заполнения пропусков
+pub const MAX_TIME_BUCKETS: usize = 10_000;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ChartType {
+    #[default]
+    Bar,
+    Line,
+    Pie,
+}
+
+impl ChartType {
+    /// Имя поля оси X, которое ожидает соответствующий виджет
+    fn x_key(self) -> &'static str {
+        match self {
+            ChartType::Bar => "category",
+            ChartType::Line | ChartType::Pie => "name",
+        }
+    }
+}
+
+/// Шаг группировки оси дат
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeBucket {
+    Day,
+    Week,
+    Month,
+    Quarter,
+    Year,
+}
+
+impl TimeBucket {
+    fn start(self, date: NaiveDate) -> NaiveDate {
+        let first_of = |month: u32| NaiveDate::from_ymd_opt(date.year(), month, 1).unwrap_or(date);
+        match self {
+            TimeBucket::Day => date,
+            TimeBucket::Week => date - Days::new(date.weekday().num_days_from_monday() as u64),
+            TimeBucket::Month => first_of(date.month()),
+            TimeBucket::Quarter => first_of((date.month() - 1) / 3 * 3 + 1),
+            TimeBucket::Year => first_of(1),
+        }
+    }
+
+    fn next(self, start: NaiveDate) -> Option<NaiveDate> {
+        match self {
+            TimeBucket::Day => start.checked_add_days(Days::new(1)),
+            TimeBucket::Week => start.checked_add_days(Days::new(7)),
+            TimeBucket::Month => start.checked_add_months(Months::new(1)),
+            TimeBucket::Quarter => start.checked_add_months(Months::new(3)),
+            TimeBucket::Year => start.checked_add_months(Months::new(12)),
+        }
+    }
+
+    fn label(self, start: NaiveDate) -> String {
+        match self {
+            TimeBucket::Day => start.format("%Y-%m-%d").to_string(),
+            TimeBucket::Week => start.format("%G-W%V").to_string(),
+            TimeBucket::Month => start.format("%Y-%m").to_string(),
+            TimeBucket::Quarter => format!("{}-Q{}", start.year(), (start.month() - 1) / 3 + 1),
+            TimeBucket::Year => start.format("%Y").to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ChartSeries {
+    pub field: String,
+    #[serde(default)]
+    pub agg: Option<Aggregation>,
+}
+
+impl ChartSeries {
+    fn aggregation(&self) -> Aggregation {
+        self.agg.unwrap_or(Aggregation::Sum)
+    }
+
+    /// Ключ серии как в `buildSeriesKey` виджета графика
+    fn key(&self) -> String {
+        let label = match self.agg {
+            None => return self.field.clone(),
+            Some(Aggregation::Sum) => "Сумма",
+            Some(Aggregation::Count) => "Количество",
+            Some(Aggregation::Avg) => "Среднее",
+            Some(Aggregation::Min) => "Минимум",
+            Some(Aggregation::Max) => "Максимум",
+        };
+        format!("{} ({})", self.field, label)
+    }
+}
+
+/// Фильтр-категория графика: совпавшие строки получают подпись `label`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ChartFilter {
+    #[serde(default)]
+    pub label: String,
+    pub column: String,
+    pub operator: FilterOperator,
+    #[serde(default)]
+    pub value: String,
+}
+
+impl ChartFilter {
+    /// Повторяет `evaluateFilter` из `ChartView`: сравнение без учёта регистра,
+    /// числовые операторы графиком не поддерживаются
+    fn matches(&self, value: &str) -> bool {
+        let target = value.to_lowercase();
+        let needle = self.value.to_lowercase();
+        match self.operator {
+            FilterOperator::Contains => target.contains(&needle),
+            FilterOperator::Eq => target == needle,
+            FilterOperator::StartsWith => target.starts_with(&needle),
+            FilterOperator::EndsWith => target.ends_with(&needle),
+            FilterOperator::In => needle
+                .split(',')
+                .map(str::trim)
+                .filter(|part| !part.is_empty())
+                .any(|part| part == target),
+            _ => false,
+        }
+    }
+
+    fn category(&self) -> &str {
+        if self.label.is_empty() {
+            &self.value
+        } else {
+            &self.label
+        }
+    }
+}
+
+/// Запрос данных графика: поля `ChartConfig` виджета плюс шаг дат,
+/// топ-N и заполнение пропусков
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ChartQuery {
+    #[serde(rename = "type")]
+    pub chart_type: ChartType,
+    pub x_axis: String,
+    pub y_axis: Vec<ChartSeries>,
+    pub filters: Vec<ChartFilter>,
+    pub include_others: bool,
+    /// Если задан, ось X разбирается как даты и группируется по интервалам
+    pub bucket: Option<TimeBucket>,
+    pub fill_gaps: bool,
+    pub top_n: Option<usize>,
+    pub other_label: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ChartSeriesInfo {
+    pub key: String,
+    pub field: String,
+    pub agg: Aggregation,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ChartResult {
+    pub x_key: &'static str,
+    pub series: Vec<ChartSeriesInfo>,
+    pub data: Vec<Map<String, Value>>,
+    /// Строки без значения оси X или с нераспознанной датой
+    pub skipped: usize,
+}
+
+/// Точка оси X со своими накопителями по каждой серии
+struct Point {
+    label: String,
+    accumulators: Vec<Accumulator>,
+}
+
+fn column<'a>(table: &'a ColumnarTable, name: &str) -> Result<&'a Column> {
+    table
+        .column(name)
+        .ok_or_else(|| anyhow!("Колонка «{}» отсутствует в наборе данных", name))
+}
+
+/// Куда попадает строка на оси X
+#[derive(Clone, Copy)]
+enum Placement {
+    /// Подпись — значение оси X
+    Axis,
+    /// Подпись — категория фильтра с этим номером
+    Category(usize),
+    /// Строка в график не попадает
+    Dropped,
+}
+
+impl ChartQuery {
+    fn validate(&self) -> Result<()> {
+        if self.x_axis.trim().is_empty() {
+            bail!("Не указана ось X");
+        }
+        if self.y_axis.is_empty() {
+            bail!("Не указано ни одной серии");
+        }
+        if self.bucket.is_some() && self.top_n.is_some() {
+            bail!("Топ-N не применяется к оси дат");
+        }
+        if self.top_n == Some(0) {
+            bail!("topN должен быть больше нуля");
+        }
+        Ok(())
+    }
+
+    /// Размещение каждой строки с учётом фильтров-категорий: условия
+    /// проверяются один раз на значение словаря колонки
+    fn placements(&self, table: &ColumnarTable, filter_columns: &[&Column]) -> Vec<Placement> {
+        let rows = 0..table.row_count;
+        if self.filters.is_empty() {
+            return rows.map(|_| Placement::Axis).collect();
+        }
+        let matched: Vec<_> = self
+            .filters
+            .iter()
+            .zip(filter_columns)
+            .map(|(filter, column)| column.map_text(|value| filter.matches(value)))
+            .collect();
+        rows.map(|row| {
+            match matched.iter().position(|mask| *mask.get(row)) {
+                // на оси дат фильтры только отбирают строки, подпись остаётся датой
+                Some(idx) if self.bucket.is_none() => Placement::Category(idx),
+                Some(_) => Placement::Axis,
+                None if self.include_others => Placement::Axis,
+                None => Placement::Dropped,
+            }
+        })
+        .collect()
+    }
+}
+
+/// Агрегированные данные для виджетов bar/line/pie
+pub fn build_chart(table: &ColumnarTable, query: &ChartQuery) -> Result<ChartResult> {
+    query.validate()?;
+    let x_column = column(table, &query.x_axis)?;
+    let series_columns = query
+        .y_axis
+        .iter()
+        .map(|series| column(table, &series.field))
+        .collect::<Result<Vec<_>>>()?;
+    let filter_columns = query
+        .filters
+        .iter()
+        .map(|filter| column(table, &filter.column))
+        .collect::<Result<Vec<_>>>()?;
+
+    let width = query.y_axis.len();
+    let mut skipped = 0;
+    let mut points: Vec<Point> = Vec::new();
+    let mut by_label: HashMap<String, usize> = HashMap::new();
+    let mut by_axis: HashMap<u64, usize> = HashMap::new();
+    let mut by_category: HashMap<usize, usize> = HashMap::new();
+    let mut by_date: BTreeMap<NaiveDate, Vec<Accumulator>> = BTreeMap::new();
+
+    let placements = query.placements(table, &filter_columns);
+    let blank_axis = x_column.map_text(|x| x.trim().is_empty());
+    let dates = query
+        .bucket
+        .map(|bucket| x_column.map_text(|x| parse_date(x).map(|d| bucket.start(d.date()))));
+
+    for (row, placement) in placements.iter().enumerate() {
+        let blank = match *placement {
+            Placement::Dropped => continue,
+            Placement::Axis => *blank_axis.get(row),
+            Placement::Category(idx) => query.filters[idx].category().trim().is_empty(),
+        };
+        if blank {
+            skipped += 1;
+            continue;
+        }
+
+        let accumulators = match &dates {
+            Some(dates) => {
+                let Some(start) = *dates.get(row) else {
+                    skipped += 1;
+                    continue;
+                };
+                by_date
+                    .entry(start)
+                    .or_insert_with(|| vec![Accumulator::default(); width])
+            }
+            None => {
+                let idx = match *placement {
+                    Placement::Category(idx) => *by_category.entry(idx).or_insert_with(|| {
+                        point_index(
+                            &mut points,
+                            &mut by_label,
+                            query.filters[idx].category(),
+                            width,
+                        )
+                    }),
+                    _ => *by_axis.entry(x_column.key(row)).or_insert_with(|| {
+                        point_index(&mut points, &mut by_label, &x_column.text(row), width)
+                    }),
+                };
+                &mut points[idx].accumulators
+            }
+        };
+        for (acc, column) in accumulators.iter_mut().zip(&series_columns) {
+            acc.push(column.parsed(row));
+        }
+    }
+
+    if let Some(bucket) = query.bucket {
+        points = time_points(bucket, by_date, query.fill_gaps, width)?;
+    } else if let Some(top_n) = query.top_n {
+        let label = query.other_label.as_deref().unwrap_or(DEFAULT_OTHER_LABEL);
+        points = top_points(points, top_n, query.y_axis[0].aggregation(), label);
+    }
+
+    let series: Vec<ChartSeriesInfo> = query
+        .y_axis
+        .iter()
+        .map(|series| ChartSeriesInfo {
+            key: series.key(),
+            field: series.field.clone(),
+            agg: series.aggregation(),
+        })
+        .collect();
+
+    let value = |acc: &Accumulator, agg: Aggregation| -> Value {
+        match agg {
+            Aggregation::Sum | Aggregation::Count => Value::from(acc.result(agg, false)),
+            // у пустого интервала нет среднего и экстремумов — пропуск на графике
+            _ if !acc.has_numbers() => Value::Null,
+            _ => Value::from(acc.result(agg, false)),
+        }
+    };
+
+    let x_key = query.chart_type.x_key();
+    let data = points
+        .iter()
+        .filter_map(|point| {
+            let mut entry = Map::new();
+            entry.insert(x_key.to_string(), Value::String(point.label.clone()));
+            if query.chart_type == ChartType::Pie {
+                let value = value(&point.accumulators[0], series[0].agg);
+                if value.as_f64().unwrap_or(0.0) == 0.0 {
+                    return None;
+                }
+                entry.insert("value".to_string(), value);
+            } else {
+                for (acc, info) in point.accumulators.iter().zip(&series) {
+                    entry.insert(info.key.clone(), value(acc, info.agg));
+                }
+            }
+            Some(entry)
+        })
+        .collect();
+
+    Ok(ChartResult {
+        x_key,
+        series,
+        data,
+        skipped,
+    })
+}
+
+/// Точка по подписи: значение оси и категория фильтра с тем же текстом
+/// попадают в одну точку
+fn point_index(
+    points: &mut Vec<Point>,
+    by_label: &mut HashMap<String, usize>,
+    label: &str,
+    width: usize,
+) -> usize {
+    *by_label.entry(label.to_string()).or_insert_with(|| {
+        points.push(Point {
+            label: label.to_string(),
+            accumulators: vec![Accumulator::default(); width],
+        });
+        points.len() - 1
+    })
+}
+
+/// Точки оси дат по порядку; при `fill_gaps` добавляются пустые интервалы
+fn time_points(
+    bucket: TimeBucket,
+    by_date: BTreeMap<NaiveDate, Vec<Accumulator>>,
+    fill_gaps: bool,
+    width: usize,
+) -> Result<Vec<Point>> {
+    let (Some(&first), Some(&last)) = (by_date.keys().next(), by_date.keys().next_back()) else {
+        return Ok(Vec::new());
+    };
+    if !fill_gaps {
+        return Ok(by_date
+            .into_iter()
+            .map(|(start, accumulators)| Point {
+                label: bucket.label(start),
+                accumulators,
+            })
+            .collect());
+    }
+
+    let mut by_date = by_date;
+    let mut points = Vec::new();
+    let mut current = Some(first);
+    while let Some(start) = current.filter(|start| *start <= last) {
+        if points.len() >= MAX_TIME_BUCKETS {
+            bail!(
+                "Слишком много интервалов (больше {}), выберите более крупный шаг",
+                MAX_TIME_BUCKETS
+            );
+        }
+        points.push(Point {
+            label: bucket.label(start),
+            accumulators: by_date
+                .remove(&start)
+                .unwrap_or_else(|| vec![Accumulator::default(); width]),
+        });
+        current = bucket.next(start);
+    }
+    Ok(points)
+}
+
+/// Первые `top_n` категорий по первой серии, остальные — одной точкой `other`
+fn top_points(mut points: Vec<Point>, top_n: usize, agg: Aggregation, other: &str) -> Vec<Point> {
+    if points.len() <= top_n {
+        return points;
+    }
+    points.sort_by(|a, b| {
+        let left = a.accumulators[0].result(agg, false);
+        let right = b.accumulators[0].result(agg, false);
+        right
+            .partial_cmp(&left)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    let tail = points.split_off(top_n);
+    let mut merged = vec![Accumulator::default(); tail[0].accumulators.len()];
+    for point in &tail {
+        for (acc, part) in merged.iter_mut().zip(&point.accumulators) {
+            acc.merge(part);
+        }
+    }
+    points.push(Point {
+        label: other.to_string(),
+        accumulators: merged,
+    });
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datasets::store::StoredTable;
+    use serde_json::json;
+
+    fn table(rows: &[[&str; 2]]) -> ColumnarTable {
+        ColumnarTable::from_table(StoredTable {
+            columns: vec!["x".into(), "amount".into()],
+            rows: rows
+                .iter()
+                .map(|row| row.iter().map(|v| v.to_string()).collect())
+                .collect(),
+        })
+    }
+
+    fn chart(table: &ColumnarTable, query: Value) -> ChartResult {
+        build_chart(table, &serde_json::from_value(query).unwrap()).unwrap()
+    }
+
+    fn column(result: &ChartResult, key: &str) -> Vec<Value> {
+        result.data.iter().map(|point| point[key].clone()).collect()
+    }
+
+    #[test]
+    fn dates_are_grouped_into_buckets_and_gaps_filled() {
+        let table = table(&[
+            ["2024-01-05", "1"],
+            ["2024-03-02", "4"],
+            ["2024-01-20", "2"],
+            ["не дата", "8"],
+        ]);
+        let query = json!({
+            "type": "line",
+            "xAxis": "x",
+            "yAxis": [{ "field": "amount" }, { "field": "amount", "agg": "avg" }],
+            "bucket": "month"
+        });
+        let result = chart(&table, query.clone());
+        assert_eq!(result.skipped, 1);
+        assert_eq!(
+            column(&result, "name"),
+            [json!("2024-01"), json!("2024-03")]
+        );
+        assert_eq!(column(&result, "amount"), [json!(3.0), json!(4.0)]);
+
+        let mut query = query;
+        query["fillGaps"] = json!(true);
+        let result = chart(&table, query);
+        assert_eq!(
+            column(&result, "name"),
+            [json!("2024-01"), json!("2024-02"), json!("2024-03")]
+        );
+        // у пустого интервала сумма — 0, среднего нет
+        assert_eq!(
+            column(&result, "amount"),
+            [json!(3.0), json!(0.0), json!(4.0)]
+        );
+        assert_eq!(
+            column(&result, "amount (Среднее)"),
+            [json!(1.5), Value::Null, json!(4.0)]
+        );
+
+        let quarters = chart(
+            &table,
+            json!({ "xAxis": "x", "yAxis": [{ "field": "amount" }], "bucket": "quarter" }),
+        );
+        assert_eq!(column(&quarters, "category"), [json!("2024-Q1")]);
+    }
+
+    #[test]
+    fn top_n_folds_the_tail_into_other() {
+        let table = table(&[["a", "5"], ["b", "3"], ["c", "1"], ["d", "2"], ["a", "1"]]);
+        let result = chart(
+            &table,
+            json!({ "xAxis": "x", "yAxis": [{ "field": "amount" }], "topN": 2 }),
+        );
+        assert_eq!(
+            column(&result, "category"),
+            [json!("a"), json!("b"), json!("Другое")]
+        );
+        assert_eq!(
+            column(&result, "amount"),
+            [json!(6.0), json!(3.0), json!(3.0)]
+        );
+
+        let result = chart(
+            &table,
+            json!({
+                "xAxis": "x",
+                "yAxis": [{ "field": "amount" }],
+                "topN": 3,
+                "otherLabel": "Прочее"
+            }),
+        );
+        assert_eq!(
+            column(&result, "category"),
+            [json!("a"), json!("b"), json!("d"), json!("Прочее")]
+        );
+
+        // категорий не больше topN — хвоста нет
+        let result = chart(
+            &table,
+            json!({ "xAxis": "x", "yAxis": [{ "field": "amount" }], "topN": 4 }),
+        );
+        assert_eq!(result.data.len(), 4);
+    }
+}
@@ -0,0 +1,439 @@
+use crate::analytics::filter::{format_number, js_number, Cell};
+use crate::datasets::handlers::{data_stamps, error, load_table};
+use crate::datasets::models::DatasetRecord;
+use crate::datasets::store::StoredTable;
+use axum::{http::StatusCode, Json};
+use serde_json::Value;
+use sqlx::{Pool, Sqlite};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::time::Instant;
+
+type ApiResult = (StatusCode, Json<Value>);
+
+/// Сколько памяти по умолчанию занимают загруженные наборы, МБ;
+/// `DATASET_MEMORY_MB=0` — наборы читаются с диска при каждом запросе
+const DEFAULT_MEMORY_MB: usize = 512;
+
+/// Битовая маска заполненных ячеек: снятый бит — пустая ячейка
+#[derive(Clone, Debug, Default)]
+pub struct Bitmap {
+    words: Vec<u64>,
+}
+
+impl Bitmap {
+    fn new(len: usize) -> Self {
+        Self {
+            words: vec![0; len.div_ceil(64)],
+        }
+    }
+
+    fn set(&mut self, idx: usize) {
+        self.words[idx / 64] |= 1 << (idx % 64);
+    }
+
+    pub fn get(&self, idx: usize) -> bool {
+        self.words
+            .get(idx / 64)
+            .is_some_and(|word| word & (1 << (idx % 64)) != 0)
+    }
+
+    fn bytes(&self) -> usize {
+        self.words.len() * 8
+    }
+}
+
+/// Ячейка, разобранная так, как её читают агрегаты
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Parsed {
+    Empty,
+    /// Только пробелы: ячейка заполнена, но это не число для агрегатов
+    Blank,
+    Number(f64),
+    Text,
+}
+
+impl Parsed {
+    pub fn of(value: &str) -> Self {
+        if value.is_empty() {
+            Parsed::Empty
+        } else if value.trim().is_empty() {
+            Parsed::Blank
+        } else {
+            js_number(value).map_or(Parsed::Text, Parsed::Number)
+        }
+    }
+}
+
+/// Колонка, все непустые ячейки которой — числа, печатающиеся ровно так,
+/// как записаны. Текст ячейки восстанавливается из числа без потерь
+#[derive(Debug)]
+pub struct NumberColumn {
+    values: Vec<f64>,
+    valid: Bitmap,
+}
+
+/// Строковая колонка со словарём: каждое значение хранится один раз,
+/// строки ссылаются на него номером
+#[derive(Debug)]
+pub struct TextColumn {
+    dict: Vec<Box<str>>,
+    parsed: Vec<Parsed>,
+    codes: Vec<u32>,
+    valid: Bitmap,
+}
+
+#[derive(Debug)]
+pub enum ColumnData {
+    Number(NumberColumn),
+    Text(TextColumn),
+}
+
+#[derive(Debug)]
+pub struct Column {
+    pub name: String,
+    pub data: ColumnData,
+}
+
+/// Набор данных в памяти по колонкам
+#[derive(Debug)]
+pub struct ColumnarTable {
+    pub columns: Vec<Column>,
+    pub row_count: usize,
+}
+
+/// Значения функции от ячеек колонки: у словарной колонки — по одному на
+/// элемент словаря, у числовой — на каждую строку
+pub enum Mapped<'a, T> {
+    Rows(Vec<T>),
+    Dict {
+        codes: &'a [u32],
+        valid: &'a Bitmap,
+        values: Vec<T>,
+        empty: T,
+    },
+}
+
+impl<T> Mapped<'_, T> {
+    pub fn get(&self, row: usize) -> &T {
+        match self {
+            Mapped::Rows(values) => &values[row],
+            Mapped::Dict {
+                codes,
+                valid,
+                values,
+                empty,
+            } => {
+                if valid.get(row) {
+                    &values[codes[row] as usize]
+                } else {
+                    empty
+                }
+            }
+        }
+    }
+}
+
+fn number_column(rows: &[Vec<String>], idx: usize) -> Option<NumberColumn> {
+    let mut values = Vec::with_capacity(rows.len());
+    let mut valid = Bitmap::new(rows.len());
+    for (row_idx, row) in rows.iter().enumerate() {
+        let text = row.get(idx).map(String::as_str).unwrap_or("");
+        if text.is_empty() {
+            values.push(0.0);
+            continue;
+        }
+        let number = js_number(text).filter(|n| format_number(*n) == text)?;
+        values.push(number);
+        valid.set(row_idx);
+    }
+    Some(NumberColumn { values, valid })
+}
+
+fn text_column(rows: &[Vec<String>], idx: usize) -> TextColumn {
+    let mut dict: Vec<Box<str>> = Vec::new();
+    let mut lookup: HashMap<&str, u32> = HashMap::new();
+    let mut codes = Vec::with_capacity(rows.len());
+    let mut valid = Bitmap::new(rows.len());
+    for (row_idx, row) in rows.iter().enumerate() {
+        let text = row.get(idx).map(String::as_str).unwrap_or("");
+        if text.is_empty() {
+            codes.push(0);
+            continue;
+        }
+        let code = *lookup.entry(text).or_insert_with(|| {
+            dict.push(text.into());
+            (dict.len() - 1) as u32
+        });
+        codes.push(code);
+        valid.set(row_idx);
+    }
+    let parsed = dict.iter().map(|value| Parsed::of(value)).collect();
+    TextColumn {
+        dict,
+        parsed,
+        codes,
+        valid,
+    }
+}
+
+impl Column {
+    fn encode(name: String, rows: &[Vec<String>], idx: usize) -> Self {
+        let data = match number_column(rows, idx) {
+            Some(numbers) => ColumnData::Number(numbers),
+            None => ColumnData::Text(text_column(rows, idx)),
+        };
+        Self { name, data }
+    }
+
+    fn valid(&self) -> &Bitmap {
+        match &self.data {
+            ColumnData::Number(column) => &column.valid,
+            ColumnData::Text(column) => &column.valid,
+        }
+    }
+
+    pub fn is_empty(&self, row: usize) -> bool {
+        !self.valid().get(row)
+    }
+
+    /// Текст ячейки в том виде, в каком он был загружен
+    pub fn text(&self, row: usize) -> Cow<'_, str> {
+        if self.is_empty(row) {
+            return Cow::Borrowed("");
+        }
+        match &self.data {
+            ColumnData::Number(column) => Cow::Owned(format_number(column.values[row])),
+            ColumnData::Text(column) => Cow::Borrowed(&column.dict[column.codes[row] as usize]),
+        }
+    }
+
+    /// Ячейка для фильтров и сортировки
+    pub fn cell(&self, row: usize) -> Cell<'_> {
+        if self.is_empty(row) {
+            return Cell::Text("");
+        }
+        match &self.data {
+            ColumnData::Number(column) => Cell::Number(column.values[row]),
+            ColumnData::Text(column) => Cell::Text(&column.dict[column.codes[row] as usize]),
+        }
+    }
+
+    pub fn parsed(&self, row: usize) -> Parsed {
+        if self.is_empty(row) {
+            return Parsed::Empty;
+        }
+        match &self.data {
+            ColumnData::Number(column) => Parsed::Number(column.values[row]),
+            ColumnData::Text(column) => column.parsed[column.codes[row] as usize],
+        }
+    }
+
+    /// Ключ группировки: равные ключи — равный текст ячеек
+    pub fn key(&self, row: usize) -> u64 {
+        if self.is_empty(row) {
+            return u64::MAX;
+        }
+        match &self.data {
+            ColumnData::Number(column) => column.values[row].to_bits(),
+            ColumnData::Text(column) => column.codes[row] as u64,
+        }
+    }
+
+    /// Функция от ячейки для всех строк; у словарной колонки она вызывается
+    /// один раз на уникальное значение
+    pub fn map_cells<T>(&self, f: impl Fn(Cell) -> T) -> Mapped<'_, T> {
+        match &self.data {
+            ColumnData::Number(column) => Mapped::Rows(
+                (0..column.values.len())
+                    .map(|row| f(self.cell(row)))
+                    .collect(),
+            ),
+            ColumnData::Text(column) => Mapped::Dict {
+                codes: &column.codes,
+                valid: &column.valid,
+                values: column.dict.iter().map(|v| f(Cell::Text(v))).collect(),
+                empty: f(Cell::Text("")),
+            },
+        }
+    }
+
+    /// То же по тексту ячейки
+    pub fn map_text<T>(&self, f: impl Fn(&str) -> T) -> Mapped<'_, T> {
+        self.map_cells(|cell| match cell {
+            Cell::Text(text) => f(text),
+            other => f(&other.to_text()),
+        })
+    }
+
+    /// Приблизительный объём колонки в памяти
+    fn bytes(&self) -> usize {
+        match &self.data {
+            ColumnData::Number(column) => column.values.len() * 8 + column.valid.bytes(),
+            ColumnData::Text(column) => {
+                column.dict.iter().map(|v| v.len() + 16).sum::<usize>()
+                    + column.parsed.len() * std::mem::size_of::<Parsed>()
+                    + column.codes.len() * 4
+                    + column.valid.bytes()
+            }
+        }
+    }
+}
+
+impl ColumnarTable {
+    /// Перекладка строк по колонкам; исходные строки после этого не нужны
+    pub fn from_table(table: StoredTable) -> Self {
+        let row_count = table.rows.len();
+        let columns = table
+            .columns
+            .into_iter()
+            .enumerate()
+            .map(|(idx, name)| Column::encode(name, &table.rows, idx))
+            .collect();
+        Self { columns, row_count }
+    }
+
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        self.columns.iter().find(|c| c.name == name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.columns.iter().map(|c| c.name.as_str())
+    }
+
+    pub fn bytes(&self) -> usize {
+        self.columns.iter().map(Column::bytes).sum()
+    }
+}
+
+/// Загруженный набор и версии данных, по которым он собран
+struct Loaded {
+    stamps: Vec<(String, String)>,
+    table: Arc<ColumnarTable>,
+    bytes: usize,
+    used: u64,
+}
+
+#[derive(Default)]
+struct Tables {
+    capacity: usize,
+    size: usize,
+    clock: u64,
+    loaded: HashMap<String, Loaded>,
+}
+
+impl Tables {
+    fn get(&mut self, id: &str, stamps: &[(String, String)]) -> Option<Arc<ColumnarTable>> {
+        self.clock += 1;
+        let loaded = self.loaded.get_mut(id).filter(|l| l.stamps == stamps)?;
+        loaded.used = self.clock;
+        Some(loaded.table.clone())
+    }
+
+    fn remove(&mut self, id: &str) {
+        if let Some(loaded) = self.loaded.remove(id) {
+            self.size -= loaded.bytes;
+        }
+    }
+
+    fn insert(&mut self, id: &str, stamps: Vec<(String, String)>, table: Arc<ColumnarTable>) {
+        self.remove(id);
+        let bytes = table.bytes();
+        if bytes > self.capacity {
+            return;
+        }
+        while self.size + bytes > self.capacity {
+            let Some(oldest) = self
+                .loaded
+                .iter()
+                .min_by_key(|(_, loaded)| loaded.used)
+                .map(|(id, _)| id.clone())
+            else {
+                break;
+            };
+            self.remove(&oldest);
+        }
+        self.clock += 1;
+        self.size += bytes;
+        self.loaded.insert(
+            id.to_string(),
+            Loaded {
+                stamps,
+                table,
+                bytes,
+                used: self.clock,
+            },
+        );
+    }
+}
+
+static TABLES: OnceLock<Mutex<Tables>> = OnceLock::new();
+
+fn tables() -> MutexGuard<'static, Tables> {
+    TABLES
+        .get_or_init(|| {
+            let capacity = env::var("DATASET_MEMORY_MB")
+                .ok()
+                .and_then(|value| value.trim().parse::<usize>().ok())
+                .unwrap_or(DEFAULT_MEMORY_MB)
+                .saturating_mul(1024 * 1024);
+            Mutex::new(Tables {
+                capacity,
+                ..Default::default()
+            })
+        })
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+/// Набор в колоночном виде. С диска читается при первом запросе и остаётся
+/// в памяти, пока не изменится или не будет вытеснен более свежими
+pub async fn load(
+    pool: &Pool<Sqlite>,
+    record: &DatasetRecord,
+) -> Result<Arc<ColumnarTable>, ApiResult> {
+    let stamps = data_stamps(pool, record).await?;
+    if let Some(stamps) = &stamps {
+        if let Some(table) = tables().get(&record.id, stamps) {
+            return Ok(table);
+        }
+    }
+
+    let started = Instant::now();
+    let rows = load_table(pool, record).await?;
+    let table = tokio::task::spawn_blocking(move || ColumnarTable::from_table(rows))
+        .await
+        .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let table = Arc::new(table);
+    println!(
+        "🧊 dataset loaded: {:<25} | Rows {:>8} | {:>6} KB | {:>6} ms",
+        record.name,
+        table.row_count,
+        table.bytes() / 1024,
+        started.elapsed().as_millis()
+    );
+    // строки SQL-наборов зависят от запроса и в памяти не держатся
+    if let Some(stamps) = stamps {
+        tables().insert(&record.id, stamps, table.clone());
+    }
+    Ok(table)
+}
+
+/// Выгрузка из памяти набора и собранных из него производных наборов
+pub fn evict_dataset(dataset_id: &str) {
+    let Some(tables) = TABLES.get() else {
+        return;
+    };
+    let mut tables = tables.lock().unwrap_or_else(|e| e.into_inner());
+    let stale: Vec<String> = tables
+        .loaded
+        .iter()
+        .filter(|(_, loaded)| loaded.stamps.iter().any(|(id, _)| id == dataset_id))
+        .map(|(id, _)| id.clone())
+        .collect();
+    for id in stale {
+        tables.remove(&id);
+    }
+}
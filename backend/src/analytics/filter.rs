@@ -0,0 +1,299 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// Операторы фильтров `PivotFilterOperator` из `web/lib/types.ts`
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterOperator {
+    Eq,
+    Neq,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    Contains,
+    NotContains,
+    In,
+    StartsWith,
+    EndsWith,
+}
+
+/// Скаляр в значении фильтра: строка или число, как в JSON конфигурации
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum FilterScalar {
+    Number(f64),
+    Text(String),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum FilterValue {
+    List(Vec<FilterScalar>),
+    Scalar(FilterScalar),
+    Null(()),
+}
+
+impl Default for FilterValue {
+    fn default() -> Self {
+        FilterValue::Scalar(FilterScalar::Text(String::new()))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PivotFilter {
+    pub column: String,
+    pub operator: FilterOperator,
+    #[serde(default)]
+    pub value: FilterValue,
+}
+
+/// Значение, с которым сравнивается фильтр: ячейка исходных данных,
+/// результат агрегации или отсутствующее поле
+#[derive(Clone, Copy, Debug)]
+pub enum Cell<'a> {
+    Text(&'a str),
+    Number(f64),
+    Missing,
+}
+
+impl Cell<'_> {
+    /// Строковое представление по правилам `String(value ?? "")`
+    pub fn to_text(self) -> String {
+        match self {
+            Cell::Text(text) => text.to_string(),
+            Cell::Number(number) => format_number(number),
+            Cell::Missing => String::new(),
+        }
+    }
+
+    /// Число по правилам `Number(value)`; `None` соответствует `NaN`
+    pub fn to_number(self) -> Option<f64> {
+        match self {
+            Cell::Text(text) => js_number(text),
+            Cell::Number(number) => Some(number),
+            Cell::Missing => None,
+        }
+    }
+}
+
+/// Ключ сортировки ячейки. Числа сравниваются как числа и идут раньше строк,
+/// строки — посимвольно. В браузере смешанные колонки сравнивались попарно,
+/// но `sort_by` требует полного порядка, поэтому числа и строки разведены по группам
+#[derive(Clone, Copy, Debug)]
+pub enum SortKey<'a> {
+    Number(f64),
+    Text(&'a str),
+}
+
+impl<'a> Cell<'a> {
+    pub fn sort_key(self) -> SortKey<'a> {
+        match self {
+            Cell::Number(number) => SortKey::Number(number),
+            Cell::Text(text) => match js_number(text) {
+                Some(number) => SortKey::Number(number),
+                None => SortKey::Text(text),
+            },
+            Cell::Missing => SortKey::Text(""),
+        }
+    }
+}
+
+impl SortKey<'_> {
+    pub fn compare(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (SortKey::Number(a), SortKey::Number(b)) => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+            (SortKey::Number(_), SortKey::Text(_)) => Ordering::Less,
+            (SortKey::Text(_), SortKey::Number(_)) => Ordering::Greater,
+            (SortKey::Text(a), SortKey::Text(b)) => a.cmp(b),
+        }
+    }
+}
+
+impl FilterScalar {
+    pub fn to_text(&self) -> String {
+        match self {
+            FilterScalar::Number(number) => format_number(*number),
+            FilterScalar::Text(text) => text.clone(),
+        }
+    }
+
+    pub fn to_number(&self) -> Option<f64> {
+        match self {
+            FilterScalar::Number(number) => Some(*number),
+            FilterScalar::Text(text) => js_number(text),
+        }
+    }
+}
+
+/// Число в том виде, в каком его печатает JavaScript: целые — без дробной части
+pub fn format_number(value: f64) -> String {
+    if value.is_infinite() {
+        return if value > 0.0 { "Infinity" } else { "-Infinity" }.to_string();
+    }
+    if value.fract() == 0.0 && value.abs() < 1e21 {
+        format!("{}", value as i128)
+    } else {
+        value.to_string()
+    }
+}
+
+/// Аналог `Number(string)`: пробелы по краям игнорируются, пустая строка — ноль,
+/// десятичный разделитель только точка
+pub fn js_number(value: &str) -> Option<f64> {
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        return Some(0.0);
+    }
+    let radix = match trimmed.get(..2) {
+        Some("0x") | Some("0X") => Some(16),
+        Some("0o") | Some("0O") => Some(8),
+        Some("0b") | Some("0B") => Some(2),
+        _ => None,
+    };
+    if let Some(radix) = radix {
+        return u64::from_str_radix(&trimmed[2..], radix)
+            .ok()
+            .map(|n| n as f64);
+    }
+    let unsigned = trimmed.strip_prefix(['+', '-']).unwrap_or(trimmed);
+    if unsigned == "Infinity" {
+        let sign = if trimmed.starts_with('-') { -1.0 } else { 1.0 };
+        return Some(sign * f64::INFINITY);
+    }
+    // Rust понимает `inf` и `NaN`, JavaScript — нет
+    if !unsigned.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+        return None;
+    }
+    trimmed.parse::<f64>().ok()
+}
+
+impl PivotFilter {
+    /// Пустое значение фильтра отключает его, как в редакторе виджета
+    fn is_empty(&self) -> bool {
+        match &self.value {
+            FilterValue::Null(()) => true,
+            FilterValue::List(items) => items.is_empty(),
+            FilterValue::Scalar(FilterScalar::Text(text)) => text.is_empty(),
+            FilterValue::Scalar(FilterScalar::Number(_)) => false,
+        }
+    }
+
+    fn first_scalar(&self) -> Option<&FilterScalar> {
+        match &self.value {
+            FilterValue::List(items) => items.first(),
+            FilterValue::Scalar(scalar) => Some(scalar),
+            FilterValue::Null(()) => None,
+        }
+    }
+
+    fn needle(&self) -> String {
+        match &self.value {
+            FilterValue::List(items) => items
+                .iter()
+                .map(FilterScalar::to_text)
+                .collect::<Vec<_>>()
+                .join(","),
+            FilterValue::Scalar(scalar) => scalar.to_text(),
+            FilterValue::Null(()) => String::new(),
+        }
+        .to_lowercase()
+    }
+
+    /// Проверка значения фильтром; повторяет `evaluateFilter` из `web/lib/pivot.ts`
+    pub fn matches(&self, value: Cell) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+
+        match self.operator {
+            FilterOperator::In => {
+                let normalized = value.to_text().to_lowercase();
+                match &self.value {
+                    FilterValue::List(items) => items
+                        .iter()
+                        .any(|item| item.to_text().to_lowercase() == normalized),
+                    _ => self
+                        .first_scalar()
+                        .map(FilterScalar::to_text)
+                        .unwrap_or_default()
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|item| !item.is_empty())
+                        .any(|item| item.to_lowercase() == normalized),
+                }
+            }
+            FilterOperator::Contains | FilterOperator::NotContains => {
+                let found = value.to_text().to_lowercase().contains(&self.needle());
+                (self.operator == FilterOperator::Contains) == found
+            }
+            FilterOperator::StartsWith | FilterOperator::EndsWith => {
+                let needle = self.needle();
+                if needle.is_empty() {
+                    return true;
+                }
+                let haystack = value.to_text().to_lowercase();
+                if self.operator == FilterOperator::StartsWith {
+                    haystack.starts_with(&needle)
+                } else {
+                    haystack.ends_with(&needle)
+                }
+            }
+            FilterOperator::Gt | FilterOperator::Gte | FilterOperator::Lt | FilterOperator::Lte => {
+                let (Some(left), Some(right)) = (
+                    value.to_number(),
+                    self.first_scalar().and_then(FilterScalar::to_number),
+                ) else {
+                    return false;
+                };
+                match self.operator {
+                    FilterOperator::Gt => left > right,
+                    FilterOperator::Gte => left >= right,
+                    FilterOperator::Lt => left < right,
+                    _ => left <= right,
+                }
+            }
+            FilterOperator::Eq | FilterOperator::Neq => {
+                let right = self
+                    .first_scalar()
+                    .map(FilterScalar::to_text)
+                    .unwrap_or_default();
+                (value.to_text() == right) == (self.operator == FilterOperator::Eq)
+            }
+        }
+    }
+}
+
+/// Фильтры по колонкам исходного набора с заранее найденными индексами
+pub struct CompiledFilters<'a> {
+    filters: Vec<(usize, &'a PivotFilter)>,
+}
+
+impl<'a> CompiledFilters<'a> {
+    /// Колонки фильтров должны существовать в наборе данных
+    pub fn compile(filters: &'a [PivotFilter], columns: &[String]) -> Result<Self> {
+        let mut compiled = Vec::with_capacity(filters.len());
+        for filter in filters {
+            let Some(idx) = columns.iter().position(|c| c == &filter.column) else {
+                bail!(
+                    "Колонка фильтра «{}» отсутствует в наборе данных",
+                    filter.column
+                );
+            };
+            compiled.push((idx, filter));
+        }
+        Ok(Self { filters: compiled })
+    }
+
+    pub fn matches(&self, row: &[String]) -> bool {
+        self.filters.iter().all(|(idx, filter)| {
+            let cell = row
+                .get(*idx)
+                .map(|v| Cell::Text(v))
+                .unwrap_or(Cell::Missing);
+            filter.matches(cell)
+        })
+    }
+}
@@ -0,0 +1,176 @@
+use anyhow::{bail, Result};
+use chrono::{NaiveDateTime, Timelike};
+
+const NBSP: char = '\u{a0}';
+
+/// Локаль форматирования значений отчёта; повторяет вывод `toLocaleString`
+/// для поддерживаемых языков
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Locale {
+    #[default]
+    Ru,
+    En,
+    De,
+}
+
+impl Locale {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().replace('_', "-").as_str() {
+            "" | "ru" | "ru-ru" => Ok(Locale::Ru),
+            "en" | "en-us" => Ok(Locale::En),
+            "de" | "de-de" => Ok(Locale::De),
+            other => bail!("Неподдерживаемая локаль: {}", other),
+        }
+    }
+
+    fn decimal(self) -> char {
+        match self {
+            Locale::En => '.',
+            Locale::Ru | Locale::De => ',',
+        }
+    }
+
+    fn group(self) -> char {
+        match self {
+            Locale::Ru => NBSP,
+            Locale::En => ',',
+            Locale::De => '.',
+        }
+    }
+}
+
+/// Округление «половина от нуля», как в `Intl.NumberFormat`, по точной
+/// десятичной записи числа; возвращает цифры без знака
+fn round_decimal(value: f64, digits: usize) -> String {
+    let exact = format!("{:.*}", digits + 20, value.abs());
+    let (int_part, frac_part) = exact.split_once('.').unwrap_or((&exact, ""));
+    let mut kept: Vec<u8> = int_part
+        .bytes()
+        .chain(frac_part.bytes().take(digits))
+        .collect();
+    let round_up = frac_part.as_bytes().get(digits).is_some_and(|&d| d >= b'5');
+
+    if round_up {
+        let mut idx = kept.len();
+        loop {
+            if idx == 0 {
+                kept.insert(0, b'1');
+                break;
+            }
+            idx -= 1;
+            if kept[idx] == b'9' {
+                kept[idx] = b'0';
+            } else {
+                kept[idx] += 1;
+                break;
+            }
+        }
+    }
+
+    let split = kept.len() - digits;
+    let text = String::from_utf8(kept).unwrap_or_default();
+    if digits == 0 {
+        text
+    } else {
+        format!("{}.{}", &text[..split], &text[split..])
+    }
+}
+
+/// Число с разделителями разрядов и заданным числом знаков после запятой
+pub fn format_number(
+    value: f64,
+    min_fraction: usize,
+    max_fraction: usize,
+    locale: Locale,
+) -> String {
+    if !value.is_finite() {
+        return if value.is_nan() {
+            "NaN".to_string()
+        } else if value > 0.0 {
+            "∞".to_string()
+        } else {
+            "-∞".to_string()
+        };
+    }
+
+    let rounded = round_decimal(value, max_fraction);
+    let (int_part, frac_part) = rounded.split_once('.').unwrap_or((&rounded, ""));
+    let frac_part = frac_part.trim_end_matches('0');
+    let frac_part = if frac_part.len() < min_fraction {
+        format!("{:0<width$}", frac_part, width = min_fraction)
+    } else {
+        frac_part.to_string()
+    };
+
+    let mut grouped = String::new();
+    for (idx, digit) in int_part.chars().enumerate() {
+        if idx > 0 && (int_part.len() - idx) % 3 == 0 {
+            grouped.push(locale.group());
+        }
+        grouped.push(digit);
+    }
+
+    let sign = if value < 0.0 { "-" } else { "" };
+    if frac_part.is_empty() {
+        format!("{}{}", sign, grouped)
+    } else {
+        format!("{}{}{}{}", sign, grouped, locale.decimal(), frac_part)
+    }
+}
+
+/// Денежная сумма без копеек с символом валюты в позиции, принятой в локали
+pub fn format_currency(value: f64, currency: &str, locale: Locale) -> String {
+    let amount = format_number(value.abs(), 0, 0, locale);
+    let sign = if value < 0.0 { "-" } else { "" };
+    let code = currency.trim().to_ascii_uppercase();
+    match locale {
+        Locale::Ru => {
+            let symbol = match code.as_str() {
+                "RUB" => "₽",
+                "USD" => "$",
+                "EUR" => "€",
+                other => other,
+            };
+            format!("{}{}{}{}", sign, amount, NBSP, symbol)
+        }
+        Locale::En => match code.as_str() {
+            "USD" => format!("{}${}", sign, amount),
+            "EUR" => format!("{}€{}", sign, amount),
+            other => format!("{}{}{}{}", sign, other, NBSP, amount),
+        },
+        Locale::De => {
+            let symbol = match code.as_str() {
+                "USD" => "$",
+                "EUR" => "€",
+                other => other,
+            };
+            format!("{}{}{}{}", sign, amount, NBSP, symbol)
+        }
+    }
+}
+
+pub fn format_date(value: NaiveDateTime, locale: Locale) -> String {
+    match locale {
+        Locale::Ru => value.format("%d.%m.%Y").to_string(),
+        Locale::En => value.format("%-m/%-d/%Y").to_string(),
+        Locale::De => value.format("%-d.%-m.%Y").to_string(),
+    }
+}
+
+pub fn format_datetime(value: NaiveDateTime, locale: Locale) -> String {
+    let date = format_date(value, locale);
+    match locale {
+        Locale::En => {
+            let (pm, hour) = value.hour12();
+            format!(
+                "{}, {}:{:02}:{:02} {}",
+                date,
+                hour,
+                value.minute(),
+                value.second(),
+                if pm { "PM" } else { "AM" }
+            )
+        }
+        Locale::Ru | Locale::De => format!("{}, {}", date, value.format("%H:%M:%S")),
+    }
+}
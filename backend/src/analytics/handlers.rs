@@ -0,0 +1,197 @@
+use crate::analytics::cache::{self, CacheStats};
+use crate::analytics::chart::{build_chart, ChartQuery};
+use crate::analytics::columnar;
+use crate::analytics::format::Locale;
+use crate::analytics::pivot::{apply_pivot, PivotConfig};
+use crate::analytics::report::{evaluate_report, FormatOptions, ReportConfig};
+use crate::analytics::rows::{query_rows, RowsPage, RowsQuery};
+use crate::analytics::stats::{dataset_stats, StatsQuery};
+use crate::auth::handlers::Claims;
+use crate::datasets::handlers::{error, find_dataset, load_dataset};
+use crate::datasets::schema::ColumnSchema;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Response,
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{Pool, Sqlite};
+use std::time::Instant;
+
+type ApiResult = (StatusCode, Json<serde_json::Value>);
+
+/// Конфигурация отчёта виджета плюс параметры форматирования
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportQuery {
+    #[serde(flatten)]
+    pub config: ReportConfig,
+    #[serde(default)]
+    pub locale: Option<String>,
+    #[serde(default)]
+    pub currency: Option<String>,
+}
+
+pub async fn pivot_query(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+    Json(config): Json<PivotConfig>,
+) -> Result<Response, ApiResult> {
+    let record = find_dataset(&pool, &claims, &id).await?;
+    let key = cache::key(&pool, cache::KIND_PIVOT, &record, &config).await?;
+    if let Some(response) = cache::lookup(key.as_ref()) {
+        println!("📊 pivot {:<25} | cache hit", record.name);
+        return Ok(response);
+    }
+    let table = columnar::load(&pool, &record).await?;
+    let started = Instant::now();
+
+    let result = tokio::task::spawn_blocking(move || apply_pivot(&table, &config))
+        .await
+        .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(|e| error(StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    println!(
+        "📊 pivot {:<25} | Rows {:>8} → {:>6} | {:>6} ms",
+        record.name,
+        record.row_count,
+        result.data.len(),
+        started.elapsed().as_millis()
+    );
+    cache::respond(key, &result)
+}
+
+/// Расчёт метрик отчёта и подстановка их в шаблон без участия браузера
+pub async fn report_query(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+    Json(query): Json<ReportQuery>,
+) -> Result<Response, ApiResult> {
+    let mut options = FormatOptions::default();
+    if let Some(locale) = query.locale.as_deref() {
+        options.locale =
+            Locale::parse(locale).map_err(|e| error(StatusCode::BAD_REQUEST, e.to_string()))?;
+    }
+    if let Some(currency) = query.currency.clone().filter(|c| !c.trim().is_empty()) {
+        options.currency = currency;
+    }
+
+    let record = find_dataset(&pool, &claims, &id).await?;
+    let key = cache::key(&pool, cache::KIND_REPORT, &record, &query).await?;
+    if let Some(response) = cache::lookup(key.as_ref()) {
+        println!("📝 report {:<25} | cache hit", record.name);
+        return Ok(response);
+    }
+    let table = columnar::load(&pool, &record).await?;
+    let started = Instant::now();
+    let config = query.config;
+
+    let result = tokio::task::spawn_blocking(move || evaluate_report(&table, &config, &options))
+        .await
+        .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    println!(
+        "📝 report {:<25} | Metrics {:>4} | {:>6} ms",
+        record.name,
+        result.values.len(),
+        started.elapsed().as_millis()
+    );
+    cache::respond(key, &result)
+}
+
+/// Страница строк для ленивой подгрузки таблицы
+pub async fn rows_query(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+    Json(query): Json<RowsQuery>,
+) -> Result<Json<RowsPage>, ApiResult> {
+    let (record, table) = load_dataset(&pool, &claims, &id).await?;
+    let version = format!("{}@{}", record.id, record.updated_at);
+
+    let page = tokio::task::spawn_blocking(move || query_rows(&table, &query, &version))
+        .await
+        .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(|e| error(StatusCode::BAD_REQUEST, e.to_string()))?;
+    Ok(Json(page))
+}
+
+/// Готовые серии для виджетов bar/line/pie
+pub async fn chart_query(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+    Json(query): Json<ChartQuery>,
+) -> Result<Response, ApiResult> {
+    let record = find_dataset(&pool, &claims, &id).await?;
+    let key = cache::key(&pool, cache::KIND_CHART, &record, &query).await?;
+    if let Some(response) = cache::lookup(key.as_ref()) {
+        println!("📈 chart {:<25} | cache hit", record.name);
+        return Ok(response);
+    }
+    let table = columnar::load(&pool, &record).await?;
+    let started = Instant::now();
+
+    let result = tokio::task::spawn_blocking(move || build_chart(&table, &query))
+        .await
+        .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(|e| error(StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    println!(
+        "📈 chart {:<25} | Rows {:>8} → {:>6} | {:>6} ms",
+        record.name,
+        record.row_count,
+        result.data.len(),
+        started.elapsed().as_millis()
+    );
+    cache::respond(key, &result)
+}
+
+/// Статистика по колонкам и предупреждения о качестве данных;
+/// `?top=` — сколько самых частых значений вернуть
+pub async fn stats_query(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+    Query(query): Query<StatsQuery>,
+) -> Result<Response, ApiResult> {
+    let record = find_dataset(&pool, &claims, &id).await?;
+    let key = cache::key(&pool, cache::KIND_STATS, &record, &query).await?;
+    if let Some(response) = cache::lookup(key.as_ref()) {
+        println!("🔎 stats {:<25} | cache hit", record.name);
+        return Ok(response);
+    }
+    let table = columnar::load(&pool, &record).await?;
+    let schema: Vec<ColumnSchema> = serde_json::from_str(&record.schema).unwrap_or_default();
+    let started = Instant::now();
+
+    let result = tokio::task::spawn_blocking(move || dataset_stats(&table, &schema, &query))
+        .await
+        .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(|e| error(StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    println!(
+        "🔎 stats {:<25} | Columns {:>4} | Warnings {:>4} | {:>6} ms",
+        record.name,
+        result.columns.len(),
+        result.warnings.len(),
+        started.elapsed().as_millis()
+    );
+    cache::respond(key, &result)
+}
+
+/// Счётчики кеша результатов: попадания и промахи по видам запросов,
+/// занятый объём, вытеснения и сбросы
+pub async fn cache_stats() -> Json<CacheStats> {
+    Json(cache::stats())
+}
+
+pub async fn clear_cache() -> ApiResult {
+    cache::clear();
+    println!("🧹 query cache cleared");
+    (StatusCode::OK, Json(json!({ "stats": cache::stats() })))
+}
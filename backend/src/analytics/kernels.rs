@@ -0,0 +1,141 @@
+use crate::analytics::columnar::{Column, ColumnarTable, Parsed};
+use crate::analytics::filter::PivotFilter;
+use crate::analytics::pivot::Accumulator;
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+
+/// Номера строк, прошедших фильтры, по возрастанию
+pub type Selection = Vec<u32>;
+
+pub fn select_all(table: &ColumnarTable) -> Selection {
+    (0..table.row_count as u32).collect()
+}
+
+/// Фильтры применяются по очереди к уже отобранным строкам; условие
+/// проверяется один раз на значение словаря, а не на каждую строку
+pub fn filter(table: &ColumnarTable, filters: &[PivotFilter]) -> Result<Selection> {
+    let mut compiled = Vec::with_capacity(filters.len());
+    for filter in filters {
+        let Some(column) = table.column(&filter.column) else {
+            bail!(
+                "Колонка фильтра «{}» отсутствует в наборе данных",
+                filter.column
+            );
+        };
+        compiled.push((column, filter));
+    }
+
+    let mut selection = select_all(table);
+    for (column, filter) in compiled {
+        let matched = column.map_cells(|cell| filter.matches(cell));
+        selection.retain(|&row| *matched.get(row as usize));
+    }
+    Ok(selection)
+}
+
+/// Группы строк выборки в порядке первого появления
+pub struct Groups {
+    /// Номер группы для каждой строки выборки
+    pub of_row: Vec<u32>,
+    /// Первая строка каждой группы — по ней читаются значения измерений
+    pub first_rows: Vec<u32>,
+}
+
+impl Groups {
+    pub fn len(&self) -> usize {
+        self.first_rows.len()
+    }
+}
+
+/// Группировка выборки по значениям колонок: ключ строки — номера значений
+/// в словарях и сами числа, без сборки строк
+pub fn group_by(columns: &[&Column], selection: &[u32]) -> Groups {
+    let mut of_row = Vec::with_capacity(selection.len());
+    let mut first_rows = Vec::new();
+    match columns {
+        [] => {
+            first_rows.extend(selection.first());
+            of_row.resize(selection.len(), 0);
+        }
+        [column] => {
+            let mut index: HashMap<u64, u32> = HashMap::new();
+            for &row in selection {
+                let next = first_rows.len() as u32;
+                let group = *index.entry(column.key(row as usize)).or_insert_with(|| {
+                    first_rows.push(row);
+                    next
+                });
+                of_row.push(group);
+            }
+        }
+        _ => {
+            let mut index: HashMap<Vec<u64>, u32> = HashMap::new();
+            for &row in selection {
+                let key = columns.iter().map(|c| c.key(row as usize)).collect();
+                let next = first_rows.len() as u32;
+                let group = *index.entry(key).or_insert_with(|| {
+                    first_rows.push(row);
+                    next
+                });
+                of_row.push(group);
+            }
+        }
+    }
+    Groups { of_row, first_rows }
+}
+
+/// Накопление колонки по группам: строка `i` выборки попадает в
+/// `accumulators[slots[i] * stride + offset]`. `None` — поле `*`, считаются строки
+pub fn accumulate(
+    column: Option<&Column>,
+    selection: &[u32],
+    slots: &[u32],
+    accumulators: &mut [Accumulator],
+    stride: usize,
+    offset: usize,
+) {
+    let target = |slot: u32| slot as usize * stride + offset;
+    match column {
+        None => {
+            for &slot in slots {
+                accumulators[target(slot)].push_row();
+            }
+        }
+        Some(column) => {
+            for (&row, &slot) in selection.iter().zip(slots) {
+                accumulators[target(slot)].push(column.parsed(row as usize));
+            }
+        }
+    }
+}
+
+/// Числа колонки по правилам отчётов: пустые ячейки и текст пропускаются,
+/// строка из одних пробелов — ноль, бесконечности не считаются
+pub fn report_numbers<'a>(
+    column: &'a Column,
+    selection: &'a [u32],
+) -> impl Iterator<Item = f64> + 'a {
+    selection
+        .iter()
+        .filter_map(move |&row| match column.parsed(row as usize) {
+            Parsed::Number(n) if n.is_finite() => Some(n),
+            Parsed::Blank => Some(0.0),
+            _ => None,
+        })
+}
+
+/// Непустые значения колонки в выборке: первая строка значения и число
+/// строк с ним, в порядке первого появления
+pub fn value_counts(column: &Column, selection: &[u32]) -> Vec<(u32, usize)> {
+    let filled: Selection = selection
+        .iter()
+        .copied()
+        .filter(|&row| !column.is_empty(row as usize))
+        .collect();
+    let groups = group_by(&[column], &filled);
+    let mut counts = vec![0; groups.len()];
+    for &group in &groups.of_row {
+        counts[group as usize] += 1;
+    }
+    groups.first_rows.into_iter().zip(counts).collect()
+}
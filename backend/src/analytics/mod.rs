@@ -0,0 +1,52 @@
+use axum::routing::{get, post};
+use axum::Router;
+use sqlx::{Pool, Sqlite};
+
+pub mod cache;
+pub mod chart;
+pub mod columnar;
+pub mod filter;
+pub mod format;
+pub mod handlers;
+pub mod kernels;
+pub mod pivot;
+pub mod report;
+pub mod rows;
+pub mod stats;
+
+use crate::protect;
+
+/// Запросы к сохранённым наборам данных: расчёты выполняются на сервере,
+/// клиент получает только готовый результат
+pub fn setup_router(pool: Pool<Sqlite>) -> Router {
+    Router::new()
+        .route(
+            "/api/datasets/:id/pivot",
+            post(handlers::pivot_query).route_layer(protect!(pool, "User")),
+        )
+        .route(
+            "/api/datasets/:id/report",
+            post(handlers::report_query).route_layer(protect!(pool, "User")),
+        )
+        .route(
+            "/api/datasets/:id/rows",
+            post(handlers::rows_query).route_layer(protect!(pool, "User")),
+        )
+        .route(
+            "/api/datasets/:id/chart",
+            post(handlers::chart_query).route_layer(protect!(pool, "User")),
+        )
+        .route(
+            "/api/datasets/:id/stats",
+            get(handlers::stats_query).route_layer(protect!(pool, "User")),
+        )
+        .route(
+            "/api/cache/stats",
+            get(handlers::cache_stats).route_layer(protect!(pool, "Admin")),
+        )
+        .route(
+            "/api/cache/clear",
+            post(handlers::clear_cache).route_layer(protect!(pool, "Admin")),
+        )
+        .with_state(pool)
+}
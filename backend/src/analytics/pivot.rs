@@ -0,0 +1,583 @@
+use crate::analytics::columnar::{Column, ColumnarTable, Parsed};
+use crate::analytics::filter::{Cell, PivotFilter};
+use crate::analytics::kernels::{self, accumulate, group_by};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// Ключ колонки значений, когда разбивки по колонкам нет
+const ALL_COLUMNS_KEY: &str = "__all__";
+
+/// Поле `*` в агрегации `count` считает все строки группы
+const ANY_FIELD: &str = "*";
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Aggregation {
+    Sum,
+    #[default]
+    Count,
+    Avg,
+    Min,
+    Max,
+}
+
+impl Aggregation {
+    fn label(self) -> &'static str {
+        match self {
+            Aggregation::Sum => "SUM",
+            Aggregation::Count => "COUNT",
+            Aggregation::Avg => "AVG",
+            Aggregation::Min => "MIN",
+            Aggregation::Max => "MAX",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PivotValue {
+    pub field: String,
+    #[serde(default)]
+    pub agg: Aggregation,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+}
+
+impl PivotValue {
+    /// Заголовок значения: псевдоним или `SUM(поле)`
+    pub fn alias(&self) -> String {
+        match self.alias.as_deref().map(str::trim) {
+            Some(alias) if !alias.is_empty() => alias.to_string(),
+            _ => format!("{}({})", self.agg.label(), self.field),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SortDirection {
+    Asc,
+    Desc,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PivotSort {
+    pub column: String,
+    pub direction: SortDirection,
+}
+
+/// Конфигурация сводной таблицы — тот же контракт, что `PivotConfig` в `web/lib/types.ts`
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PivotConfig {
+    pub rows: Vec<String>,
+    pub columns: Vec<String>,
+    pub values: Vec<PivotValue>,
+    pub filters: Vec<PivotFilter>,
+    pub post_filters: Vec<PivotFilter>,
+    pub sort: Vec<PivotSort>,
+    pub limit: Option<usize>,
+}
+
+impl PivotConfig {
+    pub fn is_pivot(&self) -> bool {
+        !self.rows.is_empty() || !self.columns.is_empty() || !self.values.is_empty()
+    }
+}
+
+/// Описание колонки значений в результате
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ValueColumnInfo {
+    pub key: String,
+    pub header: String,
+    pub source: PivotValue,
+    pub column_label: String,
+}
+
+/// Ответ в форме `PivotApplyResult`, чтобы виджет рисовал его без изменений
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PivotResult {
+    pub data: Vec<Map<String, Value>>,
+    pub dimension_fields: Vec<String>,
+    pub value_columns: Vec<ValueColumnInfo>,
+    pub is_pivot: bool,
+}
+
+/// Накопитель одной агрегации; пустые ячейки в числовые агрегаты не попадают
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Accumulator {
+    rows: usize,
+    filled: usize,
+    numeric: usize,
+    sum: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Default for Accumulator {
+    fn default() -> Self {
+        Self {
+            rows: 0,
+            filled: 0,
+            numeric: 0,
+            sum: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+}
+
+impl Accumulator {
+    /// Строка для `count` по полю `*`
+    pub(crate) fn push_row(&mut self) {
+        self.rows += 1;
+    }
+
+    pub(crate) fn push(&mut self, value: Parsed) {
+        self.rows += 1;
+        match value {
+            Parsed::Empty => {}
+            Parsed::Blank | Parsed::Text => self.filled += 1,
+            Parsed::Number(number) => {
+                self.filled += 1;
+                self.numeric += 1;
+                self.sum += number;
+                self.min = self.min.min(number);
+                self.max = self.max.max(number);
+            }
+        }
+    }
+
+    /// Объединение накопителей, например при сворачивании хвоста в «Другое»
+    pub(crate) fn merge(&mut self, other: &Accumulator) {
+        self.rows += other.rows;
+        self.filled += other.filled;
+        self.numeric += other.numeric;
+        self.sum += other.sum;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    /// Есть ли числа для sum/avg/min/max
+    pub(crate) fn has_numbers(&self) -> bool {
+        self.numeric > 0
+    }
+
+    pub(crate) fn result(&self, agg: Aggregation, any_field: bool) -> f64 {
+        if agg == Aggregation::Count {
+            return if any_field { self.rows } else { self.filled } as f64;
+        }
+        if self.numeric == 0 {
+            return 0.0;
+        }
+        match agg {
+            Aggregation::Sum => self.sum,
+            Aggregation::Avg => self.sum / self.numeric as f64,
+            Aggregation::Min => self.min,
+            Aggregation::Max => self.max,
+            Aggregation::Count => unreachable!(),
+        }
+    }
+}
+
+struct ColumnEntry {
+    key: String,
+    label: String,
+}
+
+struct ResultRow<'a> {
+    dimensions: Vec<Cow<'a, str>>,
+    values: Vec<f64>,
+    totals: HashMap<String, f64>,
+}
+
+fn column<'a>(table: &'a ColumnarTable, name: &str, role: &str) -> Result<&'a Column> {
+    match table.column(name) {
+        Some(column) => Ok(column),
+        None => bail!("Колонка {} «{}» отсутствует в наборе данных", role, name),
+    }
+}
+
+/// Сводная таблица по сохранённому набору; повторяет `applyPivot` из `web/lib/pivot.ts`
+pub fn apply_pivot(table: &ColumnarTable, config: &PivotConfig) -> Result<PivotResult> {
+    let filtered = kernels::filter(table, &config.filters)?;
+
+    if !config.is_pivot() {
+        return Ok(plain_result(table, &filtered, config.limit));
+    }
+
+    let row_fields = config
+        .rows
+        .iter()
+        .map(|name| column(table, name, "строк"))
+        .collect::<Result<Vec<_>>>()?;
+    let column_fields = config
+        .columns
+        .iter()
+        .map(|name| column(table, name, "столбцов"))
+        .collect::<Result<Vec<_>>>()?;
+    let value_fields = config
+        .values
+        .iter()
+        .map(|value| match value.field.as_str() {
+            ANY_FIELD => Ok(None),
+            name => column(table, name, "значений").map(Some),
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // колонки разбивки и группы строк — в порядке появления
+    let entries: Vec<ColumnEntry>;
+    let row_entries: Vec<u32>;
+    if column_fields.is_empty() {
+        entries = vec![ColumnEntry {
+            key: ALL_COLUMNS_KEY.to_string(),
+            label: String::new(),
+        }];
+        row_entries = vec![0; filtered.len()];
+    } else {
+        let groups = group_by(&column_fields, &filtered);
+        entries = groups
+            .first_rows
+            .iter()
+            .map(|&row| {
+                let values: Vec<Cow<str>> =
+                    column_fields.iter().map(|c| c.text(row as usize)).collect();
+                column_entry(&config.columns, &values)
+            })
+            .collect();
+        row_entries = groups.of_row;
+    }
+    let groups = group_by(&row_fields, &filtered);
+
+    // накопители: итоги группы по каждому значению и ячейки по колонкам разбивки
+    let value_count = config.values.len();
+    let mut totals = vec![Accumulator::default(); groups.len() * value_count];
+    let mut cells = vec![Accumulator::default(); groups.len() * entries.len() * value_count];
+    let cell_slots: Vec<u32> = groups
+        .of_row
+        .iter()
+        .zip(&row_entries)
+        .map(|(&group, &entry)| group * entries.len() as u32 + entry)
+        .collect();
+    for (value_idx, field) in value_fields.iter().enumerate() {
+        let field = *field;
+        accumulate(
+            field,
+            &filtered,
+            &groups.of_row,
+            &mut totals,
+            value_count,
+            value_idx,
+        );
+        accumulate(
+            field,
+            &filtered,
+            &cell_slots,
+            &mut cells,
+            value_count,
+            value_idx,
+        );
+    }
+
+    let aliases: Vec<String> = config.values.iter().map(PivotValue::alias).collect();
+    let mut value_columns = Vec::with_capacity(entries.len() * value_count);
+    for entry in &entries {
+        for (value, alias) in config.values.iter().zip(&aliases) {
+            let all = entry.key == ALL_COLUMNS_KEY;
+            value_columns.push(ValueColumnInfo {
+                key: if all {
+                    alias.clone()
+                } else {
+                    format!("{}::{}", alias, entry.key)
+                },
+                header: if all {
+                    alias.clone()
+                } else {
+                    format!("{} • {}", alias, entry.label)
+                },
+                source: value.clone(),
+                column_label: entry.label.clone(),
+            });
+        }
+    }
+
+    let width = entries.len() * value_count;
+    let result = |acc: &Accumulator, value_idx: usize| {
+        acc.result(
+            config.values[value_idx].agg,
+            value_fields[value_idx].is_none(),
+        )
+    };
+    let mut result_rows: Vec<ResultRow> = groups
+        .first_rows
+        .iter()
+        .enumerate()
+        .map(|(group, &row)| {
+            let group_totals = &totals[group * value_count..(group + 1) * value_count];
+            let totals = aliases
+                .iter()
+                .zip(group_totals)
+                .enumerate()
+                .map(|(idx, (alias, acc))| (alias.clone(), result(acc, idx)))
+                .collect();
+            let values = cells[group * width..(group + 1) * width]
+                .iter()
+                .enumerate()
+                .map(|(idx, acc)| result(acc, idx % value_count))
+                .collect();
+            ResultRow {
+                dimensions: row_fields.iter().map(|c| c.text(row as usize)).collect(),
+                values,
+                totals,
+            }
+        })
+        .collect();
+
+    let lookup = RowLookup {
+        dimensions: &config.rows,
+        value_keys: value_columns.iter().map(|c| c.key.as_str()).collect(),
+    };
+
+    result_rows.retain(|row| {
+        config
+            .post_filters
+            .iter()
+            .all(|filter| filter.matches(lookup.post_filter_value(row, &filter.column)))
+    });
+
+    if !config.sort.is_empty() {
+        result_rows.sort_by(|a, b| {
+            for rule in &config.sort {
+                let ordering = compare_cells(
+                    lookup.sort_value(a, &rule.column),
+                    lookup.sort_value(b, &rule.column),
+                );
+                if ordering != Ordering::Equal {
+                    return match rule.direction {
+                        SortDirection::Asc => ordering,
+                        SortDirection::Desc => ordering.reverse(),
+                    };
+                }
+            }
+            Ordering::Equal
+        });
+    }
+
+    if let Some(limit) = config.limit.filter(|&limit| limit > 0) {
+        result_rows.truncate(limit);
+    }
+
+    let data = result_rows
+        .iter()
+        .map(|row| {
+            let mut object = Map::new();
+            for (field, value) in config.rows.iter().zip(&row.dimensions) {
+                object.insert(field.clone(), Value::String(value.to_string()));
+            }
+            for (key, value) in lookup.value_keys.iter().zip(&row.values) {
+                object.insert(key.to_string(), Value::from(*value));
+            }
+            object
+        })
+        .collect();
+
+    Ok(PivotResult {
+        data,
+        dimension_fields: config.rows.clone(),
+        value_columns,
+        is_pivot: true,
+    })
+}
+
+/// Без измерений и значений возвращаются отфильтрованные строки как есть
+fn plain_result(table: &ColumnarTable, rows: &[u32], limit: Option<usize>) -> PivotResult {
+    let take = limit.filter(|&l| l > 0).unwrap_or(rows.len());
+    let data = rows
+        .iter()
+        .take(take)
+        .map(|&row| {
+            table
+                .columns
+                .iter()
+                .map(|column| {
+                    let text = column.text(row as usize).into_owned();
+                    (column.name.clone(), Value::String(text))
+                })
+                .collect()
+        })
+        .collect();
+    PivotResult {
+        data,
+        dimension_fields: table.names().map(str::to_string).collect(),
+        value_columns: Vec::new(),
+        is_pivot: false,
+    }
+}
+
+fn column_entry(columns: &[String], values: &[Cow<str>]) -> ColumnEntry {
+    let key = columns
+        .iter()
+        .zip(values)
+        .map(|(col, value)| format!("{}={}", col, value))
+        .collect::<Vec<_>>()
+        .join("||");
+    let label = columns
+        .iter()
+        .zip(values)
+        .map(|(col, value)| format!("{}: {}", col, value))
+        .collect::<Vec<_>>()
+        .join(" • ");
+    ColumnEntry { key, label }
+}
+
+/// Поиск значения строки результата по имени поля для постфильтров и сортировки
+struct RowLookup<'a> {
+    dimensions: &'a [String],
+    value_keys: Vec<&'a str>,
+}
+
+impl RowLookup<'_> {
+    fn row_value<'r>(&self, row: &'r ResultRow, column: &str) -> Option<Cell<'r>> {
+        if let Some(idx) = self.dimensions.iter().position(|d| d == column) {
+            return Some(Cell::Text(&row.dimensions[idx]));
+        }
+        self.value_keys
+            .iter()
+            .position(|key| *key == column)
+            .map(|idx| Cell::Number(row.values[idx]))
+    }
+
+    /// Постфильтры сначала смотрят в итоги по псевдониму, затем в саму строку
+    fn post_filter_value<'r>(&self, row: &'r ResultRow, column: &str) -> Cell<'r> {
+        if let Some(total) = row.totals.get(column) {
+            return Cell::Number(*total);
+        }
+        self.row_value(row, column).unwrap_or(Cell::Missing)
+    }
+
+    /// Сортировка — наоборот: сначала поле строки, затем итоги
+    fn sort_value<'r>(&self, row: &'r ResultRow, column: &str) -> Cell<'r> {
+        self.row_value(row, column)
+            .or_else(|| row.totals.get(column).map(|total| Cell::Number(*total)))
+            .unwrap_or(Cell::Missing)
+    }
+}
+
+pub fn compare_cells(left: Cell, right: Cell) -> Ordering {
+    left.sort_key().compare(&right.sort_key())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datasets::store::StoredTable;
+    use serde_json::json;
+
+    fn table() -> ColumnarTable {
+        let rows = [
+            ["North", "A", "10"],
+            ["North", "B", ""],
+            ["South", "A", "5"],
+            ["South", "A", " "],
+            ["East", "B", "abc"],
+        ];
+        ColumnarTable::from_table(StoredTable {
+            columns: vec!["region".into(), "product".into(), "amount".into()],
+            rows: rows
+                .iter()
+                .map(|row| row.iter().map(|v| v.to_string()).collect())
+                .collect(),
+        })
+    }
+
+    fn pivot(config: Value) -> PivotResult {
+        apply_pivot(&table(), &serde_json::from_value(config).unwrap()).unwrap()
+    }
+
+    fn column(result: &PivotResult, key: &str) -> Vec<Value> {
+        result.data.iter().map(|row| row[key].clone()).collect()
+    }
+
+    #[test]
+    fn count_star_counts_rows_and_field_counts_filled_cells() {
+        let result = pivot(json!({
+            "rows": ["region"],
+            "values": [
+                { "field": "*", "agg": "count", "alias": "n" },
+                { "field": "amount", "agg": "count", "alias": "filled" },
+                { "field": "amount", "agg": "sum", "alias": "sum" },
+                { "field": "amount", "agg": "avg", "alias": "avg" }
+            ]
+        }));
+        // группы — в порядке появления
+        assert_eq!(
+            column(&result, "region"),
+            [json!("North"), json!("South"), json!("East")]
+        );
+        assert_eq!(column(&result, "n"), [json!(2.0), json!(2.0), json!(1.0)]);
+        // пустая ячейка не заполнена, ячейка из пробелов — заполнена
+        assert_eq!(
+            column(&result, "filled"),
+            [json!(1.0), json!(2.0), json!(1.0)]
+        );
+        // в числовые агрегаты попадают только числа; без чисел — 0
+        assert_eq!(
+            column(&result, "sum"),
+            [json!(10.0), json!(5.0), json!(0.0)]
+        );
+        assert_eq!(
+            column(&result, "avg"),
+            [json!(10.0), json!(5.0), json!(0.0)]
+        );
+    }
+
+    #[test]
+    fn post_filters_read_totals_before_row_fields() {
+        // псевдоним совпадает с измерением: постфильтр видит итог, а не название
+        let result = pivot(json!({
+            "rows": ["region"],
+            "columns": ["product"],
+            "values": [{ "field": "amount", "agg": "sum", "alias": "region" }],
+            "postFilters": [{ "column": "region", "operator": "gt", "value": 1 }]
+        }));
+        assert_eq!(column(&result, "region"), [json!("North"), json!("South")]);
+        assert_eq!(
+            column(&result, "region::product=A"),
+            [json!(10.0), json!(5.0)]
+        );
+    }
+
+    #[test]
+    fn sort_reads_row_fields_before_totals() {
+        // псевдоним совпадает с измерением: сортирует название, а не итог
+        let by_dimension = pivot(json!({
+            "rows": ["region"],
+            "columns": ["product"],
+            "values": [{ "field": "amount", "agg": "sum", "alias": "region" }],
+            "sort": [{ "column": "region", "direction": "asc" }]
+        }));
+        assert_eq!(
+            column(&by_dimension, "region"),
+            [json!("East"), json!("North"), json!("South")]
+        );
+
+        // с разбивкой по колонкам псевдонима нет среди полей строки — сортируют итоги
+        let by_total = pivot(json!({
+            "rows": ["region"],
+            "columns": ["product"],
+            "values": [{ "field": "amount", "agg": "sum", "alias": "s" }],
+            "sort": [{ "column": "s", "direction": "desc" }]
+        }));
+        assert_eq!(
+            column(&by_total, "region"),
+            [json!("North"), json!("South"), json!("East")]
+        );
+    }
+}
@@ -0,0 +1,344 @@
+use crate::analytics::columnar::{Column, ColumnarTable};
+use crate::analytics::filter;
+use crate::analytics::format::{
+    format_currency, format_date, format_datetime, format_number, Locale,
+};
+use crate::analytics::kernels::{report_numbers, select_all, Selection};
+use crate::datasets::schema::parse_date;
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const EMPTY_VALUE: &str = "—";
+const DEFAULT_CURRENCY: &str = "RUB";
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum MetricAggregation {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+    MinDate,
+    MaxDate,
+    Percent,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum MetricFormat {
+    Number,
+    Integer,
+    Currency,
+    Date,
+    Datetime,
+    Percent,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum ConditionOperator {
+    #[default]
+    Eq,
+    Neq,
+    Contains,
+    StartsWith,
+    EndsWith,
+}
+
+/// Метрика отчёта — контракт `ReportMetric` из `web/lib/types.ts`
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportMetric {
+    pub id: String,
+    #[serde(default)]
+    pub label: String,
+    #[serde(default)]
+    pub field: String,
+    pub aggregation: MetricAggregation,
+    #[serde(default)]
+    pub format: Option<MetricFormat>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub condition_field: Option<String>,
+    #[serde(default)]
+    pub condition_operator: Option<ConditionOperator>,
+    #[serde(default)]
+    pub condition_value: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ReportConfig {
+    pub template: String,
+    pub metrics: Vec<ReportMetric>,
+    pub title: Option<String>,
+}
+
+/// Параметры форматирования значений: локаль и валюта для `currency`
+#[derive(Clone, Debug)]
+pub struct FormatOptions {
+    pub locale: Locale,
+    pub currency: String,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            locale: Locale::Ru,
+            currency: DEFAULT_CURRENCY.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportResult {
+    pub values: BTreeMap<String, String>,
+    pub rendered: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Метрики, ссылающиеся на отсутствующие колонки: в браузере они молча
+    /// превращались в «—» или ноль, здесь об этом сообщается явно
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// Промежуточное значение метрики до форматирования
+enum MetricValue {
+    Number(f64),
+    Date(NaiveDateTime),
+    Empty,
+}
+
+fn format_value(
+    value: MetricValue,
+    format: Option<MetricFormat>,
+    options: &FormatOptions,
+) -> String {
+    let locale = options.locale;
+    match (value, format) {
+        (MetricValue::Empty, _) => EMPTY_VALUE.to_string(),
+        (MetricValue::Number(n), None) => filter::format_number(n),
+        (MetricValue::Number(n), Some(MetricFormat::Integer)) => format_number(n, 0, 0, locale),
+        (MetricValue::Number(n), Some(MetricFormat::Number)) => format_number(n, 2, 2, locale),
+        (MetricValue::Number(n), Some(MetricFormat::Currency)) => {
+            format_currency(n, &options.currency, locale)
+        }
+        (MetricValue::Number(n), Some(MetricFormat::Percent)) => {
+            format!("{}%", format_number(n, 1, 1, locale))
+        }
+        (MetricValue::Date(d), None | Some(MetricFormat::Date)) => format_date(d, locale),
+        (MetricValue::Date(d), Some(MetricFormat::Datetime)) => format_datetime(d, locale),
+        // числа как даты и даты как числа в браузере давали бессмыслицу
+        _ => EMPTY_VALUE.to_string(),
+    }
+}
+
+/// Даты разбираются форматами загрузки (ISO и `ДД.ММ.ГГГГ`),
+/// а не американскими правилами `new Date(string)`
+fn coerce_date(value: &str) -> Option<NaiveDateTime> {
+    parse_date(value)
+}
+
+fn matches_condition(value: &str, operator: ConditionOperator, expected: &str) -> bool {
+    let target = value.to_lowercase();
+    let expected = expected.to_lowercase();
+    match operator {
+        ConditionOperator::Eq => target == expected,
+        ConditionOperator::Neq => target != expected,
+        ConditionOperator::Contains => target.contains(&expected),
+        ConditionOperator::StartsWith => target.starts_with(&expected),
+        ConditionOperator::EndsWith => target.ends_with(&expected),
+    }
+}
+
+/// Доступ к колонкам набора по именам
+struct Rows<'a> {
+    table: &'a ColumnarTable,
+}
+
+impl<'a> Rows<'a> {
+    fn column(&self, name: &str) -> Option<&'a Column> {
+        self.table.column(name)
+    }
+
+    /// Строки, прошедшие условие метрики
+    fn conditioned(&self, metric: &ReportMetric) -> Selection {
+        let mut all = select_all(self.table);
+        let Some(expected) = metric
+            .condition_value
+            .as_deref()
+            .filter(|v| !v.trim().is_empty())
+        else {
+            return all;
+        };
+        let field = metric
+            .condition_field
+            .as_deref()
+            .filter(|f| !f.is_empty())
+            .unwrap_or(&metric.field)
+            .trim();
+        if field.is_empty() {
+            return all;
+        }
+
+        let operator = metric.condition_operator.unwrap_or_default();
+        match self.column(field) {
+            Some(column) => {
+                let matched = column.map_text(|value| matches_condition(value, operator, expected));
+                all.retain(|&row| *matched.get(row as usize));
+            }
+            // отсутствующая колонка читается как пустые ячейки
+            None if matches_condition("", operator, expected) => {}
+            None => all.clear(),
+        }
+        all
+    }
+}
+
+fn compute_metric(metric: &ReportMetric, rows: &Rows, options: &FormatOptions) -> String {
+    let total = rows.table.row_count;
+
+    if metric.field.is_empty() {
+        return match metric.aggregation {
+            MetricAggregation::Count => {
+                format_value(MetricValue::Number(total as f64), metric.format, options)
+            }
+            MetricAggregation::Percent if total > 0 => {
+                let matched = rows.conditioned(metric).len();
+                let percent = matched as f64 / total as f64 * 100.0;
+                format_value(
+                    MetricValue::Number(percent),
+                    Some(metric.format.unwrap_or(MetricFormat::Percent)),
+                    options,
+                )
+            }
+            _ => EMPTY_VALUE.to_string(),
+        };
+    }
+
+    let conditioned = rows.conditioned(metric);
+    let column = rows.column(&metric.field);
+    let numbers = || {
+        column
+            .map(|column| report_numbers(column, &conditioned))
+            .into_iter()
+            .flatten()
+    };
+
+    let value = match metric.aggregation {
+        MetricAggregation::Count => MetricValue::Number(conditioned.len() as f64),
+        MetricAggregation::Sum => MetricValue::Number(numbers().sum()),
+        MetricAggregation::Avg => {
+            let nums: Vec<f64> = numbers().collect();
+            if nums.is_empty() {
+                MetricValue::Empty
+            } else {
+                MetricValue::Number(nums.iter().sum::<f64>() / nums.len() as f64)
+            }
+        }
+        MetricAggregation::Min => numbers()
+            .reduce(f64::min)
+            .map_or(MetricValue::Empty, MetricValue::Number),
+        MetricAggregation::Max => numbers()
+            .reduce(f64::max)
+            .map_or(MetricValue::Empty, MetricValue::Number),
+        MetricAggregation::MinDate | MetricAggregation::MaxDate => {
+            let picked = column.and_then(|column| {
+                let parsed = column.map_text(coerce_date);
+                let dates = conditioned
+                    .iter()
+                    .filter_map(|&row| *parsed.get(row as usize));
+                if metric.aggregation == MetricAggregation::MinDate {
+                    dates.min()
+                } else {
+                    dates.max()
+                }
+            });
+            let format = Some(metric.format.unwrap_or(MetricFormat::Date));
+            return format_value(
+                picked.map_or(MetricValue::Empty, MetricValue::Date),
+                format,
+                options,
+            );
+        }
+        MetricAggregation::Percent => {
+            let nums: Vec<f64> = numbers().collect();
+            let all = select_all(rows.table);
+            let denominator: f64 = column
+                .map(|column| report_numbers(column, &all).sum())
+                .unwrap_or(0.0);
+            if nums.is_empty() || denominator == 0.0 {
+                MetricValue::Empty
+            } else {
+                let percent = nums.iter().sum::<f64>() / denominator * 100.0;
+                let format = Some(metric.format.unwrap_or(MetricFormat::Percent));
+                return format_value(MetricValue::Number(percent), format, options);
+            }
+        }
+    };
+    format_value(value, metric.format, options)
+}
+
+/// Подстановка значений в плейсхолдеры `{{ id }}`; неизвестные — «—»
+pub fn render_template(template: &str, values: &BTreeMap<String, String>) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let inner = after.trim_start();
+        let token_len = inner
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+            .unwrap_or(inner.len());
+        let tail = inner[token_len..].trim_start();
+        if token_len > 0 && tail.starts_with("}}") {
+            let token = &inner[..token_len];
+            rendered.push_str(values.get(token).map(String::as_str).unwrap_or(EMPTY_VALUE));
+            rest = &tail[2..];
+        } else {
+            rendered.push_str("{{");
+            rest = after;
+        }
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// Расчёт всех метрик и подстановка в шаблон; повторяет `evaluateReport`
+/// из `web/lib/report.ts`
+pub fn evaluate_report(
+    table: &ColumnarTable,
+    config: &ReportConfig,
+    options: &FormatOptions,
+) -> ReportResult {
+    let rows = Rows { table };
+    let mut values = BTreeMap::new();
+    let mut warnings = Vec::new();
+
+    for metric in &config.metrics {
+        let referenced = [
+            Some(metric.field.as_str()),
+            metric.condition_field.as_deref(),
+        ];
+        for field in referenced.into_iter().flatten().map(str::trim) {
+            if !field.is_empty() && rows.column(field).is_none() {
+                warnings.push(format!(
+                    "Метрика «{}»: колонка «{}» отсутствует в наборе данных",
+                    metric.id, field
+                ));
+            }
+        }
+        values.insert(metric.id.clone(), compute_metric(metric, &rows, options));
+    }
+
+    ReportResult {
+        rendered: render_template(&config.template, &values),
+        values,
+        title: config.title.clone(),
+        warnings,
+    }
+}
@@ -0,0 +1,163 @@
+use crate::analytics::filter::{Cell, CompiledFilters, PivotFilter, SortKey};
+use crate::analytics::pivot::{PivotSort, SortDirection};
+use crate::datasets::store::StoredTable;
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cmp::Ordering;
+
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+pub const MAX_PAGE_SIZE: usize = 5000;
+
+/// Запрос страницы строк: фильтры и сортировка как в `PivotConfig`,
+/// плюс проекция колонок и пагинация смещением или курсором
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RowsQuery {
+    pub offset: Option<usize>,
+    pub limit: Option<usize>,
+    pub cursor: Option<String>,
+    pub sort: Vec<PivotSort>,
+    pub filters: Vec<PivotFilter>,
+    pub columns: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RowsPage {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+    /// Число строк после фильтров
+    pub total: usize,
+    pub offset: usize,
+    /// Курсор следующей страницы; `None` на последней странице
+    pub next_cursor: Option<String>,
+}
+
+impl RowsQuery {
+    /// Отпечаток запроса: курсор действителен только для той же версии набора,
+    /// тех же фильтров и той же сортировки
+    fn fingerprint(&self, version: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(version.as_bytes());
+        hasher.update(serde_json::to_vec(&self.sort).unwrap_or_default());
+        hasher.update(serde_json::to_vec(&self.filters).unwrap_or_default());
+        let digest = hasher.finalize();
+        digest[..8].iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn start(&self, fingerprint: &str) -> Result<usize> {
+        let Some(cursor) = self.cursor.as_deref().filter(|c| !c.is_empty()) else {
+            return Ok(self.offset.unwrap_or(0));
+        };
+        let (offset, hash) = cursor
+            .split_once(':')
+            .ok_or_else(|| anyhow!("Некорректный курсор"))?;
+        let offset = offset
+            .parse::<usize>()
+            .map_err(|_| anyhow!("Некорректный курсор"))?;
+        if hash != fingerprint {
+            bail!("Курсор относится к другому запросу или к устаревшей версии набора");
+        }
+        Ok(offset)
+    }
+}
+
+fn column_index(columns: &[String], name: &str) -> Result<usize> {
+    columns
+        .iter()
+        .position(|c| c == name)
+        .ok_or_else(|| anyhow!("Колонка «{}» отсутствует в наборе данных", name))
+}
+
+fn sort_key(row: &[String], idx: usize) -> SortKey<'_> {
+    row.get(idx)
+        .map(|v| Cell::Text(v))
+        .unwrap_or(Cell::Missing)
+        .sort_key()
+}
+
+/// Страница строк набора; `version` — идентификатор версии данных для курсоров
+pub fn query_rows(table: &StoredTable, query: &RowsQuery, version: &str) -> Result<RowsPage> {
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if limit == 0 || limit > MAX_PAGE_SIZE {
+        bail!("Размер страницы должен быть от 1 до {}", MAX_PAGE_SIZE);
+    }
+
+    let fingerprint = query.fingerprint(version);
+    let offset = query.start(&fingerprint)?;
+
+    let projection = if query.columns.is_empty() {
+        (0..table.columns.len()).collect()
+    } else {
+        query
+            .columns
+            .iter()
+            .map(|name| column_index(&table.columns, name))
+            .collect::<Result<Vec<_>>>()?
+    };
+    let sort = query
+        .sort
+        .iter()
+        .map(|rule| Ok((column_index(&table.columns, &rule.column)?, rule.direction)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let filters = CompiledFilters::compile(&query.filters, &table.columns)?;
+    let mut matched: Vec<&[String]> = table
+        .rows
+        .iter()
+        .map(Vec::as_slice)
+        .filter(|row| filters.matches(row))
+        .collect();
+
+    if !sort.is_empty() {
+        // ключи считаются один раз, а не при каждом сравнении
+        let mut keyed: Vec<(Vec<SortKey>, &[String])> = matched
+            .iter()
+            .map(|row| {
+                (
+                    sort.iter().map(|(idx, _)| sort_key(row, *idx)).collect(),
+                    *row,
+                )
+            })
+            .collect();
+        keyed.sort_by(|(a, _), (b, _)| {
+            for ((left, right), (_, direction)) in a.iter().zip(b).zip(&sort) {
+                let ordering = left.compare(right);
+                if ordering != Ordering::Equal {
+                    return match direction {
+                        SortDirection::Asc => ordering,
+                        SortDirection::Desc => ordering.reverse(),
+                    };
+                }
+            }
+            Ordering::Equal
+        });
+        matched = keyed.into_iter().map(|(_, row)| row).collect();
+    }
+
+    let total = matched.len();
+    let rows: Vec<Vec<String>> = matched
+        .iter()
+        .skip(offset)
+        .take(limit)
+        .map(|row| {
+            projection
+                .iter()
+                .map(|&idx| row.get(idx).cloned().unwrap_or_default())
+                .collect()
+        })
+        .collect();
+
+    let end = offset.saturating_add(rows.len());
+    Ok(RowsPage {
+        columns: projection
+            .iter()
+            .map(|&idx| table.columns[idx].clone())
+            .collect(),
+        rows,
+        total,
+        offset,
+        next_cursor: (end < total).then(|| format!("{}:{}", end, fingerprint)),
+    })
+}
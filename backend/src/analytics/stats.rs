@@ -0,0 +1,619 @@
+use crate::analytics::columnar::{Column, ColumnarTable};
+use crate::analytics::filter::format_number;
+use crate::analytics::kernels::{select_all, value_counts};
+use crate::datasets::schema::{parse_date, value_type, ColumnSchema, ColumnType};
+use crate::exporter::parse_number;
+use anyhow::{bail, Result};
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
+
+/// Сколько самых частых значений возвращается по умолчанию
+const DEFAULT_TOP: usize = 10;
+const MAX_TOP: usize = 100;
+/// Сколько значений показывается в предупреждении для примера
+const MAX_EXAMPLES: usize = 5;
+/// Выброс — число дальше трёх межквартильных размахов от квартилей
+const OUTLIER_FENCE: f64 = 3.0;
+/// Порядковые номера дней Excel, соответствующие 1954–2119 годам
+const EXCEL_SERIALS: RangeInclusive<f64> = 20_000.0..=80_000.0;
+/// Числа вида ГГГГММДД
+const COMPACT_DATES: RangeInclusive<f64> = 19_000_101.0..=21_001_231.0;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct StatsQuery {
+    /// Сколько самых частых значений вернуть по каждой колонке
+    pub top: usize,
+}
+
+impl Default for StatsQuery {
+    fn default() -> Self {
+        Self { top: DEFAULT_TOP }
+    }
+}
+
+/// Число непустых значений каждого типа
+#[derive(Serialize, Default, Clone, Copy, Debug)]
+pub struct TypeCounts {
+    pub string: usize,
+    pub number: usize,
+    pub date: usize,
+    pub boolean: usize,
+}
+
+impl TypeCounts {
+    fn slot(&mut self, column_type: ColumnType) -> &mut usize {
+        match column_type {
+            ColumnType::String => &mut self.string,
+            ColumnType::Number => &mut self.number,
+            ColumnType::Date => &mut self.date,
+            ColumnType::Boolean => &mut self.boolean,
+        }
+    }
+
+    /// Самый частый тип; при равенстве типизированные важнее строк
+    fn dominant(&self) -> Option<ColumnType> {
+        let mut best: Option<(ColumnType, usize)> = None;
+        for (column_type, count) in [
+            (ColumnType::Number, self.number),
+            (ColumnType::Date, self.date),
+            (ColumnType::Boolean, self.boolean),
+            (ColumnType::String, self.string),
+        ] {
+            if count > best.map_or(0, |(_, most)| most) {
+                best = Some((column_type, count));
+            }
+        }
+        best.map(|(column_type, _)| column_type)
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct TopValue {
+    pub value: String,
+    pub count: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Quantiles {
+    pub p5: f64,
+    pub p25: f64,
+    pub p75: f64,
+    pub p95: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct NumberStats {
+    pub count: usize,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub median: f64,
+    pub quantiles: Quantiles,
+}
+
+#[derive(Serialize, Debug)]
+pub struct DateStats {
+    pub count: usize,
+    pub min: String,
+    pub max: String,
+    /// Дней между первой и последней датой
+    pub days: i64,
+}
+
+/// Значения длиной от `from` до `to` символов включительно
+#[derive(Serialize, Debug)]
+pub struct LengthBucket {
+    pub from: usize,
+    pub to: usize,
+    pub count: usize,
+}
+
+/// Длины непустых значений в символах; корзины растут вдвое: 1, 2–3, 4–7…
+#[derive(Serialize, Debug)]
+pub struct LengthStats {
+    pub min: usize,
+    pub max: usize,
+    pub mean: f64,
+    pub buckets: Vec<LengthBucket>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ColumnStats {
+    pub name: String,
+    /// Тип из схемы набора
+    #[serde(rename = "type")]
+    pub column_type: ColumnType,
+    /// Самый частый тип среди значений; `None` — колонка пустая
+    pub detected_type: Option<ColumnType>,
+    pub filled: usize,
+    pub nulls: usize,
+    pub distinct: usize,
+    pub value_types: TypeCounts,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub numbers: Option<NumberStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dates: Option<DateStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lengths: Option<LengthStats>,
+    pub top: Vec<TopValue>,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum WarningKind {
+    /// Значения другого типа в типизированной колонке
+    MixedTypes,
+    /// Числа далеко за пределами основной массы значений
+    Outliers,
+    /// Пробелы в начале или в конце значения
+    Whitespace,
+    /// Числа среди дат или даты среди чисел: следы преобразования в Excel
+    DateNumberCoercion,
+}
+
+#[derive(Serialize, Debug)]
+pub struct QualityWarning {
+    pub column: String,
+    pub kind: WarningKind,
+    /// Сколько строк затронуто
+    pub count: usize,
+    pub message: String,
+    pub examples: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DatasetStats {
+    pub row_count: usize,
+    pub columns: Vec<ColumnStats>,
+    pub warnings: Vec<QualityWarning>,
+}
+
+/// Уникальное значение колонки
+struct Distinct<'a> {
+    text: Cow<'a, str>,
+    count: usize,
+    kind: ColumnType,
+}
+
+/// Числа колонки по возрастанию с числом повторов каждого
+struct Numbers {
+    values: Vec<(f64, usize)>,
+    count: usize,
+}
+
+impl Numbers {
+    fn new(mut values: Vec<(f64, usize)>) -> Option<Self> {
+        values.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let count = values.iter().map(|(_, count)| count).sum();
+        (count > 0).then_some(Self { values, count })
+    }
+
+    /// Число на месте `idx` в развёрнутом упорядоченном списке
+    fn at(&self, idx: usize) -> f64 {
+        let mut seen = 0;
+        for &(value, count) in &self.values {
+            seen += count;
+            if idx < seen {
+                return value;
+            }
+        }
+        self.values[self.values.len() - 1].0
+    }
+
+    /// Квантиль с линейной интерполяцией между соседними значениями
+    fn quantile(&self, p: f64) -> f64 {
+        let position = p * (self.count - 1) as f64;
+        let lower = self.at(position.floor() as usize);
+        let upper = self.at(position.ceil() as usize);
+        lower + (upper - lower) * position.fract()
+    }
+
+    fn stats(&self) -> NumberStats {
+        let sum: f64 = self.values.iter().map(|&(v, c)| v * c as f64).sum();
+        NumberStats {
+            count: self.count,
+            min: self.values[0].0,
+            max: self.values[self.values.len() - 1].0,
+            mean: sum / self.count as f64,
+            median: self.quantile(0.5),
+            quantiles: Quantiles {
+                p5: self.quantile(0.05),
+                p25: self.quantile(0.25),
+                p75: self.quantile(0.75),
+                p95: self.quantile(0.95),
+            },
+        }
+    }
+}
+
+fn type_label(column_type: ColumnType) -> &'static str {
+    match column_type {
+        ColumnType::String => "текст",
+        ColumnType::Number => "числа",
+        ColumnType::Date => "даты",
+        ColumnType::Boolean => "логические значения",
+    }
+}
+
+fn format_date(value: NaiveDateTime, with_time: bool) -> String {
+    if with_time {
+        value.format("%Y-%m-%dT%H:%M:%S").to_string()
+    } else {
+        value.format("%Y-%m-%d").to_string()
+    }
+}
+
+/// Дата, которую Excel хранит порядковым номером дня
+fn excel_date(number: f64) -> Option<NaiveDate> {
+    if number.fract() != 0.0 || !EXCEL_SERIALS.contains(&number) {
+        return None;
+    }
+    NaiveDate::from_ymd_opt(1899, 12, 30).map(|epoch| epoch + Duration::days(number as i64))
+}
+
+/// Дата, записанная числом ГГГГММДД
+fn compact_date(number: f64) -> Option<NaiveDate> {
+    if number.fract() != 0.0 || !COMPACT_DATES.contains(&number) {
+        return None;
+    }
+    let number = number as u32;
+    NaiveDate::from_ymd_opt((number / 10_000) as i32, number / 100 % 100, number % 100)
+}
+
+fn warning(
+    column: &str,
+    kind: WarningKind,
+    matched: &[&Distinct],
+    message: String,
+    example: impl Fn(&Distinct) -> String,
+) -> QualityWarning {
+    QualityWarning {
+        column: column.to_string(),
+        kind,
+        count: matched.iter().map(|value| value.count).sum(),
+        message,
+        examples: matched
+            .iter()
+            .take(MAX_EXAMPLES)
+            .map(|v| example(v))
+            .collect(),
+    }
+}
+
+fn plain(value: &Distinct) -> String {
+    value.text.to_string()
+}
+
+/// Число и дата, которой оно, вероятно, было
+fn as_date(value: &Distinct, to_date: fn(f64) -> Option<NaiveDate>) -> String {
+    match parse_number(&value.text).and_then(to_date) {
+        Some(date) => format!("{} → {}", value.text, date.format("%Y-%m-%d")),
+        None => plain(value),
+    }
+}
+
+/// Числа среди дат и даты среди чисел. Значения парного типа не считаются
+/// смешанными: это типичный след пересохранения выгрузки в Excel
+fn coercion_warnings(
+    name: &str,
+    values: &[Distinct],
+    detected: ColumnType,
+    filled: usize,
+) -> Vec<QualityWarning> {
+    let of_kind = |kind: ColumnType| -> Vec<&Distinct> {
+        values.iter().filter(|value| value.kind == kind).collect()
+    };
+    let count = |matched: &[&Distinct]| matched.iter().map(|v| v.count).sum::<usize>();
+    let mut warnings = Vec::new();
+    match detected {
+        ColumnType::Date => {
+            let numbers = of_kind(ColumnType::Number);
+            if !numbers.is_empty() {
+                let message = format!(
+                    "Колонка «{}»: числа среди дат — {} из {}; возможно, даты сохранены порядковыми номерами Excel",
+                    name,
+                    count(&numbers),
+                    filled
+                );
+                warnings.push(warning(
+                    name,
+                    WarningKind::DateNumberCoercion,
+                    &numbers,
+                    message,
+                    |value| as_date(value, excel_date),
+                ));
+            }
+        }
+        ColumnType::Number => {
+            let dates = of_kind(ColumnType::Date);
+            if !dates.is_empty() {
+                let message = format!(
+                    "Колонка «{}»: даты среди чисел — {} из {}",
+                    name,
+                    count(&dates),
+                    filled
+                );
+                warnings.push(warning(
+                    name,
+                    WarningKind::DateNumberCoercion,
+                    &dates,
+                    message,
+                    plain,
+                ));
+            }
+            // отдельные числа вида ГГГГММДД бывают и настоящими суммами,
+            // поэтому предупреждение — только когда таких большинство
+            let numbers = of_kind(ColumnType::Number);
+            let compact: Vec<&Distinct> = numbers
+                .iter()
+                .copied()
+                .filter(|value| parse_number(&value.text).and_then(compact_date).is_some())
+                .collect();
+            if !compact.is_empty() && count(&compact) * 2 >= count(&numbers) {
+                let message = format!(
+                    "Колонка «{}»: числа похожи на даты ГГГГММДД — {} из {}",
+                    name,
+                    count(&compact),
+                    filled
+                );
+                warnings.push(warning(
+                    name,
+                    WarningKind::DateNumberCoercion,
+                    &compact,
+                    message,
+                    |value| as_date(value, compact_date),
+                ));
+            }
+        }
+        _ => {}
+    }
+    warnings
+}
+
+/// Значения не того типа в колонке, где преобладает число, дата или
+/// логическое значение; в текстовых колонках числа и даты — норма
+fn mixed_types_warning(
+    name: &str,
+    values: &[Distinct],
+    detected: ColumnType,
+    filled: usize,
+) -> Option<QualityWarning> {
+    let paired = match detected {
+        ColumnType::String => return None,
+        ColumnType::Number => Some(ColumnType::Date),
+        ColumnType::Date => Some(ColumnType::Number),
+        ColumnType::Boolean => None,
+    };
+    let odd: Vec<&Distinct> = values
+        .iter()
+        .filter(|value| value.kind != detected && Some(value.kind) != paired)
+        .collect();
+    if odd.is_empty() {
+        return None;
+    }
+    let count = odd.iter().map(|value| value.count).sum::<usize>();
+    let message = format!(
+        "Колонка «{}»: смешанные типы — {} из {} значений не {}",
+        name,
+        count,
+        filled,
+        type_label(detected)
+    );
+    Some(warning(name, WarningKind::MixedTypes, &odd, message, plain))
+}
+
+fn whitespace_warning(name: &str, values: &[Distinct], filled: usize) -> Option<QualityWarning> {
+    let padded: Vec<&Distinct> = values
+        .iter()
+        .filter(|value| value.text.trim() != value.text)
+        .collect();
+    if padded.is_empty() {
+        return None;
+    }
+    let message = format!(
+        "Колонка «{}»: пробелы в начале или в конце значения — {} из {}",
+        name,
+        padded.iter().map(|value| value.count).sum::<usize>(),
+        filled
+    );
+    Some(warning(
+        name,
+        WarningKind::Whitespace,
+        &padded,
+        message,
+        |value| format!("«{}»", value.text),
+    ))
+}
+
+/// Выбросы по правилу Тьюки; самые далёкие от квартилей — первыми в примерах
+fn outliers_warning(name: &str, numbers: &Numbers, stats: &NumberStats) -> Option<QualityWarning> {
+    let spread = stats.quantiles.p75 - stats.quantiles.p25;
+    if spread <= 0.0 {
+        return None;
+    }
+    let low = stats.quantiles.p25 - OUTLIER_FENCE * spread;
+    let high = stats.quantiles.p75 + OUTLIER_FENCE * spread;
+    let distance = |value: f64| (low - value).max(value - high);
+    let mut outliers: Vec<(f64, usize)> = numbers
+        .values
+        .iter()
+        .copied()
+        .filter(|&(value, _)| value < low || value > high)
+        .collect();
+    if outliers.is_empty() {
+        return None;
+    }
+    outliers.sort_by(|a, b| distance(b.0).total_cmp(&distance(a.0)));
+    let count = outliers.iter().map(|(_, count)| count).sum();
+    let round = |value: f64| format_number((value * 100.0).round() / 100.0);
+    Some(QualityWarning {
+        column: name.to_string(),
+        kind: WarningKind::Outliers,
+        count,
+        message: format!(
+            "Колонка «{}»: выбросы — {} из {} чисел вне диапазона {} … {}",
+            name,
+            count,
+            numbers.count,
+            round(low),
+            round(high)
+        ),
+        examples: outliers
+            .iter()
+            .take(MAX_EXAMPLES)
+            .map(|&(value, _)| format_number(value))
+            .collect(),
+    })
+}
+
+fn date_stats(values: &[Distinct]) -> Option<DateStats> {
+    let dates: Vec<(NaiveDateTime, usize)> = values
+        .iter()
+        .filter(|value| value.kind == ColumnType::Date)
+        .filter_map(|value| parse_date(&value.text).map(|date| (date, value.count)))
+        .collect();
+    let min = dates.iter().map(|(date, _)| *date).min()?;
+    let max = dates.iter().map(|(date, _)| *date).max()?;
+    let with_time = dates.iter().any(|(date, _)| date.time() != NaiveTime::MIN);
+    Some(DateStats {
+        count: dates.iter().map(|(_, count)| count).sum(),
+        min: format_date(min, with_time),
+        max: format_date(max, with_time),
+        days: (max - min).num_days(),
+    })
+}
+
+fn length_stats(values: &[Distinct], filled: usize) -> Option<LengthStats> {
+    if filled == 0 {
+        return None;
+    }
+    let lengths: Vec<(usize, usize)> = values
+        .iter()
+        .map(|value| (value.text.chars().count(), value.count))
+        .collect();
+    let mut buckets: BTreeMap<u32, usize> = BTreeMap::new();
+    for &(length, count) in &lengths {
+        *buckets.entry(length.max(1).ilog2()).or_default() += count;
+    }
+    Some(LengthStats {
+        min: lengths.iter().map(|(length, _)| *length).min().unwrap_or(0),
+        max: lengths.iter().map(|(length, _)| *length).max().unwrap_or(0),
+        mean: lengths.iter().map(|&(l, c)| (l * c) as f64).sum::<f64>() / filled as f64,
+        buckets: buckets
+            .into_iter()
+            .map(|(power, count)| LengthBucket {
+                from: 1 << power,
+                to: (1 << (power + 1)) - 1,
+                count,
+            })
+            .collect(),
+    })
+}
+
+fn column_stats(
+    column: &Column,
+    declared: Option<ColumnType>,
+    selection: &[u32],
+    top: usize,
+    warnings: &mut Vec<QualityWarning>,
+) -> ColumnStats {
+    let values: Vec<Distinct> = value_counts(column, selection)
+        .into_iter()
+        .map(|(row, count)| {
+            let text = column.text(row as usize);
+            let kind = value_type(&text);
+            Distinct { text, count, kind }
+        })
+        .collect();
+    let filled: usize = values.iter().map(|value| value.count).sum();
+    let mut value_types = TypeCounts::default();
+    for value in &values {
+        *value_types.slot(value.kind) += value.count;
+    }
+    let detected = value_types.dominant();
+    let name = column.name.as_str();
+
+    let mut numbers = None;
+    let mut dates = None;
+    match detected {
+        Some(ColumnType::Number) => {
+            let parsed = values
+                .iter()
+                .filter(|value| value.kind == ColumnType::Number)
+                .filter_map(|value| parse_number(&value.text).map(|n| (n, value.count)))
+                .collect();
+            if let Some(sorted) = Numbers::new(parsed) {
+                let stats = sorted.stats();
+                warnings.extend(outliers_warning(name, &sorted, &stats));
+                numbers = Some(stats);
+            }
+        }
+        Some(ColumnType::Date) => dates = date_stats(&values),
+        _ => {}
+    }
+    if let Some(detected) = detected {
+        warnings.extend(mixed_types_warning(name, &values, detected, filled));
+        warnings.extend(coercion_warnings(name, &values, detected, filled));
+    }
+    warnings.extend(whitespace_warning(name, &values, filled));
+
+    let mut ranked: Vec<&Distinct> = values.iter().collect();
+    ranked.sort_by_key(|value| Reverse(value.count));
+    ColumnStats {
+        name: column.name.clone(),
+        column_type: declared.or(detected).unwrap_or(ColumnType::String),
+        detected_type: detected,
+        filled,
+        nulls: selection.len() - filled,
+        distinct: values.len(),
+        value_types,
+        numbers,
+        dates,
+        lengths: length_stats(&values, filled),
+        top: ranked
+            .into_iter()
+            .take(top)
+            .map(|value| TopValue {
+                value: value.text.to_string(),
+                count: value.count,
+            })
+            .collect(),
+    }
+}
+
+/// Профиль набора: статистика по каждой колонке и предупреждения о качестве
+/// данных. Значения разбираются один раз на уникальное значение колонки
+pub fn dataset_stats(
+    table: &ColumnarTable,
+    schema: &[ColumnSchema],
+    query: &StatsQuery,
+) -> Result<DatasetStats> {
+    if query.top > MAX_TOP {
+        bail!("Число частых значений — от 0 до {}", MAX_TOP);
+    }
+    let selection = select_all(table);
+    let mut warnings = Vec::new();
+    let columns = table
+        .columns
+        .iter()
+        .map(|column| {
+            let declared = schema
+                .iter()
+                .find(|c| c.name == column.name)
+                .map(|c| c.column_type);
+            column_stats(column, declared, &selection, query.top, &mut warnings)
+        })
+        .collect();
+    Ok(DatasetStats {
+        row_count: table.row_count,
+        columns,
+        warnings,
+    })
+}
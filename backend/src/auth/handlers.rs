@@ -194,7 +194,7 @@ pub async fn me(
     }
 }
 
-pub async fn logout(jar: CookieJar) -> (StatusCode, HeaderMap, Json<serde_json::Value>) {
+pub async fn logout(_jar: CookieJar) -> (StatusCode, HeaderMap, Json<serde_json::Value>) {
     let mut cookie = Cookie::new("remora_token", "");
     cookie.set_path("/");
     cookie.set_http_only(true);
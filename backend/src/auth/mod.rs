@@ -1,59 +1,14 @@
 use axum::routing::{delete, get, post};
 use axum::Router;
-use sqlx::sqlite::SqlitePoolOptions;
-use std::{env, fs, path::PathBuf};
+use sqlx::{Pool, Sqlite};
 
-mod db;
+pub(crate) mod db;
 pub mod handlers;
 mod models;
 
 use crate::protect;
 
-pub async fn setup_router() -> Router {
-    let cwd = env::current_dir().expect("❌ Cannot get current dir");
-    println!("🔍 Current working dir: {}", cwd.display());
-
-    // 🗂️ Универсальный путь для базы данных
-    // 1. Если есть /app/data → используем его (Docker)
-    // 2. Иначе — backend/data (локальная разработка)
-    let db_dir = if PathBuf::from("/app/data").exists() {
-        PathBuf::from("/app/data")
-    } else if cwd.join("data").exists() {
-        cwd.join("data")
-    } else {
-        cwd.join("backend/data")
-    };
-
-    // ✅ Создаём директорию, если её нет
-    if let Err(e) = fs::create_dir_all(&db_dir) {
-        eprintln!(
-            "⚠️ Failed to ensure data dir: {} — {:?}",
-            db_dir.display(),
-            e
-        );
-    }
-
-    let db_path = db_dir.join("users.db");
-
-    // ✅ Создаём файл, если он не существует
-    if !db_path.exists() {
-        match fs::File::create(&db_path) {
-            Ok(_) => println!("🆕 Created empty database file at {}", db_path.display()),
-            Err(e) => eprintln!("⚠️ Failed to create database file: {}", e),
-        }
-    }
-
-    // 🧩 Формируем URL для SQLite
-    let db_url = format!("sqlite://{}", db_path.display());
-    println!("📦 Using database at: {}", db_url);
-
-    // 🗄️ Подключаемся
-    let pool = SqlitePoolOptions::new()
-        .max_connections(5)
-        .connect(&db_url)
-        .await
-        .unwrap_or_else(|e| panic!("❌ Cannot connect to users.db: {e:?}"));
-
+pub async fn setup_router(pool: Pool<Sqlite>) -> Router {
     // ⚙️ Применяем миграцию
     if let Err(e) = sqlx::query(models::USER_MIGRATION).execute(&pool).await {
         panic!("❌ Migration failed: {}", e);
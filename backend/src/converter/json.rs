@@ -0,0 +1,90 @@
+use serde_json::{Map, Value};
+use std::io::{self, Read};
+
+/// Ключи объекта-обёртки, под которыми API обычно отдают массив записей
+const WRAPPER_KEYS: &[&str] = &["data", "rows", "items", "records", "results"];
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Значение JSON как текст ячейки: вложенные объекты и массивы остаются JSON
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        Value::Bool(flag) => flag.to_string(),
+        Value::Number(number) => number.to_string(),
+        nested => nested.to_string(),
+    }
+}
+
+fn from_objects(items: &[Value]) -> io::Result<(Vec<String>, Vec<Vec<String>>)> {
+    let mut columns: Vec<String> = Vec::new();
+    for item in items {
+        let object = item
+            .as_object()
+            .ok_or_else(|| invalid("элементы массива должны быть объектами"))?;
+        for key in object.keys() {
+            if !columns.contains(key) {
+                columns.push(key.clone());
+            }
+        }
+    }
+    let rows = items
+        .iter()
+        .filter_map(Value::as_object)
+        .map(|object| {
+            columns
+                .iter()
+                .map(|name| object.get(name).map(cell).unwrap_or_default())
+                .collect()
+        })
+        .collect();
+    Ok((columns, rows))
+}
+
+fn from_arrays(items: &[Value]) -> io::Result<Vec<Vec<String>>> {
+    items
+        .iter()
+        .map(|item| {
+            item.as_array()
+                .map(|row| row.iter().map(cell).collect())
+                .ok_or_else(|| invalid("строки таблицы должны быть массивами"))
+        })
+        .collect()
+}
+
+fn from_array(items: &[Value]) -> io::Result<(Vec<String>, Vec<Vec<String>>)> {
+    match items.first() {
+        None => Ok((vec![], vec![])),
+        Some(Value::Array(_)) => Ok(super::utils::split_header_rows(from_arrays(items)?)),
+        Some(_) => from_objects(items),
+    }
+}
+
+fn from_object(object: &Map<String, Value>) -> io::Result<(Vec<String>, Vec<Vec<String>>)> {
+    if let (Some(Value::Array(columns)), Some(Value::Array(rows))) =
+        (object.get("columns"), object.get("rows"))
+    {
+        if rows.iter().all(Value::is_array) {
+            return Ok((columns.iter().map(cell).collect(), from_arrays(rows)?));
+        }
+    }
+    WRAPPER_KEYS
+        .iter()
+        .find_map(|key| object.get(*key).and_then(Value::as_array))
+        .map(|items| from_array(items))
+        .unwrap_or_else(|| Err(invalid("в объекте нет массива записей")))
+}
+
+/// Конвертация JSON → Vec<Vec<String>>: массив объектов, массив массивов
+/// (первый — заголовки), `{columns, rows}` или обёртка вида `{"data": [...]}`
+pub fn convert_json_to_vec<R: Read>(reader: R) -> io::Result<(Vec<String>, Vec<Vec<String>>)> {
+    let value: Value = serde_json::from_reader(reader).map_err(io::Error::from)?;
+    match &value {
+        Value::Array(items) => from_array(items),
+        Value::Object(object) => from_object(object),
+        _ => Err(invalid("ожидается массив или объект")),
+    }
+}
@@ -1,6 +1,7 @@
 use csv::ReaderBuilder;
 use std::io::{self, Cursor, Read, Seek};
 
+pub mod json;
 pub mod ods;
 pub mod utils;
 pub mod xlsx;
@@ -22,7 +23,7 @@ pub fn convert_csv_to_vec<R: Read>(mut reader: R) -> io::Result<(Vec<String>, Ve
         .map(|&d| (d, sample.iter().filter(|&&c| c == d).count()))
         .collect::<Vec<_>>();
 
-    counts.sort_by(|a, b| b.1.cmp(&a.1)); // по убыванию количества
+    counts.sort_by_key(|&(_, count)| std::cmp::Reverse(count)); // по убыванию количества
     let detected = if counts.first().map(|(_, c)| *c).unwrap_or(0) > 0 {
         counts.first().unwrap().0
     } else {
@@ -61,3 +62,23 @@ pub fn convert_ods_to_vec<R: Read + Seek>(
     let rows = ods::convert_ods_to_vec(reader)?;
     Ok(utils::split_header_rows(rows))
 }
+
+/// Разбор загруженного файла по расширению имени: CSV, XLSX, ODS или JSON
+pub fn convert_by_extension(
+    filename: &str,
+    data: Vec<u8>,
+) -> io::Result<(Vec<String>, Vec<Vec<String>>)> {
+    let ext = filename.to_lowercase();
+    let reader = io::BufReader::new(Cursor::new(data));
+    if ext.ends_with(".csv") {
+        convert_csv_to_vec(reader)
+    } else if ext.ends_with(".xlsx") {
+        convert_xlsx_to_vec(reader)
+    } else if ext.ends_with(".ods") {
+        convert_ods_to_vec(reader)
+    } else if ext.ends_with(".json") {
+        json::convert_json_to_vec(reader)
+    } else {
+        Err(io::Error::new(io::ErrorKind::InvalidInput, "unsupported"))
+    }
+}
@@ -51,13 +51,11 @@ pub fn convert_ods_to_vec<R: Read + Seek>(mut reader: R) -> io::Result<Vec<Vec<S
                 }
                 _ => {}
             },
-            Ok(Event::Text(t)) => {
-                if in_cell {
-                    let raw = t.as_ref();
-                    let text = unescape(std::str::from_utf8(raw).unwrap_or(""))
-                        .unwrap_or(Cow::Borrowed(""));
-                    current_text.push_str(&text);
-                }
+            Ok(Event::Text(t)) if in_cell => {
+                let raw = t.as_ref();
+                let text =
+                    unescape(std::str::from_utf8(raw).unwrap_or("")).unwrap_or(Cow::Borrowed(""));
+                current_text.push_str(&text);
             }
             Ok(Event::End(ref e)) => match e.name().as_ref() {
                 b"table:table-cell" => {
@@ -73,10 +71,8 @@ pub fn convert_ods_to_vec<R: Read + Seek>(mut reader: R) -> io::Result<Vec<Vec<S
                     };
                     current_row.push(value);
                 }
-                b"table:table-row" => {
-                    if !current_row.is_empty() {
-                        rows.push(current_row.clone());
-                    }
+                b"table:table-row" if !current_row.is_empty() => {
+                    rows.push(current_row.clone());
                 }
                 _ => {}
             },
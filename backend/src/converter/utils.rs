@@ -52,10 +52,8 @@ pub fn extract_header_rows<R: Read>(rdr: &mut Reader<R>) -> (Vec<String>, Vec<Ve
         .map(|s| s.to_string())
         .collect();
     let mut rows = Vec::new();
-    for record in rdr.records() {
-        if let Ok(rec) = record {
-            rows.push(rec.iter().map(|s| s.to_string()).collect());
-        }
+    for rec in rdr.records().flatten() {
+        rows.push(rec.iter().map(|s| s.to_string()).collect());
     }
     (headers, rows)
 }
@@ -3,7 +3,6 @@ use chrono::NaiveDate;
 use quick_xml::escape::unescape;
 use quick_xml::events::Event;
 use std::borrow::Cow;
-use std::collections::HashSet;
 use std::io::{self, BufReader, Read, Seek};
 use zip::read::ZipArchive;
 
@@ -12,12 +11,12 @@ pub fn convert_xlsx_to_vec<R: Read + Seek>(reader: R) -> io::Result<Vec<Vec<Stri
     let buf_reader = BufReader::new(reader);
     let mut zip = open_zip(buf_reader)?;
     let shared_strings = read_shared_strings(&mut zip)?;
-    let (rows, _) = read_sheet(&mut zip, &shared_strings)?;
+    let rows = read_sheet(&mut zip, &shared_strings)?;
     Ok(rows)
 }
 
 /// Чтение sharedStrings.xml
-fn read_shared_strings<R: Read + Seek>(zip: &mut ZipArchive<R>) -> io::Result<Vec<String>> {
+pub fn read_shared_strings<R: Read + Seek>(zip: &mut ZipArchive<R>) -> io::Result<Vec<String>> {
     let xml = match read_zip_file(zip, "xl/sharedStrings.xml")? {
         Some(s) => s,
         None => return Ok(Vec::new()),
@@ -56,8 +55,8 @@ fn read_shared_strings<R: Read + Seek>(zip: &mut ZipArchive<R>) -> io::Result<Ve
 /// Чтение листа
 fn read_sheet<R: Read + Seek>(
     zip: &mut ZipArchive<R>,
-    shared: &Vec<String>,
-) -> io::Result<(Vec<Vec<String>>, HashSet<(usize, usize)>)> {
+    shared: &[String],
+) -> io::Result<Vec<Vec<String>>> {
     let xml = match read_zip_file(zip, "xl/worksheets/sheet1.xml")? {
         Some(s) => s,
         None => {
@@ -204,7 +203,7 @@ fn read_sheet<R: Read + Seek>(
         }
     }
 
-    Ok((rows, HashSet::new()))
+    Ok(rows)
 }
 
 fn format_excel_value(v: &str) -> String {
@@ -0,0 +1,386 @@
+use crate::datasets::cleaning::{self, CleaningReport};
+use crate::datasets::models::DatasetRecord;
+use crate::datasets::schema::{parse_bool, parse_date, ColumnSchema, ColumnType};
+use crate::datasets::store::{CleanedTable, StoredTable};
+use crate::exporter::parse_number;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Сколько примеров неподходящих значений попадает в сообщение об ошибке
+const MAX_TYPE_EXAMPLES: usize = 3;
+
+/// Что делать со строкой, ключ которой уже есть в наборе
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum AppendMode {
+    /// Оставить прежнюю строку
+    #[default]
+    Insert,
+    /// Заменить прежнюю строку новой, если она отличается
+    Upsert,
+}
+
+/// Настройка дозагрузки, хранится с набором; без ключей строки
+/// добавляются все подряд
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AppendConfig {
+    #[serde(default)]
+    pub keys: Vec<String>,
+    #[serde(default)]
+    pub mode: AppendMode,
+}
+
+/// Файл не подходит к набору или ключи не совпадают с его колонками —
+/// отдаётся клиенту как 400
+#[derive(Debug)]
+pub struct AppendRejected(pub String);
+
+impl std::fmt::Display for AppendRejected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for AppendRejected {}
+
+/// Настройка дозагрузки из JSON записи набора
+pub fn parse_config(raw: Option<&str>) -> Option<AppendConfig> {
+    raw.and_then(|raw| serde_json::from_str(raw).ok())
+}
+
+/// Итог дозагрузки
+#[derive(Serialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct AppendReport {
+    pub inserted: usize,
+    pub updated: usize,
+    pub skipped: usize,
+    pub rows: usize,
+    pub warnings: Vec<String>,
+}
+
+fn fits(value: &str, column_type: ColumnType) -> bool {
+    match column_type {
+        ColumnType::String => true,
+        ColumnType::Number => parse_number(value).is_some(),
+        ColumnType::Date => parse_date(value).is_some(),
+        ColumnType::Boolean => parse_bool(value).is_some(),
+    }
+}
+
+fn type_label(column_type: ColumnType) -> &'static str {
+    match column_type {
+        ColumnType::String => "строка",
+        ColumnType::Number => "число",
+        ColumnType::Date => "дата",
+        ColumnType::Boolean => "да/нет",
+    }
+}
+
+/// Проверка нового файла по схеме набора: все колонки набора должны быть,
+/// значения — подходить по типу. Лишние колонки отбрасываются с предупреждением
+pub fn validate_incoming(schema: &[ColumnSchema], incoming: &StoredTable) -> Result<Vec<String>> {
+    let missing: Vec<&str> = schema
+        .iter()
+        .map(|c| c.name.as_str())
+        .filter(|name| !incoming.columns.iter().any(|c| c == name))
+        .collect();
+    if !missing.is_empty() {
+        bail!(AppendRejected(format!(
+            "В файле нет колонок набора: {}",
+            missing.join(", ")
+        )));
+    }
+
+    let mut errors = Vec::new();
+    for column in schema {
+        let Some(idx) = incoming.columns.iter().position(|c| *c == column.name) else {
+            continue;
+        };
+        let bad: Vec<&str> = incoming
+            .rows
+            .iter()
+            .filter_map(|row| row.get(idx).map(|v| v.trim()))
+            .filter(|value| !value.is_empty() && !fits(value, column.column_type))
+            .take(MAX_TYPE_EXAMPLES)
+            .collect();
+        if !bad.is_empty() {
+            errors.push(format!(
+                "«{}» — ожидается {}, в файле: {}",
+                column.name,
+                type_label(column.column_type),
+                bad.join(", ")
+            ));
+        }
+    }
+    if !errors.is_empty() {
+        bail!(AppendRejected(format!(
+            "Значения не подходят по типу: {}",
+            errors.join("; ")
+        )));
+    }
+
+    let extra: Vec<&str> = incoming
+        .columns
+        .iter()
+        .map(String::as_str)
+        .filter(|name| !schema.iter().any(|c| c.name == *name))
+        .collect();
+    Ok(if extra.is_empty() {
+        Vec::new()
+    } else {
+        vec![format!(
+            "Колонки, которых нет в наборе, пропущены: {}",
+            extra.join(", ")
+        )]
+    })
+}
+
+/// Перестановка колонок нового файла в порядок колонок набора
+pub fn align(incoming: StoredTable, columns: &[String]) -> StoredTable {
+    let order: Vec<Option<usize>> = columns
+        .iter()
+        .map(|name| incoming.columns.iter().position(|c| c == name))
+        .collect();
+    let rows = incoming
+        .rows
+        .into_iter()
+        .map(|row| {
+            order
+                .iter()
+                .map(|idx| {
+                    idx.and_then(|idx| row.get(idx).cloned())
+                        .unwrap_or_default()
+                })
+                .collect()
+        })
+        .collect();
+    StoredTable {
+        columns: columns.to_vec(),
+        rows,
+    }
+}
+
+/// Проверка ключевых колонок по списку колонок набора
+pub fn validate_keys(keys: &[String], columns: &[String]) -> Result<()> {
+    for key in keys {
+        if !columns.contains(key) {
+            bail!(AppendRejected(format!(
+                "Ключевая колонка «{}» отсутствует в наборе данных",
+                key
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Добавление строк к набору с колонками в одном порядке. Повторы ключа
+/// внутри нового файла сравниваются с уже добавленными строками, поэтому
+/// при `upsert` побеждает последняя строка, при `insert` — первая
+pub fn merge(
+    existing: &mut StoredTable,
+    incoming: StoredTable,
+    config: &AppendConfig,
+) -> AppendReport {
+    let mut report = AppendReport::default();
+    let width = existing.columns.len();
+    for row in &mut existing.rows {
+        row.resize(width, String::new());
+    }
+    let key_idx: Vec<usize> = config
+        .keys
+        .iter()
+        .filter_map(|key| existing.columns.iter().position(|c| c == key))
+        .collect();
+    let key_of = |row: &[String]| -> Option<String> {
+        let parts: Vec<&str> = key_idx
+            .iter()
+            .map(|&idx| row.get(idx).map_or("", |v| v.trim()))
+            .collect();
+        (!parts.iter().all(|p| p.is_empty())).then(|| parts.join("\u{1f}"))
+    };
+
+    let mut index: HashMap<String, usize> = HashMap::new();
+    if !key_idx.is_empty() {
+        for (pos, row) in existing.rows.iter().enumerate() {
+            if let Some(key) = key_of(row) {
+                index.entry(key).or_insert(pos);
+            }
+        }
+    }
+
+    let mut empty_keys = 0;
+    for row in incoming.rows {
+        if key_idx.is_empty() {
+            existing.rows.push(row);
+            report.inserted += 1;
+            continue;
+        }
+        let Some(key) = key_of(&row) else {
+            empty_keys += 1;
+            continue;
+        };
+        match index.get(&key) {
+            None => {
+                index.insert(key, existing.rows.len());
+                existing.rows.push(row);
+                report.inserted += 1;
+            }
+            Some(&pos) if config.mode == AppendMode::Upsert && existing.rows[pos] != row => {
+                existing.rows[pos] = row;
+                report.updated += 1;
+            }
+            Some(_) => report.skipped += 1,
+        }
+    }
+    if empty_keys > 0 {
+        report.skipped += empty_keys;
+        report
+            .warnings
+            .push(format!("Строк с пустым ключом пропущено: {}", empty_keys));
+    }
+    report.rows = existing.rows.len();
+    report
+}
+
+/// Дозагрузка файла в текущие строки набора: рецепт очистки, проверка по
+/// схеме, слияние по ключам. Исходный файл в результате — прежний вместе
+/// с новым
+pub fn apply(
+    record: &DatasetRecord,
+    current_source: StoredTable,
+    mut current: StoredTable,
+    incoming: StoredTable,
+    config: &AppendConfig,
+) -> Result<(CleanedTable, AppendReport, CleaningReport)> {
+    let schema: Vec<ColumnSchema> = serde_json::from_str(&record.schema).unwrap_or_default();
+    let columns: Vec<String> = schema.iter().map(|c| c.name.clone()).collect();
+    validate_keys(&config.keys, &columns)?;
+    let steps = cleaning::parse_recipe(&record.cleaning);
+    let (cleaned, cleaning_report) = cleaning::clean_source(incoming, &steps, true, None)?;
+    let warnings = validate_incoming(&schema, &cleaned.table)?;
+    let incoming = align(cleaned.table, &current.columns);
+    let mut report = merge(&mut current, incoming, config);
+    report.warnings.splice(0..0, warnings);
+    let source = cleaned
+        .source
+        .map(|raw| concat_sources(current_source, raw));
+    let cleaned = CleanedTable {
+        source_filename: None,
+        source,
+        schema: cleaning_report.schema(&current),
+        table: current,
+        cleaning: record.cleaning.clone(),
+    };
+    Ok((cleaned, report, cleaning_report))
+}
+
+/// Ключи ко всему набору заново — когда строки собираются из исходного
+/// файла всех дозагрузок (смена рецепта, замена файла); возвращает число
+/// отброшенных строк — повторов и строк с пустым ключом
+pub fn dedupe(table: &mut StoredTable, config: &AppendConfig) -> usize {
+    if config.keys.is_empty() {
+        return 0;
+    }
+    let mut merged = StoredTable {
+        columns: table.columns.clone(),
+        rows: Vec::new(),
+    };
+    let rows = std::mem::take(&mut table.rows);
+    let report = merge(
+        &mut merged,
+        StoredTable {
+            columns: table.columns.clone(),
+            rows,
+        },
+        config,
+    );
+    *table = merged;
+    report.skipped + report.updated
+}
+
+/// Исходный файл набора после дозагрузки: строки нового файла дописываются
+/// к прежним, колонки объединяются по именам
+pub fn concat_sources(mut current: StoredTable, incoming: StoredTable) -> StoredTable {
+    for name in &incoming.columns {
+        if !current.columns.contains(name) {
+            current.columns.push(name.clone());
+        }
+    }
+    let columns = current.columns.clone();
+    let width = columns.len();
+    for row in &mut current.rows {
+        row.resize(width, String::new());
+    }
+    current.rows.extend(align(incoming, &columns).rows);
+    current
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(rows: &[[&str; 2]]) -> StoredTable {
+        StoredTable {
+            columns: vec!["id".into(), "value".into()],
+            rows: rows
+                .iter()
+                .map(|row| row.iter().map(|v| v.to_string()).collect())
+                .collect(),
+        }
+    }
+
+    fn keyed(mode: AppendMode) -> AppendConfig {
+        AppendConfig {
+            keys: vec!["id".into()],
+            mode,
+        }
+    }
+
+    #[test]
+    fn insert_skips_known_keys() {
+        let mut existing = table(&[["1", "a"], ["2", "b"]]);
+        let incoming = table(&[["2", "changed"], ["3", "c"], ["3", "dup"], ["", "x"]]);
+        let report = merge(&mut existing, incoming, &keyed(AppendMode::Insert));
+        assert_eq!(
+            (report.inserted, report.updated, report.skipped, report.rows),
+            (1, 0, 3, 3)
+        );
+        assert_eq!(
+            existing.rows,
+            table(&[["1", "a"], ["2", "b"], ["3", "c"]]).rows
+        );
+        assert_eq!(report.warnings, ["Строк с пустым ключом пропущено: 1"]);
+    }
+
+    #[test]
+    fn upsert_replaces_changed_rows() {
+        let mut existing = table(&[["1", "a"], ["2", "b"]]);
+        let incoming = table(&[["1", "a"], ["2", "new"], ["3", "c"], ["3", "last"]]);
+        let report = merge(&mut existing, incoming, &keyed(AppendMode::Upsert));
+        assert_eq!(
+            (report.inserted, report.updated, report.skipped, report.rows),
+            (1, 2, 1, 3)
+        );
+        assert_eq!(
+            existing.rows,
+            table(&[["1", "a"], ["2", "new"], ["3", "last"]]).rows
+        );
+    }
+
+    #[test]
+    fn without_keys_every_row_is_added() {
+        let mut existing = table(&[["1", "a"]]);
+        let report = merge(
+            &mut existing,
+            table(&[["1", "a"], ["", ""]]),
+            &AppendConfig::default(),
+        );
+        assert_eq!(
+            (report.inserted, report.updated, report.skipped, report.rows),
+            (2, 0, 0, 3)
+        );
+    }
+}
@@ -0,0 +1,573 @@
+use crate::datasets::append::{self, AppendConfig};
+use crate::datasets::schema::{infer_schema, parse_bool, parse_date, ColumnSchema, ColumnType};
+use crate::datasets::store::{normalize_name, CleanedTable, StoredTable};
+use crate::exporter::parse_number;
+use crate::expr::Value;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Предел числа шагов одного рецепта
+pub const MAX_CLEANING_STEPS: usize = 100;
+/// Предел числа колонок, на которые делится одна колонка
+const MAX_SPLIT_PARTS: usize = 50;
+
+fn default_separator() -> String {
+    " ".to_string()
+}
+
+/// Шаг очистки; рецепт — упорядоченный список шагов, который применяется
+/// к исходному файлу при загрузке и при каждой замене файла.
+/// Пустой список колонок означает «все колонки»
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "op", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum CleaningStep {
+    /// Пробелы по краям значений и заголовков
+    Trim {
+        #[serde(default)]
+        columns: Vec<String>,
+    },
+    DropEmptyRows,
+    DropEmptyColumns,
+    /// Повторы строк по выбранным колонкам; остаётся первая строка
+    Deduplicate {
+        #[serde(default)]
+        columns: Vec<String>,
+    },
+    Rename {
+        from: String,
+        to: String,
+    },
+    RemoveColumns {
+        columns: Vec<String>,
+    },
+    /// Перечисленные колонки ставятся первыми, остальные — следом в прежнем порядке
+    Reorder {
+        columns: Vec<String>,
+    },
+    /// Деление колонки по разделителю; последняя часть забирает остаток
+    Split {
+        column: String,
+        separator: String,
+        into: Vec<String>,
+        #[serde(default)]
+        keep_original: bool,
+    },
+    /// Склейка колонок через разделитель; новая колонка встаёт на место первой
+    Merge {
+        columns: Vec<String>,
+        #[serde(default = "default_separator")]
+        separator: String,
+        into: String,
+        #[serde(default)]
+        keep_original: bool,
+    },
+    /// Пустые ячейки заполняются значением сверху (объединённые ячейки выгрузок)
+    FillDown {
+        #[serde(default)]
+        columns: Vec<String>,
+    },
+    /// Замена подстроки или, при `wholeCell`, всего значения ячейки
+    Replace {
+        #[serde(default)]
+        columns: Vec<String>,
+        from: String,
+        #[serde(default)]
+        to: String,
+        #[serde(default)]
+        whole_cell: bool,
+    },
+    /// Приведение значений к типу; неподходящие значения очищаются
+    ChangeType {
+        column: String,
+        #[serde(rename = "type")]
+        column_type: ColumnType,
+    },
+}
+
+impl CleaningStep {
+    pub fn op(&self) -> &'static str {
+        match self {
+            CleaningStep::Trim { .. } => "trim",
+            CleaningStep::DropEmptyRows => "dropEmptyRows",
+            CleaningStep::DropEmptyColumns => "dropEmptyColumns",
+            CleaningStep::Deduplicate { .. } => "deduplicate",
+            CleaningStep::Rename { .. } => "rename",
+            CleaningStep::RemoveColumns { .. } => "removeColumns",
+            CleaningStep::Reorder { .. } => "reorder",
+            CleaningStep::Split { .. } => "split",
+            CleaningStep::Merge { .. } => "merge",
+            CleaningStep::FillDown { .. } => "fillDown",
+            CleaningStep::Replace { .. } => "replace",
+            CleaningStep::ChangeType { .. } => "changeType",
+        }
+    }
+}
+
+/// Итог одного шага: сколько ячеек, строк или колонок он затронул
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct StepReport {
+    pub op: &'static str,
+    pub changed: usize,
+    pub skipped: bool,
+}
+
+/// Итог применения рецепта
+#[derive(Serialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CleaningReport {
+    pub rows_before: usize,
+    pub rows_after: usize,
+    pub columns_before: usize,
+    pub columns_after: usize,
+    pub steps: Vec<StepReport>,
+    pub warnings: Vec<String>,
+    /// Типы, заданные шагами `changeType`, по итоговым именам колонок
+    #[serde(skip)]
+    types: HashMap<String, ColumnType>,
+}
+
+impl CleaningReport {
+    /// Схема очищенной таблицы: выведенная по значениям, с типами из рецепта
+    pub fn schema(&self, table: &StoredTable) -> Vec<ColumnSchema> {
+        let mut schema = infer_schema(&table.columns, &table.rows);
+        for column in &mut schema {
+            if let Some(column_type) = self.types.get(&column.name) {
+                column.column_type = *column_type;
+            }
+        }
+        schema
+    }
+}
+
+/// Рецепт из JSON записи набора
+pub fn parse_recipe(raw: &str) -> Vec<CleaningStep> {
+    serde_json::from_str(raw).unwrap_or_default()
+}
+
+fn check_name(name: &str, step: usize) -> Result<()> {
+    if normalize_name(name).is_none() {
+        bail!("Шаг {}: некорректное имя колонки", step);
+    }
+    Ok(())
+}
+
+/// Проверка рецепта без данных: имена, число частей и шагов. Ссылки
+/// на колонки проверяются при применении — файл может смениться
+pub fn validate_recipe(steps: &[CleaningStep]) -> Result<()> {
+    if steps.len() > MAX_CLEANING_STEPS {
+        bail!("Шагов очистки не может быть больше {}", MAX_CLEANING_STEPS);
+    }
+    for (idx, step) in steps.iter().enumerate() {
+        let number = idx + 1;
+        match step {
+            CleaningStep::Rename { to, .. } => check_name(to, number)?,
+            CleaningStep::RemoveColumns { columns } | CleaningStep::Reorder { columns }
+                if columns.is_empty() =>
+            {
+                bail!("Шаг {}: не выбраны колонки", number)
+            }
+            CleaningStep::Split {
+                separator, into, ..
+            } => {
+                if separator.is_empty() {
+                    bail!("Шаг {}: пустой разделитель", number);
+                }
+                if into.is_empty() || into.len() > MAX_SPLIT_PARTS {
+                    bail!(
+                        "Шаг {}: колонка делится на 1–{} частей",
+                        number,
+                        MAX_SPLIT_PARTS
+                    );
+                }
+                for name in into {
+                    check_name(name, number)?;
+                }
+            }
+            CleaningStep::Merge { columns, into, .. } => {
+                if columns.len() < 2 {
+                    bail!("Шаг {}: для склейки нужны хотя бы две колонки", number);
+                }
+                check_name(into, number)?;
+            }
+            CleaningStep::Replace { from, .. } if from.is_empty() => {
+                bail!("Шаг {}: не задано заменяемое значение", number)
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Применение рецепта по порядку. Шаг, который ссылается на отсутствующую
+/// колонку или создал бы повтор имени, пропускается с предупреждением,
+/// чтобы замена файла не ломала загрузку
+pub fn apply_recipe(table: &mut StoredTable, steps: &[CleaningStep]) -> CleaningReport {
+    // короткие строки дополняются, чтобы шаги могли опираться на индексы
+    let width = table.columns.len();
+    for row in &mut table.rows {
+        row.resize(width, String::new());
+    }
+    let mut report = CleaningReport {
+        rows_before: table.rows.len(),
+        columns_before: width,
+        ..Default::default()
+    };
+    for (idx, step) in steps.iter().enumerate() {
+        let (changed, skipped) = match apply_step(table, step, idx + 1, &mut report) {
+            Ok(changed) => (changed, false),
+            Err(e) => {
+                report.warnings.push(format!(
+                    "Шаг {} ({}): {} — шаг пропущен",
+                    idx + 1,
+                    step.op(),
+                    e
+                ));
+                (0, true)
+            }
+        };
+        report.steps.push(StepReport {
+            op: step.op(),
+            changed,
+            skipped,
+        });
+    }
+    report.rows_after = table.rows.len();
+    report.columns_after = table.columns.len();
+    report
+}
+
+/// Очистка исходного файла набора по рецепту; `keep_source` — исходный файл
+/// новый и его нужно сохранить. Ключи дозагрузки применяются после рецепта:
+/// исходный файл хранит строки всех дозагрузок подряд
+pub fn clean_source(
+    source: StoredTable,
+    steps: &[CleaningStep],
+    keep_source: bool,
+    keys: Option<&AppendConfig>,
+) -> Result<(CleanedTable, CleaningReport)> {
+    let cleaning = serde_json::to_string(steps)?;
+    let mut table = source.clone();
+    let mut report = apply_recipe(&mut table, steps);
+    let removed = keys.map_or(0, |config| append::dedupe(&mut table, config));
+    if removed > 0 {
+        report.rows_after = table.rows.len();
+        report.warnings.push(format!(
+            "Строк отброшено по ключевым колонкам (повторы и пустые ключи): {}",
+            removed
+        ));
+    }
+    let cleaned = CleanedTable {
+        source_filename: None,
+        source: keep_source.then_some(source),
+        schema: report.schema(&table),
+        table,
+        cleaning,
+    };
+    Ok((cleaned, report))
+}
+
+fn find_column(table: &StoredTable, name: &str) -> Result<usize> {
+    match table.columns.iter().position(|c| c == name) {
+        Some(idx) => Ok(idx),
+        None => bail!("колонка «{}» отсутствует", name),
+    }
+}
+
+/// Индексы выбранных колонок; пустой выбор — все колонки
+fn select_columns(table: &StoredTable, names: &[String]) -> Result<Vec<usize>> {
+    if names.is_empty() {
+        return Ok((0..table.columns.len()).collect());
+    }
+    names.iter().map(|name| find_column(table, name)).collect()
+}
+
+fn ensure_free(table: &StoredTable, name: &str, except: &[usize]) -> Result<()> {
+    let taken = table
+        .columns
+        .iter()
+        .enumerate()
+        .any(|(idx, c)| c == name && !except.contains(&idx));
+    if taken {
+        bail!("колонка «{}» уже есть", name);
+    }
+    Ok(())
+}
+
+/// Оставляет колонки с указанными индексами в заданном порядке
+fn project(table: &mut StoredTable, order: &[usize]) {
+    table.columns = order
+        .iter()
+        .map(|&idx| table.columns[idx].clone())
+        .collect();
+    for row in &mut table.rows {
+        *row = order
+            .iter()
+            .map(|&idx| std::mem::take(&mut row[idx]))
+            .collect();
+    }
+}
+
+fn convert(value: &str, column_type: ColumnType) -> Option<String> {
+    match column_type {
+        ColumnType::String => Some(value.to_string()),
+        ColumnType::Number => parse_number(value).map(|n| Value::Number(n).to_cell()),
+        ColumnType::Date => parse_date(value).map(|d| Value::Date(d).to_cell()),
+        ColumnType::Boolean => parse_bool(value).map(|b| b.to_string()),
+    }
+}
+
+fn apply_step(
+    table: &mut StoredTable,
+    step: &CleaningStep,
+    number: usize,
+    report: &mut CleaningReport,
+) -> Result<usize> {
+    let types = &mut report.types;
+    let changed = match step {
+        CleaningStep::Trim { columns } => {
+            let selected = select_columns(table, columns)?;
+            let mut headers = table.columns.clone();
+            for &idx in &selected {
+                let trimmed = headers[idx].trim();
+                if !trimmed.is_empty() {
+                    headers[idx] = trimmed.to_string();
+                }
+            }
+            let unique: HashSet<&String> = headers.iter().collect();
+            if unique.len() != headers.len() {
+                bail!("после обрезки пробелов совпадут имена колонок");
+            }
+            table.columns = headers;
+            let mut changed = 0;
+            for row in &mut table.rows {
+                for &idx in &selected {
+                    let cell = &mut row[idx];
+                    let trimmed = cell.trim();
+                    if trimmed.len() != cell.len() {
+                        *cell = trimmed.to_string();
+                        changed += 1;
+                    }
+                }
+            }
+            changed
+        }
+        CleaningStep::DropEmptyRows => {
+            let before = table.rows.len();
+            table
+                .rows
+                .retain(|row| row.iter().any(|cell| !cell.trim().is_empty()));
+            before - table.rows.len()
+        }
+        CleaningStep::DropEmptyColumns => {
+            let keep: Vec<usize> = (0..table.columns.len())
+                .filter(|&idx| table.rows.iter().any(|row| !row[idx].trim().is_empty()))
+                .collect();
+            let removed = table.columns.len() - keep.len();
+            project(table, &keep);
+            removed
+        }
+        CleaningStep::Deduplicate { columns } => {
+            let selected = select_columns(table, columns)?;
+            let mut seen = HashSet::new();
+            let before = table.rows.len();
+            table.rows.retain(|row| {
+                let key: Vec<&str> = selected.iter().map(|&idx| row[idx].as_str()).collect();
+                seen.insert(key.join("\u{1f}"))
+            });
+            before - table.rows.len()
+        }
+        CleaningStep::Rename { from, to } => {
+            let idx = find_column(table, from)?;
+            let to = normalize_name(to).unwrap_or_default();
+            ensure_free(table, &to, &[idx])?;
+            if let Some(column_type) = types.remove(from) {
+                types.insert(to.clone(), column_type);
+            }
+            table.columns[idx] = to;
+            1
+        }
+        CleaningStep::RemoveColumns { columns } => {
+            let removed = select_columns(table, columns)?;
+            let keep: Vec<usize> = (0..table.columns.len())
+                .filter(|idx| !removed.contains(idx))
+                .collect();
+            for name in columns {
+                types.remove(name);
+            }
+            project(table, &keep);
+            removed.len()
+        }
+        CleaningStep::Reorder { columns } => {
+            let mut order = select_columns(table, columns)?;
+            let mut seen = HashSet::new();
+            order.retain(|idx| seen.insert(*idx));
+            let rest: Vec<usize> = (0..table.columns.len())
+                .filter(|idx| !order.contains(idx))
+                .collect();
+            order.extend(rest);
+            project(table, &order);
+            columns.len()
+        }
+        CleaningStep::Split {
+            column,
+            separator,
+            into,
+            keep_original,
+        } => {
+            let source = find_column(table, column)?;
+            let replaced: &[usize] = if *keep_original { &[] } else { &[source] };
+            let mut names = HashSet::new();
+            for name in into {
+                ensure_free(table, name, replaced)?;
+                if !names.insert(name) {
+                    bail!("колонка «{}» указана дважды", name);
+                }
+            }
+            let mut changed = 0;
+            for row in &mut table.rows {
+                let mut parts: Vec<String> = row[source]
+                    .splitn(into.len(), separator.as_str())
+                    .map(|part| part.trim().to_string())
+                    .collect();
+                if parts.len() > 1 {
+                    changed += 1;
+                }
+                parts.resize(into.len(), String::new());
+                let at = if *keep_original { source + 1 } else { source };
+                if !keep_original {
+                    row.remove(source);
+                }
+                row.splice(at..at, parts);
+            }
+            let at = if *keep_original {
+                source + 1
+            } else {
+                types.remove(column);
+                table.columns.remove(source);
+                source
+            };
+            table.columns.splice(at..at, into.iter().cloned());
+            changed
+        }
+        CleaningStep::Merge {
+            columns,
+            separator,
+            into,
+            keep_original,
+        } => {
+            let selected = select_columns(table, columns)?;
+            let replaced: &[usize] = if *keep_original { &[] } else { &selected };
+            ensure_free(table, into, replaced)?;
+            let first = *selected.iter().min().unwrap_or(&0);
+            let merged: Vec<String> = table
+                .rows
+                .iter()
+                .map(|row| {
+                    selected
+                        .iter()
+                        .map(|&idx| row[idx].trim())
+                        .filter(|part| !part.is_empty())
+                        .collect::<Vec<_>>()
+                        .join(separator)
+                })
+                .collect();
+            if !keep_original {
+                let keep: Vec<usize> = (0..table.columns.len())
+                    .filter(|idx| !selected.contains(idx))
+                    .collect();
+                for name in columns {
+                    types.remove(name);
+                }
+                project(table, &keep);
+            }
+            let at = if *keep_original {
+                selected.iter().max().map_or(0, |idx| idx + 1)
+            } else {
+                first
+            };
+            table.columns.insert(at, into.clone());
+            for (row, value) in table.rows.iter_mut().zip(merged) {
+                row.insert(at, value);
+            }
+            table.rows.len()
+        }
+        CleaningStep::FillDown { columns } => {
+            let selected = select_columns(table, columns)?;
+            let mut last = vec![String::new(); table.columns.len()];
+            let mut changed = 0;
+            for row in &mut table.rows {
+                for &idx in &selected {
+                    if row[idx].trim().is_empty() {
+                        if !last[idx].is_empty() {
+                            row[idx] = last[idx].clone();
+                            changed += 1;
+                        }
+                    } else {
+                        last[idx] = row[idx].clone();
+                    }
+                }
+            }
+            changed
+        }
+        CleaningStep::Replace {
+            columns,
+            from,
+            to,
+            whole_cell,
+        } => {
+            let selected = select_columns(table, columns)?;
+            let mut changed = 0;
+            for row in &mut table.rows {
+                for &idx in &selected {
+                    let cell = &mut row[idx];
+                    if *whole_cell {
+                        if cell.trim() == from.trim() {
+                            *cell = to.clone();
+                            changed += 1;
+                        }
+                    } else if cell.contains(from.as_str()) {
+                        *cell = cell.replace(from.as_str(), to);
+                        changed += 1;
+                    }
+                }
+            }
+            changed
+        }
+        CleaningStep::ChangeType {
+            column,
+            column_type,
+        } => {
+            let idx = find_column(table, column)?;
+            let mut cleared = 0;
+            let mut changed = 0;
+            for row in &mut table.rows {
+                let cell = &mut row[idx];
+                if cell.trim().is_empty() {
+                    continue;
+                }
+                match convert(cell, *column_type) {
+                    Some(value) if value != *cell => {
+                        *cell = value;
+                        changed += 1;
+                    }
+                    Some(_) => {}
+                    None => {
+                        cell.clear();
+                        cleared += 1;
+                    }
+                }
+            }
+            types.insert(column.clone(), *column_type);
+            if cleared > 0 {
+                report.warnings.push(format!(
+                    "Шаг {} (changeType): значений колонки «{}», не приведённых к типу, очищено: {}",
+                    number, column, cleared
+                ));
+            }
+            changed + cleared
+        }
+    };
+    Ok(changed)
+}
@@ -0,0 +1,153 @@
+use crate::datasets::schema::{ColumnSchema, ColumnType};
+use crate::datasets::store::{normalize_name, StoredTable};
+use crate::expr::{self, Compiled};
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+
+/// Предел числа вычисляемых колонок одного набора
+pub const MAX_COMPUTED_COLUMNS: usize = 50;
+
+/// Вычисляемая колонка в запросе на сохранение
+#[derive(Deserialize, Clone, Debug)]
+pub struct ComputedColumnInput {
+    pub name: String,
+    pub expression: String,
+}
+
+/// Сохранённая вычисляемая колонка; тип результата определяется при проверке
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ComputedColumn {
+    pub name: String,
+    pub expression: String,
+    #[serde(rename = "type")]
+    pub column_type: ColumnType,
+}
+
+impl ComputedColumn {
+    fn schema(&self) -> ColumnSchema {
+        ColumnSchema {
+            name: self.name.clone(),
+            column_type: self.column_type,
+            nullable: true,
+        }
+    }
+}
+
+/// Список вычисляемых колонок из JSON записи набора
+pub fn parse_computed(raw: &str) -> Vec<ComputedColumn> {
+    serde_json::from_str(raw).unwrap_or_default()
+}
+
+/// Схема набора вместе с вычисляемыми колонками
+pub fn extended_schema(schema: &[ColumnSchema], computed: &[ComputedColumn]) -> Vec<ColumnSchema> {
+    schema
+        .iter()
+        .cloned()
+        .chain(computed.iter().map(ComputedColumn::schema))
+        .collect()
+}
+
+/// Проверка списка колонок по схеме набора; каждая колонка может ссылаться
+/// на исходные колонки и на вычисляемые, объявленные раньше неё
+pub fn validate_columns(
+    schema: &[ColumnSchema],
+    inputs: &[ComputedColumnInput],
+) -> Result<Vec<ComputedColumn>> {
+    if inputs.len() > MAX_COMPUTED_COLUMNS {
+        bail!(
+            "Вычисляемых колонок не может быть больше {}",
+            MAX_COMPUTED_COLUMNS
+        );
+    }
+    let mut scope = schema.to_vec();
+    let mut columns = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        let name = normalize_name(&input.name)
+            .ok_or_else(|| anyhow!("Некорректное имя вычисляемой колонки"))?;
+        if scope.iter().any(|c| c.name == name) {
+            bail!("Колонка «{}» уже есть в наборе данных", name);
+        }
+        let compiled = expr::compile(&input.expression, &scope)
+            .map_err(|e| anyhow!("Колонка «{}»: {}", name, e))?;
+        let column = ComputedColumn {
+            name,
+            expression: input.expression.trim().to_string(),
+            column_type: compiled.result_type().column_type(),
+        };
+        scope.push(column.schema());
+        columns.push(column);
+    }
+    Ok(columns)
+}
+
+/// Компиляция сохранённых колонок по порядку; колонка с ошибкой — `None`
+fn compile_all(
+    schema: &[ColumnSchema],
+    computed: &[ComputedColumn],
+) -> (Vec<Option<Compiled>>, Vec<String>) {
+    let mut scope = schema.to_vec();
+    let mut compiled: Vec<Option<Compiled>> = Vec::with_capacity(computed.len());
+    let mut errors = Vec::new();
+    for column in computed {
+        match expr::compile(&column.expression, &scope) {
+            Ok(expression) => compiled.push(Some(expression)),
+            Err(e) => {
+                errors.push(format!("Колонка «{}»: {}", column.name, e));
+                compiled.push(None);
+            }
+        }
+        scope.push(column.schema());
+    }
+    (compiled, errors)
+}
+
+/// Ошибки вычисляемых колонок на другой схеме — например, новой версии файла
+pub fn check_computed(schema: &[ColumnSchema], computed: &[ComputedColumn]) -> Vec<String> {
+    compile_all(schema, computed).1
+}
+
+/// Дописывает вычисляемые колонки к строкам набора. Колонка, выражение которой
+/// перестало сходиться со схемой (например, после замены файла), остаётся пустой,
+/// чтобы не ломать виджеты; описания таких ошибок возвращаются
+pub fn apply_computed(
+    table: &mut StoredTable,
+    schema: &[ColumnSchema],
+    computed: &[ComputedColumn],
+) -> Vec<String> {
+    let (compiled, errors) = compile_all(schema, computed);
+
+    let width = table.columns.len();
+    table
+        .columns
+        .extend(computed.iter().map(|column| column.name.clone()));
+    for row in &mut table.rows {
+        // короткие строки дополняются, чтобы индексы вычисляемых колонок совпали
+        row.resize(width, String::new());
+        for expression in &compiled {
+            let value = expression
+                .as_ref()
+                .map(|e| e.eval(row).to_cell())
+                .unwrap_or_default();
+            row.push(value);
+        }
+    }
+    errors
+}
+
+/// Значения выражения на первых строках — для предпросмотра в редакторе
+pub fn preview(
+    table: &StoredTable,
+    schema: &[ColumnSchema],
+    expression: &str,
+    rows: usize,
+) -> Result<(ColumnType, Vec<String>)> {
+    let compiled = expr::compile(expression, schema)?;
+    let values = table
+        .rows
+        .iter()
+        .take(rows)
+        .map(|row| compiled.eval(row).to_cell())
+        .collect();
+    Ok((compiled.result_type().column_type(), values))
+}
@@ -0,0 +1,451 @@
+use crate::analytics::filter::format_number;
+use crate::datasets::schema::{parse_date, ColumnSchema, ColumnType};
+use crate::datasets::store::StoredTable;
+use crate::exporter::parse_number;
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Предел строк производного набора: соединение «многие ко многим»
+/// легко раздувает результат
+pub const MAX_DERIVED_ROWS: usize = 1_000_000;
+/// Предел числа наборов в объединении
+pub const MAX_UNION_SOURCES: usize = 20;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum JoinKind {
+    #[default]
+    Inner,
+    Left,
+    Full,
+}
+
+/// Пара ключевых колонок: левая из первого набора, правая — из второго
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct JoinKey {
+    pub left: String,
+    pub right: String,
+}
+
+/// Описание производного набора; хранится в `definition` вместе с флагом
+/// материализации
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DerivedSpec {
+    #[serde(rename_all = "camelCase")]
+    Join {
+        left: String,
+        right: String,
+        on: Vec<JoinKey>,
+        #[serde(default)]
+        how: JoinKind,
+    },
+    #[serde(rename_all = "camelCase")]
+    Union {
+        datasets: Vec<String>,
+        /// Имя колонки с названием исходного набора; без неё строки неразличимы
+        #[serde(default)]
+        source_column: Option<String>,
+    },
+}
+
+/// Содержимое `definition` производного набора
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DerivedDefinition {
+    #[serde(flatten)]
+    pub spec: DerivedSpec,
+    /// Строки сохранены в файл и пересобираются по запросу;
+    /// иначе набор строится заново при каждом чтении
+    #[serde(default)]
+    pub materialized: bool,
+}
+
+impl DerivedSpec {
+    /// Идентификаторы исходных наборов по порядку
+    pub fn sources(&self) -> Vec<&str> {
+        match self {
+            DerivedSpec::Join { left, right, .. } => vec![left.as_str(), right.as_str()],
+            DerivedSpec::Union { datasets, .. } => datasets.iter().map(String::as_str).collect(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            DerivedSpec::Join { on, .. } if on.is_empty() => {
+                bail!("Не указаны ключевые колонки соединения")
+            }
+            DerivedSpec::Union { datasets, .. } if datasets.len() < 2 => {
+                bail!("Для объединения нужно не меньше двух наборов")
+            }
+            DerivedSpec::Union { datasets, .. } if datasets.len() > MAX_UNION_SOURCES => {
+                bail!("Объединять можно не больше {} наборов", MAX_UNION_SOURCES)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Исходный набор для построения: имя, схема и строки
+pub struct Source {
+    pub name: String,
+    pub schema: Vec<ColumnSchema>,
+    pub table: StoredTable,
+}
+
+impl Source {
+    fn index(&self, column: &str) -> Result<usize> {
+        self.table
+            .columns
+            .iter()
+            .position(|c| c == column)
+            .ok_or_else(|| {
+                anyhow::anyhow!("Колонка «{}» отсутствует в наборе «{}»", column, self.name)
+            })
+    }
+
+    fn column_type(&self, column: &str) -> ColumnType {
+        self.schema
+            .iter()
+            .find(|c| c.name == column)
+            .map_or(ColumnType::String, |c| c.column_type)
+    }
+}
+
+/// Сводка построения: что совпало, что нет и на что обратить внимание
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct DerivedReport {
+    pub rows: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub matched_left: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unmatched_left: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unmatched_right: Option<usize>,
+    /// Ключи, встречающиеся в наборе больше одного раза
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duplicate_left_keys: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duplicate_right_keys: Option<usize>,
+    /// Строки с пустым ключом: они ни с чем не соединяются
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub empty_keys: Option<usize>,
+    pub warnings: Vec<String>,
+}
+
+fn type_name(column_type: ColumnType) -> &'static str {
+    match column_type {
+        ColumnType::String => "string",
+        ColumnType::Number => "number",
+        ColumnType::Date => "date",
+        ColumnType::Boolean => "boolean",
+    }
+}
+
+/// Тип, к которому приводятся обе стороны ключа; при расхождении число
+/// важнее даты, дата — строки
+fn common_type(left: ColumnType, right: ColumnType) -> ColumnType {
+    if left == right {
+        left
+    } else if left == ColumnType::Number || right == ColumnType::Number {
+        ColumnType::Number
+    } else if left == ColumnType::Date || right == ColumnType::Date {
+        ColumnType::Date
+    } else {
+        ColumnType::String
+    }
+}
+
+/// Каноническая запись значения ключа: `1.0` и `1`, `01.02.2024`
+/// и `2024-02-01` совпадают
+fn key_part(value: &str, column_type: ColumnType) -> String {
+    let value = value.trim();
+    let canonical = match column_type {
+        ColumnType::Number => parse_number(value).map(format_number),
+        ColumnType::Date => parse_date(value).map(|d| d.format("%Y-%m-%dT%H:%M:%S").to_string()),
+        ColumnType::Boolean => Some(value.to_lowercase()),
+        ColumnType::String => None,
+    };
+    canonical.unwrap_or_else(|| value.to_string())
+}
+
+/// Ключ строки; `None`, если хотя бы одна часть пуста
+fn row_key(row: &[String], columns: &[(usize, ColumnType)]) -> Option<Vec<String>> {
+    columns
+        .iter()
+        .map(|&(idx, column_type)| {
+            let value = row.get(idx).map(String::as_str).unwrap_or("");
+            (!value.trim().is_empty()).then(|| key_part(value, column_type))
+        })
+        .collect()
+}
+
+fn check_size(rows: usize) -> Result<()> {
+    if rows > MAX_DERIVED_ROWS {
+        bail!(
+            "Результат превышает {} строк — проверьте ключи соединения",
+            MAX_DERIVED_ROWS
+        );
+    }
+    Ok(())
+}
+
+/// Соединение двух наборов по ключевым колонкам. Колонки правого набора,
+/// совпавшие по имени с левыми, получают суффикс с именем набора;
+/// правые ключи в результат не попадают — их значения совпадают с левыми
+pub fn join(
+    left: &Source,
+    right: &Source,
+    on: &[JoinKey],
+    how: JoinKind,
+) -> Result<(StoredTable, DerivedReport)> {
+    let mut report = DerivedReport::default();
+    let mut left_keys = Vec::with_capacity(on.len());
+    let mut right_keys = Vec::with_capacity(on.len());
+    for key in on {
+        let (left_type, right_type) = (left.column_type(&key.left), right.column_type(&key.right));
+        if left_type != right_type {
+            report.warnings.push(format!(
+                "Ключ «{}» ({}) и «{}» ({}) разных типов; значения сравниваются как {}",
+                key.left,
+                type_name(left_type),
+                key.right,
+                type_name(right_type),
+                type_name(common_type(left_type, right_type))
+            ));
+        }
+        let common = common_type(left_type, right_type);
+        left_keys.push((left.index(&key.left)?, common));
+        right_keys.push((right.index(&key.right)?, common));
+    }
+
+    let right_key_idx: Vec<usize> = right_keys.iter().map(|(idx, _)| *idx).collect();
+    let right_columns: Vec<usize> = (0..right.table.columns.len())
+        .filter(|idx| !right_key_idx.contains(idx))
+        .collect();
+
+    let mut columns = left.table.columns.clone();
+    for &idx in &right_columns {
+        let name = &right.table.columns[idx];
+        if columns.contains(name) {
+            columns.push(format!("{} ({})", name, right.name));
+        } else {
+            columns.push(name.clone());
+        }
+    }
+
+    let mut empty_keys = 0;
+    let mut index: HashMap<Vec<String>, Vec<usize>> = HashMap::new();
+    for (row_idx, row) in right.table.rows.iter().enumerate() {
+        match row_key(row, &right_keys) {
+            Some(key) => index.entry(key).or_default().push(row_idx),
+            None => empty_keys += 1,
+        }
+    }
+    let duplicate_right = index.values().filter(|rows| rows.len() > 1).count();
+
+    let left_width = left.table.columns.len();
+    let mut rows = Vec::new();
+    let mut right_matched = vec![false; right.table.rows.len()];
+    let mut left_counts: HashMap<Vec<String>, usize> = HashMap::new();
+    let mut matched_left = 0;
+
+    for row in &left.table.rows {
+        let key = row_key(row, &left_keys);
+        let matches = match &key {
+            Some(key) => {
+                *left_counts.entry(key.clone()).or_default() += 1;
+                index.get(key).map(Vec::as_slice).unwrap_or(&[])
+            }
+            None => {
+                empty_keys += 1;
+                &[]
+            }
+        };
+        let mut base = row.clone();
+        base.resize(left_width, String::new());
+
+        if matches.is_empty() {
+            if how != JoinKind::Inner {
+                base.extend(right_columns.iter().map(|_| String::new()));
+                rows.push(base);
+            }
+            continue;
+        }
+        matched_left += 1;
+        for &right_idx in matches {
+            right_matched[right_idx] = true;
+            let right_row = &right.table.rows[right_idx];
+            let mut combined = base.clone();
+            combined.extend(
+                right_columns
+                    .iter()
+                    .map(|&idx| right_row.get(idx).cloned().unwrap_or_default()),
+            );
+            rows.push(combined);
+        }
+        check_size(rows.len())?;
+    }
+
+    let unmatched_right = right_matched.iter().filter(|matched| !**matched).count();
+    if how == JoinKind::Full {
+        for (right_idx, right_row) in right.table.rows.iter().enumerate() {
+            if right_matched[right_idx] {
+                continue;
+            }
+            // ключевые колонки левой стороны заполняются значениями правой
+            let mut combined = vec![String::new(); left_width];
+            for ((left_idx, _), (right_key, _)) in left_keys.iter().zip(&right_keys) {
+                combined[*left_idx] = right_row.get(*right_key).cloned().unwrap_or_default();
+            }
+            combined.extend(
+                right_columns
+                    .iter()
+                    .map(|&idx| right_row.get(idx).cloned().unwrap_or_default()),
+            );
+            rows.push(combined);
+        }
+        check_size(rows.len())?;
+    }
+
+    let duplicate_left = left_counts.values().filter(|count| **count > 1).count();
+    if duplicate_left > 0 && duplicate_right > 0 {
+        report.warnings.push(format!(
+            "Ключи повторяются в обоих наборах ({} и {}): соединение «многие ко многим» размножает строки",
+            duplicate_left, duplicate_right
+        ));
+    } else if duplicate_right > 0 {
+        report.warnings.push(format!(
+            "В наборе «{}» повторяющихся ключей: {} — строки «{}» будут продублированы",
+            right.name, duplicate_right, left.name
+        ));
+    }
+    if empty_keys > 0 {
+        report.warnings.push(format!(
+            "Строк с пустым ключом: {} — они не участвуют в соединении",
+            empty_keys
+        ));
+    }
+    if matched_left == 0 && !left.table.rows.is_empty() && !right.table.rows.is_empty() {
+        report
+            .warnings
+            .push("Ни одна строка не совпала по ключам".to_string());
+    }
+
+    report.rows = rows.len();
+    report.matched_left = Some(matched_left);
+    report.unmatched_left = Some(left.table.rows.len() - matched_left);
+    report.unmatched_right = Some(unmatched_right);
+    report.duplicate_left_keys = Some(duplicate_left);
+    report.duplicate_right_keys = Some(duplicate_right);
+    report.empty_keys = Some(empty_keys);
+    Ok((StoredTable { columns, rows }, report))
+}
+
+/// Объединение наборов с одинаковым составом колонок; колонки сопоставляются
+/// по именам, порядок берётся из первого набора
+pub fn union(
+    sources: &[Source],
+    source_column: Option<&str>,
+) -> Result<(StoredTable, DerivedReport)> {
+    let Some(first) = sources.first() else {
+        bail!("Нет наборов для объединения");
+    };
+    let mut report = DerivedReport::default();
+    let mut columns = first.table.columns.clone();
+
+    let mut mappings = Vec::with_capacity(sources.len());
+    for source in sources {
+        let missing: Vec<&str> = columns
+            .iter()
+            .filter(|c| !source.table.columns.contains(c))
+            .map(String::as_str)
+            .collect();
+        let extra: Vec<&str> = source
+            .table
+            .columns
+            .iter()
+            .filter(|c| !columns.contains(c))
+            .map(String::as_str)
+            .collect();
+        if !missing.is_empty() || !extra.is_empty() {
+            let mut problems = Vec::new();
+            if !missing.is_empty() {
+                problems.push(format!("нет колонок {}", missing.join(", ")));
+            }
+            if !extra.is_empty() {
+                problems.push(format!("лишние колонки {}", extra.join(", ")));
+            }
+            bail!(
+                "Набор «{}» не совпадает по составу колонок с «{}»: {}",
+                source.name,
+                first.name,
+                problems.join("; ")
+            );
+        }
+        for column in &columns {
+            let (expected, actual) = (first.column_type(column), source.column_type(column));
+            if expected != actual {
+                report.warnings.push(format!(
+                    "Колонка «{}»: в «{}» {}, в «{}» {}",
+                    column,
+                    first.name,
+                    type_name(expected),
+                    source.name,
+                    type_name(actual)
+                ));
+            }
+        }
+        let mapping: Vec<usize> = columns
+            .iter()
+            .map(|c| {
+                source
+                    .table
+                    .columns
+                    .iter()
+                    .position(|s| s == c)
+                    .unwrap_or(0)
+            })
+            .collect();
+        mappings.push(mapping);
+    }
+
+    let total: usize = sources.iter().map(|s| s.table.rows.len()).sum();
+    check_size(total)?;
+    let source_column = source_column.map(str::trim).filter(|c| !c.is_empty());
+    if let Some(name) = source_column {
+        if columns.iter().any(|c| c == name) {
+            bail!("Колонка «{}» уже есть в наборах", name);
+        }
+        columns.push(name.to_string());
+    }
+
+    let mut rows = Vec::with_capacity(total);
+    for (source, mapping) in sources.iter().zip(&mappings) {
+        for row in &source.table.rows {
+            let mut aligned: Vec<String> = mapping
+                .iter()
+                .map(|&idx| row.get(idx).cloned().unwrap_or_default())
+                .collect();
+            if source_column.is_some() {
+                aligned.push(source.name.clone());
+            }
+            rows.push(aligned);
+        }
+    }
+    report.rows = rows.len();
+    Ok((StoredTable { columns, rows }, report))
+}
+
+/// Построение производного набора по готовым исходным наборам
+pub fn build(spec: &DerivedSpec, sources: &[Source]) -> Result<(StoredTable, DerivedReport)> {
+    match spec {
+        DerivedSpec::Join { on, how, .. } => match sources {
+            [left, right] => join(left, right, on, *how),
+            _ => bail!("Для соединения нужны ровно два набора"),
+        },
+        DerivedSpec::Union { source_column, .. } => union(sources, source_column.as_deref()),
+    }
+}
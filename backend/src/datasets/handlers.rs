@@ -0,0 +1,1282 @@
+use crate::auth::db::get_user_by_login;
+use crate::auth::handlers::Claims;
+use crate::converter::convert_by_extension;
+use crate::datasets::append::{self, AppendConfig, AppendMode, AppendRejected};
+use crate::datasets::cleaning::{self, CleaningReport, CleaningStep};
+use crate::datasets::computed::{self, apply_computed, parse_computed, ComputedColumnInput};
+use crate::datasets::derived::{self, DerivedDefinition, DerivedReport, DerivedSpec, Source};
+use crate::datasets::models::{DatasetInfo, DatasetRecord, KIND_DERIVED, KIND_FILE, KIND_SQL};
+use crate::datasets::refresh::{self, RefreshSource, RefreshStatus};
+use crate::datasets::schema::ColumnSchema;
+use crate::datasets::store::{self, CleanedTable, StoredTable, VersionConflict};
+use crate::datasets::versions::{self, SchemaDiff, VersionInfo, WidgetColumns};
+use crate::middleware::auth::has_role;
+use axum::{
+    extract::{Multipart, Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::{Pool, Sqlite};
+use std::time::Instant;
+
+type ApiResult = (StatusCode, Json<serde_json::Value>);
+
+#[derive(Deserialize)]
+pub struct RenameInput {
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct ComputedInput {
+    pub columns: Vec<ComputedColumnInput>,
+}
+
+#[derive(Deserialize)]
+pub struct ExpressionInput {
+    pub expression: String,
+}
+
+/// Сколько строк показывается в предпросмотре выражения
+const PREVIEW_ROWS: usize = 20;
+
+#[derive(Deserialize)]
+pub struct CleaningInput {
+    pub steps: Vec<CleaningStep>,
+}
+
+#[derive(Deserialize)]
+pub struct ImportInput {
+    pub name: Option<String>,
+    pub source: RefreshSource,
+    #[serde(default)]
+    pub cleaning: Vec<CleaningStep>,
+}
+
+#[derive(Deserialize)]
+pub struct SourceInput {
+    /// `null` — отвязать набор от источника
+    pub source: Option<RefreshSource>,
+}
+
+/// Сколько очищенных строк показывается в предпросмотре рецепта
+const CLEANING_PREVIEW_ROWS: usize = 50;
+
+#[derive(Deserialize)]
+pub struct DerivedInput {
+    pub name: String,
+    #[serde(flatten)]
+    pub definition: DerivedDefinition,
+}
+
+/// Сколько строк производного набора показывается до сохранения
+const DERIVED_PREVIEW_ROWS: usize = 50;
+
+pub(crate) fn error(status: StatusCode, message: impl Into<String>) -> ApiResult {
+    (status, Json(json!({ "error": message.into() })))
+}
+
+/// Администраторы видят все наборы, пользователи — только свои
+pub(crate) fn can_access(claims: &Claims, record: &DatasetRecord) -> bool {
+    record.owner == claims.sub || has_role(&claims.role, "Admin")
+}
+
+/// Набор по идентификатору из URL с проверкой доступа; ошибка — готовый ответ
+pub(crate) async fn find_dataset(
+    pool: &Pool<Sqlite>,
+    claims: &Claims,
+    id: &str,
+) -> Result<DatasetRecord, ApiResult> {
+    if !store::is_valid_id(id) {
+        return Err(error(StatusCode::NOT_FOUND, "Набор данных не найден"));
+    }
+    match store::get_dataset(pool, id).await {
+        Ok(Some(record)) if can_access(claims, &record) => Ok(record),
+        Ok(_) => Err(error(StatusCode::NOT_FOUND, "Набор данных не найден")),
+        Err(e) => Err(error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+}
+
+pub async fn list_datasets(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(claims): Extension<Claims>,
+) -> ApiResult {
+    let owner = (!has_role(&claims.role, "Admin")).then_some(claims.sub.as_str());
+    match store::list_datasets(&pool, owner).await {
+        Ok(records) => {
+            let datasets: Vec<DatasetInfo> = records.into_iter().map(Into::into).collect();
+            (StatusCode::OK, Json(json!({ "datasets": datasets })))
+        }
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Поля формы загрузки файла
+struct UploadForm {
+    name: Option<String>,
+    file: Option<(String, Vec<u8>)>,
+    cleaning: Option<String>,
+    /// Виджеты дашбордов с колонками набора — для предупреждений о замене файла
+    widgets: Option<String>,
+    /// Ключевые колонки дозагрузки (JSON-массив) и режим `insert`/`upsert`
+    keys: Option<String>,
+    mode: Option<String>,
+    /// Только показать разницу схем и итог очистки, ничего не сохраняя
+    dry_run: bool,
+}
+
+async fn read_upload_form(multipart: &mut Multipart) -> Result<UploadForm, ApiResult> {
+    let mut form = UploadForm {
+        name: None,
+        file: None,
+        cleaning: None,
+        widgets: None,
+        keys: None,
+        mode: None,
+        dry_run: false,
+    };
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => return Err(error(StatusCode::BAD_REQUEST, e.to_string())),
+        };
+        let field_name = field.name().unwrap_or("").to_string();
+        match field_name.as_str() {
+            "name" | "cleaning" | "widgets" | "keys" | "mode" | "dryRun" => {
+                let text = match field.text().await {
+                    Ok(text) => text,
+                    Err(e) => return Err(error(StatusCode::BAD_REQUEST, e.to_string())),
+                };
+                match field_name.as_str() {
+                    "name" => form.name = Some(text),
+                    "cleaning" => form.cleaning = Some(text),
+                    "widgets" => form.widgets = Some(text),
+                    "keys" => form.keys = Some(text),
+                    "mode" => form.mode = Some(text),
+                    _ => form.dry_run = text.trim() == "true",
+                }
+            }
+            "file" => {
+                let filename = field.file_name().unwrap_or("неизвестно").to_string();
+                match field.bytes().await {
+                    Ok(bytes) => form.file = Some((filename, bytes.to_vec())),
+                    Err(e) => return Err(error(StatusCode::BAD_REQUEST, e.to_string())),
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(form)
+}
+
+/// Разбор загруженного файла в отдельном потоке
+async fn parse_upload(filename: &str, bytes: Vec<u8>) -> Result<StoredTable, ApiResult> {
+    let parse_name = filename.to_string();
+    let parsed =
+        tokio::task::spawn_blocking(move || convert_by_extension(&parse_name, bytes)).await;
+    let (columns, rows) = match parsed {
+        Ok(Ok(table)) => table,
+        Ok(Err(e)) if e.kind() == std::io::ErrorKind::InvalidInput => {
+            return Err(error(
+                StatusCode::BAD_REQUEST,
+                "Поддерживаются только файлы CSV, XLSX, ODS и JSON",
+            ))
+        }
+        Ok(Err(e)) => {
+            return Err(error(
+                StatusCode::BAD_REQUEST,
+                format!("Не удалось разобрать файл: {}", e),
+            ))
+        }
+        Err(e) => return Err(error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    };
+    if columns.is_empty() {
+        return Err(error(
+            StatusCode::BAD_REQUEST,
+            "В файле нет заголовков колонок",
+        ));
+    }
+    Ok(StoredTable { columns, rows })
+}
+
+fn parse_cleaning(raw: &str) -> Result<Vec<CleaningStep>, ApiResult> {
+    let steps: Vec<CleaningStep> = serde_json::from_str(raw).map_err(|e| {
+        error(
+            StatusCode::BAD_REQUEST,
+            format!("Некорректный рецепт очистки: {}", e),
+        )
+    })?;
+    cleaning::validate_recipe(&steps).map_err(|e| error(StatusCode::BAD_REQUEST, e.to_string()))?;
+    Ok(steps)
+}
+
+/// Очистка исходной таблицы по рецепту в отдельном потоке
+async fn clean_table(
+    source: StoredTable,
+    steps: Vec<CleaningStep>,
+    keep_source: bool,
+    keys: Option<AppendConfig>,
+) -> Result<(CleanedTable, CleaningReport), ApiResult> {
+    let cleaned = tokio::task::spawn_blocking(move || {
+        cleaning::clean_source(source, &steps, keep_source, keys.as_ref())
+    })
+    .await;
+    match cleaned {
+        Ok(Ok(cleaned)) => Ok(cleaned),
+        Ok(Err(e)) => Err(error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+        Err(e) => Err(error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+}
+
+fn log_cleaning(name: &str, report: &CleaningReport) {
+    for warning in &report.warnings {
+        eprintln!("⚠️ dataset {}: {}", name, warning);
+    }
+}
+
+/// Загрузка набора: поле `file` (CSV/XLSX/ODS/JSON), необязательные поля `name`
+/// и `cleaning` — рецепт очистки JSON-массивом шагов
+pub async fn create_dataset(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(claims): Extension<Claims>,
+    mut multipart: Multipart,
+) -> ApiResult {
+    let form = match read_upload_form(&mut multipart).await {
+        Ok(form) => form,
+        Err(response) => return response,
+    };
+    let Some((filename, bytes)) = form.file else {
+        return error(StatusCode::BAD_REQUEST, "Файл не передан");
+    };
+    let raw_name = form
+        .name
+        .filter(|n| !n.trim().is_empty())
+        .unwrap_or_else(|| filename.clone());
+    let Some(name) = store::normalize_name(&raw_name) else {
+        return error(StatusCode::BAD_REQUEST, "Некорректное имя набора данных");
+    };
+    let steps = match form.cleaning.as_deref().map(parse_cleaning) {
+        Some(Ok(steps)) => steps,
+        Some(Err(response)) => return response,
+        None => Vec::new(),
+    };
+
+    let started = Instant::now();
+    let size_kb = bytes.len() as f64 / 1024.0;
+    let source = match parse_upload(&filename, bytes).await {
+        Ok(table) => table,
+        Err(response) => return response,
+    };
+    let has_recipe = !steps.is_empty();
+    let (mut cleaned, report) = match clean_table(source, steps, true, None).await {
+        Ok(cleaned) => cleaned,
+        Err(response) => return response,
+    };
+    cleaned.source_filename = Some(filename);
+
+    match store::create_dataset(&pool, &name, &claims.sub, cleaned).await {
+        Ok(record) => {
+            println!(
+                "💾 dataset saved: {:<25} | {:>7.2} KB | Rows {:>6} | {:>6} ms",
+                record.name,
+                size_kb,
+                record.row_count,
+                started.elapsed().as_millis()
+            );
+            log_cleaning(&record.name, &report);
+            let mut body = json!(DatasetInfo::from(record));
+            if has_recipe {
+                body["cleaningReport"] = json!(report);
+            }
+            (StatusCode::OK, Json(body))
+        }
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Загруженный набор, которому можно заменить файл или рецепт очистки
+async fn find_file_dataset(
+    pool: &Pool<Sqlite>,
+    claims: &Claims,
+    id: &str,
+) -> Result<DatasetRecord, ApiResult> {
+    let record = find_dataset(pool, claims, id).await?;
+    if record.kind != KIND_FILE {
+        return Err(error(StatusCode::BAD_REQUEST, "Набор не загружен из файла"));
+    }
+    Ok(record)
+}
+
+/// Исходный файл набора до очистки
+async fn read_source(id: &str) -> Result<StoredTable, ApiResult> {
+    let file_id = id.to_string();
+    match tokio::task::spawn_blocking(move || store::read_source(&file_id)).await {
+        Ok(Ok(Some(table))) => Ok(table),
+        Ok(Ok(None)) => Err(error(StatusCode::NOT_FOUND, "Файл набора данных не найден")),
+        Ok(Err(e)) => Err(error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+        Err(e) => Err(error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+}
+
+/// Разница схем текущей версии набора и новых строк
+fn schema_diff(
+    record: &DatasetRecord,
+    schema: &[ColumnSchema],
+    widgets: &[WidgetColumns],
+) -> SchemaDiff {
+    let current: Vec<ColumnSchema> = serde_json::from_str(&record.schema).unwrap_or_default();
+    let computed = parse_computed(&record.computed);
+    versions::diff_schema(&current, schema, &computed, widgets)
+}
+
+fn parse_widgets(raw: Option<&str>) -> Result<Vec<WidgetColumns>, ApiResult> {
+    match raw {
+        None => Ok(Vec::new()),
+        Some(raw) => serde_json::from_str(raw).map_err(|e| {
+            error(
+                StatusCode::BAD_REQUEST,
+                format!("Некорректный список виджетов: {}", e),
+            )
+        }),
+    }
+}
+
+/// Сохранение очищенных строк новой версией и ответ с обновлённым набором,
+/// отчётом очистки и разницей схем
+async fn save_cleaned(
+    pool: &Pool<Sqlite>,
+    record: &DatasetRecord,
+    cleaned: CleanedTable,
+    report: CleaningReport,
+    diff: SchemaDiff,
+) -> ApiResult {
+    match store::replace_cleaned(pool, record, cleaned).await {
+        Ok(record) => {
+            log_cleaning(&record.name, &report);
+            for warning in &diff.warnings {
+                eprintln!("⚠️ dataset {}: {}", record.name, warning);
+            }
+            let info = DatasetInfo::from(record);
+            (
+                StatusCode::OK,
+                Json(json!({ "dataset": info, "cleaningReport": report, "diff": diff })),
+            )
+        }
+        Err(e) if e.is::<VersionConflict>() => error(StatusCode::CONFLICT, e.to_string()),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Замена файла набора новой версией: сохранённый рецепт очистки применяется
+/// к новому файлу, в ответе — разница схем с прежней версией. Поле `widgets`
+/// (JSON) — виджеты с колонками набора, `dryRun=true` — только проверка
+pub async fn replace_dataset_file(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+    mut multipart: Multipart,
+) -> ApiResult {
+    let record = match find_file_dataset(&pool, &claims, &id).await {
+        Ok(record) => record,
+        Err(response) => return response,
+    };
+    let form = match read_upload_form(&mut multipart).await {
+        Ok(form) => form,
+        Err(response) => return response,
+    };
+    let Some((filename, bytes)) = form.file else {
+        return error(StatusCode::BAD_REQUEST, "Файл не передан");
+    };
+    let widgets = match parse_widgets(form.widgets.as_deref()) {
+        Ok(widgets) => widgets,
+        Err(response) => return response,
+    };
+    let started = Instant::now();
+    let source = match parse_upload(&filename, bytes).await {
+        Ok(table) => table,
+        Err(response) => return response,
+    };
+    let steps = cleaning::parse_recipe(&record.cleaning);
+    let keys = append::parse_config(record.append_config.as_deref());
+    let (mut cleaned, report) = match clean_table(source, steps, true, keys).await {
+        Ok(cleaned) => cleaned,
+        Err(response) => return response,
+    };
+    let diff = schema_diff(&record, &cleaned.schema, &widgets);
+    if form.dry_run {
+        return (
+            StatusCode::OK,
+            Json(json!({ "dryRun": true, "cleaningReport": report, "diff": diff })),
+        );
+    }
+    cleaned.source_filename = Some(filename);
+    println!(
+        "💾 dataset file replaced: {:<25} | v{} | Rows {:>6} | {:>6} ms",
+        record.name,
+        record.version + 1,
+        report.rows_after,
+        started.elapsed().as_millis()
+    );
+    save_cleaned(&pool, &record, cleaned, report, diff).await
+}
+
+/// Ключи и режим дозагрузки: из формы, иначе сохранённые с набором
+fn append_config(record: &DatasetRecord, form: &UploadForm) -> Result<AppendConfig, ApiResult> {
+    let mut config = append::parse_config(record.append_config.as_deref()).unwrap_or_default();
+    if let Some(raw) = form.keys.as_deref() {
+        config.keys = serde_json::from_str(raw).map_err(|e| {
+            error(
+                StatusCode::BAD_REQUEST,
+                format!("Некорректный список ключевых колонок: {}", e),
+            )
+        })?;
+    }
+    if let Some(mode) = form.mode.as_deref() {
+        config.mode = match mode.trim() {
+            "insert" => AppendMode::Insert,
+            "upsert" => AppendMode::Upsert,
+            _ => {
+                return Err(error(
+                    StatusCode::BAD_REQUEST,
+                    "Режим дозагрузки: insert или upsert",
+                ))
+            }
+        };
+    }
+    Ok(config)
+}
+
+/// Дозагрузка файла в набор: строки проходят рецепт очистки и проверку по
+/// схеме набора, строки с известным ключом пропускаются (`insert`) или
+/// заменяют прежние (`upsert`). Поля формы: `file`, `keys` (JSON-массив),
+/// `mode`, `dryRun`; ключи и режим запоминаются для следующих дозагрузок
+pub async fn append_dataset(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+    mut multipart: Multipart,
+) -> ApiResult {
+    let record = match find_file_dataset(&pool, &claims, &id).await {
+        Ok(record) => record,
+        Err(response) => return response,
+    };
+    let form = match read_upload_form(&mut multipart).await {
+        Ok(form) => form,
+        Err(response) => return response,
+    };
+    let config = match append_config(&record, &form) {
+        Ok(config) => config,
+        Err(response) => return response,
+    };
+    let Some((filename, bytes)) = form.file else {
+        return error(StatusCode::BAD_REQUEST, "Файл не передан");
+    };
+
+    let started = Instant::now();
+    let incoming = match parse_upload(&filename, bytes).await {
+        Ok(table) => table,
+        Err(response) => return response,
+    };
+    let appended = match store::append_rows(&pool, &id, filename, incoming, &config, form.dry_run)
+        .await
+    {
+        Ok(appended) => appended,
+        Err(e) if e.is::<AppendRejected>() => return error(StatusCode::BAD_REQUEST, e.to_string()),
+        Err(e) => return error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    let (updated, report, cleaning_report) = (appended.record, appended.report, appended.cleaning);
+    if form.dry_run {
+        return (
+            StatusCode::OK,
+            Json(json!({ "dryRun": true, "append": report, "cleaningReport": cleaning_report })),
+        );
+    }
+
+    println!(
+        "➕ dataset appended: {:<25} | v{} | +{} ~{} ={} | Rows {:>6} | {:>6} ms",
+        updated.name,
+        updated.version,
+        report.inserted,
+        report.updated,
+        report.skipped,
+        updated.row_count,
+        started.elapsed().as_millis()
+    );
+    log_cleaning(&updated.name, &cleaning_report);
+    for warning in &report.warnings {
+        eprintln!("⚠️ dataset {}: {}", updated.name, warning);
+    }
+    let info = DatasetInfo::from(updated);
+    (
+        StatusCode::OK,
+        Json(json!({
+            "dataset": info,
+            "append": report,
+            "cleaningReport": cleaning_report
+        })),
+    )
+}
+
+/// Набор из источника — файла в каталоге импорта или HTTP-адреса;
+/// источник запоминается для последующих обновлений
+pub async fn import_dataset(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<ImportInput>,
+) -> ApiResult {
+    if let Err(e) = refresh::validate_source(&payload.source) {
+        return error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+    if let Err(e) = cleaning::validate_recipe(&payload.cleaning) {
+        return error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+    let started = Instant::now();
+    let (filename, source) = match refresh::load(&payload.source).await {
+        Ok(loaded) => loaded,
+        Err(e) => return error(StatusCode::BAD_GATEWAY, e.to_string()),
+    };
+    let raw_name = payload
+        .name
+        .filter(|n| !n.trim().is_empty())
+        .unwrap_or_else(|| filename.clone());
+    let Some(name) = store::normalize_name(&raw_name) else {
+        return error(StatusCode::BAD_REQUEST, "Некорректное имя набора данных");
+    };
+    let (mut cleaned, report) = match clean_table(source, payload.cleaning, true, None).await {
+        Ok(cleaned) => cleaned,
+        Err(response) => return response,
+    };
+    cleaned.source_filename = Some(filename);
+
+    let record = match store::create_dataset(&pool, &name, &claims.sub, cleaned).await {
+        Ok(record) => record,
+        Err(e) => return error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    let saved = async {
+        let source = serde_json::to_string(&payload.source)?;
+        store::set_refresh_source(&pool, &record.id, Some(&source)).await?;
+        refresh::save_status(
+            &pool,
+            &record.id,
+            &RefreshStatus::succeeded(started, &record),
+        )
+        .await?;
+        store::get_dataset(&pool, &record.id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("набор {} не найден", record.id))
+    };
+    match saved.await {
+        Ok(record) => {
+            println!(
+                "📥 dataset imported: {:<25} | Rows {:>6} | {:>6} ms",
+                record.name,
+                record.row_count,
+                started.elapsed().as_millis()
+            );
+            log_cleaning(&record.name, &report);
+            (
+                StatusCode::OK,
+                Json(json!({ "dataset": DatasetInfo::from(record), "cleaningReport": report })),
+            )
+        }
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Привязка загруженного набора к источнику обновления или отвязка от него
+pub async fn update_source(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+    Json(payload): Json<SourceInput>,
+) -> ApiResult {
+    if let Err(response) = find_file_dataset(&pool, &claims, &id).await {
+        return response;
+    }
+    let raw = match &payload.source {
+        Some(source) => {
+            if let Err(e) = refresh::validate_source(source) {
+                return error(StatusCode::BAD_REQUEST, e.to_string());
+            }
+            match serde_json::to_string(source) {
+                Ok(raw) => Some(raw),
+                Err(e) => return error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+            }
+        }
+        None => None,
+    };
+    if let Err(e) = store::set_refresh_source(&pool, &id, raw.as_deref()).await {
+        return error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
+    match store::get_dataset(&pool, &id).await {
+        Ok(Some(record)) => (StatusCode::OK, Json(json!(DatasetInfo::from(record)))),
+        Ok(None) => error(StatusCode::NOT_FOUND, "Набор данных не найден"),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Повторное чтение источника: новая версия набора с прежним рецептом.
+/// Ошибка источника сохраняется в `lastRefresh`, строки остаются прежними
+pub async fn refresh_dataset(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> ApiResult {
+    let record = match find_file_dataset(&pool, &claims, &id).await {
+        Ok(record) => record,
+        Err(response) => return response,
+    };
+    if record.refresh_source.is_none() {
+        return error(StatusCode::BAD_REQUEST, "У набора нет источника обновления");
+    }
+    match refresh::refresh_dataset(&pool, &record).await {
+        Ok(refreshed) => {
+            let schema: Vec<ColumnSchema> =
+                serde_json::from_str(&refreshed.record.schema).unwrap_or_default();
+            let diff = schema_diff(&record, &schema, &[]);
+            log_cleaning(&refreshed.record.name, &refreshed.report);
+            (
+                StatusCode::OK,
+                Json(json!({
+                    "dataset": DatasetInfo::from(refreshed.record),
+                    "cleaningReport": refreshed.report,
+                    "diff": diff
+                })),
+            )
+        }
+        Err(e) if e.is::<VersionConflict>() => error(StatusCode::CONFLICT, e.to_string()),
+        Err(e) => error(StatusCode::BAD_GATEWAY, e.to_string()),
+    }
+}
+
+pub async fn get_dataset(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> ApiResult {
+    match find_dataset(&pool, &claims, &id).await {
+        Ok(record) => (StatusCode::OK, Json(json!(DatasetInfo::from(record)))),
+        Err(response) => response,
+    }
+}
+
+/// Исходные наборы производного набора со схемами (с вычисляемыми колонками).
+/// Доступ владельца к источникам проверяется при каждой сборке: права
+/// могли измениться после создания производного набора
+async fn load_sources(
+    pool: &Pool<Sqlite>,
+    owner: &str,
+    spec: &DerivedSpec,
+) -> Result<Vec<Source>, ApiResult> {
+    let mut owner_is_admin = None;
+    let mut sources = Vec::new();
+    for id in spec.sources() {
+        let record = match store::get_dataset(pool, id).await {
+            Ok(Some(record)) => record,
+            Ok(None) => {
+                return Err(error(
+                    StatusCode::NOT_FOUND,
+                    format!("Исходный набор {} не найден", id),
+                ))
+            }
+            Err(e) => return Err(error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+        };
+        if record.owner != owner {
+            if owner_is_admin.is_none() {
+                owner_is_admin = Some(
+                    get_user_by_login(pool, owner)
+                        .await
+                        .is_some_and(|user| has_role(&user.role, "Admin")),
+                );
+            }
+            if owner_is_admin != Some(true) {
+                return Err(error(
+                    StatusCode::FORBIDDEN,
+                    format!("У владельца набора нет доступа к исходному набору {}", id),
+                ));
+            }
+        }
+        // производные наборы могут опираться на другие производные
+        let table = Box::pin(load_table(pool, &record)).await?;
+        let info = DatasetInfo::from(record);
+        sources.push(Source {
+            name: info.name,
+            schema: info.schema,
+            table,
+        });
+    }
+    Ok(sources)
+}
+
+/// Построение производного набора в отдельном потоке
+async fn build_derived(
+    pool: &Pool<Sqlite>,
+    owner: &str,
+    spec: &DerivedSpec,
+) -> Result<(StoredTable, DerivedReport), ApiResult> {
+    let sources = load_sources(pool, owner, spec).await?;
+    let spec = spec.clone();
+    match tokio::task::spawn_blocking(move || derived::build(&spec, &sources)).await {
+        Ok(Ok(built)) => Ok(built),
+        Ok(Err(e)) => Err(error(StatusCode::BAD_REQUEST, e.to_string())),
+        Err(e) => Err(error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+}
+
+fn derived_definition(record: &DatasetRecord) -> Result<DerivedDefinition, ApiResult> {
+    record
+        .definition
+        .as_deref()
+        .and_then(|raw| serde_json::from_str(raw).ok())
+        .ok_or_else(|| {
+            error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Некорректное описание производного набора",
+            )
+        })
+}
+
+/// Исходные строки набора: файл, результат сохранённого запроса
+/// или производный набор, собираемый при чтении
+async fn load_source(
+    pool: &Pool<Sqlite>,
+    record: &DatasetRecord,
+) -> Result<StoredTable, ApiResult> {
+    if record.kind == KIND_SQL {
+        return crate::sql::engine::materialize(pool, record)
+            .await
+            .map_err(|e| {
+                error(
+                    StatusCode::BAD_REQUEST,
+                    format!("Не удалось выполнить запрос набора: {}", e),
+                )
+            });
+    }
+    if record.kind == KIND_DERIVED {
+        let definition = derived_definition(record)?;
+        if !definition.materialized {
+            return Ok(build_derived(pool, &record.owner, &definition.spec)
+                .await?
+                .0);
+        }
+    }
+    let file_id = record.id.clone();
+    let loaded = tokio::task::spawn_blocking(move || store::read_table(&file_id)).await;
+    match loaded {
+        Ok(Ok(Some(table))) => Ok(table),
+        Ok(Ok(None)) => Err(error(StatusCode::NOT_FOUND, "Файл набора данных не найден")),
+        Ok(Err(e)) => Err(error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+        Err(e) => Err(error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+}
+
+/// Набор вместе со строками и вычисляемыми колонками; ошибки доступа
+/// и чтения — готовый ответ
+pub(crate) async fn load_dataset(
+    pool: &Pool<Sqlite>,
+    claims: &Claims,
+    id: &str,
+) -> Result<(DatasetRecord, StoredTable), ApiResult> {
+    let record = find_dataset(pool, claims, id).await?;
+    let table = load_table(pool, &record).await?;
+    Ok((record, table))
+}
+
+/// Строки набора с вычисляемыми колонками без проверки доступа
+pub(crate) async fn load_table(
+    pool: &Pool<Sqlite>,
+    record: &DatasetRecord,
+) -> Result<StoredTable, ApiResult> {
+    let table = load_source(pool, record).await?;
+    let computed = parse_computed(&record.computed);
+    if computed.is_empty() {
+        return Ok(table);
+    }
+
+    let schema: Vec<ColumnSchema> = serde_json::from_str(&record.schema).unwrap_or_default();
+    let applied = tokio::task::spawn_blocking(move || {
+        let mut table = table;
+        let errors = apply_computed(&mut table, &schema, &computed);
+        (table, errors)
+    })
+    .await;
+    match applied {
+        Ok((table, errors)) => {
+            for message in errors {
+                eprintln!("⚠️ dataset {}: {}", record.id, message);
+            }
+            Ok(table)
+        }
+        Err(e) => Err(error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+}
+
+/// Версия данных набора для кеша результатов запросов: пары (набор, отметка)
+/// для самого набора и, если производный набор собирается при чтении, для
+/// его источников. `None` — строки дают сохранённый SQL-запрос, от каких
+/// наборов они зависят, заранее неизвестно, и результат не кешируется
+pub(crate) async fn data_stamps(
+    pool: &Pool<Sqlite>,
+    record: &DatasetRecord,
+) -> Result<Option<Vec<(String, String)>>, ApiResult> {
+    if record.kind == KIND_SQL {
+        return Ok(None);
+    }
+    // вычисляемые колонки меняются без новой версии строк
+    let stamp = format!(
+        "{}@{}@{}",
+        record.version, record.updated_at, record.computed
+    );
+    let mut stamps = vec![(record.id.clone(), stamp)];
+    if record.kind == KIND_DERIVED {
+        let definition = derived_definition(record)?;
+        if !definition.materialized {
+            for id in definition.spec.sources() {
+                let source = match store::get_dataset(pool, id).await {
+                    Ok(Some(source)) => source,
+                    Ok(None) => return Ok(None),
+                    Err(e) => return Err(error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+                };
+                match Box::pin(data_stamps(pool, &source)).await? {
+                    Some(nested) => stamps.extend(nested),
+                    None => return Ok(None),
+                }
+            }
+        }
+    }
+    Ok(Some(stamps))
+}
+
+/// Содержимое набора в формате ответа `/api/upload`, чтобы дашборд
+/// открывался без повторной загрузки файла
+pub async fn get_dataset_data(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> Result<Json<StoredTable>, ApiResult> {
+    let (_, table) = load_dataset(&pool, &claims, &id).await?;
+    Ok(Json(table))
+}
+
+pub async fn rename_dataset(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+    Json(payload): Json<RenameInput>,
+) -> ApiResult {
+    let Some(name) = store::normalize_name(&payload.name) else {
+        return error(StatusCode::BAD_REQUEST, "Некорректное имя набора данных");
+    };
+    if let Err(response) = find_dataset(&pool, &claims, &id).await {
+        return response;
+    }
+    match store::rename_dataset(&pool, &id, &name).await {
+        Ok(true) => get_dataset(State(pool), Extension(claims), Path(id)).await,
+        Ok(false) => error(StatusCode::NOT_FOUND, "Набор данных не найден"),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+pub async fn delete_dataset(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> ApiResult {
+    let record = match find_dataset(&pool, &claims, &id).await {
+        Ok(record) => record,
+        Err(response) => return response,
+    };
+    // соединения, объединения и запросы без источника не собрать — сначала
+    // удаляются они
+    match store::dependent_datasets(&pool, &record).await {
+        Ok(dependents) if !dependents.is_empty() => {
+            return (
+                StatusCode::CONFLICT,
+                Json(json!({
+                    "error": "Набор используется производными наборами или SQL-запросами — сначала удалите их",
+                    "dependents": dependents,
+                })),
+            )
+        }
+        Ok(_) => {}
+        Err(e) => return error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+    match store::delete_dataset(&pool, &id).await {
+        Ok(true) => {
+            println!("🗑️ dataset deleted: {}", id);
+            (StatusCode::OK, Json(json!({ "deleted": id })))
+        }
+        Ok(false) => error(StatusCode::NOT_FOUND, "Набор данных не найден"),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+pub async fn get_computed(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> ApiResult {
+    match find_dataset(&pool, &claims, &id).await {
+        Ok(record) => (
+            StatusCode::OK,
+            Json(json!({ "computed": parse_computed(&record.computed) })),
+        ),
+        Err(response) => response,
+    }
+}
+
+/// Замена списка вычисляемых колонок; список проверяется целиком
+/// по исходной схеме набора
+pub async fn update_computed(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+    Json(payload): Json<ComputedInput>,
+) -> ApiResult {
+    let record = match find_dataset(&pool, &claims, &id).await {
+        Ok(record) => record,
+        Err(response) => return response,
+    };
+    let schema: Vec<ColumnSchema> = serde_json::from_str(&record.schema).unwrap_or_default();
+    let columns = match computed::validate_columns(&schema, &payload.columns) {
+        Ok(columns) => columns,
+        Err(e) => return error(StatusCode::BAD_REQUEST, e.to_string()),
+    };
+    let raw = match serde_json::to_string(&columns) {
+        Ok(raw) => raw,
+        Err(e) => return error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+    match store::set_computed(&pool, &id, &raw).await {
+        Ok(true) => {
+            println!("🧮 computed columns: {} | {}", record.name, columns.len());
+            get_dataset(State(pool), Extension(claims), Path(id)).await
+        }
+        Ok(false) => error(StatusCode::NOT_FOUND, "Набор данных не найден"),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Проверка выражения по схеме набора (с уже сохранёнными вычисляемыми
+/// колонками) и значения на первых строках
+pub async fn validate_computed(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+    Json(payload): Json<ExpressionInput>,
+) -> ApiResult {
+    let (record, table) = match load_dataset(&pool, &claims, &id).await {
+        Ok(loaded) => loaded,
+        Err(response) => return response,
+    };
+    let info = DatasetInfo::from(record);
+    match computed::preview(&table, &info.schema, &payload.expression, PREVIEW_ROWS) {
+        Ok((column_type, values)) => (
+            StatusCode::OK,
+            Json(json!({ "type": column_type, "preview": values })),
+        ),
+        Err(e) => error(StatusCode::BAD_REQUEST, e.to_string()),
+    }
+}
+
+/// Проверка доступа к исходным наборам и построение производного набора
+async fn build_for_user(
+    pool: &Pool<Sqlite>,
+    claims: &Claims,
+    spec: &DerivedSpec,
+) -> Result<(StoredTable, DerivedReport), ApiResult> {
+    spec.validate()
+        .map_err(|e| error(StatusCode::BAD_REQUEST, e.to_string()))?;
+    for id in spec.sources() {
+        find_dataset(pool, claims, id).await?;
+    }
+    build_derived(pool, &claims.sub, spec).await
+}
+
+/// Предпросмотр соединения или объединения без сохранения
+pub async fn preview_derived(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(claims): Extension<Claims>,
+    Json(spec): Json<DerivedSpec>,
+) -> ApiResult {
+    match build_for_user(&pool, &claims, &spec).await {
+        Ok((mut table, report)) => {
+            table.rows.truncate(DERIVED_PREVIEW_ROWS);
+            (
+                StatusCode::OK,
+                Json(json!({ "columns": table.columns, "rows": table.rows, "report": report })),
+            )
+        }
+        Err(response) => response,
+    }
+}
+
+/// Сохранение производного набора: материализованного — сразу со строками,
+/// ленивого — только с описанием и схемой по пробной сборке
+pub async fn create_derived(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(claims): Extension<Claims>,
+    Json(input): Json<DerivedInput>,
+) -> ApiResult {
+    let Some(name) = store::normalize_name(&input.name) else {
+        return error(StatusCode::BAD_REQUEST, "Некорректное имя набора данных");
+    };
+    let started = Instant::now();
+    let (table, report) = match build_for_user(&pool, &claims, &input.definition.spec).await {
+        Ok(built) => built,
+        Err(response) => return response,
+    };
+    let definition = json!(input.definition);
+    let created = if input.definition.materialized {
+        store::create_materialized_dataset(
+            &pool,
+            &name,
+            &claims.sub,
+            KIND_DERIVED,
+            &definition,
+            table,
+        )
+        .await
+    } else {
+        store::create_virtual_dataset(&pool, &name, &claims.sub, KIND_DERIVED, &definition, &table)
+            .await
+    };
+    match created {
+        Ok(record) => {
+            println!(
+                "🔗 derived dataset saved: {:<25} | Rows {:>6} | {:>6} ms",
+                record.name,
+                record.row_count,
+                started.elapsed().as_millis()
+            );
+            let info = DatasetInfo::from(record);
+            (
+                StatusCode::OK,
+                Json(json!({ "dataset": info, "report": report })),
+            )
+        }
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Пересборка материализованного производного набора по текущим исходным
+pub async fn rebuild_derived(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> ApiResult {
+    let record = match find_dataset(&pool, &claims, &id).await {
+        Ok(record) => record,
+        Err(response) => return response,
+    };
+    if record.kind != KIND_DERIVED {
+        return error(StatusCode::BAD_REQUEST, "Набор не является производным");
+    }
+    let definition = match derived_definition(&record) {
+        Ok(definition) => definition,
+        Err(response) => return response,
+    };
+    let (table, report) = match build_derived(&pool, &record.owner, &definition.spec).await {
+        Ok(built) => built,
+        Err(response) => return response,
+    };
+    if definition.materialized {
+        if let Err(e) = store::replace_table(&pool, &id, table).await {
+            return error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+        }
+    }
+    match store::get_dataset(&pool, &id).await {
+        Ok(Some(record)) => {
+            println!(
+                "🔗 derived dataset rebuilt: {} | Rows {:>6}",
+                record.name, report.rows
+            );
+            let info = DatasetInfo::from(record);
+            (
+                StatusCode::OK,
+                Json(json!({ "dataset": info, "report": report })),
+            )
+        }
+        Ok(None) => error(StatusCode::NOT_FOUND, "Набор данных не найден"),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+pub async fn get_cleaning(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> ApiResult {
+    match find_dataset(&pool, &claims, &id).await {
+        Ok(record) => (
+            StatusCode::OK,
+            Json(json!({ "steps": cleaning::parse_recipe(&record.cleaning) })),
+        ),
+        Err(response) => response,
+    }
+}
+
+/// Замена рецепта очистки: рецепт сразу применяется к исходному файлу набора
+pub async fn update_cleaning(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+    Json(payload): Json<CleaningInput>,
+) -> ApiResult {
+    let record = match find_file_dataset(&pool, &claims, &id).await {
+        Ok(record) => record,
+        Err(response) => return response,
+    };
+    if let Err(e) = cleaning::validate_recipe(&payload.steps) {
+        return error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+    let source = match read_source(&id).await {
+        Ok(source) => source,
+        Err(response) => return response,
+    };
+    // у старых наборов исходного файла нет — сохраняем его при первой очистке
+    let keys = append::parse_config(record.append_config.as_deref());
+    let (cleaned, report) = match clean_table(source, payload.steps, true, keys).await {
+        Ok(cleaned) => cleaned,
+        Err(response) => return response,
+    };
+    println!(
+        "🧹 cleaning recipe: {} | steps {} | Rows {} → {}",
+        record.name,
+        report.steps.len(),
+        report.rows_before,
+        report.rows_after
+    );
+    let diff = schema_diff(&record, &cleaned.schema, &[]);
+    save_cleaned(&pool, &record, cleaned, report, diff).await
+}
+
+/// Результат рецепта на исходном файле без сохранения
+pub async fn preview_cleaning(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+    Json(payload): Json<CleaningInput>,
+) -> ApiResult {
+    let record = match find_file_dataset(&pool, &claims, &id).await {
+        Ok(record) => record,
+        Err(response) => return response,
+    };
+    if let Err(e) = cleaning::validate_recipe(&payload.steps) {
+        return error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+    let source = match read_source(&id).await {
+        Ok(source) => source,
+        Err(response) => return response,
+    };
+    let keys = append::parse_config(record.append_config.as_deref());
+    match clean_table(source, payload.steps, false, keys).await {
+        Ok((cleaned, report)) => {
+            let mut table = cleaned.table;
+            table.rows.truncate(CLEANING_PREVIEW_ROWS);
+            (
+                StatusCode::OK,
+                Json(json!({
+                    "columns": table.columns,
+                    "schema": cleaned.schema,
+                    "rows": table.rows,
+                    "report": report
+                })),
+            )
+        }
+        Err(response) => response,
+    }
+}
+
+/// История версий загруженного набора, новые — первыми
+pub async fn list_versions(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<String>,
+) -> ApiResult {
+    let record = match find_file_dataset(&pool, &claims, &id).await {
+        Ok(record) => record,
+        Err(response) => return response,
+    };
+    // наборы, загруженные до появления версий, получают запись о текущей версии
+    if let Err(e) = versions::ensure_version(&pool, &record).await {
+        return error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
+    match versions::list_versions(&pool, &id).await {
+        Ok(list) => {
+            let list: Vec<VersionInfo> = list
+                .into_iter()
+                .map(|v| VersionInfo::new(v, record.version))
+                .collect();
+            (
+                StatusCode::OK,
+                Json(json!({ "current": record.version, "versions": list })),
+            )
+        }
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Набор и сохранённая версия из URL; текущая версия — не цель для отката
+async fn find_version(
+    pool: &Pool<Sqlite>,
+    claims: &Claims,
+    id: &str,
+    version: i64,
+) -> Result<(DatasetRecord, versions::VersionRecord), ApiResult> {
+    let record = find_file_dataset(pool, claims, id).await?;
+    if version == record.version {
+        return Err(error(StatusCode::BAD_REQUEST, "Эта версия уже текущая"));
+    }
+    match versions::get_version(pool, id, version).await {
+        Ok(Some(target)) => Ok((record, target)),
+        Ok(None) => Err(error(StatusCode::NOT_FOUND, "Версия не найдена")),
+        Err(e) => Err(error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+}
+
+/// Что изменится в схеме при откате к версии
+pub async fn version_diff(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(claims): Extension<Claims>,
+    Path((id, version)): Path<(String, i64)>,
+) -> ApiResult {
+    match find_version(&pool, &claims, &id, version).await {
+        Ok((record, target)) => {
+            let schema: Vec<ColumnSchema> =
+                serde_json::from_str(&target.schema).unwrap_or_default();
+            let diff = schema_diff(&record, &schema, &[]);
+            (StatusCode::OK, Json(json!({ "diff": diff })))
+        }
+        Err(response) => response,
+    }
+}
+
+/// Откат к сохранённой версии; откат сам становится новой версией
+pub async fn rollback_version(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(claims): Extension<Claims>,
+    Path((id, version)): Path<(String, i64)>,
+) -> ApiResult {
+    let (record, target) = match find_version(&pool, &claims, &id, version).await {
+        Ok(found) => found,
+        Err(response) => return response,
+    };
+    let schema: Vec<ColumnSchema> = serde_json::from_str(&target.schema).unwrap_or_default();
+    let diff = schema_diff(&record, &schema, &[]);
+    match store::restore_version(&pool, &record, &target).await {
+        Ok(restored) => {
+            println!(
+                "⏪ dataset rolled back: {} | v{} → v{}",
+                restored.name, target.version, restored.version
+            );
+            let info = DatasetInfo::from(restored);
+            (
+                StatusCode::OK,
+                Json(json!({ "dataset": info, "diff": diff })),
+            )
+        }
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
@@ -0,0 +1,128 @@
+use axum::routing::{get, post, put};
+use axum::Router;
+use sqlx::{Pool, Sqlite};
+
+pub mod append;
+pub mod cleaning;
+pub mod computed;
+pub mod derived;
+pub mod handlers;
+pub mod models;
+pub mod refresh;
+pub mod schema;
+pub mod store;
+pub mod versions;
+
+use crate::protect;
+use crate::storage;
+
+/// Маршруты сохранённых наборов данных; пользователь работает со своими наборами,
+/// администратор — со всеми
+pub async fn setup_router(pool: Pool<Sqlite>) -> Router {
+    for migration in [models::DATASET_MIGRATION, versions::VERSIONS_MIGRATION] {
+        if let Err(e) = sqlx::query(migration).execute(&pool).await {
+            panic!("❌ Dataset migration failed: {}", e);
+        }
+    }
+    for (column, definition) in [
+        ("kind", "TEXT NOT NULL DEFAULT 'file'"),
+        ("definition", "TEXT"),
+        ("computed", "TEXT NOT NULL DEFAULT '[]'"),
+        ("cleaning", "TEXT NOT NULL DEFAULT '[]'"),
+        ("version", "INTEGER NOT NULL DEFAULT 1"),
+        ("append_config", "TEXT"),
+        ("refresh_source", "TEXT"),
+        ("last_refresh", "TEXT"),
+    ] {
+        if let Err(e) = storage::ensure_column(&pool, "datasets", column, definition).await {
+            panic!("❌ Dataset migration failed: {}", e);
+        }
+    }
+
+    Router::new()
+        .route(
+            "/api/datasets",
+            get(handlers::list_datasets)
+                .post(handlers::create_dataset)
+                .route_layer(protect!(pool, "User")),
+        )
+        .route(
+            "/api/datasets/import",
+            post(handlers::import_dataset).route_layer(protect!(pool, "User")),
+        )
+        .route(
+            "/api/datasets/derived",
+            post(handlers::create_derived).route_layer(protect!(pool, "User")),
+        )
+        .route(
+            "/api/datasets/derived/preview",
+            post(handlers::preview_derived).route_layer(protect!(pool, "User")),
+        )
+        .route(
+            "/api/datasets/:id",
+            get(handlers::get_dataset)
+                .delete(handlers::delete_dataset)
+                .route_layer(protect!(pool, "User")),
+        )
+        .route(
+            "/api/datasets/:id/data",
+            get(handlers::get_dataset_data).route_layer(protect!(pool, "User")),
+        )
+        .route(
+            "/api/datasets/:id/upload",
+            post(handlers::replace_dataset_file).route_layer(protect!(pool, "User")),
+        )
+        .route(
+            "/api/datasets/:id/append",
+            post(handlers::append_dataset).route_layer(protect!(pool, "User")),
+        )
+        .route(
+            "/api/datasets/:id/source",
+            put(handlers::update_source).route_layer(protect!(pool, "User")),
+        )
+        .route(
+            "/api/datasets/:id/refresh",
+            post(handlers::refresh_dataset).route_layer(protect!(pool, "User")),
+        )
+        .route(
+            "/api/datasets/:id/versions",
+            get(handlers::list_versions).route_layer(protect!(pool, "User")),
+        )
+        .route(
+            "/api/datasets/:id/versions/:version/diff",
+            get(handlers::version_diff).route_layer(protect!(pool, "User")),
+        )
+        .route(
+            "/api/datasets/:id/versions/:version/rollback",
+            post(handlers::rollback_version).route_layer(protect!(pool, "User")),
+        )
+        .route(
+            "/api/datasets/:id/cleaning",
+            get(handlers::get_cleaning)
+                .put(handlers::update_cleaning)
+                .route_layer(protect!(pool, "User")),
+        )
+        .route(
+            "/api/datasets/:id/cleaning/preview",
+            post(handlers::preview_cleaning).route_layer(protect!(pool, "User")),
+        )
+        .route(
+            "/api/datasets/:id/rebuild",
+            post(handlers::rebuild_derived).route_layer(protect!(pool, "User")),
+        )
+        .route(
+            "/api/datasets/:id/rename",
+            post(handlers::rename_dataset).route_layer(protect!(pool, "User")),
+        )
+        .route(
+            "/api/datasets/:id/computed",
+            get(handlers::get_computed)
+                .put(handlers::update_computed)
+                .route_layer(protect!(pool, "User")),
+        )
+        .route(
+            "/api/datasets/:id/computed/validate",
+            post(handlers::validate_computed).route_layer(protect!(pool, "User")),
+        )
+        .with_state(pool)
+}
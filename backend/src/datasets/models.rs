@@ -0,0 +1,127 @@
+use crate::datasets::append::{self, AppendConfig};
+use crate::datasets::cleaning::{parse_recipe, CleaningStep};
+use crate::datasets::computed::{extended_schema, parse_computed, ComputedColumn};
+use crate::datasets::refresh::{self, RefreshSource, RefreshStatus};
+use crate::datasets::schema::ColumnSchema;
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::FromRow;
+
+pub const DATASET_MIGRATION: &str = r#"
+CREATE TABLE IF NOT EXISTS datasets (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    owner TEXT NOT NULL,
+    source_filename TEXT,
+    schema TEXT NOT NULL DEFAULT '[]',
+    row_count INTEGER NOT NULL DEFAULT 0,
+    size_bytes INTEGER NOT NULL DEFAULT 0,
+    uploaded_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    kind TEXT NOT NULL DEFAULT 'file',
+    definition TEXT,
+    computed TEXT NOT NULL DEFAULT '[]',
+    cleaning TEXT NOT NULL DEFAULT '[]',
+    version INTEGER NOT NULL DEFAULT 1,
+    append_config TEXT,
+    refresh_source TEXT,
+    last_refresh TEXT
+);
+CREATE INDEX IF NOT EXISTS idx_datasets_owner ON datasets (owner);
+"#;
+
+/// Набор из загруженного файла; строки лежат в каталоге данных
+pub const KIND_FILE: &str = "file";
+/// Виртуальный набор: сохранённый SQL-запрос, выполняется при каждом чтении
+pub const KIND_SQL: &str = "sql";
+/// Соединение или объединение других наборов: материализованное (строки
+/// в файле) или вычисляемое при чтении
+pub const KIND_DERIVED: &str = "derived";
+
+/// Строка таблицы `datasets`; схема, вычисляемые колонки и рецепт очистки
+/// хранятся JSON-массивами, описание виртуального набора — JSON-объектом в `definition`
+#[derive(FromRow, Debug, Clone)]
+pub struct DatasetRecord {
+    pub id: String,
+    pub name: String,
+    pub owner: String,
+    pub source_filename: Option<String>,
+    pub schema: String,
+    pub row_count: i64,
+    pub size_bytes: i64,
+    pub uploaded_at: String,
+    pub updated_at: String,
+    pub kind: String,
+    pub definition: Option<String>,
+    pub computed: String,
+    pub cleaning: String,
+    /// Номер текущей версии; растёт при каждой замене строк
+    pub version: i64,
+    /// Ключи и режим дозагрузки (JSON-объект)
+    pub append_config: Option<String>,
+    /// Откуда набор обновляется: файл в каталоге импорта или URL (JSON-объект)
+    pub refresh_source: Option<String>,
+    /// Итог последнего обновления из источника (JSON-объект)
+    pub last_refresh: Option<String>,
+}
+
+/// Метаданные набора данных в ответах API
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DatasetInfo {
+    pub id: String,
+    pub name: String,
+    pub owner: String,
+    pub source_filename: Option<String>,
+    pub schema: Vec<ColumnSchema>,
+    pub row_count: i64,
+    pub size_bytes: i64,
+    pub uploaded_at: String,
+    pub updated_at: String,
+    pub kind: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub definition: Option<Value>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub computed: Vec<ComputedColumn>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub cleaning: Vec<CleaningStep>,
+    pub version: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub append: Option<AppendConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_source: Option<RefreshSource>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_refresh: Option<RefreshStatus>,
+}
+
+impl From<DatasetRecord> for DatasetInfo {
+    fn from(record: DatasetRecord) -> Self {
+        let schema: Vec<ColumnSchema> = serde_json::from_str(&record.schema).unwrap_or_default();
+        let computed = parse_computed(&record.computed);
+        Self {
+            schema: extended_schema(&schema, &computed),
+            computed,
+            cleaning: parse_recipe(&record.cleaning),
+            append: append::parse_config(record.append_config.as_deref()),
+            refresh_source: refresh::parse_source(record.refresh_source.as_deref()),
+            last_refresh: record
+                .last_refresh
+                .as_deref()
+                .and_then(|s| serde_json::from_str(s).ok()),
+            definition: record
+                .definition
+                .as_deref()
+                .and_then(|d| serde_json::from_str(d).ok()),
+            id: record.id,
+            name: record.name,
+            owner: record.owner,
+            source_filename: record.source_filename,
+            row_count: record.row_count,
+            size_bytes: record.size_bytes,
+            uploaded_at: record.uploaded_at,
+            updated_at: record.updated_at,
+            kind: record.kind,
+            version: record.version,
+        }
+    }
+}
@@ -0,0 +1,868 @@
+use crate::converter::convert_by_extension;
+use crate::datasets::append;
+use crate::datasets::cleaning::{self, CleaningReport};
+use crate::datasets::models::DatasetRecord;
+use crate::datasets::store::{self, StoredTable};
+use crate::storage::data_dir;
+use anyhow::{anyhow, bail, Result};
+use axum::body::Bytes;
+use axum::http::{header, Request, StatusCode, Uri};
+use chrono::{SecondsFormat, Utc};
+use http_body_util::{BodyExt, Empty, Limited};
+use hyper_util::rt::TokioIo;
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Sqlite};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use std::{env, fs};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{lookup_host, TcpStream};
+use tokio::time::timeout;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+use tokio_rustls::TlsConnector;
+
+/// Предел размера источника — тот же, что у загрузки файла
+pub const MAX_SOURCE_BYTES: usize = 50 * 1024 * 1024;
+/// Сколько ждать соединения и ответа источника
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(60);
+const MAX_REDIRECTS: usize = 5;
+const SUPPORTED_EXTENSIONS: &[&str] = &["csv", "xlsx", "ods", "json"];
+
+/// Откуда набор берёт строки при обновлении
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum RefreshSource {
+    /// Файл в каталоге импорта; путь — относительно каталога
+    Path { path: String },
+    /// Адрес, который отдаёт CSV, XLSX, ODS или JSON; `format` нужен, если
+    /// ни адрес, ни `Content-Type` формата не выдают
+    Url {
+        url: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        format: Option<String>,
+    },
+}
+
+/// Источник обновления из JSON записи набора
+pub fn parse_source(raw: Option<&str>) -> Option<RefreshSource> {
+    raw.and_then(|raw| serde_json::from_str(raw).ok())
+}
+
+/// Итог последнего обновления; при ошибке набор остаётся прежним
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshStatus {
+    pub at: String,
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rows: Option<i64>,
+    pub duration_ms: u64,
+}
+
+impl RefreshStatus {
+    pub fn succeeded(started: Instant, record: &DatasetRecord) -> Self {
+        Self {
+            at: now(),
+            ok: true,
+            error: None,
+            version: Some(record.version),
+            rows: Some(record.row_count),
+            duration_ms: started.elapsed().as_millis() as u64,
+        }
+    }
+
+    pub fn failed(started: Instant, error: &anyhow::Error) -> Self {
+        Self {
+            at: now(),
+            ok: false,
+            error: Some(error.to_string()),
+            version: None,
+            rows: None,
+            duration_ms: started.elapsed().as_millis() as u64,
+        }
+    }
+}
+
+fn now() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Сохранение итога обновления в записи набора
+pub async fn save_status(pool: &Pool<Sqlite>, id: &str, status: &RefreshStatus) -> Result<()> {
+    store::set_last_refresh(pool, id, &serde_json::to_string(status)?).await?;
+    Ok(())
+}
+
+/// Каталог, из которого разрешено читать файлы: `IMPORT_DIR` или
+/// `imports` в каталоге данных
+pub fn import_dir() -> PathBuf {
+    env::var("IMPORT_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| data_dir().join("imports"))
+}
+
+fn supported_extension(name: &str) -> Option<&'static str> {
+    let (_, ext) = name.rsplit_once('.')?;
+    SUPPORTED_EXTENSIONS
+        .iter()
+        .copied()
+        .find(|known| ext.eq_ignore_ascii_case(known))
+}
+
+/// Файл источника внутри каталога импорта; ссылки и `..` не выводят за его пределы
+fn resolve_path(path: &str) -> Result<PathBuf> {
+    let root = import_dir();
+    let root = root
+        .canonicalize()
+        .map_err(|_| anyhow!("Каталог импорта {} недоступен", root.display()))?;
+    let full = root
+        .join(path.trim())
+        .canonicalize()
+        .map_err(|_| anyhow!("Файл «{}» не найден в каталоге импорта", path))?;
+    if !full.starts_with(&root) {
+        bail!("Путь «{}» выходит за пределы каталога импорта", path);
+    }
+    if !full.is_file() {
+        bail!("«{}» не является файлом", path);
+    }
+    Ok(full)
+}
+
+/// Проверка адреса, к которому разрешено подключаться источнику
+type AddressCheck = fn(IpAddr) -> bool;
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        || a == 0
+        || a >= 240
+        || (a == 100 && (64..128).contains(&b))
+        || (a == 192 && b == 0 && c == 0)
+        || (a == 198 && (b == 18 || b == 19)))
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    if let Some(v4) = ip.to_ipv4_mapped() {
+        return is_public_v4(v4);
+    }
+    let segments = ip.segments();
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || segments[..6] == [0; 6]
+        || segments[0] & 0xfe00 == 0xfc00
+        || segments[0] & 0xffc0 == 0xfe80
+        || segments[0] & 0xffc0 == 0xfec0
+        || (segments[0] == 0x64 && segments[1] == 0xff9b)
+        || (segments[0] == 0x2001 && segments[1] == 0xdb8))
+}
+
+/// Публичный адрес интернета. Loopback, частные сети, link-local (в том
+/// числе адрес метаданных облака 169.254.169.254) и служебные диапазоны
+/// закрыты: иначе через источник набора можно прочитать внутренние сервисы
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => is_public_v4(v4),
+        IpAddr::V6(v6) => is_public_v6(v6),
+    }
+}
+
+/// Имя сервера без квадратных скобок IPv6
+fn host_of(uri: &Uri) -> &str {
+    uri.host()
+        .unwrap_or_default()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+}
+
+fn is_https(uri: &Uri) -> bool {
+    uri.scheme_str() == Some("https")
+}
+
+fn default_port(uri: &Uri) -> u16 {
+    uri.port_u16()
+        .unwrap_or(if is_https(uri) { 443 } else { 80 })
+}
+
+/// Адрес для подключения. Имя разрешается один раз, и соединение идёт на
+/// проверенный адрес — повторный запрос DNS не подменит его внутренним
+async fn resolve(uri: &Uri, allowed: AddressCheck) -> Result<SocketAddr> {
+    let host = host_of(uri);
+    let port = default_port(uri);
+    let addresses: Vec<SocketAddr> = match host.parse::<IpAddr>() {
+        Ok(ip) => vec![SocketAddr::new(ip, port)],
+        Err(_) => timeout(CONNECT_TIMEOUT, lookup_host((host, port)))
+            .await
+            .map_err(|_| {
+                anyhow!(
+                    "Имя сервера {} не разрешилось за {} с",
+                    host,
+                    CONNECT_TIMEOUT.as_secs()
+                )
+            })?
+            .map_err(|e| anyhow!("Сервер {} не найден: {}", host, e))?
+            .collect(),
+    };
+    if addresses.is_empty() {
+        bail!("Сервер {} не найден", host);
+    }
+    addresses
+        .into_iter()
+        .find(|address| allowed(address.ip()))
+        .ok_or_else(|| {
+            anyhow!(
+                "Адрес {} ведёт во внутреннюю сеть — такие источники запрещены",
+                host
+            )
+        })
+}
+
+fn parse_url(url: &str) -> Result<Uri> {
+    let uri: Uri = url
+        .trim()
+        .parse()
+        .map_err(|_| anyhow!("Некорректный адрес «{}»", url))?;
+    match uri.scheme_str() {
+        Some("http") | Some("https") => {}
+        _ => bail!("Поддерживаются только адреса http:// и https://"),
+    }
+    if uri.host().is_none_or(str::is_empty) {
+        bail!("В адресе «{}» нет имени сервера", url);
+    }
+    Ok(uri)
+}
+
+/// Проверка источника перед сохранением: файл существует в каталоге импорта
+/// и его формат известен, у адреса понятная схема
+pub fn validate_source(source: &RefreshSource) -> Result<()> {
+    match source {
+        RefreshSource::Path { path } => {
+            resolve_path(path)?;
+            if supported_extension(path).is_none() {
+                bail!("Поддерживаются только файлы CSV, XLSX, ODS и JSON");
+            }
+        }
+        RefreshSource::Url { url, format } => {
+            let uri = parse_url(url)?;
+            if host_of(&uri).parse().is_ok_and(|ip| !is_public(ip)) {
+                bail!(
+                    "Адрес {} ведёт во внутреннюю сеть — такие источники запрещены",
+                    host_of(&uri)
+                );
+            }
+            if let Some(format) = format {
+                if !SUPPORTED_EXTENSIONS.contains(&format.to_lowercase().as_str()) {
+                    bail!("Формат источника: csv, xlsx, ods или json");
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Расширение по `Content-Type` ответа
+fn extension_for(content_type: &str) -> Option<&'static str> {
+    let mime = content_type.split(';').next()?.trim().to_lowercase();
+    match mime.as_str() {
+        "text/csv" | "application/csv" | "text/plain" => Some("csv"),
+        "application/json" => Some("json"),
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet" => Some("xlsx"),
+        "application/vnd.oasis.opendocument.spreadsheet" => Some("ods"),
+        _ => None,
+    }
+}
+
+/// Имя файла для конвертера: последний сегмент адреса и расширение из
+/// явного формата, адреса или `Content-Type` — в этом порядке
+fn url_filename(uri: &Uri, format: Option<&str>, content_type: Option<&str>) -> Result<String> {
+    let segment = uri
+        .path()
+        .rsplit('/')
+        .find(|s| !s.is_empty())
+        .unwrap_or_else(|| uri.host().unwrap_or("source"));
+    let segment = percent_encoding::percent_decode_str(segment)
+        .decode_utf8_lossy()
+        .into_owned();
+    let extension = format
+        .map(str::to_lowercase)
+        .or_else(|| supported_extension(&segment).map(str::to_string))
+        .or_else(|| content_type.and_then(extension_for).map(str::to_string))
+        .ok_or_else(|| {
+            anyhow!(
+                "Не удалось определить формат источника — укажите format: csv, xlsx, ods или json"
+            )
+        })?;
+    Ok(match supported_extension(&segment) {
+        Some(ext) if ext == extension => segment,
+        _ => format!("{}.{}", segment, extension),
+    })
+}
+
+/// Настройки TLS: корневые сертификаты Mozilla, только HTTP/1.1
+fn tls_connector() -> TlsConnector {
+    static CONFIG: OnceLock<Arc<ClientConfig>> = OnceLock::new();
+    let config = CONFIG.get_or_init(|| {
+        let roots = RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        };
+        let mut config = ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        Arc::new(config)
+    });
+    TlsConnector::from(config.clone())
+}
+
+/// Запрос по готовому соединению — открытому или зашифрованному
+async fn send<S>(stream: S, uri: &Uri) -> Result<hyper::Response<hyper::body::Incoming>>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let host = host_of(uri);
+    let (mut sender, connection) =
+        hyper::client::conn::http1::handshake::<_, Empty<Bytes>>(TokioIo::new(stream)).await?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            eprintln!("⚠️ refresh connection: {}", e);
+        }
+    });
+    let authority = uri.authority().map(|a| a.as_str()).unwrap_or(host);
+    let path = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    let request = Request::get(path)
+        .header(header::HOST, authority)
+        .header(header::USER_AGENT, "remora-backend")
+        .header(header::ACCEPT, "*/*")
+        .body(Empty::new())?;
+    timeout(RESPONSE_TIMEOUT, sender.send_request(request))
+        .await
+        .map_err(|_| {
+            anyhow!(
+                "Источник {} не ответил за {} с",
+                host,
+                RESPONSE_TIMEOUT.as_secs()
+            )
+        })?
+        .map_err(|e| anyhow!("Ошибка запроса к {}: {}", host, e))
+}
+
+/// Один запрос GET по HTTP/1.1 или HTTPS; переадресации возвращаются вызывающему
+async fn get(uri: &Uri, allowed: AddressCheck) -> Result<hyper::Response<hyper::body::Incoming>> {
+    let host = host_of(uri);
+    let address = resolve(uri, allowed).await?;
+    let timed_out = || {
+        anyhow!(
+            "Источник {} не ответил за {} с",
+            host,
+            CONNECT_TIMEOUT.as_secs()
+        )
+    };
+    let stream = timeout(CONNECT_TIMEOUT, TcpStream::connect(address))
+        .await
+        .map_err(|_| timed_out())?
+        .map_err(|e| anyhow!("Не удалось подключиться к {}: {}", host, e))?;
+    if !is_https(uri) {
+        return send(stream, uri).await;
+    }
+    let name = ServerName::try_from(host.to_string())
+        .map_err(|_| anyhow!("Некорректное имя сервера {}", host))?;
+    let stream = timeout(CONNECT_TIMEOUT, tls_connector().connect(name, stream))
+        .await
+        .map_err(|_| timed_out())?
+        .map_err(|e| anyhow!("Ошибка TLS при подключении к {}: {}", host, e))?;
+    send(stream, uri).await
+}
+
+async fn fetch_url(url: &str, format: Option<&str>) -> Result<(String, Vec<u8>)> {
+    fetch(url, format, is_public).await
+}
+
+/// Загрузка по адресу; каждая переадресация проверяется заново
+async fn fetch(
+    url: &str,
+    format: Option<&str>,
+    allowed: AddressCheck,
+) -> Result<(String, Vec<u8>)> {
+    let mut uri = parse_url(url)?;
+    for _ in 0..=MAX_REDIRECTS {
+        let response = get(&uri, allowed).await?;
+        let status = response.status();
+        if status.is_redirection() {
+            let location = response
+                .headers()
+                .get(header::LOCATION)
+                .and_then(|v| v.to_str().ok())
+                .ok_or_else(|| anyhow!("Переадресация без адреса ({})", status))?;
+            let scheme = uri.scheme_str().unwrap_or("http");
+            uri = if location.starts_with("//") {
+                parse_url(&format!("{}:{}", scheme, location))?
+            } else if location.starts_with('/') {
+                let authority = uri.authority().map(|a| a.as_str()).unwrap_or_default();
+                parse_url(&format!("{}://{}{}", scheme, authority, location))?
+            } else {
+                parse_url(location)?
+            };
+            continue;
+        }
+        if status != StatusCode::OK {
+            bail!("Источник ответил {}", status);
+        }
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let filename = url_filename(&uri, format, content_type.as_deref())?;
+        let body = Limited::new(response.into_body(), MAX_SOURCE_BYTES);
+        let bytes = timeout(RESPONSE_TIMEOUT, body.collect())
+            .await
+            .map_err(|_| {
+                anyhow!(
+                    "Источник не передал данные за {} с",
+                    RESPONSE_TIMEOUT.as_secs()
+                )
+            })?
+            .map_err(|e| {
+                if e.is::<http_body_util::LengthLimitError>() {
+                    anyhow!("Источник больше {} МБ", MAX_SOURCE_BYTES / 1024 / 1024)
+                } else {
+                    anyhow!("Ошибка чтения ответа: {}", e)
+                }
+            })?
+            .to_bytes();
+        return Ok((filename, bytes.to_vec()));
+    }
+    bail!("Слишком много переадресаций");
+}
+
+fn read_path(path: &str) -> Result<(String, Vec<u8>)> {
+    let full = resolve_path(path)?;
+    if fs::metadata(&full)?.len() > MAX_SOURCE_BYTES as u64 {
+        bail!("Файл больше {} МБ", MAX_SOURCE_BYTES / 1024 / 1024);
+    }
+    let filename = full
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.to_string());
+    Ok((filename, fs::read(&full)?))
+}
+
+/// Чтение источника и разбор существующими конвертерами
+pub async fn load(source: &RefreshSource) -> Result<(String, StoredTable)> {
+    let (filename, bytes) = match source {
+        RefreshSource::Path { path } => {
+            let path = path.clone();
+            tokio::task::spawn_blocking(move || read_path(&path)).await??
+        }
+        RefreshSource::Url { url, format } => fetch_url(url, format.as_deref()).await?,
+    };
+    let name = filename.clone();
+    let (columns, rows) = tokio::task::spawn_blocking(move || convert_by_extension(&name, bytes))
+        .await?
+        .map_err(|e| anyhow!("Не удалось разобрать «{}»: {}", filename, e))?;
+    if columns.is_empty() {
+        bail!("В источнике нет заголовков колонок");
+    }
+    Ok((filename, StoredTable { columns, rows }))
+}
+
+/// Обновлённый набор и отчёт очистки
+pub struct Refreshed {
+    pub record: DatasetRecord,
+    pub report: CleaningReport,
+}
+
+async fn run(pool: &Pool<Sqlite>, record: &DatasetRecord) -> Result<Refreshed> {
+    let source = parse_source(record.refresh_source.as_deref())
+        .ok_or_else(|| anyhow!("У набора нет источника обновления"))?;
+    let (filename, table) = load(&source).await?;
+    let steps = cleaning::parse_recipe(&record.cleaning);
+    let keys = append::parse_config(record.append_config.as_deref());
+    let (mut cleaned, report) = tokio::task::spawn_blocking(move || {
+        cleaning::clean_source(table, &steps, true, keys.as_ref())
+    })
+    .await??;
+    cleaned.source_filename = Some(filename);
+    let record = store::replace_cleaned(pool, record, cleaned).await?;
+    Ok(Refreshed { record, report })
+}
+
+/// Новая версия набора из его источника: рецепт очистки и ключи дозагрузки
+/// применяются как при замене файла. Итог — и удачный, и с ошибкой —
+/// сохраняется в `last_refresh`
+pub async fn refresh_dataset(pool: &Pool<Sqlite>, record: &DatasetRecord) -> Result<Refreshed> {
+    let started = Instant::now();
+    let mut result = run(pool, record).await;
+    let status = match &result {
+        Ok(refreshed) => RefreshStatus::succeeded(started, &refreshed.record),
+        Err(e) => RefreshStatus::failed(started, e),
+    };
+    let raw = serde_json::to_string(&status)?;
+    store::set_last_refresh(pool, &record.id, &raw).await?;
+    if let Ok(refreshed) = &mut result {
+        refreshed.record.last_refresh = Some(raw);
+    }
+    match &result {
+        Ok(refreshed) => println!(
+            "🔄 dataset refreshed: {:<25} | v{} | Rows {:>6} | {:>6} ms",
+            refreshed.record.name,
+            refreshed.record.version,
+            refreshed.record.row_count,
+            status.duration_ms
+        ),
+        Err(e) => eprintln!("❌ dataset refresh failed: {} — {}", record.name, e),
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datasets::models::DATASET_MIGRATION;
+    use sqlx::sqlite::SqlitePoolOptions;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    const CSV: &str = "Город,Сумма\nМосква,10\nКазань,20\n";
+    const JSON: &str = r#"[{"Город":"Омск","Сумма":5}]"#;
+
+    /// Для тестов доступен только 127.0.0.1, остальные адреса — как в работе
+    fn local(ip: IpAddr) -> bool {
+        ip == IpAddr::V4(Ipv4Addr::LOCALHOST) || is_public(ip)
+    }
+
+    fn respond(path: &str) -> String {
+        let (status, headers, body) = match path {
+            "/sales.csv" => ("200 OK", "Content-Type: text/csv\r\n", CSV),
+            "/export" => ("200 OK", "Content-Type: application/json\r\n", JSON),
+            "/moved" => ("302 Found", "Location: /sales.csv\r\n", ""),
+            "/internal" => (
+                "302 Found",
+                "Location: http://169.254.169.254/latest/meta-data\r\n",
+                "",
+            ),
+            "/loop" => ("302 Found", "Location: /loop\r\n", ""),
+            _ => ("404 Not Found", "", "not found"),
+        };
+        format!(
+            "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            headers,
+            body.len(),
+            body
+        )
+    }
+
+    /// Локальный HTTP-сервер вместо настоящего источника
+    async fn serve() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut head = Vec::new();
+                    let mut buf = [0u8; 1024];
+                    while !head.ends_with(b"\r\n\r\n") {
+                        match stream.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(n) => head.extend_from_slice(&buf[..n]),
+                        }
+                    }
+                    let head = String::from_utf8_lossy(&head);
+                    let path = head.split_whitespace().nth(1).unwrap_or("/");
+                    let _ = stream.write_all(respond(path).as_bytes()).await;
+                });
+            }
+        });
+        format!("http://{}", address)
+    }
+
+    fn convert((filename, bytes): (String, Vec<u8>)) -> (String, StoredTable) {
+        let (columns, rows) = convert_by_extension(&filename, bytes).unwrap();
+        (filename, StoredTable { columns, rows })
+    }
+
+    /// Общий каталог импорта для всех тестов: `IMPORT_DIR` задаётся один раз
+    fn import_root() -> &'static PathBuf {
+        static ROOT: OnceLock<PathBuf> = OnceLock::new();
+        ROOT.get_or_init(|| {
+            let base = tempfile::tempdir().unwrap().keep();
+            let root = base.join("imports");
+            fs::create_dir_all(&root).unwrap();
+            fs::write(root.join("sales.csv"), CSV).unwrap();
+            fs::write(root.join("notes.txt"), "text").unwrap();
+            fs::write(base.join("secret.csv"), "Пароль\nqwerty\n").unwrap();
+            env::set_var("IMPORT_DIR", &root);
+            root
+        })
+    }
+
+    async fn memory_pool() -> Pool<Sqlite> {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::raw_sql(DATASET_MIGRATION)
+            .execute(&pool)
+            .await
+            .unwrap();
+        pool
+    }
+
+    async fn dataset_with_source(pool: &Pool<Sqlite>, source: &RefreshSource) -> DatasetRecord {
+        sqlx::query(
+            "INSERT INTO datasets (id, name, owner, uploaded_at, updated_at, refresh_source)
+             VALUES ('ds', 'Продажи', '1', '2024-01-01', '2024-01-01', ?)",
+        )
+        .bind(serde_json::to_string(source).unwrap())
+        .execute(pool)
+        .await
+        .unwrap();
+        store::get_dataset(pool, "ds").await.unwrap().unwrap()
+    }
+
+    #[test]
+    fn internal_addresses_are_not_public() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fe80::1",
+            "fd00::1",
+            "::ffff:10.0.0.1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in ["8.8.8.8", "93.184.216.34", "2606:4700::1111"] {
+            assert!(is_public(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[test]
+    fn url_schemes_and_literal_addresses() {
+        assert!(parse_url("https://example.com/data.csv").is_ok());
+        assert!(parse_url("http://example.com/data.csv").is_ok());
+        let ftp = parse_url("ftp://example.com/data.csv").unwrap_err();
+        assert_eq!(
+            ftp.to_string(),
+            "Поддерживаются только адреса http:// и https://"
+        );
+        for url in [
+            "http://127.0.0.1:8080/api",
+            "http://169.254.169.254/latest",
+            "http://[::1]/x.csv",
+        ] {
+            let source = RefreshSource::Url {
+                url: url.to_string(),
+                format: None,
+            };
+            let error = validate_source(&source).unwrap_err().to_string();
+            assert!(error.contains("внутреннюю сеть"), "{}: {}", url, error);
+        }
+    }
+
+    #[tokio::test]
+    async fn fetches_csv_and_json() {
+        let base = serve().await;
+        let (name, table) = convert(
+            fetch(&format!("{}/sales.csv", base), None, local)
+                .await
+                .unwrap(),
+        );
+        assert_eq!(name, "sales.csv");
+        assert_eq!(table.columns, ["Город", "Сумма"]);
+        assert_eq!(table.rows.len(), 2);
+
+        let (name, table) = convert(
+            fetch(&format!("{}/export", base), None, local)
+                .await
+                .unwrap(),
+        );
+        assert_eq!(name, "export.json");
+        assert_eq!(table.rows, [["Омск", "5"]]);
+    }
+
+    #[tokio::test]
+    async fn follows_redirects() {
+        let base = serve().await;
+        let (name, bytes) = fetch(&format!("{}/moved", base), None, local)
+            .await
+            .unwrap();
+        assert_eq!(name, "sales.csv");
+        assert_eq!(bytes, CSV.as_bytes());
+
+        let error = fetch(&format!("{}/loop", base), None, local)
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Слишком много переадресаций");
+    }
+
+    #[tokio::test]
+    async fn redirect_target_is_checked() {
+        let base = serve().await;
+        let error = fetch(&format!("{}/internal", base), None, local)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Адрес 169.254.169.254 ведёт во внутреннюю сеть — такие источники запрещены"
+        );
+    }
+
+    #[tokio::test]
+    async fn loopback_is_rejected_by_default() {
+        let base = serve().await.replace("127.0.0.1", "localhost");
+        let error = fetch_url(&format!("{}/sales.csv", base), None)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Адрес localhost ведёт во внутреннюю сеть — такие источники запрещены"
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_source_reports_status() {
+        let base = serve().await;
+        let error = fetch(&format!("{}/missing.csv", base), None, local)
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Источник ответил 404 Not Found");
+    }
+
+    #[tokio::test]
+    async fn https_certificate_is_verified() {
+        use tokio_rustls::rustls::pki_types::PrivateKeyDer;
+        use tokio_rustls::rustls::ServerConfig;
+        use tokio_rustls::TlsAcceptor;
+
+        let certified = rcgen::generate_simple_self_signed(vec!["127.0.0.1".to_string()]).unwrap();
+        let key = PrivateKeyDer::Pkcs8(certified.signing_key.serialize_der().into());
+        let config = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(vec![certified.cert.der().clone()], key)
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let _ = acceptor.accept(stream).await;
+            }
+        });
+
+        let error = fetch(&format!("https://{}/sales.csv", address), None, local)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.starts_with("Ошибка TLS при подключении к 127.0.0.1"),
+            "{}",
+            error
+        );
+    }
+
+    #[tokio::test]
+    async fn imports_from_import_dir() {
+        import_root();
+        let source = RefreshSource::Path {
+            path: "sales.csv".to_string(),
+        };
+        validate_source(&source).unwrap();
+        let (name, table) = load(&source).await.unwrap();
+        assert_eq!(name, "sales.csv");
+        assert_eq!(table.rows, [["Москва", "10"], ["Казань", "20"]]);
+    }
+
+    #[tokio::test]
+    async fn import_dir_rejects_traversal_and_unknown_formats() {
+        import_root();
+        let outside = RefreshSource::Path {
+            path: "../secret.csv".to_string(),
+        };
+        assert_eq!(
+            validate_source(&outside).unwrap_err().to_string(),
+            "Путь «../secret.csv» выходит за пределы каталога импорта"
+        );
+        assert!(load(&outside).await.is_err());
+
+        let text = RefreshSource::Path {
+            path: "notes.txt".to_string(),
+        };
+        assert_eq!(
+            validate_source(&text).unwrap_err().to_string(),
+            "Поддерживаются только файлы CSV, XLSX, ODS и JSON"
+        );
+        let missing = RefreshSource::Path {
+            path: "absent.csv".to_string(),
+        };
+        assert_eq!(
+            validate_source(&missing).unwrap_err().to_string(),
+            "Файл «absent.csv» не найден в каталоге импорта"
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_refresh_is_saved_in_last_refresh() {
+        import_root();
+        let pool = memory_pool().await;
+        let source = RefreshSource::Path {
+            path: "../secret.csv".to_string(),
+        };
+        let record = dataset_with_source(&pool, &source).await;
+        assert!(refresh_dataset(&pool, &record).await.is_err());
+
+        let saved = store::get_dataset(&pool, "ds").await.unwrap().unwrap();
+        let status: RefreshStatus = serde_json::from_str(&saved.last_refresh.unwrap()).unwrap();
+        assert!(!status.ok);
+        assert_eq!(
+            status.error.as_deref(),
+            Some("Путь «../secret.csv» выходит за пределы каталога импорта")
+        );
+        assert_eq!(status.version, None);
+        assert_eq!(saved.version, 1);
+    }
+
+    #[tokio::test]
+    async fn refresh_from_internal_url_is_refused() {
+        let pool = memory_pool().await;
+        let base = serve().await;
+        let source = RefreshSource::Url {
+            url: format!("{}/sales.csv", base),
+            format: None,
+        };
+        let record = dataset_with_source(&pool, &source).await;
+        let Err(error) = refresh_dataset(&pool, &record).await else {
+            panic!("обновление из внутренней сети должно завершиться ошибкой");
+        };
+        assert!(error.to_string().contains("внутреннюю сеть"));
+
+        let saved = store::get_dataset(&pool, "ds").await.unwrap().unwrap();
+        let status: RefreshStatus = serde_json::from_str(&saved.last_refresh.unwrap()).unwrap();
+        assert!(!status.ok);
+        assert_eq!(status.error, Some(error.to_string()));
+    }
+}
@@ -0,0 +1,116 @@
+use crate::exporter::parse_number;
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+/// Сколько непустых значений просматривается при определении типа колонки
+const INFERENCE_SAMPLE: usize = 1000;
+
+const DATETIME_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M",
+    "%d.%m.%Y %H:%M:%S",
+    "%d.%m.%Y %H:%M",
+];
+
+const DATE_FORMATS: &[&str] = &["%Y-%m-%d", "%d.%m.%Y", "%d/%m/%Y", "%Y/%m/%d"];
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ColumnType {
+    String,
+    Number,
+    Date,
+    Boolean,
+}
+
+/// Описание колонки сохранённого набора данных
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ColumnSchema {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub column_type: ColumnType,
+    /// В колонке встречаются пустые значения
+    pub nullable: bool,
+}
+
+/// Разбор даты в форматах, которые встречаются в выгрузках: ISO 8601
+/// (с временем и зоной или без) и русские `ДД.ММ.ГГГГ`
+pub fn parse_date(value: &str) -> Option<NaiveDateTime> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+    if let Ok(parsed) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Some(parsed.naive_utc());
+    }
+    for format in DATETIME_FORMATS {
+        if let Ok(parsed) = NaiveDateTime::parse_from_str(value, format) {
+            return Some(parsed);
+        }
+    }
+    DATE_FORMATS.iter().find_map(|format| {
+        NaiveDate::parse_from_str(value, format)
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+    })
+}
+
+/// Логическое значение в записи выгрузок: `true`/`false` или `да`/`нет`
+pub fn parse_bool(value: &str) -> Option<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "true" | "да" => Some(true),
+        "false" | "нет" => Some(false),
+        _ => None,
+    }
+}
+
+/// Тип отдельного значения: число, логическое, дата или строка
+pub fn value_type(value: &str) -> ColumnType {
+    if parse_number(value).is_some() {
+        ColumnType::Number
+    } else if parse_bool(value).is_some() {
+        ColumnType::Boolean
+    } else if parse_date(value).is_some() {
+        ColumnType::Date
+    } else {
+        ColumnType::String
+    }
+}
+
+/// Тип колонки по первым непустым значениям; всё, что не сводится
+/// к одному типу, считается строкой
+fn infer_type<'a>(values: impl Iterator<Item = &'a str>) -> ColumnType {
+    let mut candidate: Option<ColumnType> = None;
+    for value in values.take(INFERENCE_SAMPLE) {
+        let current = value_type(value);
+        if current == ColumnType::String {
+            return ColumnType::String;
+        }
+        match candidate {
+            None => candidate = Some(current),
+            Some(previous) if previous != current => return ColumnType::String,
+            _ => {}
+        }
+    }
+    candidate.unwrap_or(ColumnType::String)
+}
+
+/// Схема набора данных по заголовкам и строкам
+pub fn infer_schema<'a>(columns: &[String], rows: &'a [Vec<String>]) -> Vec<ColumnSchema> {
+    columns
+        .iter()
+        .enumerate()
+        .map(|(idx, name)| {
+            let cell = |row: &'a Vec<String>| row.get(idx).map(|v| v.trim()).unwrap_or("");
+            let values = rows.iter().map(cell).filter(|v| !v.is_empty());
+            ColumnSchema {
+                name: name.clone(),
+                column_type: infer_type(values),
+                nullable: rows.iter().any(|row| cell(row).is_empty()),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,732 @@
+use crate::analytics::{cache, columnar};
+use crate::datasets::append::{self, AppendConfig, AppendReport};
+use crate::datasets::cleaning::CleaningReport;
+use crate::datasets::derived::DerivedDefinition;
+use crate::datasets::models::{DatasetRecord, KIND_DERIVED, KIND_FILE};
+use crate::datasets::schema::{infer_schema, ColumnSchema};
+use crate::datasets::versions::{self, VersionRecord};
+use crate::sql::engine;
+use crate::storage::data_subdir;
+use anyhow::Result;
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::query::QueryAs;
+use sqlx::sqlite::SqliteArguments;
+use sqlx::{Pool, Sqlite};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+const DATASETS_DIR: &str = "datasets";
+const MAX_NAME_LEN: usize = 200;
+
+/// Колонки перечисляются явно: `SELECT *`, подготовленный соединением пула
+/// до `ALTER TABLE` на другом соединении, вернёт старый набор колонок
+const RECORD_COLUMNS: &str = "id, name, owner, source_filename, schema, row_count, size_bytes, \
+    uploaded_at, updated_at, kind, definition, computed, cleaning, version, append_config, \
+    refresh_source, last_refresh";
+
+/// Содержимое набора данных в том же виде, что отдаёт `/api/upload`
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct StoredTable {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// Идентификатор приходит из URL — пускаем только то, что мы сами выдали
+pub fn is_valid_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= 64 && id.chars().all(|c| c.is_ascii_hexdigit() || c == '-')
+}
+
+/// Отображаемое имя набора: без управляющих символов и не длиннее 200 символов
+pub fn normalize_name(name: &str) -> Option<String> {
+    let cleaned: String = name
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_NAME_LEN)
+        .collect();
+    let cleaned = cleaned.trim();
+    (!cleaned.is_empty()).then(|| cleaned.to_string())
+}
+
+/// Сброс всего, что посчитано по прежним строкам набора
+fn dataset_changed(id: &str) {
+    cache::invalidate_dataset(id);
+    columnar::evict_dataset(id);
+}
+
+fn now() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Строки загруженного набора после очистки вместе с исходным файлом и рецептом
+pub struct CleanedTable {
+    /// `None` — имя файла не менялось
+    pub source_filename: Option<String>,
+    /// Файл до очистки; `None` — исходный файл не менялся
+    pub source: Option<StoredTable>,
+    pub table: StoredTable,
+    pub schema: Vec<ColumnSchema>,
+    /// Рецепт очистки JSON-массивом
+    pub cleaning: String,
+}
+
+fn data_path(id: &str) -> io::Result<PathBuf> {
+    Ok(data_subdir(DATASETS_DIR)?.join(format!("{}.json", id)))
+}
+
+/// Исходный файл до очистки хранится рядом со строками, чтобы рецепт
+/// можно было поменять без повторной загрузки
+fn source_path(id: &str) -> io::Result<PathBuf> {
+    Ok(data_subdir(DATASETS_DIR)?.join(format!("{}.source.json", id)))
+}
+
+fn remove_if_exists(path: io::Result<PathBuf>) -> io::Result<()> {
+    match fs::remove_file(path?) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+fn write_table(id: &str, table: &StoredTable) -> Result<u64> {
+    write_json(data_path(id)?, table)
+}
+
+fn write_json(path: PathBuf, table: &StoredTable) -> Result<u64> {
+    let tmp = path.with_extension("json.tmp");
+    let mut writer = BufWriter::new(fs::File::create(&tmp)?);
+    serde_json::to_writer(&mut writer, table)?;
+    writer.flush()?;
+    drop(writer);
+    fs::rename(&tmp, &path)?;
+    Ok(fs::metadata(&path)?.len())
+}
+
+/// Запись очищенных строк и, если он поменялся, исходного файла
+fn write_cleaned(id: &str, source: Option<&StoredTable>, table: &StoredTable) -> Result<u64> {
+    if let Some(source) = source {
+        write_json(source_path(id)?, source)?;
+    }
+    write_table(id, table)
+}
+
+/// Чтение строк набора с диска; `None`, если файла нет
+pub fn read_table(id: &str) -> Result<Option<StoredTable>> {
+    read_json(data_path(id)?)
+}
+
+/// Исходный файл набора до очистки; у наборов, загруженных до появления
+/// рецептов, исходным считается сохранённый файл строк
+pub fn read_source(id: &str) -> Result<Option<StoredTable>> {
+    match read_json(source_path(id)?)? {
+        Some(source) => Ok(Some(source)),
+        None => read_table(id),
+    }
+}
+
+fn read_json(path: PathBuf) -> Result<Option<StoredTable>> {
+    let file = match fs::File::open(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(Some(serde_json::from_reader(BufReader::new(file))?))
+}
+
+/// Сохранение нового набора: строки и исходный файл — в каталоге данных,
+/// метаданные и рецепт очистки — в SQLite
+pub async fn create_dataset(
+    pool: &Pool<Sqlite>,
+    name: &str,
+    owner: &str,
+    cleaned: CleanedTable,
+) -> Result<DatasetRecord> {
+    let id = uuid::Uuid::new_v4().to_string();
+    let schema = serde_json::to_string(&cleaned.schema)?;
+    let row_count = cleaned.table.rows.len() as i64;
+
+    let file_id = id.clone();
+    let (source, table) = (cleaned.source, cleaned.table);
+    let size_bytes =
+        tokio::task::spawn_blocking(move || write_cleaned(&file_id, source.as_ref(), &table))
+            .await?? as i64;
+
+    let record = NewRecord {
+        id: &id,
+        name,
+        owner,
+        source_filename: cleaned.source_filename.as_deref(),
+        schema: &schema,
+        row_count,
+        size_bytes,
+        kind: KIND_FILE,
+        definition: None,
+        cleaning: &cleaned.cleaning,
+    };
+    if let Err(e) = insert_record(pool, record).await {
+        let _ = remove_if_exists(data_path(&id));
+        let _ = remove_if_exists(source_path(&id));
+        return Err(e);
+    }
+    let record = fetch_inserted(pool, &id).await?;
+    versions::record_version(pool, &record, None).await?;
+    Ok(record)
+}
+
+/// Замены строк одного набора идут по очереди: файлы текущей версии общие,
+/// и параллельная запись перепутала бы их с метаданными другой версии
+static WRITERS: OnceLock<Mutex<HashMap<String, Arc<AsyncMutex<()>>>>> = OnceLock::new();
+
+async fn lock_writes(id: &str) -> OwnedMutexGuard<()> {
+    let lock = {
+        let mut writers = WRITERS
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        writers.retain(|_, lock| Arc::strong_count(lock) > 1);
+        writers.entry(id.to_string()).or_default().clone()
+    };
+    lock.lock_owned().await
+}
+
+/// Копия текущих файлов набора в историю версий перед заменой; наборы,
+/// загруженные до появления версий, получают запись о версии здесь же.
+/// Возвращает состояние набора на момент замены
+async fn archive_current(pool: &Pool<Sqlite>, id: &str) -> Result<DatasetRecord> {
+    let record = fetch_inserted(pool, id).await?;
+    versions::ensure_version(pool, &record).await?;
+    let (id, version) = (record.id.clone(), record.version);
+    tokio::task::spawn_blocking(move || {
+        versions::archive_files(&id, version, &data_path(&id)?, &source_path(&id)?)
+    })
+    .await??;
+    Ok(record)
+}
+
+/// Новая версия набора: номер выделяет тот же UPDATE, что меняет метаданные,
+/// запись в истории добавляется в той же транзакции
+async fn commit_version<'q>(
+    pool: &Pool<Sqlite>,
+    update: QueryAs<'q, Sqlite, DatasetRecord, SqliteArguments<'q>>,
+    restored_from: Option<i64>,
+) -> Result<DatasetRecord> {
+    let mut tx = pool.begin().await?;
+    let record = update.fetch_one(&mut *tx).await?;
+    versions::record_version(&mut *tx, &record, restored_from).await?;
+    tx.commit().await?;
+    dataset_changed(&record.id);
+    versions::prune_versions(pool, &record.id, record.version).await?;
+    Ok(record)
+}
+
+/// Набор сменил версию, пока готовилась замена, — отдаётся клиенту как 409
+#[derive(Debug)]
+pub struct VersionConflict;
+
+impl std::fmt::Display for VersionConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Набор данных изменился, пока шла обработка, — повторите операцию")
+    }
+}
+
+impl std::error::Error for VersionConflict {}
+
+/// Новая версия загруженного набора: новый файл или новый рецепт очистки.
+/// Строки готовились по версии `record`; если набор успели заменить,
+/// замена отклоняется с [`VersionConflict`]. Прежние файлы остаются
+/// в истории версий
+pub async fn replace_cleaned(
+    pool: &Pool<Sqlite>,
+    record: &DatasetRecord,
+    cleaned: CleanedTable,
+) -> Result<DatasetRecord> {
+    let _writes = lock_writes(&record.id).await;
+    if fetch_inserted(pool, &record.id).await?.version != record.version {
+        return Err(VersionConflict.into());
+    }
+    commit_cleaned(pool, &record.id, cleaned, None).await
+}
+
+/// Запись очищенных строк новой версией под блокировкой записи набора;
+/// `append_config` меняется тем же UPDATE, что выделяет версию
+async fn commit_cleaned(
+    pool: &Pool<Sqlite>,
+    id: &str,
+    cleaned: CleanedTable,
+    append_config: Option<&str>,
+) -> Result<DatasetRecord> {
+    archive_current(pool, id).await?;
+    let schema = serde_json::to_string(&cleaned.schema)?;
+    let row_count = cleaned.table.rows.len() as i64;
+    let file_id = id.to_string();
+    let (source, table) = (cleaned.source, cleaned.table);
+    let size_bytes =
+        tokio::task::spawn_blocking(move || write_cleaned(&file_id, source.as_ref(), &table))
+            .await?? as i64;
+    let sql = format!(
+        "UPDATE datasets SET schema = ?, row_count = ?, size_bytes = ?, cleaning = ?,
+         source_filename = COALESCE(?, source_filename),
+         append_config = COALESCE(?, append_config), version = version + 1, updated_at = ?
+         WHERE id = ? RETURNING {}",
+        RECORD_COLUMNS
+    );
+    let update = sqlx::query_as(&sql)
+        .bind(&schema)
+        .bind(row_count)
+        .bind(size_bytes)
+        .bind(&cleaned.cleaning)
+        .bind(cleaned.source_filename)
+        .bind(append_config)
+        .bind(now())
+        .bind(id);
+    commit_version(pool, update, None).await
+}
+
+/// Итог дозагрузки: набор после неё (при пробном прогоне — как был)
+/// и отчёты слияния и очистки
+pub struct Appended {
+    pub record: DatasetRecord,
+    pub report: AppendReport,
+    pub cleaning: CleaningReport,
+}
+
+/// Дозагрузка файла новой версией. Запись набора, его строки и исходный
+/// файл читаются под блокировкой записи, поэтому параллельные дозагрузки,
+/// замены файла и смены рецепта не теряют строк. Ключи и режим сохраняются
+/// вместе с версией; `dry_run` — только отчёт, без записи
+pub async fn append_rows(
+    pool: &Pool<Sqlite>,
+    id: &str,
+    filename: String,
+    incoming: StoredTable,
+    config: &AppendConfig,
+    dry_run: bool,
+) -> Result<Appended> {
+    let _writes = lock_writes(id).await;
+    let record = fetch_inserted(pool, id).await?;
+    let (merge_record, merge_config) = (record.clone(), config.clone());
+    let (mut cleaned, report, cleaning) = tokio::task::spawn_blocking(move || {
+        let missing = || anyhow::anyhow!("Файл набора данных не найден");
+        let current_source = read_source(&merge_record.id)?.ok_or_else(missing)?;
+        let current = read_table(&merge_record.id)?.ok_or_else(missing)?;
+        append::apply(
+            &merge_record,
+            current_source,
+            current,
+            incoming,
+            &merge_config,
+        )
+    })
+    .await??;
+    if dry_run {
+        return Ok(Appended {
+            record,
+            report,
+            cleaning,
+        });
+    }
+    cleaned.source_filename = Some(filename);
+    let raw_config = serde_json::to_string(config)?;
+    let record = commit_cleaned(pool, id, cleaned, Some(&raw_config)).await?;
+    Ok(Appended {
+        record,
+        report,
+        cleaning,
+    })
+}
+
+/// Откат к сохранённой версии: её файлы и метаданные становятся новой версией
+pub async fn restore_version(
+    pool: &Pool<Sqlite>,
+    record: &DatasetRecord,
+    target: &VersionRecord,
+) -> Result<DatasetRecord> {
+    let _writes = lock_writes(&record.id).await;
+    archive_current(pool, &record.id).await?;
+    let (id, version) = (record.id.clone(), target.version);
+    let size_bytes = tokio::task::spawn_blocking(move || {
+        versions::restore_files(&id, version, &data_path(&id)?, &source_path(&id)?)
+    })
+    .await?? as i64;
+    let sql = format!(
+        "UPDATE datasets SET schema = ?, row_count = ?, size_bytes = ?, cleaning = ?,
+         source_filename = ?, version = version + 1, updated_at = ? WHERE id = ? RETURNING {}",
+        RECORD_COLUMNS
+    );
+    let update = sqlx::query_as(&sql)
+        .bind(&target.schema)
+        .bind(target.row_count)
+        .bind(size_bytes)
+        .bind(&target.cleaning)
+        .bind(&target.source_filename)
+        .bind(now())
+        .bind(&record.id);
+    commit_version(pool, update, Some(target.version)).await
+}
+
+/// Сохранение материализованного производного набора: строки в файле,
+/// описание — для пересборки
+pub async fn create_materialized_dataset(
+    pool: &Pool<Sqlite>,
+    name: &str,
+    owner: &str,
+    kind: &str,
+    definition: &Value,
+    table: StoredTable,
+) -> Result<DatasetRecord> {
+    let definition = serde_json::to_string(definition)?;
+    let id = uuid::Uuid::new_v4().to_string();
+    let schema = serde_json::to_string(&infer_schema(&table.columns, &table.rows))?;
+    let row_count = table.rows.len() as i64;
+
+    let file_id = id.clone();
+    let size_bytes =
+        tokio::task::spawn_blocking(move || write_table(&file_id, &table)).await?? as i64;
+
+    let record = NewRecord {
+        id: &id,
+        name,
+        owner,
+        source_filename: None,
+        schema: &schema,
+        row_count,
+        size_bytes,
+        kind,
+        definition: Some(&definition),
+        cleaning: "[]",
+    };
+    if let Err(e) = insert_record(pool, record).await {
+        let _ = remove_if_exists(data_path(&id));
+        return Err(e);
+    }
+    let record = fetch_inserted(pool, &id).await?;
+    versions::record_version(pool, &record, None).await?;
+    Ok(record)
+}
+
+/// Пересборка строк материализованного набора — новая версия с пересчётом
+/// схемы, числа строк и размера
+pub async fn replace_table(
+    pool: &Pool<Sqlite>,
+    id: &str,
+    table: StoredTable,
+) -> Result<DatasetRecord> {
+    let _writes = lock_writes(id).await;
+    archive_current(pool, id).await?;
+    let schema = serde_json::to_string(&infer_schema(&table.columns, &table.rows))?;
+    let row_count = table.rows.len() as i64;
+    let file_id = id.to_string();
+    let size_bytes =
+        tokio::task::spawn_blocking(move || write_table(&file_id, &table)).await?? as i64;
+    let sql = format!(
+        "UPDATE datasets SET schema = ?, row_count = ?, size_bytes = ?, version = version + 1,
+         updated_at = ? WHERE id = ? RETURNING {}",
+        RECORD_COLUMNS
+    );
+    let update = sqlx::query_as(&sql)
+        .bind(&schema)
+        .bind(row_count)
+        .bind(size_bytes)
+        .bind(now())
+        .bind(id);
+    commit_version(pool, update, None).await
+}
+
+/// Сохранение виртуального набора: файла строк нет, схема и число строк
+/// берутся из пробного выполнения
+pub async fn create_virtual_dataset(
+    pool: &Pool<Sqlite>,
+    name: &str,
+    owner: &str,
+    kind: &str,
+    definition: &Value,
+    preview: &StoredTable,
+) -> Result<DatasetRecord> {
+    let id = uuid::Uuid::new_v4().to_string();
+    let schema = serde_json::to_string(&infer_schema(&preview.columns, &preview.rows))?;
+    let definition = serde_json::to_string(definition)?;
+    let record = NewRecord {
+        id: &id,
+        name,
+        owner,
+        source_filename: None,
+        schema: &schema,
+        row_count: preview.rows.len() as i64,
+        size_bytes: 0,
+        kind,
+        definition: Some(&definition),
+        cleaning: "[]",
+    };
+    insert_record(pool, record).await?;
+    fetch_inserted(pool, &id).await
+}
+
+struct NewRecord<'a> {
+    id: &'a str,
+    name: &'a str,
+    owner: &'a str,
+    source_filename: Option<&'a str>,
+    schema: &'a str,
+    row_count: i64,
+    size_bytes: i64,
+    kind: &'a str,
+    definition: Option<&'a str>,
+    cleaning: &'a str,
+}
+
+async fn insert_record(pool: &Pool<Sqlite>, record: NewRecord<'_>) -> Result<()> {
+    let timestamp = now();
+    sqlx::query(
+        "INSERT INTO datasets (id, name, owner, source_filename, schema, row_count, size_bytes, uploaded_at, updated_at, kind, definition, cleaning)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(record.id)
+    .bind(record.name)
+    .bind(record.owner)
+    .bind(record.source_filename)
+    .bind(record.schema)
+    .bind(record.row_count)
+    .bind(record.size_bytes)
+    .bind(&timestamp)
+    .bind(&timestamp)
+    .bind(record.kind)
+    .bind(record.definition)
+    .bind(record.cleaning)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+async fn fetch_inserted(pool: &Pool<Sqlite>, id: &str) -> Result<DatasetRecord> {
+    get_dataset(pool, id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("набор {} не найден после вставки", id))
+}
+
+pub async fn get_dataset(pool: &Pool<Sqlite>, id: &str) -> sqlx::Result<Option<DatasetRecord>> {
+    sqlx::query_as::<_, DatasetRecord>(&format!(
+        "SELECT {} FROM datasets WHERE id = ?",
+        RECORD_COLUMNS
+    ))
+    .bind(id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Список наборов; `owner = None` — все наборы (для администраторов)
+pub async fn list_datasets(
+    pool: &Pool<Sqlite>,
+    owner: Option<&str>,
+) -> sqlx::Result<Vec<DatasetRecord>> {
+    match owner {
+        Some(owner) => {
+            sqlx::query_as::<_, DatasetRecord>(&format!(
+                "SELECT {} FROM datasets WHERE owner = ? ORDER BY uploaded_at DESC",
+                RECORD_COLUMNS
+            ))
+            .bind(owner)
+            .fetch_all(pool)
+            .await
+        }
+        None => {
+            sqlx::query_as::<_, DatasetRecord>(&format!(
+                "SELECT {} FROM datasets ORDER BY uploaded_at DESC",
+                RECORD_COLUMNS
+            ))
+            .fetch_all(pool)
+            .await
+        }
+    }
+}
+
+pub async fn rename_dataset(pool: &Pool<Sqlite>, id: &str, name: &str) -> sqlx::Result<bool> {
+    let result = sqlx::query("UPDATE datasets SET name = ?, updated_at = ? WHERE id = ?")
+        .bind(name)
+        .bind(now())
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Замена списка вычисляемых колонок (JSON-массив)
+pub async fn set_computed(pool: &Pool<Sqlite>, id: &str, computed: &str) -> sqlx::Result<bool> {
+    let result = sqlx::query("UPDATE datasets SET computed = ?, updated_at = ? WHERE id = ?")
+        .bind(computed)
+        .bind(now())
+        .bind(id)
+        .execute(pool)
+        .await?;
+    dataset_changed(id);
+    Ok(result.rows_affected() > 0)
+}
+
+/// Сохранение источника обновления (JSON-объект); `None` — отвязать источник
+pub async fn set_refresh_source(
+    pool: &Pool<Sqlite>,
+    id: &str,
+    source: Option<&str>,
+) -> sqlx::Result<()> {
+    sqlx::query("UPDATE datasets SET refresh_source = ?, last_refresh = NULL WHERE id = ?")
+        .bind(source)
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Итог последнего обновления из источника (JSON-объект)
+pub async fn set_last_refresh(pool: &Pool<Sqlite>, id: &str, status: &str) -> sqlx::Result<()> {
+    sqlx::query("UPDATE datasets SET last_refresh = ? WHERE id = ?")
+        .bind(status)
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Наборы, которые сломает удаление `record`: производные, в описании которых
+/// он указан как источник, и сохранённые SQL-запросы владельца к его таблице
+pub async fn dependent_datasets(
+    pool: &Pool<Sqlite>,
+    record: &DatasetRecord,
+) -> Result<Vec<String>> {
+    let derived: Vec<(String, Option<String>)> =
+        sqlx::query_as("SELECT id, definition FROM datasets WHERE kind = ? ORDER BY id")
+            .bind(KIND_DERIVED)
+            .fetch_all(pool)
+            .await?;
+    let mut dependents: Vec<String> = derived
+        .into_iter()
+        .filter(|(_, definition)| {
+            definition
+                .as_deref()
+                .and_then(|raw| serde_json::from_str::<DerivedDefinition>(raw).ok())
+                .is_some_and(|definition| definition.spec.sources().contains(&record.id.as_str()))
+        })
+        .map(|(dependent, _)| dependent)
+        .collect();
+    dependents.extend(engine::dependent_queries(pool, record).await?);
+    dependents.sort();
+    Ok(dependents)
+}
+
+/// Удаление метаданных, файла со строками, исходного файла и истории версий
+pub async fn delete_dataset(pool: &Pool<Sqlite>, id: &str) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM datasets WHERE id = ?")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Ok(false);
+    }
+    dataset_changed(id);
+    remove_if_exists(data_path(id))?;
+    remove_if_exists(source_path(id))?;
+    versions::delete_versions(pool, id).await?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datasets::models::{DATASET_MIGRATION, KIND_SQL};
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    async fn insert(pool: &Pool<Sqlite>, id: &str, kind: &str, definition: Option<&str>) {
+        sqlx::query(
+            "INSERT INTO datasets (id, name, owner, uploaded_at, updated_at, kind, definition) \
+             VALUES (?, ?, 'u', '', '', ?, ?)",
+        )
+        .bind(id)
+        .bind(id)
+        .bind(kind)
+        .bind(definition)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    async fn memory_pool() -> Pool<Sqlite> {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::raw_sql(DATASET_MIGRATION)
+            .execute(&pool)
+            .await
+            .unwrap();
+        pool
+    }
+
+    async fn dependents(pool: &Pool<Sqlite>, id: &str) -> Vec<String> {
+        let record = get_dataset(pool, id).await.unwrap().unwrap();
+        dependent_datasets(pool, &record).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn derived_datasets_depend_on_their_sources() {
+        let pool = memory_pool().await;
+        insert(&pool, "a", KIND_FILE, None).await;
+        insert(&pool, "b", KIND_FILE, None).await;
+        insert(
+            &pool,
+            "join",
+            KIND_DERIVED,
+            Some(r#"{"type":"join","left":"a","right":"b","on":[{"left":"k","right":"k"}]}"#),
+        )
+        .await;
+        insert(
+            &pool,
+            "union",
+            KIND_DERIVED,
+            Some(r#"{"type":"union","datasets":["b","join"],"materialized":true}"#),
+        )
+        .await;
+
+        assert_eq!(dependents(&pool, "a").await, ["join"]);
+        assert_eq!(dependents(&pool, "b").await, ["join", "union"]);
+        assert_eq!(dependents(&pool, "join").await, ["union"]);
+        assert!(dependents(&pool, "union").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn saved_queries_depend_on_their_tables() {
+        let pool = memory_pool().await;
+        insert(&pool, "a", KIND_FILE, None).await;
+        insert(&pool, "b", KIND_FILE, None).await;
+        insert(&pool, "c", KIND_FILE, None).await;
+        // «c» и «d» называются одинаково: таблицы `sales` и `sales_2`
+        insert(&pool, "d", KIND_FILE, None).await;
+        rename_dataset(&pool, "c", "sales.csv").await.unwrap();
+        rename_dataset(&pool, "d", "Sales.xlsx").await.unwrap();
+        insert(&pool, "q1", KIND_SQL, Some(r#"{"sql":"SELECT * FROM A"}"#)).await;
+        insert(
+            &pool,
+            "q2",
+            KIND_SQL,
+            Some(r#"{"sql":"SELECT 'b' FROM a"}"#),
+        )
+        .await;
+        insert(
+            &pool,
+            "q3",
+            KIND_SQL,
+            Some(r#"{"sql":"SELECT * FROM sales_2"}"#),
+        )
+        .await;
+
+        assert_eq!(dependents(&pool, "a").await, ["q1", "q2"]);
+        assert!(dependents(&pool, "b").await.is_empty());
+        // без «c» таблица `sales_2` исчезает, её набор становится `sales`
+        assert_eq!(dependents(&pool, "c").await, ["q3"]);
+        assert_eq!(dependents(&pool, "d").await, ["q3"]);
+        assert!(dependents(&pool, "q1").await.is_empty());
+    }
+}
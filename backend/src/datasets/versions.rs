@@ -0,0 +1,376 @@
+use crate::datasets::computed::{check_computed, ComputedColumn};
+use crate::datasets::models::DatasetRecord;
+use crate::datasets::schema::{ColumnSchema, ColumnType};
+use crate::storage::data_subdir;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sqlx::{Executor, FromRow, Pool, Sqlite};
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const VERSIONS_MIGRATION: &str = r#"
+CREATE TABLE IF NOT EXISTS dataset_versions (
+    dataset_id TEXT NOT NULL,
+    version INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    source_filename TEXT,
+    schema TEXT NOT NULL DEFAULT '[]',
+    cleaning TEXT NOT NULL DEFAULT '[]',
+    row_count INTEGER NOT NULL DEFAULT 0,
+    size_bytes INTEGER NOT NULL DEFAULT 0,
+    restored_from INTEGER,
+    PRIMARY KEY (dataset_id, version)
+);
+"#;
+
+/// Сколько версий набора хранится; более старые удаляются вместе с файлами
+pub const MAX_DATASET_VERSIONS: i64 = 20;
+
+const VERSIONS_DIR: &str = "dataset_versions";
+
+const VERSION_COLUMNS: &str =
+    "version, created_at, source_filename, schema, cleaning, row_count, size_bytes, restored_from";
+
+/// Строка таблицы `dataset_versions`: метаданные набора на момент версии.
+/// Файлы текущей версии лежат в каталоге наборов, прежних — в каталоге версий
+#[derive(FromRow, Debug, Clone)]
+pub struct VersionRecord {
+    pub version: i64,
+    pub created_at: String,
+    pub source_filename: Option<String>,
+    pub schema: String,
+    pub cleaning: String,
+    pub row_count: i64,
+    pub size_bytes: i64,
+    pub restored_from: Option<i64>,
+}
+
+/// Версия набора в ответах API
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionInfo {
+    pub version: i64,
+    pub created_at: String,
+    pub source_filename: Option<String>,
+    pub schema: Vec<ColumnSchema>,
+    pub row_count: i64,
+    pub size_bytes: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub restored_from: Option<i64>,
+    pub current: bool,
+}
+
+impl VersionInfo {
+    pub fn new(record: VersionRecord, current: i64) -> Self {
+        Self {
+            schema: serde_json::from_str(&record.schema).unwrap_or_default(),
+            current: record.version == current,
+            version: record.version,
+            created_at: record.created_at,
+            source_filename: record.source_filename,
+            row_count: record.row_count,
+            size_bytes: record.size_bytes,
+            restored_from: record.restored_from,
+        }
+    }
+}
+
+/// Виджет дашборда и колонки набора, на которые он опирается; дашборды
+/// хранит веб-приложение, поэтому список передаёт клиент
+#[derive(Deserialize, Clone, Debug)]
+pub struct WidgetColumns {
+    pub id: String,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub columns: Vec<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RetypedColumn {
+    pub name: String,
+    pub from: ColumnType,
+    pub to: ColumnType,
+}
+
+/// Разница схем двух версий и предупреждения о том, что перестанет работать
+#[derive(Serialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SchemaDiff {
+    pub added: Vec<ColumnSchema>,
+    pub removed: Vec<ColumnSchema>,
+    pub retyped: Vec<RetypedColumn>,
+    pub warnings: Vec<String>,
+}
+
+/// Сравнение схем по именам колонок; предупреждает о виджетах, которые
+/// ссылаются на исчезнувшие колонки, и о сломанных вычисляемых колонках
+pub fn diff_schema(
+    old: &[ColumnSchema],
+    new: &[ColumnSchema],
+    computed: &[ComputedColumn],
+    widgets: &[WidgetColumns],
+) -> SchemaDiff {
+    let mut diff = SchemaDiff::default();
+    for column in new {
+        match old.iter().find(|c| c.name == column.name) {
+            None => diff.added.push(column.clone()),
+            Some(previous) if previous.column_type != column.column_type => {
+                diff.retyped.push(RetypedColumn {
+                    name: column.name.clone(),
+                    from: previous.column_type,
+                    to: column.column_type,
+                })
+            }
+            Some(_) => {}
+        }
+    }
+    diff.removed = old
+        .iter()
+        .filter(|c| !new.iter().any(|n| n.name == c.name))
+        .cloned()
+        .collect();
+
+    let available: HashSet<&str> = new
+        .iter()
+        .map(|c| c.name.as_str())
+        .chain(computed.iter().map(|c| c.name.as_str()))
+        .collect();
+    for widget in widgets {
+        let missing: Vec<&str> = widget
+            .columns
+            .iter()
+            .map(String::as_str)
+            .filter(|name| !available.contains(name))
+            .collect();
+        if !missing.is_empty() {
+            diff.warnings.push(format!(
+                "Виджет «{}» использует отсутствующие колонки: {}",
+                widget.title.as_deref().unwrap_or(&widget.id),
+                missing.join(", ")
+            ));
+        }
+    }
+    diff.warnings.extend(check_computed(new, computed));
+    diff
+}
+
+fn version_dir(dataset_id: &str) -> io::Result<PathBuf> {
+    Ok(data_subdir(VERSIONS_DIR)?.join(dataset_id))
+}
+
+fn copy_if_exists(from: &Path, to: &Path) -> io::Result<bool> {
+    match fs::copy(from, to) {
+        Ok(_) => Ok(true),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Копия текущих файлов набора (строки и исходный файл) в каталог версий
+pub fn archive_files(
+    dataset_id: &str,
+    version: i64,
+    table: &Path,
+    source: &Path,
+) -> io::Result<()> {
+    let dir = version_dir(dataset_id)?;
+    fs::create_dir_all(&dir)?;
+    copy_if_exists(table, &dir.join(format!("{}.json", version)))?;
+    copy_if_exists(source, &dir.join(format!("{}.source.json", version)))?;
+    Ok(())
+}
+
+/// Возврат файлов сохранённой версии на место текущих; у версии без
+/// исходного файла исходным станет файл строк
+pub fn restore_files(
+    dataset_id: &str,
+    version: i64,
+    table: &Path,
+    source: &Path,
+) -> io::Result<u64> {
+    let dir = version_dir(dataset_id)?;
+    let archived = dir.join(format!("{}.json", version));
+    if !archived.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("файлы версии {} не найдены", version),
+        ));
+    }
+    fs::copy(&archived, table)?;
+    let archived_source = dir.join(format!("{}.source.json", version));
+    if !copy_if_exists(&archived_source, source)? {
+        match fs::remove_file(source) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(fs::metadata(table)?.len())
+}
+
+fn remove_version_files(dataset_id: &str, version: i64) -> io::Result<()> {
+    let dir = version_dir(dataset_id)?;
+    for name in [
+        format!("{}.json", version),
+        format!("{}.source.json", version),
+    ] {
+        match fs::remove_file(dir.join(name)) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// Запись о новой версии набора; версия с тем же номером — ошибка
+pub async fn record_version<'e, E>(
+    executor: E,
+    record: &DatasetRecord,
+    restored_from: Option<i64>,
+) -> sqlx::Result<()>
+where
+    E: Executor<'e, Database = Sqlite>,
+{
+    sqlx::query(
+        "INSERT INTO dataset_versions (dataset_id, version, created_at, source_filename, schema, cleaning, row_count, size_bytes, restored_from)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(&record.id)
+    .bind(record.version)
+    .bind(&record.updated_at)
+    .bind(&record.source_filename)
+    .bind(&record.schema)
+    .bind(&record.cleaning)
+    .bind(record.row_count)
+    .bind(record.size_bytes)
+    .bind(restored_from)
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Запись о текущей версии для наборов, загруженных до появления истории;
+/// если запись уже есть, она описывает ту же версию и остаётся как есть
+pub async fn ensure_version(pool: &Pool<Sqlite>, record: &DatasetRecord) -> sqlx::Result<()> {
+    sqlx::query(
+        "INSERT INTO dataset_versions (dataset_id, version, created_at, source_filename, schema, cleaning, row_count, size_bytes)
+         SELECT ?, ?, ?, ?, ?, ?, ?, ?
+         WHERE NOT EXISTS (SELECT 1 FROM dataset_versions WHERE dataset_id = ? AND version = ?)",
+    )
+    .bind(&record.id)
+    .bind(record.version)
+    .bind(&record.updated_at)
+    .bind(&record.source_filename)
+    .bind(&record.schema)
+    .bind(&record.cleaning)
+    .bind(record.row_count)
+    .bind(record.size_bytes)
+    .bind(&record.id)
+    .bind(record.version)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn list_versions(
+    pool: &Pool<Sqlite>,
+    dataset_id: &str,
+) -> sqlx::Result<Vec<VersionRecord>> {
+    sqlx::query_as::<_, VersionRecord>(&format!(
+        "SELECT {} FROM dataset_versions WHERE dataset_id = ? ORDER BY version DESC",
+        VERSION_COLUMNS
+    ))
+    .bind(dataset_id)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_version(
+    pool: &Pool<Sqlite>,
+    dataset_id: &str,
+    version: i64,
+) -> sqlx::Result<Option<VersionRecord>> {
+    sqlx::query_as::<_, VersionRecord>(&format!(
+        "SELECT {} FROM dataset_versions WHERE dataset_id = ? AND version = ?",
+        VERSION_COLUMNS
+    ))
+    .bind(dataset_id)
+    .bind(version)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Удаление версий старше последних `MAX_DATASET_VERSIONS`
+pub async fn prune_versions(pool: &Pool<Sqlite>, dataset_id: &str, current: i64) -> Result<()> {
+    let oldest_kept = current - MAX_DATASET_VERSIONS + 1;
+    let stale: Vec<(i64,)> =
+        sqlx::query_as("SELECT version FROM dataset_versions WHERE dataset_id = ? AND version < ?")
+            .bind(dataset_id)
+            .bind(oldest_kept)
+            .fetch_all(pool)
+            .await?;
+    if stale.is_empty() {
+        return Ok(());
+    }
+    sqlx::query("DELETE FROM dataset_versions WHERE dataset_id = ? AND version < ?")
+        .bind(dataset_id)
+        .bind(oldest_kept)
+        .execute(pool)
+        .await?;
+    for (version,) in stale {
+        remove_version_files(dataset_id, version)?;
+    }
+    Ok(())
+}
+
+/// Удаление всей истории набора
+pub async fn delete_versions(pool: &Pool<Sqlite>, dataset_id: &str) -> Result<()> {
+    sqlx::query("DELETE FROM dataset_versions WHERE dataset_id = ?")
+        .bind(dataset_id)
+        .execute(pool)
+        .await?;
+    match fs::remove_dir_all(version_dir(dataset_id)?) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::datasets::models::DATASET_MIGRATION;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[tokio::test]
+    async fn version_collision_is_an_error() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        for migration in [DATASET_MIGRATION, VERSIONS_MIGRATION] {
+            sqlx::raw_sql(migration).execute(&pool).await.unwrap();
+        }
+        sqlx::query(
+            "INSERT INTO datasets (id, name, owner, uploaded_at, updated_at) VALUES ('d', 'd', 'u', '', '')",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let record: DatasetRecord = sqlx::query_as("SELECT * FROM datasets WHERE id = 'd'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+
+        ensure_version(&pool, &record).await.unwrap();
+        ensure_version(&pool, &record).await.unwrap();
+        assert!(record_version(&pool, &record, None).await.is_err());
+        assert_eq!(list_versions(&pool, "d").await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,149 @@
+use axum::http::{header, HeaderMap, HeaderValue};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+/// Символы, которые RFC 5987 разрешает оставлять в `filename*` без кодирования (attr-char)
+const ATTR_CHAR: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'!')
+    .remove(b'#')
+    .remove(b'$')
+    .remove(b'&')
+    .remove(b'+')
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'^')
+    .remove(b'_')
+    .remove(b'`')
+    .remove(b'|')
+    .remove(b'~');
+
+/// Расширения, которые заменяются на расширение выбранного формата
+const KNOWN_EXTENSIONS: &[&str] = &[
+    "xlsx", "xls", "ods", "csv", "tsv", "txt", "json", "ndjson", "jsonl", "pdf", "html", "htm",
+    "md", "zip",
+];
+
+const MAX_STEM_CHARS: usize = 120;
+
+/// Имя файла для скачивания: убирает пути и запрещённые символы,
+/// ограничивает длину и гарантирует расширение `extension`
+pub fn download_filename(requested: Option<&str>, default_stem: &str, extension: &str) -> String {
+    let raw = requested.unwrap_or("");
+    // от пути остаётся только последний компонент
+    let base = raw.rsplit(['/', '\\']).next().unwrap_or("");
+
+    let cleaned: String = base
+        .chars()
+        .map(|c| {
+            if c.is_control() || matches!(c, '<' | '>' | ':' | '"' | '|' | '?' | '*') {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+    let cleaned = cleaned.trim().trim_matches('.').trim();
+
+    let stem = match cleaned.rsplit_once('.') {
+        Some((stem, ext))
+            if KNOWN_EXTENSIONS
+                .iter()
+                .any(|known| ext.eq_ignore_ascii_case(known)) =>
+        {
+            stem.trim_end()
+        }
+        _ => cleaned,
+    };
+    let stem: String = stem.chars().take(MAX_STEM_CHARS).collect();
+    let stem = stem.trim();
+    let stem = if stem.is_empty() { default_stem } else { stem };
+
+    format!("{}.{}", stem, extension)
+}
+
+/// Заголовки ответа-вложения: `filename` в ASCII для старых клиентов
+/// и `filename*` (RFC 5987) с исходным именем в UTF-8
+pub fn attachment_headers(mime: &str, filename: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_str(mime).unwrap_or(HeaderValue::from_static("application/octet-stream")),
+    );
+    headers.insert(
+        header::CONTENT_DISPOSITION,
+        HeaderValue::from_str(&content_disposition(filename))
+            .unwrap_or(HeaderValue::from_static("attachment; filename=\"export\"")),
+    );
+    headers
+}
+
+pub fn content_disposition(filename: &str) -> String {
+    let fallback = ascii_fallback(filename);
+    if fallback == filename {
+        return format!("attachment; filename=\"{}\"", fallback);
+    }
+    format!(
+        "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+        fallback,
+        utf8_percent_encode(filename, ATTR_CHAR)
+    )
+}
+
+/// ASCII-вариант имени: кириллица транслитерируется, прочие символы заменяются на `_`
+fn ascii_fallback(filename: &str) -> String {
+    let mut out = String::with_capacity(filename.len());
+    for c in filename.chars() {
+        if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | ' ' | '(' | ')') {
+            out.push(c);
+            continue;
+        }
+        let lower = c.to_lowercase().next().unwrap_or(c);
+        match transliterate(lower) {
+            Some(latin) if lower != c => {
+                let mut chars = latin.chars();
+                if let Some(first) = chars.next() {
+                    out.push(first.to_ascii_uppercase());
+                    out.push_str(chars.as_str());
+                }
+            }
+            Some(latin) => out.push_str(latin),
+            None => out.push('_'),
+        }
+    }
+    out
+}
+
+fn transliterate(c: char) -> Option<&'static str> {
+    let latin = match c {
+        'а' => "a",
+        'б' => "b",
+        'в' => "v",
+        'г' => "g",
+        'д' => "d",
+        'е' | 'ё' | 'э' => "e",
+        'ж' => "zh",
+        'з' => "z",
+        'и' => "i",
+        'й' | 'ы' => "y",
+        'к' => "k",
+        'л' => "l",
+        'м' => "m",
+        'н' => "n",
+        'о' => "o",
+        'п' => "p",
+        'р' => "r",
+        'с' => "s",
+        'т' => "t",
+        'у' => "u",
+        'ф' => "f",
+        'х' => "kh",
+        'ц' => "ts",
+        'ч' => "ch",
+        'ш' => "sh",
+        'щ' => "shch",
+        'ъ' | 'ь' => "",
+        'ю' => "yu",
+        'я' => "ya",
+        _ => return None,
+    };
+    Some(latin)
+}
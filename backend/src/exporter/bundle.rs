@@ -0,0 +1,193 @@
+use crate::download::download_filename;
+use crate::exporter::delimited::{build_delimited, CsvOptions};
+use crate::exporter::spreadsheet::{build_xlsx, SpreadsheetOptions};
+use anyhow::{bail, Result};
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::io::{Cursor, Write};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+const MANIFEST_VERSION: u32 = 1;
+
+/// Набор данных в составе архива дашборда
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleDataset {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// Табличный виджет в том виде, в каком он отображается на дашборде
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BundleTable {
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+    #[serde(default)]
+    pub spreadsheet: SpreadsheetOptions,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DashboardBundle {
+    pub dashboard: serde_json::Value,
+    #[serde(default)]
+    pub datasets: Vec<BundleDataset>,
+    #[serde(default)]
+    pub tables: Vec<BundleTable>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Manifest {
+    version: u32,
+    dashboard: Option<String>,
+    exported_at: String,
+    files: Vec<ManifestFile>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ManifestFile {
+    path: String,
+    kind: &'static str,
+    size: usize,
+    sha256: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rows: Option<usize>,
+}
+
+impl DashboardBundle {
+    /// Имя дашборда из конфигурации, если оно там есть
+    pub fn dashboard_name(&self) -> Option<&str> {
+        self.dashboard
+            .get("name")
+            .and_then(|name| name.as_str())
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !self.dashboard.is_object() {
+            bail!("Поле dashboard должно быть объектом");
+        }
+        for table in &self.tables {
+            table.spreadsheet.validate(&table.columns, &table.rows)?;
+        }
+        Ok(())
+    }
+}
+
+/// ZIP-архив, который запоминает записанные файлы для манифеста
+struct BundleWriter {
+    zip: ZipWriter<Cursor<Vec<u8>>>,
+    options: FileOptions,
+    files: Vec<ManifestFile>,
+    used: HashSet<String>,
+}
+
+impl BundleWriter {
+    fn new() -> Self {
+        Self {
+            zip: ZipWriter::new(Cursor::new(Vec::new())),
+            options: FileOptions::default().compression_method(CompressionMethod::Deflated),
+            files: Vec::new(),
+            used: HashSet::new(),
+        }
+    }
+
+    fn add(
+        &mut self,
+        path: String,
+        kind: &'static str,
+        bytes: &[u8],
+        rows: Option<usize>,
+    ) -> Result<()> {
+        self.zip.start_file(path.as_str(), self.options)?;
+        self.zip.write_all(bytes)?;
+        self.files.push(ManifestFile {
+            path,
+            kind,
+            size: bytes.len(),
+            sha256: format!("{:x}", Sha256::digest(bytes)),
+            rows,
+        });
+        Ok(())
+    }
+
+    /// Путь внутри архива без коллизий: одинаковые названия получают суффикс `-2`, `-3`…
+    fn unique_path(
+        &mut self,
+        dir: &str,
+        name: Option<&str>,
+        fallback: &str,
+        extension: &str,
+    ) -> String {
+        let file = download_filename(name, fallback, extension);
+        let stem = file
+            .strip_suffix(&format!(".{}", extension))
+            .unwrap_or(&file)
+            .to_string();
+
+        let mut candidate = format!("{}/{}", dir, file);
+        let mut counter = 2;
+        while !self.used.insert(candidate.to_lowercase()) {
+            candidate = format!("{}/{}-{}.{}", dir, stem, counter, extension);
+            counter += 1;
+        }
+        candidate
+    }
+
+    fn finish(mut self, dashboard: Option<&str>) -> Result<Vec<u8>> {
+        let manifest = Manifest {
+            version: MANIFEST_VERSION,
+            dashboard: dashboard.map(str::to_string),
+            exported_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            files: self.files,
+        };
+        self.zip.start_file("manifest.json", self.options)?;
+        self.zip.write_all(&serde_json::to_vec_pretty(&manifest)?)?;
+        Ok(self.zip.finish()?.into_inner())
+    }
+}
+
+/// ZIP для передачи дашборда: конфигурация, исходные данные в CSV,
+/// табличные виджеты в XLSX и manifest.json с контрольными суммами
+pub fn build_dashboard_bundle(bundle: &DashboardBundle) -> Result<Vec<u8>> {
+    let mut writer = BundleWriter::new();
+
+    let config = serde_json::to_vec_pretty(&bundle.dashboard)?;
+    writer.add("dashboard.json".into(), "config", &config, None)?;
+
+    for (idx, dataset) in bundle.datasets.iter().enumerate() {
+        let fallback = format!("dataset-{}", idx + 1);
+        let path = writer.unique_path("data", Some(&dataset.name), &fallback, "csv");
+        let csv = build_delimited(
+            &dataset.columns,
+            &dataset.rows,
+            b',',
+            &CsvOptions::default(),
+        )?;
+        writer.add(path, "dataset", &csv, Some(dataset.rows.len()))?;
+    }
+
+    for (idx, table) in bundle.tables.iter().enumerate() {
+        let fallback = table
+            .id
+            .clone()
+            .unwrap_or_else(|| format!("table-{}", idx + 1));
+        let path = writer.unique_path("tables", table.title.as_deref(), &fallback, "xlsx");
+        let xlsx = build_xlsx(&table.columns, &table.rows, &table.spreadsheet)?;
+        writer.add(path, "table", &xlsx, Some(table.rows.len()))?;
+    }
+
+    writer.finish(bundle.dashboard_name())
+}
@@ -0,0 +1,501 @@
+use crate::exporter::template::column_name;
+use anyhow::{bail, Result};
+use quick_xml::escape::escape;
+use rust_xlsxwriter::{
+    Color, ConditionalFormat2ColorScale, ConditionalFormat3ColorScale, ConditionalFormatCell,
+    ConditionalFormatCellRule, ConditionalFormatDataBar, ConditionalFormatTop,
+    ConditionalFormatTopRule, ConditionalFormatValue, Format, Worksheet,
+};
+use serde::Deserialize;
+use spreadsheet_ods::color::Rgb;
+use spreadsheet_ods::style::{CellStyle, StyleOrigin, StyleUse};
+use spreadsheet_ods::WorkBook;
+use std::fmt::Write;
+
+const DEFAULT_HIGHLIGHT_BACKGROUND: u32 = 0xFFC7CE;
+const DEFAULT_HIGHLIGHT_COLOR: u32 = 0x9C0006;
+
+/// Правило условного форматирования для колонки таблицы
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ConditionalFormat {
+    pub column: String,
+    #[serde(flatten)]
+    pub rule: ConditionalRule,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(
+    tag = "type",
+    rename_all = "snake_case",
+    rename_all_fields = "camelCase"
+)]
+pub enum ConditionalRule {
+    /// Сравнение значения ячейки с константой
+    CellValue {
+        operator: ComparisonOperator,
+        value: RuleValue,
+        #[serde(default)]
+        value2: Option<RuleValue>,
+        #[serde(default)]
+        style: HighlightStyle,
+    },
+    /// Цветовая шкала от минимума к максимуму (с необязательной серединой)
+    ColorScale {
+        #[serde(default = "default_min_color")]
+        min_color: String,
+        #[serde(default)]
+        mid_color: Option<String>,
+        #[serde(default = "default_max_color")]
+        max_color: String,
+    },
+    /// Гистограмма внутри ячейки
+    DataBar {
+        #[serde(default = "default_bar_color")]
+        color: String,
+    },
+    /// Первые/последние N значений или N процентов
+    TopN {
+        count: u16,
+        #[serde(default)]
+        bottom: bool,
+        #[serde(default)]
+        percent: bool,
+        #[serde(default)]
+        style: HighlightStyle,
+    },
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ComparisonOperator {
+    Equal,
+    NotEqual,
+    GreaterThan,
+    GreaterThanOrEqual,
+    LessThan,
+    LessThanOrEqual,
+    Between,
+    NotBetween,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum RuleValue {
+    Number(f64),
+    Text(String),
+}
+
+/// Оформление ячеек, попавших под правило; без цветов — светло-красная заливка
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HighlightStyle {
+    pub background: Option<String>,
+    pub color: Option<String>,
+    pub bold: bool,
+}
+
+fn default_min_color() -> String {
+    "#F8696B".into()
+}
+
+fn default_max_color() -> String {
+    "#63BE7B".into()
+}
+
+fn default_bar_color() -> String {
+    "#638EC6".into()
+}
+
+/// Правило, привязанное к номеру колонки
+pub struct ResolvedRule<'a> {
+    pub col: usize,
+    pub rule: &'a ConditionalRule,
+}
+
+/// Проверка правил и сопоставление их с колонками таблицы
+pub fn resolve_rules<'a>(
+    rules: &'a [ConditionalFormat],
+    columns: &[String],
+) -> Result<Vec<ResolvedRule<'a>>> {
+    let mut resolved = Vec::with_capacity(rules.len());
+    for format in rules {
+        let Some(col) = columns.iter().position(|c| c == &format.column) else {
+            bail!("Колонка «{}» не найдена", format.column);
+        };
+        format.rule.validate()?;
+        resolved.push(ResolvedRule {
+            col,
+            rule: &format.rule,
+        });
+    }
+    Ok(resolved)
+}
+
+impl ConditionalRule {
+    fn validate(&self) -> Result<()> {
+        match self {
+            ConditionalRule::CellValue {
+                operator,
+                value2,
+                style,
+                ..
+            } => {
+                let needs_second = matches!(
+                    operator,
+                    ComparisonOperator::Between | ComparisonOperator::NotBetween
+                );
+                if needs_second && value2.is_none() {
+                    bail!("Для условия between нужно второе значение (value2)");
+                }
+                style.validate()
+            }
+            ConditionalRule::ColorScale {
+                min_color,
+                mid_color,
+                max_color,
+            } => {
+                parse_color(min_color)?;
+                parse_color(max_color)?;
+                if let Some(mid) = mid_color {
+                    parse_color(mid)?;
+                }
+                Ok(())
+            }
+            ConditionalRule::DataBar { color } => parse_color(color).map(|_| ()),
+            ConditionalRule::TopN {
+                count,
+                percent,
+                style,
+                ..
+            } => {
+                if *count == 0 || (*percent && *count > 100) || *count > 1000 {
+                    bail!("Некорректное количество для top_n: {}", count);
+                }
+                style.validate()
+            }
+        }
+    }
+}
+
+impl HighlightStyle {
+    fn validate(&self) -> Result<()> {
+        self.colors().map(|_| ())
+    }
+
+    /// (заливка, цвет текста)
+    fn colors(&self) -> Result<(Option<u32>, Option<u32>)> {
+        let background = self.background.as_deref().map(parse_color).transpose()?;
+        let color = self.color.as_deref().map(parse_color).transpose()?;
+        if background.is_none() && color.is_none() && !self.bold {
+            return Ok((
+                Some(DEFAULT_HIGHLIGHT_BACKGROUND),
+                Some(DEFAULT_HIGHLIGHT_COLOR),
+            ));
+        }
+        Ok((background, color))
+    }
+}
+
+/// "#RRGGBB" / "RRGGBB" / "#RGB" → 0xRRGGBB
+pub fn parse_color(value: &str) -> Result<u32> {
+    let hex = value.trim().trim_start_matches('#');
+    let expanded: String = if hex.len() == 3 {
+        hex.chars().flat_map(|c| [c, c]).collect()
+    } else {
+        hex.to_string()
+    };
+    if expanded.len() != 6 {
+        bail!("Некорректный цвет: {}", value);
+    }
+    u32::from_str_radix(&expanded, 16).map_err(|_| anyhow::anyhow!("Некорректный цвет: {}", value))
+}
+
+impl RuleValue {
+    fn xlsx(&self) -> ConditionalFormatValue {
+        match self {
+            RuleValue::Number(n) => ConditionalFormatValue::from(*n),
+            RuleValue::Text(t) => ConditionalFormatValue::from(t.as_str()),
+        }
+    }
+
+    fn ods(&self) -> String {
+        match self {
+            RuleValue::Number(n) => n.to_string(),
+            RuleValue::Text(t) => format!("\"{}\"", t.replace('"', "\"\"")),
+        }
+    }
+}
+
+/// Нативное условное форматирование XLSX на строках `first_row..=last_row`
+pub fn apply_xlsx(
+    worksheet: &mut Worksheet,
+    rules: &[ResolvedRule],
+    first_row: u32,
+    last_row: u32,
+) -> Result<()> {
+    if last_row < first_row {
+        return Ok(());
+    }
+    for resolved in rules {
+        let col = resolved.col as u16;
+        match resolved.rule {
+            ConditionalRule::CellValue {
+                operator,
+                value,
+                value2,
+                style,
+            } => {
+                let first = value.xlsx();
+                let second = value2.as_ref().map(RuleValue::xlsx);
+                let rule = match (operator, second) {
+                    (ComparisonOperator::Equal, _) => ConditionalFormatCellRule::EqualTo(first),
+                    (ComparisonOperator::NotEqual, _) => {
+                        ConditionalFormatCellRule::NotEqualTo(first)
+                    }
+                    (ComparisonOperator::GreaterThan, _) => {
+                        ConditionalFormatCellRule::GreaterThan(first)
+                    }
+                    (ComparisonOperator::GreaterThanOrEqual, _) => {
+                        ConditionalFormatCellRule::GreaterThanOrEqualTo(first)
+                    }
+                    (ComparisonOperator::LessThan, _) => ConditionalFormatCellRule::LessThan(first),
+                    (ComparisonOperator::LessThanOrEqual, _) => {
+                        ConditionalFormatCellRule::LessThanOrEqualTo(first)
+                    }
+                    (ComparisonOperator::Between, Some(second)) => {
+                        ConditionalFormatCellRule::Between(first, second)
+                    }
+                    (ComparisonOperator::NotBetween, Some(second)) => {
+                        ConditionalFormatCellRule::NotBetween(first, second)
+                    }
+                    _ => bail!("Для условия between нужно второе значение (value2)"),
+                };
+                let format = ConditionalFormatCell::new()
+                    .set_rule(rule)
+                    .set_format(xlsx_format(style)?);
+                worksheet.add_conditional_format(first_row, col, last_row, col, &format)?;
+            }
+            ConditionalRule::ColorScale {
+                min_color,
+                mid_color,
+                max_color,
+            } => {
+                let min = Color::RGB(parse_color(min_color)?);
+                let max = Color::RGB(parse_color(max_color)?);
+                match mid_color {
+                    Some(mid) => {
+                        let format = ConditionalFormat3ColorScale::new()
+                            .set_minimum_color(min)
+                            .set_midpoint_color(Color::RGB(parse_color(mid)?))
+                            .set_maximum_color(max);
+                        worksheet.add_conditional_format(first_row, col, last_row, col, &format)?;
+                    }
+                    None => {
+                        let format = ConditionalFormat2ColorScale::new()
+                            .set_minimum_color(min)
+                            .set_maximum_color(max);
+                        worksheet.add_conditional_format(first_row, col, last_row, col, &format)?;
+                    }
+                }
+            }
+            ConditionalRule::DataBar { color } => {
+                let format =
+                    ConditionalFormatDataBar::new().set_fill_color(Color::RGB(parse_color(color)?));
+                worksheet.add_conditional_format(first_row, col, last_row, col, &format)?;
+            }
+            ConditionalRule::TopN {
+                count,
+                bottom,
+                percent,
+                style,
+            } => {
+                let rule = match (bottom, percent) {
+                    (false, false) => ConditionalFormatTopRule::Top(*count),
+                    (true, false) => ConditionalFormatTopRule::Bottom(*count),
+                    (false, true) => ConditionalFormatTopRule::TopPercent(*count),
+                    (true, true) => ConditionalFormatTopRule::BottomPercent(*count),
+                };
+                let format = ConditionalFormatTop::new()
+                    .set_rule(rule)
+                    .set_format(xlsx_format(style)?);
+                worksheet.add_conditional_format(first_row, col, last_row, col, &format)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn xlsx_format(style: &HighlightStyle) -> Result<Format> {
+    let (background, color) = style.colors()?;
+    let mut format = Format::new();
+    if let Some(background) = background {
+        format = format.set_background_color(Color::RGB(background));
+    }
+    if let Some(color) = color {
+        format = format.set_font_color(Color::RGB(color));
+    }
+    if style.bold {
+        format = format.set_bold();
+    }
+    Ok(format)
+}
+
+/// Именованные стили ODS для правил с подсветкой; индекс совпадает с индексом правила
+pub fn add_ods_styles(workbook: &mut WorkBook, rules: &[ResolvedRule]) -> Result<Vec<String>> {
+    let mut names = Vec::with_capacity(rules.len());
+    for (idx, resolved) in rules.iter().enumerate() {
+        let style = match resolved.rule {
+            ConditionalRule::CellValue { style, .. } | ConditionalRule::TopN { style, .. } => style,
+            _ => {
+                names.push(String::new());
+                continue;
+            }
+        };
+        let (background, color) = style.colors()?;
+        let name = format!("Conditional_{}", idx + 1);
+        let mut cell_style = CellStyle::new_empty();
+        cell_style.set_name(name.as_str());
+        cell_style.set_origin(StyleOrigin::Styles);
+        cell_style.set_styleuse(StyleUse::Named);
+        if let Some(background) = background {
+            cell_style.set_background_color(rgb(background));
+        }
+        if let Some(color) = color {
+            cell_style.set_color(rgb(color));
+        }
+        if style.bold {
+            cell_style.set_font_bold();
+        }
+        workbook.add_cellstyle(cell_style);
+        names.push(name);
+    }
+    Ok(names)
+}
+
+/// Блок `calcext:conditional-formats` для листа ODS
+pub fn ods_conditional_formats(
+    sheet_name: &str,
+    rules: &[ResolvedRule],
+    style_names: &[String],
+    first_row: u32,
+    last_row: u32,
+) -> Result<String> {
+    if rules.is_empty() || last_row < first_row {
+        return Ok(String::new());
+    }
+
+    let mut xml = String::from("<calcext:conditional-formats>");
+    for (idx, resolved) in rules.iter().enumerate() {
+        let col = column_name(resolved.col as u32);
+        let base_cell = format!("{}.{}{}", sheet_name, col, first_row + 1);
+        let range = format!(
+            "{sheet}.{col}{first}:{sheet}.{col}{last}",
+            sheet = sheet_name,
+            col = col,
+            first = first_row + 1,
+            last = last_row + 1
+        );
+        write!(
+            xml,
+            "<calcext:conditional-format calcext:target-range-address=\"{}\">",
+            escape(&range)
+        )?;
+
+        let style_name = style_names.get(idx).map(String::as_str).unwrap_or("");
+        match resolved.rule {
+            ConditionalRule::CellValue {
+                operator,
+                value,
+                value2,
+                ..
+            } => {
+                let first = value.ods();
+                let second = value2.as_ref().map(RuleValue::ods).unwrap_or_default();
+                let condition = match operator {
+                    ComparisonOperator::Equal => format!("={}", first),
+                    ComparisonOperator::NotEqual => format!("!={}", first),
+                    ComparisonOperator::GreaterThan => format!(">{}", first),
+                    ComparisonOperator::GreaterThanOrEqual => format!(">={}", first),
+                    ComparisonOperator::LessThan => format!("<{}", first),
+                    ComparisonOperator::LessThanOrEqual => format!("<={}", first),
+                    ComparisonOperator::Between => format!("between({},{})", first, second),
+                    ComparisonOperator::NotBetween => {
+                        format!("not-between({},{})", first, second)
+                    }
+                };
+                write_condition(&mut xml, style_name, &condition, &base_cell)?;
+            }
+            ConditionalRule::ColorScale {
+                min_color,
+                mid_color,
+                max_color,
+            } => {
+                xml.push_str("<calcext:color-scale>");
+                write_color_entry(&mut xml, "minimum", "0", min_color)?;
+                if let Some(mid) = mid_color {
+                    write_color_entry(&mut xml, "percentile", "50", mid)?;
+                }
+                write_color_entry(&mut xml, "maximum", "0", max_color)?;
+                xml.push_str("</calcext:color-scale>");
+            }
+            ConditionalRule::DataBar { color } => {
+                write!(
+                    xml,
+                    "<calcext:data-bar calcext:max-length=\"100\" calcext:negative-color=\"#FF0000\" \
+                     calcext:positive-color=\"#{:06X}\" calcext:axis-color=\"#000000\">\
+                     <calcext:formatting-entry calcext:value=\"0\" calcext:type=\"auto-minimum\"/>\
+                     <calcext:formatting-entry calcext:value=\"0\" calcext:type=\"auto-maximum\"/>\
+                     </calcext:data-bar>",
+                    parse_color(color)?
+                )?;
+            }
+            ConditionalRule::TopN {
+                count,
+                bottom,
+                percent,
+                ..
+            } => {
+                let kind = match (bottom, percent) {
+                    (false, false) => "top-elements",
+                    (true, false) => "bottom-elements",
+                    (false, true) => "top-percent",
+                    (true, true) => "bottom-percent",
+                };
+                let condition = format!("{}({})", kind, count);
+                write_condition(&mut xml, style_name, &condition, &base_cell)?;
+            }
+        }
+        xml.push_str("</calcext:conditional-format>");
+    }
+    xml.push_str("</calcext:conditional-formats>");
+    Ok(xml)
+}
+
+fn write_condition(xml: &mut String, style: &str, condition: &str, base_cell: &str) -> Result<()> {
+    write!(
+        xml,
+        "<calcext:condition calcext:apply-style-name=\"{}\" calcext:value=\"{}\" \
+         calcext:base-cell-address=\"{}\"/>",
+        escape(style),
+        escape(condition),
+        escape(base_cell)
+    )?;
+    Ok(())
+}
+
+fn write_color_entry(xml: &mut String, kind: &str, value: &str, color: &str) -> Result<()> {
+    write!(
+        xml,
+        "<calcext:color-scale-entry calcext:value=\"{}\" calcext:type=\"{}\" \
+         calcext:color=\"#{:06X}\"/>",
+        value,
+        kind,
+        parse_color(color)?
+    )?;
+    Ok(())
+}
+
+fn rgb(color: u32) -> Rgb<u8> {
+    Rgb::new((color >> 16) as u8, (color >> 8) as u8, color as u8)
+}
@@ -0,0 +1,229 @@
+use anyhow::{bail, Result};
+use csv::{QuoteStyle, Terminator, Writer, WriterBuilder};
+use encoding_rs::WINDOWS_1251;
+use serde::Deserialize;
+use std::borrow::Cow;
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Правила экранирования значений в CSV/TSV
+#[derive(Deserialize, Clone, Copy, Default, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Quoting {
+    #[default]
+    Necessary,
+    Always,
+    NonNumeric,
+    Never,
+}
+
+#[derive(Deserialize, Clone, Copy, Default, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+}
+
+#[derive(Deserialize, Clone, Copy, Default, Debug)]
+pub enum TextEncoding {
+    #[default]
+    #[serde(rename = "utf-8")]
+    Utf8,
+    #[serde(rename = "utf-8-bom")]
+    Utf8Bom,
+    #[serde(rename = "windows-1251")]
+    Windows1251,
+}
+
+impl TextEncoding {
+    /// Значение `charset` для заголовка Content-Type
+    pub fn charset(self) -> &'static str {
+        match self {
+            TextEncoding::Utf8 | TextEncoding::Utf8Bom => "utf-8",
+            TextEncoding::Windows1251 => "windows-1251",
+        }
+    }
+}
+
+/// Настройки выгрузки CSV/TSV, приходят в поле `csv` запроса экспорта
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CsvOptions {
+    pub delimiter: Option<char>,
+    pub quoting: Quoting,
+    pub line_ending: LineEnding,
+    pub encoding: TextEncoding,
+}
+
+impl CsvOptions {
+    /// Проверка опций до начала выгрузки — ошибки отсюда уходят клиенту как 400
+    pub fn validate(&self) -> Result<()> {
+        if let Some(delimiter) = self.delimiter {
+            if !delimiter.is_ascii() || delimiter == '"' || delimiter == '\r' || delimiter == '\n' {
+                bail!("Недопустимый разделитель: {:?}", delimiter);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Сборка CSV/TSV: `default_delimiter` задаёт формат, опции могут его переопределить
+pub fn build_delimited(
+    columns: &[String],
+    rows: &[Vec<String>],
+    default_delimiter: u8,
+    options: &CsvOptions,
+) -> Result<Vec<u8>> {
+    options.validate()?;
+
+    let mut writer = csv_writer(default_delimiter, options);
+    let mut unmappable = Unmappable::new(options.encoding);
+    writer.write_record(unmappable.record(None, columns))?;
+    for (idx, row) in rows.iter().enumerate() {
+        writer.write_record(unmappable.record(Some(idx), row))?;
+    }
+    unmappable.report(columns);
+
+    let utf8 = writer.into_inner().map_err(|err| err.into_error())?;
+    Ok(encode_text(utf8, options.encoding))
+}
+
+/// Символы, которых нет в целевой кодировке, заменяются на «?» до записи:
+/// encoding_rs подставил бы вместо них HTML-сущности `&#NNNN;` прямо в данные
+pub struct Unmappable {
+    encoding: TextEncoding,
+    count: usize,
+    /// Первая замена: строка данных (`None` — заголовок) и колонка
+    first: Option<(Option<usize>, usize)>,
+}
+
+impl Unmappable {
+    pub fn new(encoding: TextEncoding) -> Self {
+        Self {
+            encoding,
+            count: 0,
+            first: None,
+        }
+    }
+
+    /// Значения строки (UTF-8) с заменой непредставимых символов
+    pub fn record<'a>(
+        &mut self,
+        row: Option<usize>,
+        values: &'a [String],
+    ) -> impl Iterator<Item = Cow<'a, [u8]>> + use<'a, '_> {
+        values
+            .iter()
+            .enumerate()
+            .map(move |(col, value)| self.field(row, col, value))
+    }
+
+    fn field<'a>(&mut self, row: Option<usize>, col: usize, value: &'a str) -> Cow<'a, [u8]> {
+        if !matches!(self.encoding, TextEncoding::Windows1251) || value.is_ascii() {
+            return Cow::Borrowed(value.as_bytes());
+        }
+        let mut replaced = 0;
+        let mut buf = [0u8; 4];
+        let mapped: String = value
+            .chars()
+            .map(|c| {
+                if c.is_ascii() || !WINDOWS_1251.encode(c.encode_utf8(&mut buf)).2 {
+                    c
+                } else {
+                    replaced += 1;
+                    '?'
+                }
+            })
+            .collect();
+        if replaced == 0 {
+            return Cow::Borrowed(value.as_bytes());
+        }
+        self.count += replaced;
+        self.first.get_or_insert((row, col));
+        Cow::Owned(mapped.into_bytes())
+    }
+
+    /// Предупреждение в лог, если были замены
+    pub fn report(&self, columns: &[String]) {
+        let Some((row, col)) = self.first else {
+            return;
+        };
+        let column = columns
+            .get(col)
+            .cloned()
+            .unwrap_or_else(|| format!("№{}", col + 1));
+        let place = match row {
+            Some(row) => format!("колонка «{}», строка {}", column, row + 1),
+            None => format!("заголовок колонки «{}»", column),
+        };
+        eprintln!(
+            "⚠️ windows-1251: {} символов нельзя записать в этой кодировке, заменены на «?» (первый — {})",
+            self.count, place
+        );
+    }
+}
+
+/// CSV-писатель в буфер с учётом разделителя, экранирования и переводов строк
+pub fn csv_writer(default_delimiter: u8, options: &CsvOptions) -> Writer<Vec<u8>> {
+    let delimiter = options
+        .delimiter
+        .map(|c| c as u8)
+        .unwrap_or(default_delimiter);
+
+    let quote_style = match options.quoting {
+        Quoting::Necessary => QuoteStyle::Necessary,
+        Quoting::Always => QuoteStyle::Always,
+        Quoting::NonNumeric => QuoteStyle::NonNumeric,
+        Quoting::Never => QuoteStyle::Never,
+    };
+
+    let terminator = match options.line_ending {
+        LineEnding::Lf => Terminator::Any(b'\n'),
+        LineEnding::Crlf => Terminator::CRLF,
+    };
+
+    WriterBuilder::new()
+        .delimiter(delimiter)
+        .quote_style(quote_style)
+        .terminator(terminator)
+        .flexible(true)
+        .from_writer(Vec::new())
+}
+
+/// Перекодирование готового UTF-8 текста в выбранную кодировку
+pub fn encode_text(utf8: Vec<u8>, encoding: TextEncoding) -> Vec<u8> {
+    match encoding {
+        TextEncoding::Utf8 => utf8,
+        TextEncoding::Utf8Bom => {
+            let mut out = Vec::with_capacity(utf8.len() + UTF8_BOM.len());
+            out.extend_from_slice(UTF8_BOM);
+            out.extend_from_slice(&utf8);
+            out
+        }
+        TextEncoding::Windows1251 => {
+            let text = String::from_utf8_lossy(&utf8);
+            let (encoded, _, _) = WINDOWS_1251.encode(&text);
+            encoded.into_owned()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_1251_replaces_unmappable_characters() {
+        let options = CsvOptions {
+            encoding: TextEncoding::Windows1251,
+            ..CsvOptions::default()
+        };
+        let columns = vec!["Имя".to_string(), "Цена €".to_string()];
+        let rows = vec![vec!["Чай 🍵".to_string(), "日本".to_string()]];
+        let bytes = build_delimited(&columns, &rows, b',', &options).unwrap();
+        let (text, _, had_errors) = WINDOWS_1251.decode(&bytes);
+        assert!(!had_errors);
+        assert_eq!(text, "Имя,Цена €\nЧай ?,??\n");
+    }
+}
@@ -0,0 +1,435 @@
+use crate::datasets::schema::{infer_schema, ColumnSchema};
+use crate::exporter::template::column_name;
+use crate::expr::{self, BinaryOp, Compiled, Expr, Function, UnaryOp, Value};
+use anyhow::{anyhow, bail, Result};
+use chrono::Datelike;
+use serde::Deserialize;
+
+/// Вычисляемая колонка: `formula` — выражение того же языка, что и у
+/// вычисляемых колонок набора, например `[Цена] * [Количество]`
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct FormulaColumn {
+    pub name: String,
+    pub formula: String,
+}
+
+/// Синтаксис ссылок на ячейки
+#[derive(Clone, Copy)]
+pub enum Dialect {
+    /// `B2*C2`
+    Excel,
+    /// `of:=[.B2]*[.C2]` без префикса
+    OpenFormula,
+}
+
+impl Dialect {
+    fn separator(self) -> &'static str {
+        match self {
+            Dialect::Excel => ",",
+            Dialect::OpenFormula => ";",
+        }
+    }
+}
+
+/// Разбор формул экспорта: каждая видит колонки данных и вычисляемые колонки
+/// левее себя. Типы колонок данных выводятся по строкам, как у набора
+pub fn compile_formulas(
+    columns: &[String],
+    rows: &[Vec<String>],
+    formulas: &[FormulaColumn],
+) -> Result<Vec<Compiled>> {
+    let mut schema = infer_schema(columns, rows);
+    let mut compiled = Vec::with_capacity(formulas.len());
+    for column in formulas {
+        let name = column.name.trim();
+        if name.is_empty() {
+            bail!("У вычисляемой колонки нет имени");
+        }
+        if schema.iter().any(|c| c.name == name) {
+            bail!("Колонка «{}» уже существует", name);
+        }
+        let source = column.formula.trim().trim_start_matches('=');
+        let formula =
+            expr::compile(source, &schema).map_err(|err| anyhow!("Формула «{}»: {}", name, err))?;
+        schema.push(ColumnSchema {
+            name: name.to_string(),
+            column_type: formula.result_type().column_type(),
+            nullable: true,
+        });
+        compiled.push(formula);
+    }
+    Ok(compiled)
+}
+
+/// Номера колонок, на которые ссылается выражение
+pub fn referenced_columns(expr: &Expr, out: &mut Vec<usize>) {
+    match expr {
+        Expr::Literal(_) => {}
+        Expr::Column(idx, _) => out.push(*idx),
+        Expr::Unary(_, inner) => referenced_columns(inner, out),
+        Expr::Binary(_, left, right) => {
+            referenced_columns(left, out);
+            referenced_columns(right, out);
+        }
+        Expr::Call(_, args) => args.iter().for_each(|arg| referenced_columns(arg, out)),
+        Expr::Case(branches, otherwise) => {
+            for (condition, value) in branches {
+                referenced_columns(condition, out);
+                referenced_columns(value, out);
+            }
+            if let Some(otherwise) = otherwise {
+                referenced_columns(otherwise, out);
+            }
+        }
+    }
+}
+
+/// Приоритет операции в тексте формулы; всё, что пишется вызовом
+/// функции или в скобках, — `ATOM`
+const ATOM: u8 = 9;
+const NEGATION: u8 = 6;
+const POWER: u8 = 5;
+
+fn precedence(expr: &Expr) -> u8 {
+    match expr {
+        Expr::Binary(op, _, _) => match op {
+            BinaryOp::Eq
+            | BinaryOp::Ne
+            | BinaryOp::Lt
+            | BinaryOp::Le
+            | BinaryOp::Gt
+            | BinaryOp::Ge => 1,
+            BinaryOp::Concat => 2,
+            BinaryOp::Add | BinaryOp::Sub => 3,
+            BinaryOp::Mul | BinaryOp::Div => 4,
+            BinaryOp::Pow => POWER,
+            BinaryOp::Rem | BinaryOp::And | BinaryOp::Or => ATOM,
+        },
+        Expr::Unary(UnaryOp::Neg, _) => NEGATION,
+        Expr::Literal(Value::Number(n)) if *n < 0.0 => NEGATION,
+        _ => ATOM,
+    }
+}
+
+/// Текст формулы XLSX/ODS для строки `row` (с нуля). Вычисляется формула
+/// тем же движком, что и вычисляемые колонки набора (`Compiled::eval`), здесь
+/// только запись выражения функциями Excel/OpenFormula
+pub fn render(expr: &Expr, row: u32, dialect: Dialect) -> String {
+    let mut writer = Writer {
+        out: String::new(),
+        row,
+        dialect,
+    };
+    writer.expr(expr);
+    writer.out
+}
+
+struct Writer {
+    out: String,
+    row: u32,
+    dialect: Dialect,
+}
+
+impl Writer {
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Literal(value) => self.literal(value),
+            Expr::Column(idx, _) => {
+                let cell = format!("{}{}", column_name(*idx as u32), self.row + 1);
+                match self.dialect {
+                    Dialect::Excel => self.out.push_str(&cell),
+                    Dialect::OpenFormula => {
+                        self.out.push_str("[.");
+                        self.out.push_str(&cell);
+                        self.out.push(']');
+                    }
+                }
+            }
+            Expr::Unary(UnaryOp::Neg, inner) => {
+                self.out.push('-');
+                self.child(inner, NEGATION);
+            }
+            Expr::Unary(UnaryOp::Not, inner) => self.call("NOT", &[inner]),
+            Expr::Binary(BinaryOp::And, left, right) => self.call("AND", &[left, right]),
+            Expr::Binary(BinaryOp::Or, left, right) => self.call("OR", &[left, right]),
+            Expr::Binary(BinaryOp::Rem, left, right) => {
+                // остаток со знаком делимого, как `%` в выражениях; MOD берёт знак делителя
+                self.out.push('(');
+                self.child(left, 3);
+                self.out.push('-');
+                self.child(right, 4);
+                self.out.push_str("*TRUNC(");
+                self.child(left, 4);
+                self.out.push('/');
+                self.child(right, 5);
+                self.out.push_str("))");
+            }
+            Expr::Binary(op, left, right) => {
+                let own = precedence(expr);
+                let (left_min, right_min) = match op {
+                    // `^` правоассоциативен; основание-отрицание берётся в скобки
+                    BinaryOp::Pow => (NEGATION + 1, POWER),
+                    // сравнения не сцепляются
+                    _ if own == 1 => (2, 2),
+                    _ => (own, own + 1),
+                };
+                self.child(left, left_min);
+                self.out.push_str(match op {
+                    BinaryOp::Add => "+",
+                    BinaryOp::Sub => "-",
+                    BinaryOp::Mul => "*",
+                    BinaryOp::Div => "/",
+                    BinaryOp::Pow => "^",
+                    BinaryOp::Concat => "&",
+                    BinaryOp::Eq => "=",
+                    BinaryOp::Ne => "<>",
+                    BinaryOp::Lt => "<",
+                    BinaryOp::Le => "<=",
+                    BinaryOp::Gt => ">",
+                    BinaryOp::Ge => ">=",
+                    BinaryOp::Rem | BinaryOp::And | BinaryOp::Or => unreachable!("обработано выше"),
+                });
+                self.child(right, right_min);
+            }
+            Expr::Call(function, args) => self.function(*function, args),
+            Expr::Case(branches, otherwise) => self.case(branches, otherwise.as_deref()),
+        }
+    }
+
+    fn child(&mut self, expr: &Expr, min_precedence: u8) {
+        if precedence(expr) < min_precedence {
+            self.out.push('(');
+            self.expr(expr);
+            self.out.push(')');
+        } else {
+            self.expr(expr);
+        }
+    }
+
+    fn call(&mut self, name: &str, args: &[&Expr]) {
+        self.out.push_str(name);
+        self.out.push('(');
+        for (idx, arg) in args.iter().enumerate() {
+            if idx > 0 {
+                self.out.push_str(self.dialect.separator());
+            }
+            self.expr(arg);
+        }
+        self.out.push(')');
+    }
+
+    fn literal(&mut self, value: &Value) {
+        match value {
+            Value::Null => self.out.push_str("\"\""),
+            Value::Number(n) => self.out.push_str(&n.to_string()),
+            Value::Text(text) => {
+                self.out.push('"');
+                self.out.push_str(&text.replace('"', "\"\""));
+                self.out.push('"');
+            }
+            Value::Bool(true) => self.out.push_str("TRUE()"),
+            Value::Bool(false) => self.out.push_str("FALSE()"),
+            Value::Date(date) => {
+                let separator = self.dialect.separator();
+                self.out.push_str(&format!(
+                    "DATE({}{}{}{}{})",
+                    date.year(),
+                    separator,
+                    date.month(),
+                    separator,
+                    date.day()
+                ));
+            }
+        }
+    }
+
+    /// `CASE`/`IF` — вложенные `IF`; без ветви ELSE — пустая строка
+    fn case(&mut self, branches: &[(Expr, Expr)], otherwise: Option<&Expr>) {
+        let separator = self.dialect.separator();
+        for (condition, value) in branches {
+            self.out.push_str("IF(");
+            self.expr(condition);
+            self.out.push_str(separator);
+            self.expr(value);
+            self.out.push_str(separator);
+        }
+        match otherwise {
+            Some(otherwise) => self.expr(otherwise),
+            None => self.out.push_str("\"\""),
+        }
+        self.out.push_str(&")".repeat(branches.len()));
+    }
+
+    fn function(&mut self, function: Function, args: &[Expr]) {
+        let all: Vec<&Expr> = args.iter().collect();
+        let separator = self.dialect.separator();
+        match function {
+            Function::Substr => {
+                self.out.push_str("MID(");
+                self.expr(&args[0]);
+                self.out.push_str(separator);
+                self.expr(&args[1]);
+                self.out.push_str(separator);
+                match args.get(2) {
+                    Some(len) => self.expr(len),
+                    // без длины — до конца строки
+                    None => self.out.push_str("32767"),
+                }
+                self.out.push(')');
+            }
+            Function::Replace => self.call("SUBSTITUTE", &all),
+            Function::Contains => {
+                self.out.push_str("ISNUMBER(");
+                self.call("SEARCH", &[&args[1], &args[0]]);
+                self.out.push(')');
+            }
+            Function::Concat => self.call("CONCATENATE", &all),
+            Function::Round => {
+                self.out.push_str("ROUND(");
+                self.expr(&args[0]);
+                self.out.push_str(separator);
+                match args.get(1) {
+                    Some(digits) => self.expr(digits),
+                    None => self.out.push('0'),
+                }
+                self.out.push(')');
+            }
+            Function::Floor => self.call("INT", &all),
+            Function::Ceil => {
+                self.out.push_str("(-INT(-");
+                self.child(&args[0], ATOM);
+                self.out.push_str("))");
+            }
+            Function::Coalesce => {
+                // первое непустое значение: вложенные IF(x<>"", x, …)
+                let (last, rest) = all.split_last().expect("арность проверена при разборе");
+                for arg in rest {
+                    self.out.push_str("IF(");
+                    self.child(arg, 2);
+                    self.out.push_str("<>\"\"");
+                    self.out.push_str(separator);
+                    self.expr(arg);
+                    self.out.push_str(separator);
+                }
+                self.expr(last);
+                self.out.push_str(&")".repeat(rest.len()));
+            }
+            Function::IsNull => {
+                self.out.push_str("(LEN(TRIM(");
+                self.expr(&args[0]);
+                self.out.push_str("))=0)");
+            }
+            Function::Quarter => {
+                self.out.push_str("ROUNDUP(MONTH(");
+                self.expr(&args[0]);
+                self.out.push_str(")/3");
+                self.out.push_str(separator);
+                self.out.push_str("0)");
+            }
+            Function::Weekday => {
+                // понедельник — 1, как в выражениях
+                self.out.push_str("WEEKDAY(");
+                self.expr(&args[0]);
+                self.out.push_str(separator);
+                self.out.push_str("2)");
+            }
+            Function::DateDiff => {
+                self.out.push('(');
+                self.call("INT", &[&args[0]]);
+                self.out.push('-');
+                self.call("INT", &[&args[1]]);
+                self.out.push(')');
+            }
+            Function::Date if args.len() == 1 => self.call("DATEVALUE", &all),
+            Function::Number => self.call("VALUE", &all),
+            Function::Text => {
+                self.out.push('(');
+                self.child(&args[0], 3);
+                self.out.push_str("&\"\")");
+            }
+            Function::Upper
+            | Function::Lower
+            | Function::Trim
+            | Function::Len
+            | Function::Left
+            | Function::Right
+            | Function::Abs
+            | Function::Min
+            | Function::Max
+            | Function::Sum
+            | Function::Year
+            | Function::Month
+            | Function::Day
+            | Function::Date
+            | Function::Today => self.call(function.name(), &all),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile(formula: &str) -> Vec<Compiled> {
+        let columns = vec!["Цена".to_string(), "Количество".to_string()];
+        let rows = vec![vec!["10".to_string(), "3".to_string()]];
+        let formulas = vec![
+            FormulaColumn {
+                name: "Сумма".to_string(),
+                formula: formula.to_string(),
+            },
+            FormulaColumn {
+                name: "С НДС".to_string(),
+                formula: "ROUND([Сумма] * 1.2, 1)".to_string(),
+            },
+        ];
+        compile_formulas(&columns, &rows, &formulas).unwrap()
+    }
+
+    #[test]
+    fn formulas_render_and_evaluate_like_computed_columns() {
+        let compiled = compile("=[Цена] * [Количество] - 2^2");
+        assert_eq!(render(compiled[0].expr(), 1, Dialect::Excel), "A2*B2-2^2");
+        assert_eq!(
+            render(compiled[1].expr(), 1, Dialect::OpenFormula),
+            "ROUND([.C2]*1.2;1)"
+        );
+
+        let row = vec!["10".to_string(), "3".to_string()];
+        let sum = compiled[0].eval(&row);
+        assert_eq!(sum, Value::Number(26.0));
+        let row = vec![row[0].clone(), row[1].clone(), sum.to_cell()];
+        assert_eq!(compiled[1].eval(&row), Value::Number(31.2));
+    }
+
+    #[test]
+    fn operators_keep_their_grouping() {
+        let render_excel = |formula: &str| render(compile(formula)[0].expr(), 1, Dialect::Excel);
+        assert_eq!(render_excel("([Цена] - 1) * -[Количество]"), "(A2-1)*-B2");
+        assert_eq!(render_excel("[Цена] - ([Количество] - 1)"), "A2-(B2-1)");
+        assert_eq!(render_excel("-[Цена]^2 + 2^3^2"), "(-A2)^2+2^3^2");
+        assert_eq!(render_excel("(2^3)^2"), "(2^3)^2");
+        assert_eq!(render_excel("[Цена] % 3"), "(A2-3*TRUNC(A2/3))");
+        assert_eq!(
+            render_excel("IF([Цена] > 5 AND NOT [Количество] = 0, [Цена], 0)"),
+            "IF(AND(A2>5,NOT(B2=0)),A2,0)"
+        );
+        assert_eq!(
+            render_excel("CASE WHEN [Цена] > 5 THEN 1 WHEN [Цена] > 1 THEN 2 END"),
+            "IF(A2>5,1,IF(A2>1,2,\"\"))"
+        );
+    }
+
+    #[test]
+    fn formulas_are_type_checked_against_the_data() {
+        let columns = vec!["Имя".to_string()];
+        let rows = vec![vec!["Анна".to_string()]];
+        let formulas = vec![FormulaColumn {
+            name: "Удвоено".to_string(),
+            formula: "[Имя] * 2".to_string(),
+        }];
+        let err = compile_formulas(&columns, &rows, &formulas).unwrap_err();
+        assert!(err.to_string().contains("ожидается число"), "{}", err);
+    }
+}
@@ -0,0 +1,42 @@
+use anyhow::Result;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use std::io::Write;
+
+/// Строка таблицы как JSON-объект с сохранением порядка колонок
+struct RowObject<'a> {
+    columns: &'a [String],
+    row: &'a [String],
+}
+
+impl Serialize for RowObject<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.columns.len()))?;
+        for (idx, column) in self.columns.iter().enumerate() {
+            let value = self.row.get(idx).map(String::as_str).unwrap_or("");
+            map.serialize_entry(column, value)?;
+        }
+        map.end()
+    }
+}
+
+/// Выгрузка в JSON: массив объектов `{ колонка: значение }`
+pub fn build_json(columns: &[String], rows: &[Vec<String>]) -> Result<Vec<u8>> {
+    let objects: Vec<RowObject> = rows.iter().map(|row| RowObject { columns, row }).collect();
+    Ok(serde_json::to_vec(&objects)?)
+}
+
+/// Выгрузка в NDJSON: по одному объекту на строку
+pub fn build_ndjson(columns: &[String], rows: &[Vec<String>]) -> Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    for row in rows {
+        write_row_object(&mut buffer, columns, row)?;
+        buffer.push(b'\n');
+    }
+    Ok(buffer)
+}
+
+/// Запись одной строки таблицы JSON-объектом
+pub fn write_row_object<W: Write>(writer: W, columns: &[String], row: &[String]) -> Result<()> {
+    serde_json::to_writer(writer, &RowObject { columns, row })?;
+    Ok(())
+}
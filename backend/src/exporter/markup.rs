@@ -0,0 +1,323 @@
+use crate::exporter::parse_number;
+use anyhow::Result;
+use serde::Deserialize;
+use std::fmt::Write;
+
+const TABLE_STYLE: &str =
+    "border-collapse: collapse; font-family: Arial, sans-serif; font-size: 13px";
+const HEADER_STYLE: &str =
+    "border: 1px solid #d0d0d0; padding: 4px 8px; background: #f2f2f2; font-weight: bold";
+const CELL_STYLE: &str = "border: 1px solid #d0d0d0; padding: 4px 8px";
+
+/// Настройки HTML/Markdown, приходят в поле `markup` запроса экспорта
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct MarkupOptions {
+    pub title: Option<String>,
+    pub inline_css: bool,
+    pub align_numbers: bool,
+}
+
+impl Default for MarkupOptions {
+    fn default() -> Self {
+        Self {
+            title: None,
+            inline_css: false,
+            align_numbers: true,
+        }
+    }
+}
+
+impl MarkupOptions {
+    fn title(&self) -> Option<&str> {
+        self.title
+            .as_deref()
+            .map(str::trim)
+            .filter(|title| !title.is_empty())
+    }
+}
+
+/// Самостоятельный HTML-документ с таблицей
+pub fn build_html_table(
+    columns: &[String],
+    rows: &[Vec<String>],
+    options: &MarkupOptions,
+) -> Result<Vec<u8>> {
+    let width = table_width(columns, rows);
+    let numeric = numeric_columns(width, rows, options.align_numbers);
+    let css = |style: &str| {
+        if options.inline_css {
+            format!(" style=\"{}\"", style)
+        } else {
+            String::new()
+        }
+    };
+    let cell_attrs = |idx: usize, base: &str| {
+        let right = numeric.get(idx).copied().unwrap_or(false);
+        match (options.inline_css, right) {
+            (true, true) => format!(" style=\"{}; text-align: right\"", base),
+            (true, false) => format!(" style=\"{}\"", base),
+            (false, true) => " style=\"text-align: right\"".to_string(),
+            (false, false) => String::new(),
+        }
+    };
+
+    let mut html = String::new();
+    open_document(&mut html, options.title().unwrap_or("Таблица"));
+    if let Some(title) = options.title() {
+        writeln!(html, "<h1>{}</h1>", escape_html(title))?;
+    }
+
+    writeln!(html, "<table{}>", css(TABLE_STYLE))?;
+    writeln!(html, "<thead>\n<tr>")?;
+    // строки длиннее заголовка: у лишних ячеек пустой заголовок
+    for idx in 0..width {
+        let name = columns.get(idx).map(String::as_str).unwrap_or("");
+        writeln!(
+            html,
+            "<th{}>{}</th>",
+            cell_attrs(idx, HEADER_STYLE),
+            escape_html(name)
+        )?;
+    }
+    writeln!(html, "</tr>\n</thead>\n<tbody>")?;
+    for row in rows {
+        writeln!(html, "<tr>")?;
+        for idx in 0..width {
+            let value = row.get(idx).map(String::as_str).unwrap_or("");
+            writeln!(
+                html,
+                "<td{}>{}</td>",
+                cell_attrs(idx, CELL_STYLE),
+                escape_html_multiline(value)
+            )?;
+        }
+        writeln!(html, "</tr>")?;
+    }
+    writeln!(html, "</tbody>\n</table>")?;
+    close_document(&mut html);
+
+    Ok(html.into_bytes())
+}
+
+/// Таблица в GitHub-flavoured Markdown
+pub fn build_markdown_table(
+    columns: &[String],
+    rows: &[Vec<String>],
+    options: &MarkupOptions,
+) -> Result<Vec<u8>> {
+    let width = table_width(columns, rows);
+    let numeric = numeric_columns(width, rows, options.align_numbers);
+
+    let mut md = String::new();
+    if let Some(title) = options.title() {
+        writeln!(md, "# {}\n", escape_markdown_cell(title))?;
+    }
+    if width == 0 {
+        return Ok(md.into_bytes());
+    }
+
+    let header: Vec<String> = (0..width)
+        .map(|idx| {
+            let name = columns.get(idx).map(String::as_str).unwrap_or("");
+            escape_markdown_cell(name)
+        })
+        .collect();
+    writeln!(md, "| {} |", header.join(" | "))?;
+
+    let separator: Vec<&str> = numeric
+        .iter()
+        .map(|right| if *right { "---:" } else { "---" })
+        .collect();
+    writeln!(md, "| {} |", separator.join(" | "))?;
+
+    for row in rows {
+        let cells: Vec<String> = (0..width)
+            .map(|idx| escape_markdown_cell(row.get(idx).map(String::as_str).unwrap_or("")))
+            .collect();
+        writeln!(md, "| {} |", cells.join(" | "))?;
+    }
+
+    Ok(md.into_bytes())
+}
+
+/// Текстовый отчёт как HTML-документ: пустая строка разделяет абзацы
+pub fn build_html_report(
+    title: Option<&str>,
+    text: &str,
+    options: &MarkupOptions,
+) -> Result<Vec<u8>> {
+    let title = title
+        .map(str::trim)
+        .filter(|title| !title.is_empty())
+        .or(options.title());
+
+    let mut html = String::new();
+    open_document(&mut html, title.unwrap_or("Отчёт"));
+    if let Some(title) = title {
+        writeln!(html, "<h1>{}</h1>", escape_html(title))?;
+    }
+    let paragraph_style = if options.inline_css {
+        " style=\"font-family: Arial, sans-serif; font-size: 14px; line-height: 1.5\""
+    } else {
+        ""
+    };
+    for paragraph in split_paragraphs(text) {
+        writeln!(
+            html,
+            "<p{}>{}</p>",
+            paragraph_style,
+            escape_html_multiline(&paragraph)
+        )?;
+    }
+    close_document(&mut html);
+
+    Ok(html.into_bytes())
+}
+
+/// Текстовый отчёт в Markdown: текст шаблона уже является разметкой и не экранируется
+pub fn build_markdown_report(
+    title: Option<&str>,
+    text: &str,
+    options: &MarkupOptions,
+) -> Result<Vec<u8>> {
+    let title = title
+        .map(str::trim)
+        .filter(|title| !title.is_empty())
+        .or(options.title());
+
+    let mut md = String::new();
+    if let Some(title) = title {
+        writeln!(md, "# {}\n", escape_markdown_cell(title))?;
+    }
+    md.push_str(text.trim_end());
+    md.push('\n');
+    Ok(md.into_bytes())
+}
+
+/// Число колонок таблицы: заголовок или самая длинная строка
+fn table_width(columns: &[String], rows: &[Vec<String>]) -> usize {
+    columns
+        .len()
+        .max(rows.iter().map(Vec::len).max().unwrap_or(0))
+}
+
+/// Колонка считается числовой, если все непустые значения — числа
+fn numeric_columns(width: usize, rows: &[Vec<String>], enabled: bool) -> Vec<bool> {
+    if !enabled {
+        return vec![false; width];
+    }
+    (0..width)
+        .map(|idx| {
+            let mut seen = false;
+            for value in rows.iter().filter_map(|row| row.get(idx)) {
+                if value.trim().is_empty() {
+                    continue;
+                }
+                if parse_number(value).is_none() {
+                    return false;
+                }
+                seen = true;
+            }
+            seen
+        })
+        .collect()
+}
+
+fn split_paragraphs(text: &str) -> Vec<String> {
+    let mut paragraphs = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            if !current.is_empty() {
+                paragraphs.push(current.join("\n"));
+                current.clear();
+            }
+        } else {
+            current.push(line);
+        }
+    }
+    if !current.is_empty() {
+        paragraphs.push(current.join("\n"));
+    }
+    paragraphs
+}
+
+fn open_document(html: &mut String, title: &str) {
+    html.push_str("<!DOCTYPE html>\n<html lang=\"ru\">\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!("<title>{}</title>\n", escape_html(title)));
+    html.push_str("</head>\n<body>\n");
+}
+
+fn close_document(html: &mut String) {
+    html.push_str("</body>\n</html>\n");
+}
+
+pub fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn escape_html_multiline(value: &str) -> String {
+    escape_html(value)
+        .replace("\r\n", "\n")
+        .replace('\n', "<br>")
+}
+
+/// Экранирование значения ячейки Markdown-таблицы или заголовка:
+/// `|` и переносы ломают строку таблицы
+fn escape_markdown_cell(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.replace("\r\n", "\n").chars() {
+        match c {
+            '\\' | '|' | '*' | '_' | '`' | '[' | ']' | '<' | '>' | '#' | '~' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("<br>"),
+            '\r' | '\t' => escaped.push(' '),
+            _ => escaped.push(c),
+        }
+    }
+    escaped.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_covers_the_widest_row() {
+        let columns = vec!["Имя".to_string()];
+        let rows = vec![vec!["Анна".to_string(), "лишнее".to_string()]];
+        let html = build_html_table(&columns, &rows, &MarkupOptions::default()).unwrap();
+        let html = String::from_utf8(html).unwrap();
+        assert_eq!(html.matches("<th>").count(), 2);
+        assert_eq!(html.matches("<td>").count(), 2);
+    }
+
+    #[test]
+    fn markdown_title_is_escaped() {
+        let options = MarkupOptions {
+            title: Some("Отчёт | *черновик*".to_string()),
+            ..MarkupOptions::default()
+        };
+        let md = build_markdown_table(&["A".to_string()], &[], &options).unwrap();
+        let md = String::from_utf8(md).unwrap();
+        assert!(md.starts_with("# Отчёт \\| \\*черновик\\*\n"), "{}", md);
+
+        let report = build_markdown_report(Some("<b>#1</b>"), "текст", &options).unwrap();
+        let report = String::from_utf8(report).unwrap();
+        assert!(report.starts_with("# \\<b\\>\\#1\\</b\\>\n"), "{}", report);
+    }
+}
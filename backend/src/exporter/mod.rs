@@ -1,41 +1,148 @@
-use anyhow::Result;
-use icu_locid::locale;
-use rust_xlsxwriter::Workbook;
-use spreadsheet_ods::{write_ods_buf, Sheet, WorkBook};
+pub mod bundle;
+pub mod conditional;
+pub mod delimited;
+pub mod formula;
+pub mod json;
+pub mod markup;
+pub mod package;
+pub mod pdf;
+pub mod references;
+pub mod spreadsheet;
+pub mod stream;
+pub mod template;
 
-pub fn build_xlsx(columns: &[String], rows: &[Vec<String>]) -> Result<Vec<u8>> {
-    let mut workbook = Workbook::new();
-    let worksheet = workbook.add_worksheet();
+pub use delimited::{build_delimited, CsvOptions};
+pub use json::{build_json, build_ndjson};
+pub use markup::{
+    build_html_report, build_html_table, build_markdown_report, build_markdown_table, MarkupOptions,
+};
+pub use pdf::{build_report_pdf, build_table_pdf, PdfOptions};
+pub use spreadsheet::{build_ods, build_xlsx, SpreadsheetOptions};
+pub use template::{fill_xlsx_template, TemplateOptions};
 
-    for (col_idx, header) in columns.iter().enumerate() {
-        worksheet.write_string(0, col_idx as u16, header)?;
-    }
+/// Поддерживаемые форматы выгрузки таблиц
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExportFormat {
+    Xlsx,
+    Ods,
+    Csv,
+    Tsv,
+    Json,
+    Ndjson,
+    Pdf,
+    Html,
+    Markdown,
+}
 
-    for (row_idx, row) in rows.iter().enumerate() {
-        for (col_idx, value) in row.iter().enumerate() {
-            worksheet.write_string((row_idx + 1) as u32, col_idx as u16, value)?;
+impl ExportFormat {
+    /// Разбор значения поля `format`; неизвестный формат → `None`
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "xlsx" => Some(ExportFormat::Xlsx),
+            "ods" => Some(ExportFormat::Ods),
+            "csv" => Some(ExportFormat::Csv),
+            "tsv" => Some(ExportFormat::Tsv),
+            "json" => Some(ExportFormat::Json),
+            "ndjson" | "jsonl" => Some(ExportFormat::Ndjson),
+            "pdf" => Some(ExportFormat::Pdf),
+            "html" | "htm" => Some(ExportFormat::Html),
+            "md" | "markdown" => Some(ExportFormat::Markdown),
+            _ => None,
         }
     }
 
-    let buffer = workbook.save_to_buffer()?;
-    Ok(buffer)
-}
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Xlsx => "xlsx",
+            ExportFormat::Ods => "ods",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Tsv => "tsv",
+            ExportFormat::Json => "json",
+            ExportFormat::Ndjson => "ndjson",
+            ExportFormat::Pdf => "pdf",
+            ExportFormat::Html => "html",
+            ExportFormat::Markdown => "md",
+        }
+    }
 
-pub fn build_ods(columns: &[String], rows: &[Vec<String>]) -> Result<Vec<u8>> {
-    let mut workbook = WorkBook::new(locale!("en-US"));
-    let mut sheet = Sheet::new("Sheet1");
+    pub fn mime(self) -> &'static str {
+        match self {
+            ExportFormat::Xlsx => {
+                "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+            }
+            ExportFormat::Ods => "application/vnd.oasis.opendocument.spreadsheet",
+            ExportFormat::Csv => "text/csv",
+            ExportFormat::Tsv => "text/tab-separated-values",
+            ExportFormat::Json => "application/json",
+            ExportFormat::Ndjson => "application/x-ndjson",
+            ExportFormat::Pdf => "application/pdf",
+            ExportFormat::Html => "text/html; charset=utf-8",
+            ExportFormat::Markdown => "text/markdown; charset=utf-8",
+        }
+    }
 
-    for (col_idx, header) in columns.iter().enumerate() {
-        sheet.set_value(0, col_idx as u32, header.as_str());
+    /// Форматы, которые можно формировать потоково, не собирая таблицу в памяти
+    pub fn supports_streaming(self) -> bool {
+        matches!(
+            self,
+            ExportFormat::Csv | ExportFormat::Tsv | ExportFormat::Ndjson | ExportFormat::Xlsx
+        )
     }
 
-    for (row_idx, row) in rows.iter().enumerate() {
-        for (col_idx, value) in row.iter().enumerate() {
-            sheet.set_value((row_idx + 1) as u32, col_idx as u32, value.as_str());
+    /// Метка формата для логов
+    pub fn label(self) -> &'static str {
+        match self {
+            ExportFormat::Xlsx => "XLSX",
+            ExportFormat::Ods => "ODS",
+            ExportFormat::Csv => "CSV",
+            ExportFormat::Tsv => "TSV",
+            ExportFormat::Json => "JSON",
+            ExportFormat::Ndjson => "NDJSON",
+            ExportFormat::Pdf => "PDF",
+            ExportFormat::Html => "HTML",
+            ExportFormat::Markdown => "Markdown",
         }
     }
+}
+
+/// Разбор числа из текстовой ячейки: допускает пробелы-разделители тысяч,
+/// десятичную запятую и знак процента в конце
+pub fn parse_number(value: &str) -> Option<f64> {
+    let cleaned: String = value
+        .trim()
+        .trim_end_matches('%')
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '\u{a0}')
+        .map(|c| if c == ',' { '.' } else { c })
+        .collect();
+    if cleaned.is_empty() || cleaned.chars().any(|c| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    cleaned.parse::<f64>().ok().filter(|n| n.is_finite())
+}
 
-    workbook.push_sheet(sheet);
-    let buffer = write_ods_buf(&mut workbook, Vec::new())?;
-    Ok(buffer)
+/// Значение для числовой ячейки книги: только «чистые» числа без процентов,
+/// разделителей тысяч и ведущих нулей (коды вроде `007` остаются текстом)
+pub fn numeric_cell_value(value: &str) -> Option<String> {
+    let trimmed = value.trim();
+    if trimmed.len() != value.len() || trimmed.contains('%') {
+        return None;
+    }
+    let normalized = trimmed.replacen(',', ".", 1);
+    let digits = normalized.trim_start_matches('-');
+    if digits.len() > 1 && digits.starts_with('0') && !digits.starts_with("0.") {
+        return None;
+    }
+    if !digits
+        .chars()
+        .all(|c| c.is_ascii_digit() || c == '.' || c == 'e' || c == 'E' || c == '-' || c == '+')
+        || !digits.starts_with(|c: char| c.is_ascii_digit())
+    {
+        return None;
+    }
+    normalized
+        .parse::<f64>()
+        .ok()
+        .filter(|n| n.is_finite())
+        .map(|_| normalized)
 }
@@ -0,0 +1,35 @@
+use anyhow::Result;
+use std::collections::BTreeMap;
+use std::io::{Cursor, Read, Seek, Write};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// Пересборка ZIP-пакета (XLSX/ODS): файлы из `replaced` пишутся заново,
+/// файлы из `skip` выбрасываются, остальные копируются без перепаковки
+/// в исходном порядке (для ODS важно, что `mimetype` остаётся первым и несжатым)
+pub fn rewrite_zip<R: Read + Seek>(
+    zip: &mut ZipArchive<R>,
+    replaced: &BTreeMap<String, String>,
+    skip: &[&str],
+) -> Result<Vec<u8>> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    for idx in 0..zip.len() {
+        let file = zip.by_index(idx)?;
+        let name = file.name().to_string();
+        if skip.contains(&name.as_str()) {
+            continue;
+        }
+        match replaced.get(&name) {
+            Some(content) => {
+                drop(file);
+                writer.start_file(name, options)?;
+                writer.write_all(content.as_bytes())?;
+            }
+            None => writer.raw_copy_file(file)?,
+        }
+    }
+
+    Ok(writer.finish()?.into_inner())
+}
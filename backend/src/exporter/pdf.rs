@@ -0,0 +1,465 @@
+use anyhow::{anyhow, bail, Result};
+use printpdf::path::PaintMode;
+use printpdf::{
+    Color, IndirectFontRef, Line, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference, Point,
+    Rect, Rgb,
+};
+use serde::Deserialize;
+use ttf_parser::Face;
+
+// Шрифты DejaVu Sans Condensed вшиты в бинарник: покрывают кириллицу и не зависят от системы
+static REGULAR_FONT: &[u8] = include_bytes!("../../assets/fonts/DejaVuSansCondensed.ttf");
+static BOLD_FONT: &[u8] = include_bytes!("../../assets/fonts/DejaVuSansCondensed-Bold.ttf");
+
+const PT_TO_MM: f32 = 0.352_778;
+const MARGIN: f32 = 12.0;
+const CELL_PADDING: f32 = 1.5;
+const MIN_COLUMN_WIDTH: f32 = 10.0;
+const WIDTH_SAMPLE_ROWS: usize = 500;
+const DEFAULT_FONT_SIZE: f32 = 8.0;
+const ELLIPSIS: &str = "…";
+
+#[derive(Deserialize, Clone, Copy, Default, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum PageOrientation {
+    #[default]
+    Portrait,
+    Landscape,
+}
+
+#[derive(Deserialize, Clone, Copy, Default, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum PageSize {
+    #[default]
+    A4,
+    A3,
+    A5,
+    Letter,
+    Legal,
+}
+
+impl PageSize {
+    /// Размер страницы в мм (ширина, высота) в книжной ориентации
+    fn dimensions(self) -> (f32, f32) {
+        match self {
+            PageSize::A4 => (210.0, 297.0),
+            PageSize::A3 => (297.0, 420.0),
+            PageSize::A5 => (148.0, 210.0),
+            PageSize::Letter => (215.9, 279.4),
+            PageSize::Legal => (215.9, 355.6),
+        }
+    }
+}
+
+/// Настройки PDF, приходят в поле `pdf` запроса экспорта
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PdfOptions {
+    pub orientation: PageOrientation,
+    pub page_size: PageSize,
+    pub font_size: Option<f32>,
+    pub title: Option<String>,
+}
+
+impl PdfOptions {
+    pub fn validate(&self) -> Result<()> {
+        if let Some(size) = self.font_size {
+            if !(4.0..=24.0).contains(&size) {
+                bail!("Размер шрифта PDF должен быть от 4 до 24 pt");
+            }
+        }
+        Ok(())
+    }
+
+    fn page_dimensions(&self) -> (f32, f32) {
+        let (width, height) = self.page_size.dimensions();
+        match self.orientation {
+            PageOrientation::Portrait => (width, height),
+            PageOrientation::Landscape => (height, width),
+        }
+    }
+
+    fn font_size(&self) -> f32 {
+        self.font_size.unwrap_or(DEFAULT_FONT_SIZE)
+    }
+
+    fn title(&self) -> Option<&str> {
+        self.title
+            .as_deref()
+            .map(str::trim)
+            .filter(|title| !title.is_empty())
+    }
+}
+
+/// Шрифт документа вместе с метриками для расчёта ширины текста
+struct Font {
+    face: Face<'static>,
+    pdf: IndirectFontRef,
+}
+
+impl Font {
+    fn load(doc: &PdfDocumentReference, data: &'static [u8]) -> Result<Self> {
+        let face = Face::parse(data, 0).map_err(|err| anyhow!("Ошибка шрифта: {}", err))?;
+        let pdf = doc
+            .add_external_font(data)
+            .map_err(|err| anyhow!("Ошибка шрифта PDF: {}", err))?;
+        Ok(Self { face, pdf })
+    }
+
+    /// Ширина строки в мм при заданном кегле
+    fn text_width(&self, text: &str, size: f32) -> f32 {
+        let units_per_em = self.face.units_per_em() as f32;
+        let advance: f32 = text
+            .chars()
+            .filter_map(|c| self.face.glyph_index(c))
+            .map(|glyph| self.face.glyph_hor_advance(glyph).unwrap_or(0) as f32)
+            .sum();
+        advance / units_per_em * size * PT_TO_MM
+    }
+
+    /// Обрезка строки с многоточием, чтобы она поместилась в `max_width`
+    fn fit(&self, text: &str, size: f32, max_width: f32) -> String {
+        let text = text.replace(['\n', '\r', '\t'], " ");
+        if self.text_width(&text, size) <= max_width {
+            return text;
+        }
+
+        let budget = max_width - self.text_width(ELLIPSIS, size);
+        let mut fitted = String::new();
+        let mut width = 0.0;
+        for c in text.chars() {
+            let char_width = self.text_width(c.encode_utf8(&mut [0; 4]), size);
+            if width + char_width > budget {
+                break;
+            }
+            width += char_width;
+            fitted.push(c);
+        }
+        fitted.push_str(ELLIPSIS);
+        fitted
+    }
+
+    /// Перенос текста по словам; слишком длинные слова режутся посимвольно
+    fn wrap(&self, text: &str, size: f32, max_width: f32) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut current = String::new();
+
+        for word in text.split_whitespace() {
+            let candidate = if current.is_empty() {
+                word.to_string()
+            } else {
+                format!("{} {}", current, word)
+            };
+
+            if self.text_width(&candidate, size) <= max_width {
+                current = candidate;
+                continue;
+            }
+
+            if !current.is_empty() {
+                lines.push(std::mem::take(&mut current));
+            }
+
+            for c in word.chars() {
+                current.push(c);
+                if self.text_width(&current, size) > max_width && current.chars().count() > 1 {
+                    current.pop();
+                    lines.push(std::mem::replace(&mut current, c.to_string()));
+                }
+            }
+        }
+
+        if !current.is_empty() || lines.is_empty() {
+            lines.push(current);
+        }
+        lines
+    }
+}
+
+/// Документ с открытыми шрифтами и геометрией страницы
+struct PdfWriter {
+    doc: PdfDocumentReference,
+    regular: Font,
+    bold: Font,
+    width: f32,
+    height: f32,
+    font_size: f32,
+    pages: Vec<PdfLayerReference>,
+}
+
+impl PdfWriter {
+    fn new(title: &str, options: &PdfOptions) -> Result<Self> {
+        options.validate()?;
+        let (width, height) = options.page_dimensions();
+        let (doc, page, layer) = PdfDocument::new(title, Mm(width), Mm(height), "content");
+        let regular = Font::load(&doc, REGULAR_FONT)?;
+        let bold = Font::load(&doc, BOLD_FONT)?;
+        let first = doc.get_page(page).get_layer(layer);
+
+        Ok(Self {
+            doc,
+            regular,
+            bold,
+            width,
+            height,
+            font_size: options.font_size(),
+            pages: vec![first],
+        })
+    }
+
+    fn line_height(&self, size: f32) -> f32 {
+        size * PT_TO_MM * 1.3
+    }
+
+    fn content_width(&self) -> f32 {
+        self.width - MARGIN * 2.0
+    }
+
+    fn title_size(&self) -> f32 {
+        self.font_size * 1.6
+    }
+
+    fn footer_height(&self) -> f32 {
+        self.line_height(self.font_size) + 2.0
+    }
+
+    /// Слой страницы с номером `index`; недостающие страницы создаются
+    fn page(&mut self, index: usize) -> PdfLayerReference {
+        while self.pages.len() <= index {
+            let (page, layer) = self
+                .doc
+                .add_page(Mm(self.width), Mm(self.height), "content");
+            self.pages.push(self.doc.get_page(page).get_layer(layer));
+        }
+        self.pages[index].clone()
+    }
+
+    fn text(&self, layer: &PdfLayerReference, text: &str, bold: bool, size: f32, x: f32, y: f32) {
+        let font = if bold { &self.bold } else { &self.regular };
+        layer.use_text(text, size, Mm(x), Mm(y), &font.pdf);
+    }
+
+    /// Заголовок документа, возвращает координату под ним
+    fn draw_title(&self, layer: &PdfLayerReference, title: &str) -> f32 {
+        let size = self.title_size();
+        let line_height = self.line_height(size);
+        let mut y = self.height - MARGIN;
+        for line in self.bold.wrap(title, size, self.content_width()) {
+            y -= line_height;
+            self.text(layer, &line, true, size, MARGIN, y);
+        }
+        y - line_height * 0.5
+    }
+
+    fn draw_footers(&self) {
+        let total = self.pages.len();
+        for (idx, layer) in self.pages.iter().enumerate() {
+            let label = format!("Страница {} из {}", idx + 1, total);
+            let width = self.regular.text_width(&label, self.font_size);
+            layer.set_fill_color(grey(0.4));
+            self.text(
+                layer,
+                &label,
+                false,
+                self.font_size,
+                self.width - MARGIN - width,
+                MARGIN * 0.6,
+            );
+            layer.set_fill_color(grey(0.0));
+        }
+    }
+
+    fn finish(self) -> Result<Vec<u8>> {
+        self.draw_footers();
+        self.doc
+            .save_to_bytes()
+            .map_err(|err| anyhow!("Ошибка записи PDF: {}", err))
+    }
+}
+
+/// Таблица в PDF: постраничная разбивка, шапка повторяется на каждой странице
+pub fn build_table_pdf(
+    columns: &[String],
+    rows: &[Vec<String>],
+    options: &PdfOptions,
+) -> Result<Vec<u8>> {
+    let title = options.title().unwrap_or("Таблица");
+    let mut writer = PdfWriter::new(title, options)?;
+    let size = writer.font_size;
+    let row_height = writer.line_height(size) + CELL_PADDING * 2.0;
+    let widths = column_widths(&writer, columns, rows);
+    let bottom = MARGIN + writer.footer_height();
+
+    let mut page_idx = 0;
+    let mut layer = writer.page(page_idx);
+    let mut y = match options.title() {
+        Some(title) => writer.draw_title(&layer, title),
+        None => writer.height - MARGIN,
+    };
+    y = draw_table_row(&writer, &layer, columns, &widths, y, row_height, true);
+
+    for row in rows {
+        if y - row_height < bottom {
+            page_idx += 1;
+            layer = writer.page(page_idx);
+            y = writer.height - MARGIN;
+            y = draw_table_row(&writer, &layer, columns, &widths, y, row_height, true);
+        }
+        y = draw_table_row(&writer, &layer, row, &widths, y, row_height, false);
+    }
+
+    writer.finish()
+}
+
+/// Текстовый отчёт в PDF: абзацы переносятся по словам
+pub fn build_report_pdf(title: Option<&str>, text: &str, options: &PdfOptions) -> Result<Vec<u8>> {
+    let title = title
+        .map(str::trim)
+        .filter(|title| !title.is_empty())
+        .or(options.title());
+    let mut writer = PdfWriter::new(title.unwrap_or("Отчёт"), options)?;
+    let size = writer.font_size * 1.25;
+    let line_height = writer.line_height(size);
+    let bottom = MARGIN + writer.footer_height();
+
+    let mut page_idx = 0;
+    let mut layer = writer.page(page_idx);
+    let mut y = match title {
+        Some(title) => writer.draw_title(&layer, title),
+        None => writer.height - MARGIN,
+    };
+
+    for paragraph in text.lines() {
+        let lines = if paragraph.trim().is_empty() {
+            vec![String::new()]
+        } else {
+            writer.regular.wrap(paragraph, size, writer.content_width())
+        };
+
+        for line in lines {
+            if y - line_height < bottom {
+                page_idx += 1;
+                layer = writer.page(page_idx);
+                y = writer.height - MARGIN;
+            }
+            y -= line_height;
+            if !line.is_empty() {
+                writer.text(&layer, &line, false, size, MARGIN, y);
+            }
+        }
+    }
+
+    writer.finish()
+}
+
+/// Ширины колонок по содержимому, ужатые или растянутые до ширины страницы
+fn column_widths(writer: &PdfWriter, columns: &[String], rows: &[Vec<String>]) -> Vec<f32> {
+    let size = writer.font_size;
+    let count = columns
+        .len()
+        .max(rows.iter().map(Vec::len).max().unwrap_or(0));
+    if count == 0 {
+        return Vec::new();
+    }
+
+    let mut natural = vec![MIN_COLUMN_WIDTH; count];
+    for (idx, header) in columns.iter().enumerate() {
+        let width = writer.bold.text_width(header, size) + CELL_PADDING * 2.0;
+        natural[idx] = natural[idx].max(width);
+    }
+    for row in rows.iter().take(WIDTH_SAMPLE_ROWS) {
+        for (idx, value) in row.iter().enumerate() {
+            let width = writer.regular.text_width(value, size) + CELL_PADDING * 2.0;
+            natural[idx] = natural[idx].max(width);
+        }
+    }
+
+    let available = writer.content_width();
+    let total: f32 = natural.iter().sum();
+    if total <= available {
+        let scale = available / total;
+        return natural.iter().map(|w| w * scale).collect();
+    }
+
+    // Узкие колонки оставляем как есть, широкие делят остаток пропорционально
+    let fair = available / count as f32;
+    let narrow: f32 = natural.iter().filter(|w| **w <= fair).sum();
+    let wide: f32 = natural.iter().filter(|w| **w > fair).sum();
+    let remaining = (available - narrow).max(0.0);
+    natural
+        .iter()
+        .map(|w| {
+            if *w <= fair {
+                *w
+            } else {
+                (w / wide * remaining).max(MIN_COLUMN_WIDTH.min(fair))
+            }
+        })
+        .collect()
+}
+
+fn draw_table_row(
+    writer: &PdfWriter,
+    layer: &PdfLayerReference,
+    cells: &[String],
+    widths: &[f32],
+    top: f32,
+    row_height: f32,
+    header: bool,
+) -> f32 {
+    let size = writer.font_size;
+    let bottom = top - row_height;
+    let right = MARGIN + widths.iter().sum::<f32>();
+
+    if header {
+        layer.set_fill_color(grey(0.9));
+        layer.add_rect(
+            Rect::new(Mm(MARGIN), Mm(bottom), Mm(right), Mm(top)).with_mode(PaintMode::Fill),
+        );
+        layer.set_fill_color(grey(0.0));
+    }
+
+    let font = if header {
+        &writer.bold
+    } else {
+        &writer.regular
+    };
+    let baseline = bottom + CELL_PADDING + size * PT_TO_MM * 0.25;
+    let mut x = MARGIN;
+    for (idx, width) in widths.iter().enumerate() {
+        if let Some(value) = cells.get(idx).filter(|v| !v.is_empty()) {
+            let fitted = font.fit(value, size, width - CELL_PADDING * 2.0);
+            writer.text(layer, &fitted, header, size, x + CELL_PADDING, baseline);
+        }
+        x += width;
+    }
+
+    layer.set_outline_color(grey(0.6));
+    layer.set_outline_thickness(0.3);
+    layer.add_line(segment(MARGIN, bottom, right, bottom));
+    if header {
+        layer.add_line(segment(MARGIN, top, right, top));
+    }
+    let mut x = MARGIN;
+    layer.add_line(segment(x, bottom, x, top));
+    for width in widths {
+        x += width;
+        layer.add_line(segment(x, bottom, x, top));
+    }
+
+    bottom
+}
+
+fn segment(x1: f32, y1: f32, x2: f32, y2: f32) -> Line {
+    Line {
+        points: vec![
+            (Point::new(Mm(x1), Mm(y1)), false),
+            (Point::new(Mm(x2), Mm(y2)), false),
+        ],
+        is_closed: false,
+    }
+}
+
+fn grey(level: f32) -> Color {
+    Color::Rgb(Rgb::new(level, level, level, None))
+}
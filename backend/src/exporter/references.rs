@@ -0,0 +1,363 @@
+use quick_xml::escape::{partial_escape, unescape};
+
+/// Последняя строка листа Excel (с нуля)
+pub const MAX_ROW: u32 = 1_048_575;
+/// Число колонок листа Excel: последняя — XFD
+const MAX_COLUMNS: u32 = 16_384;
+
+/// Элементы, в тексте которых лежит формула
+const FORMULA_TAGS: [&str; 5] = ["f", "formula", "formula1", "formula2", "c:f"];
+
+/// Вставка данных в лист шаблона: строки ниже области данных уезжают вниз,
+/// диапазоны, которые заканчиваются в области данных, растягиваются на все строки.
+/// Номера строк — с нуля
+#[derive(Clone, Debug)]
+pub struct RowShift {
+    /// Лист, в который вставляются данные
+    pub sheet: String,
+    /// Первая строка данных (после заголовка)
+    pub data_start: u32,
+    /// Последняя строка, которую шаблон отвёл под данные
+    pub region_end: u32,
+    /// Последняя записанная строка
+    pub last_row: u32,
+    /// На сколько уезжают строки ниже области данных
+    pub by: u32,
+}
+
+/// Ссылка на ячейку: "$B$5"
+struct CellRef<'a> {
+    col: &'a str,
+    col_abs: bool,
+    row: u32,
+    row_abs: bool,
+}
+
+impl CellRef<'_> {
+    fn with_row(&self, row: u32) -> String {
+        format!(
+            "{}{}{}{}",
+            if self.col_abs { "$" } else { "" },
+            self.col,
+            if self.row_abs { "$" } else { "" },
+            row + 1
+        )
+    }
+}
+
+impl RowShift {
+    /// Новый номер строки шаблона
+    pub fn row(&self, row: u32) -> u32 {
+        if row > self.region_end {
+            row + self.by
+        } else {
+            row
+        }
+    }
+
+    fn range_end(&self, row: u32) -> u32 {
+        if row > self.region_end {
+            row + self.by
+        } else if row >= self.data_start {
+            row.max(self.last_row)
+        } else {
+            row
+        }
+    }
+
+    /// Сдвиг ссылок в тексте формулы. `local` — формула лежит на том же листе,
+    /// и ссылки без имени листа тоже относятся к нему
+    pub fn formula(&self, text: &str, local: bool) -> String {
+        self.rewrite(text, local, true)
+    }
+
+    /// Сдвиг разметки листа: адреса ячеек, `ref`/`sqref` объединений, условного
+    /// форматирования и проверок, текст формул. Для чужих листов (`local = false`)
+    /// меняются только формулы со ссылками на лист с данными
+    pub fn sheet_xml(&self, xml: &str, local: bool) -> String {
+        let mut out = String::with_capacity(xml.len());
+        let mut rest = xml;
+        while let Some(lt) = rest.find('<') {
+            out.push_str(&rest[..lt]);
+            rest = &rest[lt..];
+            let Some(gt) = rest.find('>') else {
+                break;
+            };
+            let tag = &rest[..=gt];
+            rest = &rest[gt + 1..];
+            let name = tag[1..]
+                .split(|c: char| c.is_whitespace() || c == '/' || c == '>')
+                .next()
+                .unwrap_or("");
+
+            let attributes: &[(&str, bool)] = match name {
+                "c" => &[("r", false)],
+                "f" | "mergeCell" | "hyperlink" => &[("ref", false)],
+                "conditionalFormatting" | "dataValidation" => &[("sqref", true)],
+                "autoFilter" => &[("ref", true)],
+                _ => &[],
+            };
+            if local && !attributes.is_empty() {
+                out.push_str(&self.tag_attributes(tag, attributes));
+            } else {
+                out.push_str(tag);
+            }
+
+            if FORMULA_TAGS.contains(&name) && !tag.ends_with("/>") {
+                let close = format!("</{}>", name);
+                if let Some(end) = rest.find(&close) {
+                    let content = &rest[..end];
+                    match unescape(content) {
+                        Ok(text) => out.push_str(&partial_escape(&self.formula(&text, local))),
+                        Err(_) => out.push_str(content),
+                    }
+                    rest = &rest[end..];
+                }
+            }
+        }
+        out.push_str(rest);
+        out
+    }
+
+    fn tag_attributes(&self, tag: &str, attributes: &[(&str, bool)]) -> String {
+        let mut out = tag.to_string();
+        for (attribute, expand) in attributes {
+            let needle = format!(" {}=\"", attribute);
+            let Some(start) = out.find(&needle).map(|pos| pos + needle.len()) else {
+                continue;
+            };
+            let Some(end) = out[start..].find('"').map(|pos| start + pos) else {
+                continue;
+            };
+            let value = self.rewrite(&out[start..end], true, *expand);
+            out.replace_range(start..end, &value);
+        }
+        out
+    }
+
+    fn rewrite(&self, text: &str, local: bool, expand: bool) -> String {
+        let mut out = String::with_capacity(text.len() + 8);
+        let mut pos = 0;
+        while pos < text.len() {
+            let rest = &text[pos..];
+            let c = rest.chars().next().unwrap_or_default();
+            if c == '"' {
+                let end = pos + literal_end(rest, '"');
+                out.push_str(&text[pos..end]);
+                pos = end;
+                continue;
+            }
+            let boundary = text[..pos]
+                .chars()
+                .next_back()
+                .is_none_or(|prev| !is_name_char(prev) && !matches!(prev, '$' | '\'' | '!'));
+            if boundary {
+                if let Some((len, replaced)) = self.reference(rest, local, expand) {
+                    out.push_str(&replaced);
+                    pos += len;
+                    continue;
+                }
+            }
+            let end = if c == '\'' {
+                pos + literal_end(rest, '\'')
+            } else if is_name_char(c) {
+                pos + name_end(rest)
+            } else {
+                pos + c.len_utf8()
+            };
+            out.push_str(&text[pos..end]);
+            pos = end;
+        }
+        out
+    }
+
+    /// Ссылка в начале `text`: [лист!]A1[:B2] → (длина, новый текст)
+    fn reference(&self, text: &str, local: bool, expand: bool) -> Option<(usize, String)> {
+        let mut pos = 0;
+        let mut sheet = None;
+        if text.starts_with('\'') {
+            let end = literal_end(text, '\'');
+            if end < 2 || !text[..end].ends_with('\'') || !text[end..].starts_with('!') {
+                return None;
+            }
+            sheet = Some(text[1..end - 1].replace("''", "'"));
+            pos = end + 1;
+        } else {
+            let end = name_end(text);
+            if end > 0 && text[end..].starts_with('!') {
+                sheet = Some(text[..end].to_string());
+                pos = end + 1;
+            }
+        }
+
+        let (first, first_len) = cell_ref(&text[pos..])?;
+        let mut end = pos + first_len;
+        let mut second = None;
+        if text[end..].starts_with(':') {
+            if let Some((cell, len)) = cell_ref(&text[end + 1..]) {
+                second = Some(cell);
+                end += 1 + len;
+            }
+        }
+        if text[end..]
+            .chars()
+            .next()
+            .is_some_and(|next| is_name_char(next) || matches!(next, '(' | '!' | '$'))
+        {
+            return None;
+        }
+
+        let applies = match &sheet {
+            Some(name) => name.to_lowercase() == self.sheet.to_lowercase(),
+            None => local,
+        };
+        if !applies {
+            return Some((end, text[..end].to_string()));
+        }
+        let mut out = text[..pos].to_string();
+        out.push_str(&first.with_row(self.row(first.row)));
+        if let Some(second) = second {
+            let last = if expand {
+                self.range_end(second.row)
+            } else {
+                self.row(second.row)
+            };
+            out.push(':');
+            out.push_str(&second.with_row(last));
+        }
+        Some((end, out))
+    }
+}
+
+/// "$B$5" в начале строки → (ссылка, длина)
+fn cell_ref(text: &str) -> Option<(CellRef<'_>, usize)> {
+    let bytes = text.as_bytes();
+    let mut pos = 0;
+    let col_abs = bytes.first() == Some(&b'$');
+    pos += usize::from(col_abs);
+    let col_start = pos;
+    while pos < bytes.len() && bytes[pos].is_ascii_alphabetic() && pos - col_start < 3 {
+        pos += 1;
+    }
+    let col = &text[col_start..pos];
+    let col_number = col.bytes().fold(0u32, |acc, b| {
+        acc * 26 + u32::from(b.to_ascii_uppercase() - b'A' + 1)
+    });
+    if col.is_empty() || col_number > MAX_COLUMNS {
+        return None;
+    }
+    let row_abs = bytes.get(pos) == Some(&b'$');
+    pos += usize::from(row_abs);
+    let row_start = pos;
+    while pos < bytes.len() && bytes[pos].is_ascii_digit() && pos - row_start < 7 {
+        pos += 1;
+    }
+    let row: u32 = text[row_start..pos].parse().ok()?;
+    if row == 0 || row > MAX_ROW + 1 {
+        return None;
+    }
+    Some((
+        CellRef {
+            col,
+            col_abs,
+            row: row - 1,
+            row_abs,
+        },
+        pos,
+    ))
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.'
+}
+
+fn name_end(text: &str) -> usize {
+    text.char_indices()
+        .find(|(_, c)| !is_name_char(*c))
+        .map(|(idx, _)| idx)
+        .unwrap_or(text.len())
+}
+
+/// Конец литерала в кавычках `quote` (удвоенная кавычка — экранирование)
+fn literal_end(text: &str, quote: char) -> usize {
+    let mut chars = text.char_indices().skip(1).peekable();
+    while let Some((idx, c)) = chars.next() {
+        if c == quote {
+            if chars.peek().is_some_and(|(_, next)| *next == quote) {
+                chars.next();
+                continue;
+            }
+            return idx + c.len_utf8();
+        }
+    }
+    text.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Заголовок в строке 5, данные с 6-й, записано 10 строк данных
+    fn shift() -> RowShift {
+        RowShift {
+            sheet: "Отчёт".to_string(),
+            data_start: 5,
+            region_end: 5,
+            last_row: 14,
+            by: 9,
+        }
+    }
+
+    #[test]
+    fn formulas_follow_inserted_rows() {
+        let shift = shift();
+        assert_eq!(shift.formula("SUM(B6:B6)", true), "SUM(B6:B15)");
+        assert_eq!(
+            shift.formula("SUM($B$6:$B$6)*C20", true),
+            "SUM($B$6:$B$15)*C29"
+        );
+        assert_eq!(shift.formula("A1+B5", true), "A1+B5");
+        assert_eq!(shift.formula("B7/B8", true), "B16/B17");
+        assert_eq!(shift.formula("LOG10(B7)", true), "LOG10(B16)");
+        assert_eq!(shift.formula("\"B7\"&B7", true), "\"B7\"&B16");
+    }
+
+    #[test]
+    fn sheet_qualified_references() {
+        let shift = shift();
+        assert_eq!(shift.formula("Отчёт!B7", false), "Отчёт!B16");
+        assert_eq!(
+            shift.formula("'Отчёт'!$B$6:$B$6", false),
+            "'Отчёт'!$B$6:$B$15"
+        );
+        assert_eq!(shift.formula("Другой!B7+B7", false), "Другой!B7+B7");
+        assert_eq!(shift.formula("'It''s'!B7", true), "'It''s'!B7");
+    }
+
+    #[test]
+    fn sheet_markup() {
+        let shift = shift();
+        assert_eq!(
+            shift.sheet_xml(
+                r#"<c r="B7" s="2"><f>SUM(B6:B6)&gt;0</f><v>1</v></c>"#,
+                true
+            ),
+            r#"<c r="B16" s="2"><f>SUM(B6:B15)&gt;0</f><v>1</v></c>"#
+        );
+        assert_eq!(
+            shift.sheet_xml(
+                r#"<mergeCells><mergeCell ref="A6:C6"/><mergeCell ref="A8:C9"/></mergeCells>"#,
+                true
+            ),
+            r#"<mergeCells><mergeCell ref="A6:C6"/><mergeCell ref="A17:C18"/></mergeCells>"#
+        );
+        assert_eq!(
+            shift.sheet_xml(r#"<conditionalFormatting sqref="B6:B6 D7">"#, true),
+            r#"<conditionalFormatting sqref="B6:B15 D16">"#
+        );
+        assert_eq!(
+            shift.sheet_xml(r#"<c r="B7"><f>B7</f></c>"#, false),
+            r#"<c r="B7"><f>B7</f></c>"#
+        );
+    }
+}
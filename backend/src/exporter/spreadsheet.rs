@@ -0,0 +1,420 @@
+use crate::converter::utils::{open_zip, read_zip_file};
+use crate::exporter::conditional::{self, resolve_rules, ConditionalFormat, ResolvedRule};
+use crate::exporter::formula::{
+    compile_formulas, referenced_columns, render, Dialect, FormulaColumn,
+};
+use crate::exporter::numeric_cell_value;
+use crate::exporter::package::rewrite_zip;
+use crate::exporter::template::column_name;
+use crate::expr::{Compiled, Value};
+use anyhow::{bail, Result};
+use chrono::NaiveDate;
+use icu_locid::locale;
+use rust_xlsxwriter::{Format, FormatBorder, Formula, Workbook};
+use serde::Deserialize;
+use spreadsheet_ods::style::CellStyle;
+use spreadsheet_ods::{write_ods_buf, Sheet, WorkBook};
+use std::collections::BTreeMap;
+use std::io::Cursor;
+
+const ODS_SHEET_NAME: &str = "Sheet1";
+const DEFAULT_TOTALS_LABEL: &str = "Итого";
+
+/// Настройки XLSX/ODS, приходят в поле `spreadsheet` запроса экспорта
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SpreadsheetOptions {
+    pub conditional_formats: Vec<ConditionalFormat>,
+    pub formula_columns: Vec<FormulaColumn>,
+    pub totals: Option<TotalsRow>,
+}
+
+/// Строка итогов под данными: функция для каждой нужной колонки
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TotalsRow {
+    pub label: Option<String>,
+    pub columns: BTreeMap<String, TotalFunction>,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TotalFunction {
+    Sum,
+    Average,
+    Count,
+}
+
+impl TotalFunction {
+    fn name(self) -> &'static str {
+        match self {
+            TotalFunction::Sum => "SUM",
+            TotalFunction::Average => "AVERAGE",
+            TotalFunction::Count => "COUNT",
+        }
+    }
+}
+
+impl SpreadsheetOptions {
+    /// Проверка настроек; строки нужны, чтобы вывести типы колонок для формул
+    pub fn validate(&self, columns: &[String], rows: &[Vec<String>]) -> Result<()> {
+        SheetPlan::new(columns, rows, self).map(|_| ())
+    }
+}
+
+/// Раскладка листа: колонки данных, вычисляемые колонки справа от них,
+/// строка итогов и правила условного форматирования
+struct SheetPlan<'a> {
+    headers: Vec<String>,
+    data_width: usize,
+    numeric: Vec<bool>,
+    formulas: Vec<Compiled>,
+    totals: Vec<(usize, TotalFunction)>,
+    totals_label: String,
+    rules: Vec<ResolvedRule<'a>>,
+}
+
+/// Накопитель для итоговой ячейки с семантикой SUM/AVERAGE/COUNT:
+/// учитываются только числа, текст и пустые значения пропускаются
+#[derive(Default)]
+struct TotalAccumulator {
+    sum: f64,
+    count: usize,
+}
+
+impl TotalAccumulator {
+    fn push(&mut self, value: Option<f64>) {
+        if let Some(number) = value {
+            self.sum += number;
+            self.count += 1;
+        }
+    }
+
+    fn result(&self, function: TotalFunction) -> Value {
+        match function {
+            TotalFunction::Count => Value::Number(self.count as f64),
+            TotalFunction::Sum => Value::Number(self.sum),
+            // так пересчитанный файл покажет AVERAGE без чисел
+            TotalFunction::Average if self.count == 0 => Value::Text("#DIV/0!".to_string()),
+            TotalFunction::Average => Value::Number(self.sum / self.count as f64),
+        }
+    }
+}
+
+/// Значение ячейки строки после расчёта
+enum PlannedCell<'v> {
+    Empty,
+    Text(&'v str),
+    Number(f64),
+    Formula(usize, Value),
+}
+
+impl<'a> SheetPlan<'a> {
+    fn new(
+        columns: &[String],
+        rows: &[Vec<String>],
+        options: &'a SpreadsheetOptions,
+    ) -> Result<Self> {
+        let data_width = columns.len();
+        let formulas = compile_formulas(columns, rows, &options.formula_columns)?;
+        let mut headers = columns.to_vec();
+        headers.extend(
+            options
+                .formula_columns
+                .iter()
+                .map(|column| column.name.trim().to_string()),
+        );
+
+        let mut numeric = vec![false; data_width];
+        let mut mark_numeric = |col: usize| {
+            if let Some(flag) = numeric.get_mut(col) {
+                *flag = true;
+            }
+        };
+
+        let mut referenced = Vec::new();
+        formulas
+            .iter()
+            .for_each(|formula| referenced_columns(formula.expr(), &mut referenced));
+        referenced.into_iter().for_each(&mut mark_numeric);
+
+        let mut totals = Vec::new();
+        let mut totals_label = DEFAULT_TOTALS_LABEL.to_string();
+        if let Some(row) = &options.totals {
+            for (name, function) in &row.columns {
+                let Some(col) = headers.iter().position(|h| h == name) else {
+                    bail!("Колонка итогов «{}» не найдена", name);
+                };
+                if *function != TotalFunction::Count {
+                    mark_numeric(col);
+                }
+                totals.push((col, *function));
+            }
+            if let Some(label) = row.label.as_deref().map(str::trim) {
+                totals_label = label.to_string();
+            }
+        }
+
+        let rules = resolve_rules(&options.conditional_formats, &headers)?;
+        rules.iter().for_each(|rule| mark_numeric(rule.col));
+
+        Ok(Self {
+            headers,
+            data_width,
+            numeric,
+            formulas,
+            totals,
+            totals_label,
+            rules,
+        })
+    }
+
+    fn has_totals(&self) -> bool {
+        !self.totals.is_empty()
+    }
+
+    /// Ячейки строки: данные как есть, числа в «числовых» колонках, затем формулы
+    fn row_cells<'v>(&self, row: &'v [String]) -> Vec<PlannedCell<'v>> {
+        let mut cells = Vec::with_capacity(self.headers.len());
+        // формулы считаются по тексту ячеек, как вычисляемые колонки набора
+        let mut values: Vec<String> = Vec::with_capacity(self.headers.len());
+
+        // лишние значения в строке не должны налезать на вычисляемые колонки
+        let width = if self.formulas.is_empty() {
+            self.data_width.max(row.len())
+        } else {
+            self.data_width
+        };
+        for col in 0..width {
+            let value = row.get(col).map(String::as_str).unwrap_or("");
+            let number = numeric_cell_value(value).and_then(|n| n.parse::<f64>().ok());
+            values.push(value.to_string());
+            let is_numeric = self.numeric.get(col).copied().unwrap_or(false);
+            cells.push(match number {
+                Some(number) if is_numeric => PlannedCell::Number(number),
+                _ if value.is_empty() => PlannedCell::Empty,
+                _ => PlannedCell::Text(value),
+            });
+        }
+
+        for (idx, formula) in self.formulas.iter().enumerate() {
+            let result = formula.eval(&values);
+            values.push(result.to_cell());
+            cells.push(PlannedCell::Formula(idx, result));
+        }
+        cells
+    }
+
+    fn formula_col(&self, idx: usize) -> usize {
+        self.data_width + idx
+    }
+
+    fn total_formula(&self, col: usize, function: TotalFunction, rows: usize) -> String {
+        let name = column_name(col as u32);
+        format!("{}({}2:{}{})", function.name(), name, name, rows + 1)
+    }
+
+    fn total_formula_ods(&self, col: usize, function: TotalFunction, rows: usize) -> String {
+        let name = column_name(col as u32);
+        format!(
+            "of:={}([.{}2:.{}{}])",
+            function.name(),
+            name,
+            name,
+            rows + 1
+        )
+    }
+
+    /// Накопленные итоги по уже посчитанным строкам
+    fn accumulate(&self, accumulators: &mut [TotalAccumulator], cells: &[PlannedCell]) {
+        for ((col, _), acc) in self.totals.iter().zip(accumulators.iter_mut()) {
+            acc.push(match cells.get(*col) {
+                Some(PlannedCell::Number(number)) => Some(*number),
+                Some(PlannedCell::Formula(_, Value::Number(number))) => Some(*number),
+                _ => None,
+            });
+        }
+    }
+}
+
+/// Сохранённый результат формулы XLSX: даты — серийным номером Excel
+fn cached_result(result: &Value) -> String {
+    match result {
+        Value::Number(number) => number.to_string(),
+        Value::Bool(true) => "TRUE".to_string(),
+        Value::Bool(false) => "FALSE".to_string(),
+        Value::Date(date) => excel_serial(date).to_string(),
+        Value::Null | Value::Text(_) => result.to_cell(),
+    }
+}
+
+/// Дата как число дней от 30.12.1899 (с долей суток)
+fn excel_serial(date: &chrono::NaiveDateTime) -> f64 {
+    let epoch = NaiveDate::from_ymd_opt(1899, 12, 30)
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .unwrap_or_default();
+    (*date - epoch).num_seconds() as f64 / 86_400.0
+}
+
+pub fn build_xlsx(
+    columns: &[String],
+    rows: &[Vec<String>],
+    options: &SpreadsheetOptions,
+) -> Result<Vec<u8>> {
+    let plan = SheetPlan::new(columns, rows, options)?;
+
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet();
+
+    for (col_idx, header) in plan.headers.iter().enumerate() {
+        worksheet.write_string(0, col_idx as u16, header)?;
+    }
+
+    let mut accumulators: Vec<TotalAccumulator> =
+        plan.totals.iter().map(|_| Default::default()).collect();
+
+    for (row_idx, row) in rows.iter().enumerate() {
+        let sheet_row = (row_idx + 1) as u32;
+        let cells = plan.row_cells(row);
+        for (col_idx, cell) in cells.iter().enumerate() {
+            let col = col_idx as u16;
+            match cell {
+                PlannedCell::Empty => {}
+                PlannedCell::Text(value) => {
+                    worksheet.write_string(sheet_row, col, *value)?;
+                }
+                PlannedCell::Number(number) => {
+                    worksheet.write_number(sheet_row, col, *number)?;
+                }
+                PlannedCell::Formula(idx, result) => {
+                    let text = render(plan.formulas[*idx].expr(), sheet_row, Dialect::Excel);
+                    let formula =
+                        Formula::new(format!("={}", text)).set_result(cached_result(result));
+                    worksheet.write_formula(sheet_row, plan.formula_col(*idx) as u16, formula)?;
+                }
+            }
+        }
+        plan.accumulate(&mut accumulators, &cells);
+    }
+
+    if plan.has_totals() {
+        let totals_row = (rows.len() + 1) as u32;
+        let bold = Format::new().set_bold().set_border_top(FormatBorder::Thin);
+        if !plan.totals.iter().any(|(col, _)| *col == 0) {
+            worksheet.write_string_with_format(totals_row, 0, &plan.totals_label, &bold)?;
+        }
+        for ((col, function), acc) in plan.totals.iter().zip(&accumulators) {
+            let formula = Formula::new(format!(
+                "={}",
+                plan.total_formula(*col, *function, rows.len())
+            ))
+            .set_result(cached_result(&acc.result(*function)));
+            worksheet.write_formula_with_format(totals_row, *col as u16, formula, &bold)?;
+        }
+    }
+
+    conditional::apply_xlsx(worksheet, &plan.rules, 1, rows.len() as u32)?;
+
+    let buffer = workbook.save_to_buffer()?;
+    Ok(buffer)
+}
+
+pub fn build_ods(
+    columns: &[String],
+    rows: &[Vec<String>],
+    options: &SpreadsheetOptions,
+) -> Result<Vec<u8>> {
+    let plan = SheetPlan::new(columns, rows, options)?;
+
+    let mut workbook = WorkBook::new(locale!("en-US"));
+    let mut sheet = Sheet::new(ODS_SHEET_NAME);
+
+    for (col_idx, header) in plan.headers.iter().enumerate() {
+        sheet.set_value(0, col_idx as u32, header.as_str());
+    }
+
+    let mut accumulators: Vec<TotalAccumulator> =
+        plan.totals.iter().map(|_| Default::default()).collect();
+
+    for (row_idx, row) in rows.iter().enumerate() {
+        let sheet_row = (row_idx + 1) as u32;
+        let cells = plan.row_cells(row);
+        for (col_idx, cell) in cells.iter().enumerate() {
+            let col = col_idx as u32;
+            match cell {
+                PlannedCell::Empty => {}
+                PlannedCell::Text(value) => sheet.set_value(sheet_row, col, *value),
+                PlannedCell::Number(number) => sheet.set_value(sheet_row, col, *number),
+                PlannedCell::Formula(idx, result) => {
+                    let text = render(plan.formulas[*idx].expr(), sheet_row, Dialect::OpenFormula);
+                    set_ods_formula(&mut sheet, sheet_row, col, format!("of:={}", text), result);
+                }
+            }
+        }
+        plan.accumulate(&mut accumulators, &cells);
+    }
+
+    if plan.has_totals() {
+        let totals_row = (rows.len() + 1) as u32;
+        let mut bold = CellStyle::new_empty();
+        bold.set_font_bold();
+        let bold = workbook.add_cellstyle(bold);
+        if !plan.totals.iter().any(|(col, _)| *col == 0) {
+            sheet.set_styled_value(totals_row, 0, plan.totals_label.as_str(), &bold);
+        }
+        for ((col, function), acc) in plan.totals.iter().zip(&accumulators) {
+            let formula = plan.total_formula_ods(*col, *function, rows.len());
+            set_ods_formula(
+                &mut sheet,
+                totals_row,
+                *col as u32,
+                formula,
+                &acc.result(*function),
+            );
+            sheet.set_cellstyle(totals_row, *col as u32, &bold);
+        }
+    }
+
+    let style_names = conditional::add_ods_styles(&mut workbook, &plan.rules)?;
+    workbook.push_sheet(sheet);
+    let buffer = write_ods_buf(&mut workbook, Vec::new())?;
+
+    let formats = conditional::ods_conditional_formats(
+        ODS_SHEET_NAME,
+        &plan.rules,
+        &style_names,
+        1,
+        rows.len() as u32,
+    )?;
+    if formats.is_empty() {
+        return Ok(buffer);
+    }
+    insert_into_ods_table(buffer, &formats)
+}
+
+/// Формула ODS с сохранённым результатом, чтобы файл читался без пересчёта
+fn set_ods_formula(sheet: &mut Sheet, row: u32, col: u32, formula: String, result: &Value) {
+    match result {
+        Value::Number(number) => sheet.set_value(row, col, *number),
+        Value::Bool(flag) => sheet.set_value(row, col, *flag),
+        Value::Date(date) => sheet.set_value(row, col, *date),
+        Value::Null | Value::Text(_) => sheet.set_value(row, col, result.to_cell()),
+    }
+    sheet.set_formula(row, col, formula);
+}
+
+/// Вставка разметки в конец единственного листа ODS (spreadsheet-ods не умеет
+/// писать calcext:conditional-formats сам)
+fn insert_into_ods_table(buffer: Vec<u8>, markup: &str) -> Result<Vec<u8>> {
+    let mut zip = open_zip(Cursor::new(buffer))?;
+    let content = read_zip_file(&mut zip, "content.xml")?
+        .ok_or_else(|| anyhow::anyhow!("В ODS нет content.xml"))?;
+    let Some(pos) = content.find("</table:table>") else {
+        bail!("В content.xml нет листа");
+    };
+    let patched = format!("{}{}{}", &content[..pos], markup, &content[pos..]);
+
+    let mut replaced = BTreeMap::new();
+    replaced.insert("content.xml".to_string(), patched);
+    rewrite_zip(&mut zip, &replaced, &[])
+}
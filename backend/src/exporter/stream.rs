@@ -0,0 +1,298 @@
+use crate::exporter::delimited::{csv_writer, encode_text, CsvOptions, TextEncoding, Unmappable};
+use anyhow::{anyhow, bail, Result};
+use axum::body::Bytes;
+use rust_xlsxwriter::Workbook;
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use tokio::sync::{mpsc::Sender, oneshot};
+
+/// Размер порции, после которой накопленный текст уходит клиенту
+const CHUNK_SIZE: usize = 64 * 1024;
+const XLSX_MAX_ROWS: u32 = 1_048_576;
+
+pub type ChunkSender = Sender<io::Result<Bytes>>;
+
+/// Получатель строк при потоковом разборе тела запроса
+pub trait RowSink {
+    fn columns(&mut self, columns: &[String]) -> Result<()>;
+    fn row(&mut self, row: &[String]) -> Result<()>;
+}
+
+/// Потоковый разбор тела `{ "columns": [...], "rows": [[...], ...] }`.
+/// Строки по одной передаются в `sink`, массив `rows` целиком в памяти не собирается.
+/// `columns` должны идти в теле раньше `rows`.
+pub fn read_table_stream<R: Read, S: RowSink>(reader: R, sink: &mut S) -> Result<usize> {
+    let mut deserializer = serde_json::Deserializer::from_reader(BufReader::new(reader));
+    let count = TableSeed { sink }.deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(count)
+}
+
+struct TableSeed<'a, S> {
+    sink: &'a mut S,
+}
+
+impl<'de, S: RowSink> DeserializeSeed<'de> for TableSeed<'_, S> {
+    type Value = usize;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<usize, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, S: RowSink> Visitor<'de> for TableSeed<'_, S> {
+    type Value = usize;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("объект с полями columns и rows")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<usize, A::Error> {
+        let mut has_columns = false;
+        let mut count = 0;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "columns" => {
+                    let columns: Vec<String> = map.next_value()?;
+                    self.sink.columns(&columns).map_err(de::Error::custom)?;
+                    has_columns = true;
+                }
+                "rows" => {
+                    if !has_columns {
+                        return Err(de::Error::custom("поле columns должно идти раньше rows"));
+                    }
+                    count += map.next_value_seed(RowsSeed {
+                        sink: &mut *self.sink,
+                    })?;
+                }
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        if !has_columns {
+            return Err(de::Error::missing_field("columns"));
+        }
+        Ok(count)
+    }
+}
+
+struct RowsSeed<'a, S> {
+    sink: &'a mut S,
+}
+
+impl<'de, S: RowSink> DeserializeSeed<'de> for RowsSeed<'_, S> {
+    type Value = usize;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<usize, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, S: RowSink> Visitor<'de> for RowsSeed<'_, S> {
+    type Value = usize;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("массив строк")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<usize, A::Error> {
+        let mut count = 0;
+        while let Some(row) = seq.next_element::<Vec<String>>()? {
+            self.sink.row(&row).map_err(de::Error::custom)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+/// Сигнал о том, что заголовок и первая строка тела разобраны и приняты
+/// `sink`: до него ошибку ещё можно вернуть статусом ответа, после — только
+/// оборвать тело
+pub struct FirstRow<'a, S> {
+    sink: &'a mut S,
+    ready: &'a mut Option<oneshot::Sender<Result<()>>>,
+}
+
+impl<'a, S: RowSink> FirstRow<'a, S> {
+    pub fn new(sink: &'a mut S, ready: &'a mut Option<oneshot::Sender<Result<()>>>) -> Self {
+        Self { sink, ready }
+    }
+}
+
+impl<S: RowSink> RowSink for FirstRow<'_, S> {
+    fn columns(&mut self, columns: &[String]) -> Result<()> {
+        self.sink.columns(columns)
+    }
+
+    fn row(&mut self, row: &[String]) -> Result<()> {
+        self.sink.row(row)?;
+        if let Some(ready) = self.ready.take() {
+            let _ = ready.send(Ok(()));
+        }
+        Ok(())
+    }
+}
+
+/// CSV/TSV порциями в канал ответа
+pub struct DelimitedSink {
+    writer: csv::Writer<Vec<u8>>,
+    delimiter: u8,
+    options: CsvOptions,
+    encoding: TextEncoding,
+    unmappable: Unmappable,
+    columns: Vec<String>,
+    rows: usize,
+    tx: ChunkSender,
+}
+
+impl DelimitedSink {
+    pub fn new(default_delimiter: u8, options: &CsvOptions, tx: ChunkSender) -> Result<Self> {
+        options.validate()?;
+        Ok(Self {
+            writer: csv_writer(default_delimiter, options),
+            delimiter: default_delimiter,
+            options: options.clone(),
+            encoding: options.encoding,
+            unmappable: Unmappable::new(options.encoding),
+            columns: Vec::new(),
+            rows: 0,
+            tx,
+        })
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let fresh = csv_writer(self.delimiter, &self.options);
+        let writer = std::mem::replace(&mut self.writer, fresh);
+        let utf8 = writer.into_inner().map_err(|err| err.into_error())?;
+        if utf8.is_empty() {
+            return Ok(());
+        }
+        send_chunk(&self.tx, encode_text(utf8, self.encoding))?;
+        // BOM пишется только в начало файла
+        if let TextEncoding::Utf8Bom = self.encoding {
+            self.encoding = TextEncoding::Utf8;
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        self.unmappable.report(&self.columns);
+        self.flush()
+    }
+}
+
+impl RowSink for DelimitedSink {
+    fn columns(&mut self, columns: &[String]) -> Result<()> {
+        self.writer
+            .write_record(self.unmappable.record(None, columns))?;
+        self.columns = columns.to_vec();
+        Ok(())
+    }
+
+    fn row(&mut self, row: &[String]) -> Result<()> {
+        self.writer
+            .write_record(self.unmappable.record(Some(self.rows), row))?;
+        self.rows += 1;
+        if self.writer.get_ref().len() >= CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// NDJSON порциями в канал ответа
+pub struct NdjsonSink {
+    columns: Vec<String>,
+    buffer: Vec<u8>,
+    tx: ChunkSender,
+}
+
+impl NdjsonSink {
+    pub fn new(tx: ChunkSender) -> Self {
+        Self {
+            columns: Vec::new(),
+            buffer: Vec::with_capacity(CHUNK_SIZE),
+            tx,
+        }
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        if !self.buffer.is_empty() {
+            send_chunk(&self.tx, std::mem::take(&mut self.buffer))?;
+        }
+        Ok(())
+    }
+}
+
+impl RowSink for NdjsonSink {
+    fn columns(&mut self, columns: &[String]) -> Result<()> {
+        self.columns = columns.to_vec();
+        Ok(())
+    }
+
+    fn row(&mut self, row: &[String]) -> Result<()> {
+        super::json::write_row_object(&mut self.buffer, &self.columns, row)?;
+        self.buffer.push(b'\n');
+        if self.buffer.len() >= CHUNK_SIZE {
+            send_chunk(&self.tx, std::mem::take(&mut self.buffer))?;
+        }
+        Ok(())
+    }
+}
+
+/// XLSX в режиме constant memory: строки сразу сбрасываются во временный файл
+pub struct XlsxSink {
+    workbook: Workbook,
+    next_row: u32,
+}
+
+impl XlsxSink {
+    pub fn new() -> Self {
+        let mut workbook = Workbook::new();
+        workbook.add_worksheet_with_constant_memory();
+        Self {
+            workbook,
+            next_row: 0,
+        }
+    }
+
+    fn write_row(&mut self, values: &[String]) -> Result<()> {
+        if self.next_row >= XLSX_MAX_ROWS {
+            bail!("Превышен лимит строк XLSX ({})", XLSX_MAX_ROWS);
+        }
+        let worksheet = self.workbook.worksheet_from_index(0)?;
+        for (col_idx, value) in values.iter().enumerate() {
+            worksheet.write_string(self.next_row, col_idx as u16, value)?;
+        }
+        self.next_row += 1;
+        Ok(())
+    }
+
+    /// Сохранение книги во временный файл, готовый к чтению с начала
+    pub fn finish(mut self) -> Result<File> {
+        let mut file = tempfile::tempfile()?;
+        self.workbook.save_to_writer(&mut file)?;
+        file.seek(SeekFrom::Start(0))?;
+        Ok(file)
+    }
+}
+
+impl RowSink for XlsxSink {
+    fn columns(&mut self, columns: &[String]) -> Result<()> {
+        self.write_row(columns)
+    }
+
+    fn row(&mut self, row: &[String]) -> Result<()> {
+        self.write_row(row)
+    }
+}
+
+fn send_chunk(tx: &ChunkSender, chunk: Vec<u8>) -> Result<()> {
+    tx.blocking_send(Ok(Bytes::from(chunk)))
+        .map_err(|_| anyhow!("Клиент закрыл соединение"))
+}
@@ -0,0 +1,898 @@
+use crate::converter::utils::{open_zip, read_zip_file};
+use crate::converter::xlsx::read_shared_strings;
+use crate::exporter::numeric_cell_value;
+use crate::exporter::package::rewrite_zip;
+use crate::exporter::references::{RowShift, MAX_ROW};
+use anyhow::{Context, Result};
+use quick_xml::escape::{escape, unescape};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader as XmlReader;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::io::{Cursor, Read, Seek};
+use zip::ZipArchive;
+
+const DEFAULT_MARKER: &str = "{{data}}";
+const CALC_CHAIN: &str = "xl/calcChain.xml";
+/// Число колонок листа Excel: последняя — XFD
+const MAX_COLUMNS: u32 = 16_384;
+
+/// Ссылка на шаблон в запросе экспорта
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateOptions {
+    pub name: String,
+    #[serde(default)]
+    pub named_range: Option<String>,
+    #[serde(default)]
+    pub marker: Option<String>,
+    #[serde(default = "default_include_header")]
+    pub include_header: bool,
+}
+
+fn default_include_header() -> bool {
+    true
+}
+
+/// Ошибка в содержимом шаблона или его настройке — отдаётся клиенту как 400
+#[derive(Debug)]
+pub struct TemplateError(pub String);
+
+impl std::fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+fn template_error(message: impl Into<String>) -> anyhow::Error {
+    TemplateError(message.into()).into()
+}
+
+struct SheetInfo {
+    name: String,
+    path: String,
+}
+
+struct DefinedName {
+    name: String,
+    value: String,
+}
+
+/// Место в книге, куда пишутся данные
+struct Target {
+    sheet: usize,
+    row: u32,
+    col: u32,
+    defined_name: Option<usize>,
+}
+
+/// Проверка, что загруженный файл похож на XLSX-книгу
+pub fn validate_xlsx_template(bytes: &[u8]) -> Result<()> {
+    let mut zip =
+        open_zip(Cursor::new(bytes)).map_err(|_| template_error("Файл не является XLSX"))?;
+    let workbook = read_zip_file(&mut zip, "xl/workbook.xml")?
+        .ok_or_else(|| template_error("В шаблоне нет xl/workbook.xml"))?;
+    let rels = read_zip_file(&mut zip, "xl/_rels/workbook.xml.rels")?
+        .ok_or_else(|| template_error("В шаблоне нет xl/_rels/workbook.xml.rels"))?;
+    if read_sheets(&workbook, &rels)?.is_empty() {
+        return Err(template_error("В шаблоне нет листов"));
+    }
+    Ok(())
+}
+
+/// Заполнение XLSX-шаблона: данные пишутся в именованный диапазон или с ячейки-метки,
+/// стили, формулы и остальные листы шаблона сохраняются
+pub fn fill_xlsx_template(
+    template: &[u8],
+    columns: &[String],
+    rows: &[Vec<String>],
+    options: &TemplateOptions,
+) -> Result<Vec<u8>> {
+    let mut zip =
+        open_zip(Cursor::new(template)).map_err(|_| template_error("Шаблон повреждён"))?;
+    let workbook_xml = read_zip_file(&mut zip, "xl/workbook.xml")?
+        .ok_or_else(|| template_error("В шаблоне нет xl/workbook.xml"))?;
+    let rels_xml = read_zip_file(&mut zip, "xl/_rels/workbook.xml.rels")?
+        .ok_or_else(|| template_error("В шаблоне нет xl/_rels/workbook.xml.rels"))?;
+
+    let sheets = read_sheets(&workbook_xml, &rels_xml)?;
+    let defined_names = read_defined_names(&workbook_xml)?;
+    let target = locate_target(&mut zip, &sheets, &defined_names, options)?;
+    let sheet = &sheets[target.sheet];
+
+    let mut grid: Vec<&[String]> = Vec::with_capacity(rows.len() + 1);
+    if options.include_header {
+        grid.push(columns);
+    }
+    grid.extend(rows.iter().map(Vec::as_slice));
+    let width = grid.iter().map(|row| row.len()).max().unwrap_or(0) as u32;
+
+    let sheet_xml = read_entry(&mut zip, &sheet.path)?;
+    let data_row = target.row + u32::from(options.include_header);
+    let (filled_sheet, shift) = fill_sheet(
+        &sheet_xml,
+        &sheet.name,
+        target.row,
+        target.col,
+        data_row,
+        &grid,
+    )?;
+
+    // Именованные диапазоны: целевой растягивается на данные, остальные сдвигаются
+    let target_range = target.defined_name.map(|idx| {
+        let last_col = target.col + width.max(1) - 1;
+        let sheet_ref = defined_names[idx]
+            .value
+            .rsplit_once('!')
+            .map(|(sheet_ref, _)| sheet_ref.to_string())
+            .unwrap_or_else(|| quote_sheet_name(&sheet.name));
+        format!(
+            "{}!${}${}:${}${}",
+            sheet_ref,
+            column_name(target.col),
+            target.row + 1,
+            column_name(last_col),
+            shift.last_row + 1
+        )
+    });
+    let workbook_out = rewrite_defined_names(&workbook_xml, |idx, value| {
+        match (target.defined_name, &target_range) {
+            (Some(target_idx), Some(range)) if target_idx == idx => range.clone(),
+            _ => shift.formula(value, false),
+        }
+    });
+    let workbook_out = ensure_full_calc_on_load(&workbook_out);
+
+    let mut replaced: BTreeMap<String, String> = BTreeMap::new();
+    replaced.insert(sheet.path.clone(), filled_sheet);
+    // Формулы других листов и ряды диаграмм, ссылающиеся на лист с данными
+    let mut dependents: Vec<String> = sheets
+        .iter()
+        .filter(|other| other.path != sheet.path)
+        .map(|other| other.path.clone())
+        .collect();
+    dependents.extend(
+        zip.file_names()
+            .filter(|name| name.starts_with("xl/charts/chart") && name.ends_with(".xml"))
+            .map(str::to_string),
+    );
+    for path in dependents {
+        let xml = read_entry(&mut zip, &path)?;
+        let shifted = shift.sheet_xml(&xml, false);
+        if shifted != xml {
+            replaced.insert(path, shifted);
+        }
+    }
+    replaced.insert("xl/workbook.xml".to_string(), workbook_out);
+    // calcChain ссылается на старые формулы — Excel перестроит его сам
+    replaced.insert(
+        "xl/_rels/workbook.xml.rels".to_string(),
+        remove_element_containing(&rels_xml, "<Relationship", "calcChain.xml"),
+    );
+    if let Some(content_types) = read_zip_file(&mut zip, "[Content_Types].xml")? {
+        replaced.insert(
+            "[Content_Types].xml".to_string(),
+            remove_element_containing(&content_types, "<Override", "/xl/calcChain.xml"),
+        );
+    }
+
+    rewrite_zip(&mut zip, &replaced, &[CALC_CHAIN])
+}
+
+fn locate_target<R: Read + Seek>(
+    zip: &mut ZipArchive<R>,
+    sheets: &[SheetInfo],
+    defined_names: &[DefinedName],
+    options: &TemplateOptions,
+) -> Result<Target> {
+    if let Some(range_name) = options.named_range.as_deref().map(str::trim) {
+        let idx = defined_names
+            .iter()
+            .position(|dn| dn.name.eq_ignore_ascii_case(range_name))
+            .ok_or_else(|| template_error(format!("Диапазон «{}» не найден", range_name)))?;
+        let (sheet_ref, range) = defined_names[idx].value.rsplit_once('!').ok_or_else(|| {
+            template_error(format!("Диапазон «{}» не указывает на лист", range_name))
+        })?;
+        let sheet_name = unquote_sheet_name(sheet_ref);
+        let sheet = sheets
+            .iter()
+            .position(|s| s.name == sheet_name)
+            .ok_or_else(|| template_error(format!("Лист «{}» не найден", sheet_name)))?;
+        let first_cell = range.split(':').next().unwrap_or(range);
+        let (col, row) = parse_cell_ref(first_cell)
+            .ok_or_else(|| template_error(format!("Некорректный диапазон «{}»", range)))?;
+        return Ok(Target {
+            sheet,
+            row,
+            col,
+            defined_name: Some(idx),
+        });
+    }
+
+    let marker = options
+        .marker
+        .as_deref()
+        .map(str::trim)
+        .filter(|m| !m.is_empty())
+        .unwrap_or(DEFAULT_MARKER);
+    let shared = read_shared_strings(zip)?;
+
+    for (sheet_idx, sheet) in sheets.iter().enumerate() {
+        let xml = read_entry(zip, &sheet.path)?;
+        if let Some((col, row)) = find_marker(&xml, &shared, marker)? {
+            return Ok(Target {
+                sheet: sheet_idx,
+                row,
+                col,
+                defined_name: None,
+            });
+        }
+    }
+
+    Err(template_error(format!(
+        "Метка {} не найдена в шаблоне",
+        marker
+    )))
+}
+
+/// Поиск ячейки, значение которой совпадает с меткой
+fn find_marker(xml: &str, shared: &[String], marker: &str) -> Result<Option<(u32, u32)>> {
+    let mut reader = XmlReader::from_str(xml);
+    let mut cell_ref = String::new();
+    let mut cell_type = String::new();
+    let mut text = String::new();
+    let mut in_cell = false;
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) if e.name().as_ref() == b"c" => {
+                in_cell = true;
+                text.clear();
+                cell_ref = attr(&e, b"r").unwrap_or_default();
+                cell_type = attr(&e, b"t").unwrap_or_default();
+            }
+            Event::Text(t) if in_cell => text.push_str(&t.unescape()?),
+            Event::End(e) if e.name().as_ref() == b"c" => {
+                in_cell = false;
+                let value = match cell_type.as_str() {
+                    "s" => text
+                        .trim()
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|idx| shared.get(idx))
+                        .map(String::as_str)
+                        .unwrap_or(""),
+                    "inlineStr" | "str" => text.as_str(),
+                    _ => "",
+                };
+                if value.trim() == marker {
+                    return Ok(parse_cell_ref(&cell_ref));
+                }
+            }
+            Event::Eof => return Ok(None),
+            _ => {}
+        }
+    }
+}
+
+/// Строка листа: исходный открывающий тег и ячейки по номеру колонки
+struct RowXml {
+    attrs: Vec<(String, String)>,
+    cells: BTreeMap<u32, String>,
+}
+
+/// Заполнение листа: строки шаблона ниже области данных сдвигаются вниз
+/// вместе со ссылками на них, данные пишутся с `start_row`
+fn fill_sheet(
+    xml: &str,
+    sheet_name: &str,
+    start_row: u32,
+    start_col: u32,
+    data_row: u32,
+    grid: &[&[String]],
+) -> Result<(String, RowShift)> {
+    let (before, inner, after) = split_sheet_data(xml)?;
+    let rows = parse_rows(inner)?;
+
+    // Строка под заголовком — образец строки данных, если в ней нет формул;
+    // строка с формулами (например, итоги) остаётся под данными
+    let prototype = rows
+        .get(&data_row)
+        .filter(|row| !row.cells.values().any(|raw| has_formula(raw)));
+    let region_end = if data_row > start_row && prototype.is_none() && rows.contains_key(&data_row)
+    {
+        start_row
+    } else {
+        data_row
+    };
+    let last_row = start_row + (grid.len() as u32).max(1) - 1;
+    let shift = RowShift {
+        sheet: sheet_name.to_string(),
+        data_start: data_row,
+        region_end,
+        last_row,
+        by: last_row.saturating_sub(region_end),
+    };
+    let template_last = rows.keys().next_back().copied().unwrap_or(0);
+    if last_row > MAX_ROW || shift.row(template_last) > MAX_ROW {
+        return Err(template_error(format!(
+            "Данные не помещаются на лист «{}»: строк больше {}",
+            sheet_name,
+            MAX_ROW + 1
+        )));
+    }
+    let width = grid.iter().map(|row| row.len()).max().unwrap_or(0) as u32;
+    if start_col + width > MAX_COLUMNS {
+        return Err(template_error(format!(
+            "Данные не помещаются на лист «{}»: колонок больше {}",
+            sheet_name, MAX_COLUMNS
+        )));
+    }
+
+    // Стили первой строки данных шаблона распространяются на все новые строки
+    let style_row_attrs = prototype.map(|row| row.attrs.clone());
+    let style_row_cells: BTreeMap<u32, String> = prototype
+        .map(|row| {
+            row.cells
+                .iter()
+                .filter_map(|(col, raw)| cell_style(raw).map(|s| (*col, s)))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let mut rows: BTreeMap<u32, RowXml> = rows
+        .into_iter()
+        .map(|(row_idx, row)| {
+            let cells = row
+                .cells
+                .into_iter()
+                .map(|(col, raw)| (col, shift.sheet_xml(&raw, true)))
+                .collect();
+            (
+                shift.row(row_idx),
+                RowXml {
+                    attrs: row.attrs,
+                    cells,
+                },
+            )
+        })
+        .collect();
+
+    for (offset, values) in grid.iter().enumerate() {
+        let row_idx = start_row + offset as u32;
+        let is_data = row_idx >= data_row;
+        let row = rows.entry(row_idx).or_insert_with(|| RowXml {
+            attrs: if is_data {
+                style_row_attrs.clone().unwrap_or_default()
+            } else {
+                Vec::new()
+            },
+            cells: BTreeMap::new(),
+        });
+
+        for (col_offset, value) in values.iter().enumerate() {
+            let col = start_col + col_offset as u32;
+            let style = row
+                .cells
+                .get(&col)
+                .and_then(|raw| cell_style(raw))
+                .or_else(|| {
+                    if is_data {
+                        style_row_cells.get(&col).cloned()
+                    } else {
+                        None
+                    }
+                });
+            let cell_ref = format!("{}{}", column_name(col), row_idx + 1);
+            match build_cell(&cell_ref, style.as_deref(), value) {
+                Some(cell) => {
+                    row.cells.insert(col, cell);
+                }
+                None => {
+                    row.cells.remove(&col);
+                }
+            }
+        }
+    }
+
+    let mut sheet_data = String::with_capacity(inner.len() + grid.len() * 64);
+    sheet_data.push_str("<sheetData>");
+    let mut max_row = 0;
+    let mut max_col = 0;
+    for (row_idx, row) in &rows {
+        sheet_data.push_str("<row r=\"");
+        sheet_data.push_str(&(row_idx + 1).to_string());
+        sheet_data.push('"');
+        for (key, value) in &row.attrs {
+            sheet_data.push_str(&format!(" {}=\"{}\"", key, escape(value)));
+        }
+        if row.cells.is_empty() {
+            sheet_data.push_str("/>");
+            continue;
+        }
+        sheet_data.push('>');
+        for (col, raw) in &row.cells {
+            sheet_data.push_str(raw);
+            max_col = max_col.max(*col);
+        }
+        sheet_data.push_str("</row>");
+        max_row = max_row.max(*row_idx);
+    }
+    sheet_data.push_str("</sheetData>");
+
+    let dimension = format!("A1:{}{}", column_name(max_col), max_row + 1);
+    let before = replace_dimension(before, &dimension);
+    let after = shift.sheet_xml(after, true);
+    Ok((format!("{}{}{}", before, sheet_data, after), shift))
+}
+
+fn has_formula(raw: &str) -> bool {
+    raw.contains("<f>") || raw.contains("<f ") || raw.contains("<f/>")
+}
+
+fn split_sheet_data(xml: &str) -> Result<(&str, &str, &str)> {
+    if let Some(pos) = xml.find("<sheetData/>") {
+        return Ok((&xml[..pos], "", &xml[pos + "<sheetData/>".len()..]));
+    }
+    let open = xml
+        .find("<sheetData")
+        .ok_or_else(|| template_error("В листе нет sheetData"))?;
+    let open_end = xml[open..]
+        .find('>')
+        .map(|idx| open + idx + 1)
+        .ok_or_else(|| template_error("Повреждён sheetData"))?;
+    let close = xml
+        .rfind("</sheetData>")
+        .ok_or_else(|| template_error("Повреждён sheetData"))?;
+    Ok((
+        &xml[..open],
+        &xml[open_end..close],
+        &xml[close + "</sheetData>".len()..],
+    ))
+}
+
+/// Разбор строк sheetData с сохранением исходной разметки ячеек
+fn parse_rows(inner: &str) -> Result<BTreeMap<u32, RowXml>> {
+    let mut reader = XmlReader::from_str(inner);
+    let mut rows = BTreeMap::new();
+    let mut current: Option<(u32, RowXml)> = None;
+    let mut next_row = 0u32;
+    let mut next_col = 0u32;
+    let mut cell_start: Option<(u32, usize)> = None;
+
+    loop {
+        let before = reader.buffer_position() as usize;
+        let event = reader.read_event()?;
+        let after = reader.buffer_position() as usize;
+        match event {
+            Event::Start(e) | Event::Empty(e) if e.name().as_ref() == b"row" => {
+                let is_empty = inner[before..after].ends_with("/>");
+                let row_idx = attr(&e, b"r")
+                    .and_then(|r| r.parse::<u32>().ok())
+                    .map(|r| r.saturating_sub(1))
+                    .unwrap_or(next_row);
+                next_row = row_idx + 1;
+                next_col = 0;
+                let row = RowXml {
+                    attrs: row_attrs(&e),
+                    cells: BTreeMap::new(),
+                };
+                if is_empty {
+                    rows.insert(row_idx, row);
+                } else {
+                    current = Some((row_idx, row));
+                }
+            }
+            Event::End(e) if e.name().as_ref() == b"row" => {
+                if let Some((row_idx, row)) = current.take() {
+                    rows.insert(row_idx, row);
+                }
+            }
+            Event::Start(e) if e.name().as_ref() == b"c" => {
+                let col = attr(&e, b"r")
+                    .and_then(|r| parse_cell_ref(&r))
+                    .map(|(col, _)| col)
+                    .unwrap_or(next_col);
+                next_col = col + 1;
+                cell_start = Some((col, before));
+            }
+            Event::Empty(e) if e.name().as_ref() == b"c" => {
+                let col = attr(&e, b"r")
+                    .and_then(|r| parse_cell_ref(&r))
+                    .map(|(col, _)| col)
+                    .unwrap_or(next_col);
+                next_col = col + 1;
+                if let Some((_, row)) = current.as_mut() {
+                    row.cells.insert(col, inner[before..after].to_string());
+                }
+            }
+            Event::End(e) if e.name().as_ref() == b"c" => {
+                if let (Some((col, start)), Some((_, row))) = (cell_start.take(), current.as_mut())
+                {
+                    row.cells.insert(col, inner[start..after].to_string());
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(rows)
+}
+
+/// Атрибуты строки без `r` (пишется заново) и `spans` (после заполнения устаревает)
+fn row_attrs(e: &BytesStart) -> Vec<(String, String)> {
+    e.attributes()
+        .flatten()
+        .filter(|a| !matches!(a.key.as_ref(), b"r" | b"spans"))
+        .map(|a| {
+            (
+                String::from_utf8_lossy(a.key.as_ref()).to_string(),
+                a.unescape_value()
+                    .map(|v| v.to_string())
+                    .unwrap_or_default(),
+            )
+        })
+        .collect()
+}
+
+fn build_cell(cell_ref: &str, style: Option<&str>, value: &str) -> Option<String> {
+    let style_attr = style.map(|s| format!(" s=\"{}\"", s)).unwrap_or_default();
+    if value.is_empty() {
+        return style.map(|_| format!("<c r=\"{}\"{}/>", cell_ref, style_attr));
+    }
+    if let Some(number) = numeric_cell_value(value) {
+        return Some(format!(
+            "<c r=\"{}\"{}><v>{}</v></c>",
+            cell_ref, style_attr, number
+        ));
+    }
+    Some(format!(
+        "<c r=\"{}\"{} t=\"inlineStr\"><is><t xml:space=\"preserve\">{}</t></is></c>",
+        cell_ref,
+        style_attr,
+        escape(value)
+    ))
+}
+
+fn cell_style(raw: &str) -> Option<String> {
+    let mut reader = XmlReader::from_str(raw);
+    match reader.read_event().ok()? {
+        Event::Start(e) | Event::Empty(e) => attr(&e, b"s"),
+        _ => None,
+    }
+}
+
+fn attr(e: &BytesStart, key: &[u8]) -> Option<String> {
+    e.attributes()
+        .flatten()
+        .find(|a| a.key.as_ref() == key)
+        .and_then(|a| a.unescape_value().ok())
+        .map(|v| v.to_string())
+}
+
+fn read_sheets(workbook_xml: &str, rels_xml: &str) -> Result<Vec<SheetInfo>> {
+    let mut targets = BTreeMap::new();
+    let mut reader = XmlReader::from_str(rels_xml);
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) | Event::Empty(e) if e.name().as_ref() == b"Relationship" => {
+                if let (Some(id), Some(target)) = (attr(&e, b"Id"), attr(&e, b"Target")) {
+                    targets.insert(id, target);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    let mut sheets = Vec::new();
+    let mut reader = XmlReader::from_str(workbook_xml);
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) | Event::Empty(e) if e.name().as_ref() == b"sheet" => {
+                let name = attr(&e, b"name").unwrap_or_default();
+                let target = attr(&e, b"r:id").and_then(|id| targets.get(&id).cloned());
+                if let Some(target) = target {
+                    let path = match target.strip_prefix('/') {
+                        Some(absolute) => absolute.to_string(),
+                        None => format!("xl/{}", target),
+                    };
+                    sheets.push(SheetInfo { name, path });
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(sheets)
+}
+
+fn read_defined_names(workbook_xml: &str) -> Result<Vec<DefinedName>> {
+    let mut names = Vec::new();
+    let mut reader = XmlReader::from_str(workbook_xml);
+    let mut current: Option<String> = None;
+    let mut value = String::new();
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) if e.name().as_ref() == b"definedName" => {
+                current = attr(&e, b"name");
+                value.clear();
+            }
+            Event::Text(t) if current.is_some() => value.push_str(&t.unescape()?),
+            Event::End(e) if e.name().as_ref() == b"definedName" => {
+                if let Some(name) = current.take() {
+                    names.push(DefinedName {
+                        name,
+                        value: value.trim().to_string(),
+                    });
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(names)
+}
+
+/// Замена значений `<definedName>` по порядку их следования в книге
+fn rewrite_defined_names(
+    workbook_xml: &str,
+    mut value_for: impl FnMut(usize, &str) -> String,
+) -> String {
+    let mut out = String::with_capacity(workbook_xml.len());
+    let mut rest = workbook_xml;
+    let mut idx = 0;
+    while let Some(open) = rest.find("<definedName") {
+        let Some(open_end) = rest[open..].find('>').map(|pos| open + pos + 1) else {
+            break;
+        };
+        out.push_str(&rest[..open_end]);
+        rest = &rest[open_end..];
+        if out.ends_with("/>") {
+            continue;
+        }
+        let Some(close) = rest.find("</definedName>") else {
+            break;
+        };
+        match unescape(&rest[..close]) {
+            Ok(value) => out.push_str(&escape(&value_for(idx, value.trim()))),
+            Err(_) => out.push_str(&rest[..close]),
+        }
+        idx += 1;
+        rest = &rest[close..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Формулы шаблона пересчитываются при открытии файла
+fn ensure_full_calc_on_load(workbook_xml: &str) -> String {
+    if workbook_xml.contains("fullCalcOnLoad") {
+        return workbook_xml.to_string();
+    }
+    if let Some(pos) = workbook_xml.find("<calcPr") {
+        let insert_at = pos + "<calcPr".len();
+        return format!(
+            "{} fullCalcOnLoad=\"1\"{}",
+            &workbook_xml[..insert_at],
+            &workbook_xml[insert_at..]
+        );
+    }
+    let anchor = ["</definedNames>", "</sheets>"]
+        .iter()
+        .find_map(|tag| workbook_xml.find(tag).map(|pos| pos + tag.len()));
+    match anchor {
+        Some(pos) => format!(
+            "{}<calcPr fullCalcOnLoad=\"1\"/>{}",
+            &workbook_xml[..pos],
+            &workbook_xml[pos..]
+        ),
+        None => workbook_xml.to_string(),
+    }
+}
+
+fn replace_dimension(before: &str, dimension: &str) -> String {
+    let Some(start) = before.find("<dimension") else {
+        return before.to_string();
+    };
+    let Some(end) = before[start..].find("/>").map(|idx| start + idx + 2) else {
+        return before.to_string();
+    };
+    format!(
+        "{}<dimension ref=\"{}\"/>{}",
+        &before[..start],
+        dimension,
+        &before[end..]
+    )
+}
+
+/// Удаление самозакрывающегося элемента `tag`, содержащего `needle`
+fn remove_element_containing(xml: &str, tag: &str, needle: &str) -> String {
+    let mut out = xml.to_string();
+    let mut search_from = 0;
+    while let Some(rel) = out[search_from..].find(tag) {
+        let start = search_from + rel;
+        let Some(end) = out[start..].find("/>").map(|idx| start + idx + 2) else {
+            break;
+        };
+        if out[start..end].contains(needle) {
+            out.replace_range(start..end, "");
+        } else {
+            search_from = end;
+        }
+    }
+    out
+}
+
+fn read_entry<R: Read + Seek>(zip: &mut ZipArchive<R>, name: &str) -> Result<String> {
+    let mut file = zip
+        .by_name(name)
+        .with_context(|| format!("В шаблоне нет {}", name))
+        .map_err(|err| template_error(err.to_string()))?;
+    let mut content = String::new();
+    file.read_to_string(&mut content)?;
+    Ok(content)
+}
+
+/// "B5" / "$B$5" → (колонка, строка), обе с нуля; колонки — до XFD
+fn parse_cell_ref(value: &str) -> Option<(u32, u32)> {
+    let mut col = 0u32;
+    let mut row = 0u32;
+    let mut has_col = false;
+    let mut has_row = false;
+    for c in value.trim().chars().filter(|c| *c != '$') {
+        if c.is_ascii_alphabetic() && !has_row {
+            let letter = (c.to_ascii_uppercase() as u8 - b'A' + 1) as u32;
+            col = col.checked_mul(26)?.checked_add(letter)?;
+            if col > MAX_COLUMNS {
+                return None;
+            }
+            has_col = true;
+        } else if let Some(digit) = c.to_digit(10) {
+            row = row.checked_mul(10)?.checked_add(digit)?;
+            has_row = true;
+        } else {
+            return None;
+        }
+    }
+    if !has_col || !has_row || row == 0 {
+        return None;
+    }
+    Some((col - 1, row - 1))
+}
+
+/// Номер колонки с нуля → буквенное имя ("A", "AB", ...)
+pub fn column_name(mut col: u32) -> String {
+    let mut name = Vec::new();
+    loop {
+        name.push(b'A' + (col % 26) as u8);
+        if col < 26 {
+            break;
+        }
+        col = col / 26 - 1;
+    }
+    name.reverse();
+    String::from_utf8(name).unwrap_or_default()
+}
+
+fn unquote_sheet_name(value: &str) -> String {
+    let trimmed = value.trim();
+    match trimmed
+        .strip_prefix('\'')
+        .and_then(|v| v.strip_suffix('\''))
+    {
+        Some(inner) => inner.replace("''", "'"),
+        None => trimmed.to_string(),
+    }
+}
+
+fn quote_sheet_name(name: &str) -> String {
+    if name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        name.to_string()
+    } else {
+        format!("'{}'", name.replace('\'', "''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_xlsxwriter::{Format, Workbook};
+
+    fn template() -> Vec<u8> {
+        let mut workbook = Workbook::new();
+        let sheet = workbook.add_worksheet().set_name("Отчёт").unwrap();
+        sheet.write_string(0, 0, "Продажи").unwrap();
+        sheet.write_string(4, 0, "{{data}}").unwrap();
+        sheet.write_string(6, 0, "Итого").unwrap();
+        sheet.write_formula(6, 1, "=SUM(B6:B6)").unwrap();
+        sheet
+            .merge_range(8, 0, 8, 2, "Подпись", &Format::new())
+            .unwrap();
+        let summary = workbook.add_worksheet().set_name("Сводка").unwrap();
+        summary.write_formula(0, 0, "=Отчёт!B7*2").unwrap();
+        workbook.define_name("Итоги", "=Отчёт!$B$7").unwrap();
+        workbook.save_to_buffer().unwrap()
+    }
+
+    fn entry(bytes: &[u8], name: &str) -> String {
+        let mut zip = open_zip(Cursor::new(bytes)).unwrap();
+        read_zip_file(&mut zip, name).unwrap().unwrap()
+    }
+
+    #[test]
+    fn rows_below_the_data_are_shifted() {
+        let columns = vec!["Товар".to_string(), "Сумма".to_string()];
+        let rows: Vec<Vec<String>> = (1..=3)
+            .map(|n| vec![format!("Товар {}", n), (n * 10).to_string()])
+            .collect();
+        let options = TemplateOptions {
+            name: "t".to_string(),
+            named_range: None,
+            marker: None,
+            include_header: true,
+        };
+        let filled = fill_xlsx_template(&template(), &columns, &rows, &options).unwrap();
+
+        let sheet = entry(&filled, "xl/worksheets/sheet1.xml");
+        assert!(sheet.contains(r#"<c r="B6"><v>10</v></c>"#));
+        assert!(sheet.contains(r#"<c r="B8"><v>30</v></c>"#));
+        assert!(sheet.contains("<f>SUM(B6:B8)</f>"));
+        assert!(sheet.contains(r#"<c r="B9""#));
+        assert!(sheet.contains(r#"<c r="B7"><v>20</v></c>"#));
+        assert!(sheet.contains(r#"<mergeCell ref="A11:C11"/>"#));
+
+        let summary = entry(&filled, "xl/worksheets/sheet2.xml");
+        assert!(summary.contains("<f>Отчёт!B9*2</f>"));
+        let workbook = entry(&filled, "xl/workbook.xml");
+        assert!(workbook.contains("Отчёт!$B$9</definedName>"));
+    }
+
+    #[test]
+    fn rows_beyond_the_sheet_are_refused() {
+        let rows = vec![vec!["1".to_string()]; MAX_ROW as usize];
+        let options = TemplateOptions {
+            name: "t".to_string(),
+            named_range: None,
+            marker: None,
+            include_header: false,
+        };
+        let error = fill_xlsx_template(&template(), &["x".to_string()], &rows, &options)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Данные не помещаются"), "{}", error);
+    }
+
+    #[test]
+    fn columns_beyond_the_sheet_are_refused() {
+        let columns: Vec<String> = (0..=MAX_COLUMNS).map(|idx| idx.to_string()).collect();
+        let options = TemplateOptions {
+            name: "t".to_string(),
+            named_range: None,
+            marker: None,
+            include_header: true,
+        };
+        let error = fill_xlsx_template(&template(), &columns, &[], &options)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("колонок больше 16384"), "{}", error);
+    }
+
+    #[test]
+    fn cell_references() {
+        assert_eq!(parse_cell_ref("A1"), Some((0, 0)));
+        assert_eq!(parse_cell_ref("$B$5"), Some((1, 4)));
+        assert_eq!(parse_cell_ref("xfd1048576"), Some((16_383, 1_048_575)));
+        assert_eq!(parse_cell_ref("XFE1"), None);
+        assert_eq!(parse_cell_ref("AAAAAAAAA1"), None);
+        assert_eq!(parse_cell_ref("A99999999999"), None);
+        assert_eq!(parse_cell_ref("A0"), None);
+        assert_eq!(parse_cell_ref("5B"), None);
+        assert_eq!(column_name(0), "A");
+        assert_eq!(column_name(27), "AB");
+        assert_eq!(column_name(16_383), "XFD");
+    }
+}
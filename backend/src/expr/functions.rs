@@ -0,0 +1,298 @@
+use crate::expr::{ExprType, Value};
+use anyhow::{bail, Result};
+use chrono::{Datelike, Local, NaiveDate};
+
+/// Встроенные функции выражений
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Function {
+    Upper,
+    Lower,
+    Trim,
+    Len,
+    Left,
+    Right,
+    Substr,
+    Replace,
+    Contains,
+    Concat,
+    Abs,
+    Round,
+    Floor,
+    Ceil,
+    Min,
+    Max,
+    Sum,
+    Coalesce,
+    IsNull,
+    Year,
+    Month,
+    Day,
+    Quarter,
+    Weekday,
+    Date,
+    DateDiff,
+    Today,
+    Number,
+    Text,
+}
+
+const FUNCTIONS: &[(&str, Function)] = &[
+    ("UPPER", Function::Upper),
+    ("LOWER", Function::Lower),
+    ("TRIM", Function::Trim),
+    ("LEN", Function::Len),
+    ("LEFT", Function::Left),
+    ("RIGHT", Function::Right),
+    ("SUBSTR", Function::Substr),
+    ("REPLACE", Function::Replace),
+    ("CONTAINS", Function::Contains),
+    ("CONCAT", Function::Concat),
+    ("ABS", Function::Abs),
+    ("ROUND", Function::Round),
+    ("FLOOR", Function::Floor),
+    ("CEIL", Function::Ceil),
+    ("MIN", Function::Min),
+    ("MAX", Function::Max),
+    ("SUM", Function::Sum),
+    ("COALESCE", Function::Coalesce),
+    ("ISNULL", Function::IsNull),
+    ("YEAR", Function::Year),
+    ("MONTH", Function::Month),
+    ("DAY", Function::Day),
+    ("QUARTER", Function::Quarter),
+    ("WEEKDAY", Function::Weekday),
+    ("DATE", Function::Date),
+    ("DATEDIFF", Function::DateDiff),
+    ("TODAY", Function::Today),
+    ("NUMBER", Function::Number),
+    ("TEXT", Function::Text),
+];
+
+impl Function {
+    pub fn lookup(name: &str) -> Option<Function> {
+        FUNCTIONS
+            .iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(name))
+            .map(|(_, function)| *function)
+    }
+
+    pub fn name(self) -> &'static str {
+        FUNCTIONS
+            .iter()
+            .find(|(_, function)| *function == self)
+            .map_or("?", |(name, _)| name)
+    }
+
+    /// Допустимое число аргументов: от и до (`None` — без ограничения)
+    fn arity(self) -> (usize, Option<usize>) {
+        use Function::*;
+        match self {
+            Today => (0, Some(0)),
+            Upper | Lower | Trim | Len | Abs | Floor | Ceil | IsNull | Year | Month | Day
+            | Quarter | Weekday | Number | Text => (1, Some(1)),
+            Round => (1, Some(2)),
+            Left | Right | Contains | DateDiff => (2, Some(2)),
+            Substr => (2, Some(3)),
+            Replace => (3, Some(3)),
+            Date => (1, Some(3)),
+            Concat | Min | Max | Sum | Coalesce => (1, None),
+        }
+    }
+
+    pub fn check_arity(self, count: usize) -> Result<()> {
+        let (min, max) = self.arity();
+        let fits = count >= min && max.is_none_or(|max| count <= max);
+        // DATE принимает строку или год, месяц и день
+        if !fits || (self == Function::Date && count == 2) {
+            let expected = match (self, max) {
+                (Function::Date, _) => "1 или 3".to_string(),
+                (_, Some(max)) if max == min => min.to_string(),
+                (_, Some(max)) => format!("от {} до {}", min, max),
+                (_, None) => format!("не меньше {}", min),
+            };
+            bail!(
+                "{} ожидает аргументов: {}, передано {}",
+                self.name(),
+                expected,
+                count
+            );
+        }
+        Ok(())
+    }
+
+    /// Проверка типов аргументов и тип результата
+    pub fn check(self, args: &[ExprType]) -> Result<ExprType> {
+        use Function::*;
+        let context = |idx: usize| format!("{}, аргумент {}", self.name(), idx + 1);
+        let expect_all = |expected: ExprType, from: usize| -> Result<()> {
+            for (idx, actual) in args.iter().enumerate().skip(from) {
+                expected.expect(*actual, &context(idx))?;
+            }
+            Ok(())
+        };
+        // строковые даты разбираются при вычислении, поэтому строка тоже подходит
+        let expect_dates = || -> Result<()> {
+            for (idx, actual) in args.iter().enumerate() {
+                if *actual != ExprType::Text {
+                    ExprType::Date.expect(*actual, &context(idx))?;
+                }
+            }
+            Ok(())
+        };
+
+        match self {
+            Upper | Lower | Trim | Replace => {
+                expect_all(ExprType::Text, 0)?;
+                Ok(ExprType::Text)
+            }
+            Left | Right | Substr => {
+                ExprType::Text.expect(args[0], &context(0))?;
+                expect_all(ExprType::Number, 1)?;
+                Ok(ExprType::Text)
+            }
+            Len => {
+                expect_all(ExprType::Text, 0)?;
+                Ok(ExprType::Number)
+            }
+            Contains => {
+                expect_all(ExprType::Text, 0)?;
+                Ok(ExprType::Bool)
+            }
+            Concat | Text => Ok(ExprType::Text),
+            Abs | Round | Floor | Ceil | Min | Max | Sum => {
+                expect_all(ExprType::Number, 0)?;
+                Ok(ExprType::Number)
+            }
+            Coalesce => {
+                let mut result = ExprType::Any;
+                for (idx, actual) in args.iter().enumerate() {
+                    if result == ExprType::Any {
+                        result = *actual;
+                    } else {
+                        result.expect(*actual, &context(idx))?;
+                    }
+                }
+                Ok(result)
+            }
+            IsNull => Ok(ExprType::Bool),
+            Year | Month | Day | Quarter | Weekday => {
+                expect_dates()?;
+                Ok(ExprType::Number)
+            }
+            DateDiff => {
+                expect_dates()?;
+                Ok(ExprType::Number)
+            }
+            Date if args.len() == 3 => {
+                expect_all(ExprType::Number, 0)?;
+                Ok(ExprType::Date)
+            }
+            Date => {
+                expect_dates()?;
+                Ok(ExprType::Date)
+            }
+            Today => Ok(ExprType::Date),
+            Number => Ok(ExprType::Number),
+        }
+    }
+
+    /// Вычисление; пустые и неподходящие аргументы дают пустой результат
+    pub fn call(self, args: Vec<Value>) -> Value {
+        use Function::*;
+        let text = |idx: usize| match args.get(idx) {
+            None | Some(Value::Null) => None,
+            Some(value) => Some(value.as_text()),
+        };
+        let number = |idx: usize| args.get(idx).and_then(Value::as_number);
+        let date = |idx: usize| args.get(idx).and_then(Value::as_date);
+        let count = |idx: usize| number(idx).map(|n| n.max(0.0) as usize);
+
+        let result = match self {
+            Upper => text(0).map(|s| Value::Text(s.to_uppercase())),
+            Lower => text(0).map(|s| Value::Text(s.to_lowercase())),
+            Trim => text(0).map(|s| Value::Text(s.trim().to_string())),
+            Len => text(0).map(|s| Value::Number(s.chars().count() as f64)),
+            Left => text(0)
+                .zip(count(1))
+                .map(|(s, n)| Value::Text(s.chars().take(n).collect())),
+            Right => text(0).zip(count(1)).map(|(s, n)| {
+                let len = s.chars().count();
+                Value::Text(s.chars().skip(len.saturating_sub(n)).collect())
+            }),
+            Substr => text(0).zip(number(1)).map(|(s, start)| {
+                // позиция с единицы, как в SQL и Excel
+                let skip = (start.max(1.0) as usize) - 1;
+                let take = count(2).unwrap_or(usize::MAX);
+                Value::Text(s.chars().skip(skip).take(take).collect())
+            }),
+            Replace => match (text(0), text(1)) {
+                (Some(s), Some(from)) if !from.is_empty() => {
+                    Some(Value::Text(s.replace(&from, &text(2).unwrap_or_default())))
+                }
+                (Some(s), _) => Some(Value::Text(s)),
+                _ => None,
+            },
+            Contains => text(0)
+                .zip(text(1))
+                .map(|(s, sub)| Value::Bool(s.to_lowercase().contains(&sub.to_lowercase()))),
+            Concat => Some(Value::Text(
+                (0..args.len()).filter_map(text).collect::<String>(),
+            )),
+            Abs => number(0).map(|n| Value::Number(n.abs())),
+            Round => number(0).map(|n| {
+                let digits = number(1).unwrap_or(0.0).clamp(-15.0, 15.0) as i32;
+                let factor = 10f64.powi(digits);
+                Value::Number((n * factor).round() / factor)
+            }),
+            Floor => number(0).map(|n| Value::Number(n.floor())),
+            Ceil => number(0).map(|n| Value::Number(n.ceil())),
+            Min => (0..args.len())
+                .filter_map(number)
+                .reduce(f64::min)
+                .map(Value::Number),
+            Max => (0..args.len())
+                .filter_map(number)
+                .reduce(f64::max)
+                .map(Value::Number),
+            // как в Excel: пустые аргументы пропускаются
+            Sum => (0..args.len())
+                .filter_map(number)
+                .reduce(|a, b| a + b)
+                .map(Value::Number),
+            Coalesce => args.into_iter().find(|value| !value.is_null()),
+            IsNull => {
+                Some(Value::Bool(args.first().is_none_or(|v| {
+                    v.is_null() || v.as_text().trim().is_empty()
+                })))
+            }
+            Year => date(0).map(|d| Value::Number(d.year() as f64)),
+            Month => date(0).map(|d| Value::Number(d.month() as f64)),
+            Day => date(0).map(|d| Value::Number(d.day() as f64)),
+            Quarter => date(0).map(|d| Value::Number(((d.month() - 1) / 3 + 1) as f64)),
+            // понедельник — 1, воскресенье — 7
+            Weekday => date(0).map(|d| Value::Number(d.weekday().number_from_monday() as f64)),
+            DateDiff => date(0)
+                .zip(date(1))
+                .map(|(a, b)| Value::Number((a.date() - b.date()).num_days() as f64)),
+            Date if args.len() == 3 => {
+                let part = |idx: usize| number(idx).filter(|n| n.fract() == 0.0);
+                match (part(0), part(1), part(2)) {
+                    (Some(y), Some(m), Some(d)) => {
+                        NaiveDate::from_ymd_opt(y as i32, m as u32, d as u32)
+                            .and_then(|d| d.and_hms_opt(0, 0, 0))
+                            .map(Value::Date)
+                    }
+                    _ => None,
+                }
+            }
+            Date => date(0).map(Value::Date),
+            Today => Local::now()
+                .date_naive()
+                .and_hms_opt(0, 0, 0)
+                .map(Value::Date),
+            Number => number(0).map(Value::Number),
+            Text => text(0).map(Value::Text),
+        };
+        result.unwrap_or(Value::Null)
+    }
+}
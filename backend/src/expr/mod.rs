@@ -0,0 +1,613 @@
+mod functions;
+mod parser;
+
+use crate::analytics::filter::format_number;
+use crate::datasets::schema::{parse_bool, parse_date, ColumnSchema, ColumnType};
+use crate::exporter::parse_number;
+use anyhow::{bail, Result};
+use chrono::{NaiveDateTime, Timelike};
+use std::cmp::Ordering;
+
+pub use functions::Function;
+
+/// Предел длины текста выражения
+pub const MAX_EXPRESSION_LEN: usize = 2000;
+
+/// Значение при вычислении; пустая ячейка — `Null`
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Number(f64),
+    Text(String),
+    Bool(bool),
+    Date(NaiveDateTime),
+}
+
+/// Статический тип выражения; `Any` — значение неизвестного типа (`NULL`)
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExprType {
+    Number,
+    Text,
+    Bool,
+    Date,
+    Any,
+}
+
+impl ExprType {
+    fn name(self) -> &'static str {
+        match self {
+            ExprType::Number => "число",
+            ExprType::Text => "строка",
+            ExprType::Bool => "логическое",
+            ExprType::Date => "дата",
+            ExprType::Any => "пусто",
+        }
+    }
+
+    fn from_column(column_type: ColumnType) -> Self {
+        match column_type {
+            ColumnType::Number => ExprType::Number,
+            ColumnType::Date => ExprType::Date,
+            ColumnType::Boolean => ExprType::Bool,
+            ColumnType::String => ExprType::Text,
+        }
+    }
+
+    /// Тип колонки в схеме набора для результата выражения
+    pub fn column_type(self) -> ColumnType {
+        match self {
+            ExprType::Number => ColumnType::Number,
+            ExprType::Date => ColumnType::Date,
+            ExprType::Bool => ColumnType::Boolean,
+            ExprType::Text | ExprType::Any => ColumnType::String,
+        }
+    }
+
+    /// Совместимость с ожидаемым типом: `Any` подходит везде
+    fn accepts(self, actual: ExprType) -> bool {
+        self == actual || self == ExprType::Any || actual == ExprType::Any
+    }
+
+    fn expect(self, actual: ExprType, context: &str) -> Result<()> {
+        if !self.accepts(actual) {
+            bail!(
+                "{}: ожидается {}, получено {}",
+                context,
+                self.name(),
+                actual.name()
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    /// `^`, как в Excel: минус числа связывается сильнее, `-2^2` = 4
+    Pow,
+    Concat,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+impl BinaryOp {
+    fn symbol(self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Rem => "%",
+            BinaryOp::Pow => "^",
+            BinaryOp::Concat => "&",
+            BinaryOp::Eq => "=",
+            BinaryOp::Ne => "<>",
+            BinaryOp::Lt => "<",
+            BinaryOp::Le => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Ge => ">=",
+            BinaryOp::And => "AND",
+            BinaryOp::Or => "OR",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Expr {
+    Literal(Value),
+    /// Индекс колонки в строке и её тип в схеме
+    Column(usize, ColumnType),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Call(Function, Vec<Expr>),
+    /// `CASE WHEN … THEN … ELSE … END`; `IF(c, a, b)` сводится к нему же
+    Case(Vec<(Expr, Expr)>, Option<Box<Expr>>),
+}
+
+impl Value {
+    /// Значение ячейки по типу колонки; нераспознанное остаётся строкой
+    pub fn from_cell(value: &str, column_type: ColumnType) -> Value {
+        if value.trim().is_empty() {
+            return Value::Null;
+        }
+        let parsed = match column_type {
+            ColumnType::Number => parse_number(value).map(Value::Number),
+            ColumnType::Date => parse_date(value).map(Value::Date),
+            ColumnType::Boolean => parse_bool(value).map(Value::Bool),
+            ColumnType::String => None,
+        };
+        parsed.unwrap_or_else(|| Value::Text(value.to_string()))
+    }
+
+    /// Запись значения в ячейку: числа как в JS, даты в ISO
+    pub fn to_cell(&self) -> String {
+        match self {
+            Value::Null => String::new(),
+            Value::Number(n) if n.is_finite() => format_number(*n),
+            Value::Number(_) => String::new(),
+            Value::Text(s) => s.clone(),
+            Value::Bool(b) => b.to_string(),
+            Value::Date(d) if d.num_seconds_from_midnight() == 0 && d.nanosecond() == 0 => {
+                d.format("%Y-%m-%d").to_string()
+            }
+            Value::Date(d) => d.format("%Y-%m-%dT%H:%M:%S").to_string(),
+        }
+    }
+
+    fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    /// Число из значения; строки разбираются как в ячейках
+    fn as_number(&self) -> Option<f64> {
+        match self {
+            Value::Number(n) => Some(*n),
+            Value::Text(s) => parse_number(s),
+            Value::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+            _ => None,
+        }
+    }
+
+    fn as_date(&self) -> Option<NaiveDateTime> {
+        match self {
+            Value::Date(d) => Some(*d),
+            Value::Text(s) => parse_date(s),
+            _ => None,
+        }
+    }
+
+    fn as_text(&self) -> String {
+        self.to_cell()
+    }
+
+    /// Истинность условия: пустое значение — ложь
+    fn truthy(&self) -> bool {
+        match self {
+            Value::Null => false,
+            Value::Bool(b) => *b,
+            Value::Number(n) => *n != 0.0,
+            Value::Text(s) => !s.is_empty(),
+            Value::Date(_) => true,
+        }
+    }
+
+    /// Сравнение однотипных значений; строки — без учёта регистра
+    fn compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => a.partial_cmp(b),
+            (Value::Date(a), Value::Date(b)) => Some(a.cmp(b)),
+            (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+            (Value::Text(a), Value::Text(b)) => Some(a.to_lowercase().cmp(&b.to_lowercase())),
+            (Value::Null, _) | (_, Value::Null) => None,
+            (Value::Date(a), Value::Text(b)) => parse_date(b).map(|b| a.cmp(&b)),
+            (Value::Text(a), Value::Date(b)) => parse_date(a).map(|a| a.cmp(b)),
+            (a, b) => a.as_number()?.partial_cmp(&b.as_number()?),
+        }
+    }
+}
+
+/// Проверенное выражение, готовое к вычислению по строкам набора
+#[derive(Clone, Debug)]
+pub struct Compiled {
+    expr: Expr,
+    result_type: ExprType,
+}
+
+impl Compiled {
+    pub fn result_type(&self) -> ExprType {
+        self.result_type
+    }
+
+    /// Дерево выражения: по нему экспорт пишет формулы XLSX/ODS
+    pub fn expr(&self) -> &Expr {
+        &self.expr
+    }
+
+    pub fn eval(&self, row: &[String]) -> Value {
+        eval(&self.expr, row)
+    }
+}
+
+/// Разбор и проверка выражения по схеме: неизвестные колонки и функции,
+/// неверное число аргументов и несовместимые типы — ошибки.
+///
+/// Колонки указываются именем (`Выручка`) или в квадратных скобках
+/// (`[Сумма заказа]`), строки — в одинарных или двойных кавычках,
+/// `&` склеивает строки
+pub fn compile(source: &str, schema: &[ColumnSchema]) -> Result<Compiled> {
+    if source.trim().is_empty() {
+        bail!("Пустое выражение");
+    }
+    if source.chars().count() > MAX_EXPRESSION_LEN {
+        bail!("Выражение длиннее {} символов", MAX_EXPRESSION_LEN);
+    }
+    let expr = parser::parse(source, schema)?;
+    let result_type = check(&expr)?;
+    Ok(Compiled { expr, result_type })
+}
+
+fn check(expr: &Expr) -> Result<ExprType> {
+    match expr {
+        Expr::Literal(value) => Ok(match value {
+            Value::Null => ExprType::Any,
+            Value::Number(_) => ExprType::Number,
+            Value::Text(_) => ExprType::Text,
+            Value::Bool(_) => ExprType::Bool,
+            Value::Date(_) => ExprType::Date,
+        }),
+        Expr::Column(_, column_type) => Ok(ExprType::from_column(*column_type)),
+        Expr::Unary(UnaryOp::Neg, inner) => {
+            ExprType::Number.expect(check(inner)?, "унарный минус")?;
+            Ok(ExprType::Number)
+        }
+        Expr::Unary(UnaryOp::Not, inner) => {
+            ExprType::Bool.expect(check(inner)?, "NOT")?;
+            Ok(ExprType::Bool)
+        }
+        Expr::Binary(op, left, right) => {
+            let (left, right) = (check(left)?, check(right)?);
+            let context = format!("оператор {}", op.symbol());
+            match op {
+                BinaryOp::Concat => Ok(ExprType::Text),
+                BinaryOp::And | BinaryOp::Or => {
+                    ExprType::Bool.expect(left, &context)?;
+                    ExprType::Bool.expect(right, &context)?;
+                    Ok(ExprType::Bool)
+                }
+                BinaryOp::Eq
+                | BinaryOp::Ne
+                | BinaryOp::Lt
+                | BinaryOp::Le
+                | BinaryOp::Gt
+                | BinaryOp::Ge => {
+                    // даты сравниваются со строковыми литералами вида '2024-01-01'
+                    let date_text = matches!(
+                        (left, right),
+                        (ExprType::Date, ExprType::Text) | (ExprType::Text, ExprType::Date)
+                    );
+                    if !date_text {
+                        left.expect(right, &context)?;
+                    }
+                    Ok(ExprType::Bool)
+                }
+                BinaryOp::Sub if left == ExprType::Date && right == ExprType::Date => {
+                    // разность дат — в днях
+                    Ok(ExprType::Number)
+                }
+                _ => {
+                    ExprType::Number.expect(left, &context)?;
+                    ExprType::Number.expect(right, &context)?;
+                    Ok(ExprType::Number)
+                }
+            }
+        }
+        Expr::Call(function, args) => {
+            let types = args.iter().map(check).collect::<Result<Vec<_>>>()?;
+            function.check(&types)
+        }
+        Expr::Case(branches, otherwise) => {
+            let mut result = ExprType::Any;
+            let mut unify = |actual: ExprType| -> Result<()> {
+                if result == ExprType::Any {
+                    result = actual;
+                } else {
+                    result.expect(actual, "ветви CASE/IF")?;
+                }
+                Ok(())
+            };
+            for (condition, value) in branches {
+                ExprType::Bool.expect(check(condition)?, "условие CASE/IF")?;
+                unify(check(value)?)?;
+            }
+            if let Some(otherwise) = otherwise {
+                unify(check(otherwise)?)?;
+            }
+            Ok(result)
+        }
+    }
+}
+
+fn eval(expr: &Expr, row: &[String]) -> Value {
+    match expr {
+        Expr::Literal(value) => value.clone(),
+        Expr::Column(idx, column_type) => Value::from_cell(
+            row.get(*idx).map(String::as_str).unwrap_or(""),
+            *column_type,
+        ),
+        Expr::Unary(UnaryOp::Neg, inner) => match eval(inner, row).as_number() {
+            Some(n) => Value::Number(-n),
+            None => Value::Null,
+        },
+        Expr::Unary(UnaryOp::Not, inner) => match eval(inner, row) {
+            Value::Null => Value::Null,
+            value => Value::Bool(!value.truthy()),
+        },
+        Expr::Binary(BinaryOp::And, left, right) => {
+            // короткое замыкание: правая часть не считается, если левая ложна
+            let left = eval(left, row);
+            if !left.is_null() && !left.truthy() {
+                return Value::Bool(false);
+            }
+            match (left, eval(right, row)) {
+                (_, right) if !right.is_null() && !right.truthy() => Value::Bool(false),
+                (Value::Null, _) | (_, Value::Null) => Value::Null,
+                _ => Value::Bool(true),
+            }
+        }
+        Expr::Binary(BinaryOp::Or, left, right) => {
+            let left = eval(left, row);
+            if left.truthy() {
+                return Value::Bool(true);
+            }
+            match (left, eval(right, row)) {
+                (_, right) if right.truthy() => Value::Bool(true),
+                (Value::Null, _) | (_, Value::Null) => Value::Null,
+                _ => Value::Bool(false),
+            }
+        }
+        Expr::Binary(op, left, right) => binary(*op, eval(left, row), eval(right, row)),
+        Expr::Call(function, args) => {
+            let values: Vec<Value> = args.iter().map(|arg| eval(arg, row)).collect();
+            function.call(values)
+        }
+        Expr::Case(branches, otherwise) => branches
+            .iter()
+            .find(|(condition, _)| eval(condition, row).truthy())
+            .map(|(_, value)| eval(value, row))
+            .or_else(|| otherwise.as_ref().map(|value| eval(value, row)))
+            .unwrap_or(Value::Null),
+    }
+}
+
+/// Двуместные операции; пустой операнд даёт пустой результат,
+/// кроме склейки строк, где он считается пустой строкой
+fn binary(op: BinaryOp, left: Value, right: Value) -> Value {
+    if op == BinaryOp::Concat {
+        return Value::Text(format!("{}{}", left.as_text(), right.as_text()));
+    }
+    if left.is_null() || right.is_null() {
+        return Value::Null;
+    }
+    let ordering = || left.compare(&right);
+    let number = |f: fn(f64, f64) -> f64| match (left.as_number(), right.as_number()) {
+        (Some(a), Some(b)) => {
+            let result = f(a, b);
+            if result.is_finite() {
+                Value::Number(result)
+            } else {
+                Value::Null
+            }
+        }
+        _ => Value::Null,
+    };
+    match op {
+        BinaryOp::Sub => match (&left, &right) {
+            (Value::Date(a), Value::Date(b)) => {
+                Value::Number((*a - *b).num_seconds() as f64 / 86_400.0)
+            }
+            _ => number(|a, b| a - b),
+        },
+        BinaryOp::Add => number(|a, b| a + b),
+        BinaryOp::Mul => number(|a, b| a * b),
+        // деление на ноль даёт пустое значение, а не ошибку
+        BinaryOp::Div => number(|a, b| a / b),
+        BinaryOp::Rem => number(|a, b| a % b),
+        BinaryOp::Pow => number(f64::powf),
+        BinaryOp::Eq => ordering().map_or(Value::Null, |o| Value::Bool(o == Ordering::Equal)),
+        BinaryOp::Ne => ordering().map_or(Value::Null, |o| Value::Bool(o != Ordering::Equal)),
+        BinaryOp::Lt => ordering().map_or(Value::Null, |o| Value::Bool(o == Ordering::Less)),
+        BinaryOp::Le => ordering().map_or(Value::Null, |o| Value::Bool(o != Ordering::Greater)),
+        BinaryOp::Gt => ordering().map_or(Value::Null, |o| Value::Bool(o == Ordering::Greater)),
+        BinaryOp::Ge => ordering().map_or(Value::Null, |o| Value::Bool(o != Ordering::Less)),
+        BinaryOp::Concat | BinaryOp::And | BinaryOp::Or => unreachable!("обработано выше"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema() -> Vec<ColumnSchema> {
+        [
+            ("Выручка", ColumnType::Number),
+            ("Себестоимость", ColumnType::Number),
+            ("Дата заказа", ColumnType::Date),
+            ("Имя", ColumnType::String),
+            ("Активен", ColumnType::Boolean),
+        ]
+        .into_iter()
+        .map(|(name, column_type)| ColumnSchema {
+            name: name.to_string(),
+            column_type,
+            nullable: true,
+        })
+        .collect()
+    }
+
+    fn row() -> Vec<String> {
+        ["100", "60", "2024-03-15", " Анна ", "true"]
+            .into_iter()
+            .map(String::from)
+            .collect()
+    }
+
+    fn eval_on(source: &str, row: &[String]) -> Value {
+        compile(source, &schema()).unwrap().eval(row)
+    }
+
+    fn eval_str(source: &str) -> Value {
+        eval_on(source, &row())
+    }
+
+    fn type_of(source: &str) -> ExprType {
+        compile(source, &schema()).unwrap().result_type()
+    }
+
+    fn error(source: &str) -> String {
+        compile(source, &schema()).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn operator_precedence() {
+        assert_eq!(eval_str("1 + 2 * 3"), Value::Number(7.0));
+        assert_eq!(eval_str("(1 + 2) * 3"), Value::Number(9.0));
+        assert_eq!(eval_str("10 - 4 - 3"), Value::Number(3.0));
+        assert_eq!(eval_str("2 ^ 3 ^ 2"), Value::Number(512.0));
+        assert_eq!(eval_str("-2 ^ 2"), Value::Number(4.0));
+        assert_eq!(eval_str("2 * 3 ^ 2"), Value::Number(18.0));
+        assert_eq!(eval_str("-7 % 3"), Value::Number(-1.0));
+        assert_eq!(eval_str("'№' & 1 + 1"), Value::Text("№2".to_string()));
+        assert_eq!(eval_str("NOT 1 > 2 AND TRUE"), Value::Bool(true));
+        assert_eq!(eval_str("FALSE AND TRUE OR TRUE"), Value::Bool(true));
+    }
+
+    #[test]
+    fn columns_literals_and_functions() {
+        assert_eq!(eval_str("Выручка - Себестоимость"), Value::Number(40.0));
+        assert_eq!(eval_str("[выручка] * 2"), Value::Number(200.0));
+        assert_eq!(eval_str("YEAR([Дата заказа])"), Value::Number(2024.0));
+        assert_eq!(eval_str("QUARTER([Дата заказа])"), Value::Number(1.0));
+        assert_eq!(
+            eval_str("UPPER(TRIM(Имя))"),
+            Value::Text("АННА".to_string())
+        );
+        assert_eq!(eval_str("'it''s' & \"\""), Value::Text("it's".to_string()));
+        assert_eq!(eval_str("[Дата заказа] >= '2024-03-01'"), Value::Bool(true));
+        assert_eq!(eval_str("ROUND(2.5) + ROUND(-2.5)"), Value::Number(0.0));
+        assert_eq!(eval_str("ROUND(1.005, 1)"), Value::Number(1.0));
+        assert_eq!(eval_str("SUM(1, Выручка, NULL)"), Value::Number(101.0));
+        assert_eq!(
+            eval_str("IF(Активен, 'да', 'нет')"),
+            Value::Text("да".to_string())
+        );
+        assert_eq!(
+            eval_str("CASE WHEN Выручка > 500 THEN 'много' WHEN Выручка > 50 THEN 'средне' END"),
+            Value::Text("средне".to_string())
+        );
+    }
+
+    #[test]
+    fn nulls() {
+        let mut row = row();
+        row[0] = String::new();
+        assert_eq!(eval_on("Выручка - Себестоимость", &row), Value::Null);
+        assert_eq!(eval_on("COALESCE(Выручка, 0)", &row), Value::Number(0.0));
+        assert_eq!(eval_on("ISNULL(Выручка)", &row), Value::Bool(true));
+        assert_eq!(eval_on("Выручка > 1", &row), Value::Null);
+        assert_eq!(eval_on("Выручка > 1 AND FALSE", &row), Value::Bool(false));
+        assert_eq!(eval_on("Выручка & '!'", &row), Value::Text("!".to_string()));
+        assert_eq!(eval_str("Выручка / 0"), Value::Null);
+        assert_eq!(
+            eval_on("CASE WHEN Выручка > 1 THEN 1 ELSE 2 END", &row),
+            Value::Number(2.0)
+        );
+    }
+
+    #[test]
+    fn result_types() {
+        assert_eq!(type_of("Выручка - Себестоимость"), ExprType::Number);
+        assert_eq!(type_of("Выручка & ''"), ExprType::Text);
+        assert_eq!(type_of("[Дата заказа] - [Дата заказа]"), ExprType::Number);
+        assert_eq!(type_of("DATE(2024, 1, 31)"), ExprType::Date);
+        assert_eq!(type_of("Выручка > 1 OR Активен"), ExprType::Bool);
+        assert_eq!(type_of("NULL + 1"), ExprType::Number);
+        assert_eq!(
+            type_of("CASE WHEN TRUE THEN NULL ELSE 1 END"),
+            ExprType::Number
+        );
+        assert_eq!(type_of("NULL"), ExprType::Any);
+    }
+
+    #[test]
+    fn type_errors() {
+        assert!(error("Имя * 2").contains("ожидается число, получено строка"));
+        assert!(error("-Имя").contains("унарный минус"));
+        assert!(error("NOT Выручка").contains("NOT"));
+        assert!(error("IF(Выручка, 1, 2)").contains("условие CASE/IF"));
+        assert!(error("IF(Активен, 1, 'x')").contains("ветви CASE/IF"));
+        assert!(error("Выручка = 'x'").contains("оператор ="));
+        assert!(error("COALESCE(Выручка, 'x')").contains("COALESCE, аргумент 2"));
+        assert!(error("LEN(Выручка)").contains("LEN, аргумент 1"));
+        assert!(error("YEAR(Выручка)").contains("ожидается дата"));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(error("   "), "Пустое выражение");
+        assert_eq!(error("1 +"), "Выражение оборвано");
+        assert_eq!(error("(1 + 2"), "Ожидается «)»");
+        assert_eq!(error("1 + 2)"), "Лишний текст после выражения");
+        assert_eq!(error("1 2"), "Лишний текст после выражения");
+        assert_eq!(error("[Выручка"), "Незакрытая скобка «[»");
+        assert_eq!(error("'abc"), "Незакрытая кавычка");
+        assert_eq!(error("1 $ 2"), "Неожиданный символ «$»");
+        assert_eq!(error("FOO(1)"), "Неизвестная функция «FOO»");
+        assert_eq!(
+            error("Прибыль + 1"),
+            "Колонка «Прибыль» отсутствует в наборе данных"
+        );
+        assert_eq!(error("CASE ELSE 1 END"), "CASE без ветвей WHEN");
+        assert_eq!(error("CASE WHEN TRUE THEN 1"), "Ожидается END");
+        assert_eq!(
+            error("IF(TRUE, 1)"),
+            "IF ожидает 3 аргумента: условие, значение, иначе"
+        );
+        assert_eq!(
+            error("ROUND()"),
+            "ROUND ожидает аргументов: от 1 до 2, передано 0"
+        );
+        assert_eq!(
+            error("DATE(2024, 1)"),
+            "DATE ожидает аргументов: 1 или 3, передано 2"
+        );
+    }
+
+    #[test]
+    fn limits() {
+        let deep = format!("{}1{}", "(".repeat(200), ")".repeat(200));
+        assert_eq!(error(&deep), "Слишком глубокая вложенность выражения");
+        let negations = format!("{}1", "-".repeat(200));
+        assert_eq!(error(&negations), "Слишком глубокая вложенность выражения");
+        let powers = vec!["2"; 200].join("^");
+        assert_eq!(error(&powers), "Слишком глубокая вложенность выражения");
+        let long = vec!["1"; MAX_EXPRESSION_LEN].join("+");
+        assert!(error(&long).starts_with("Выражение длиннее"));
+    }
+}
@@ -0,0 +1,396 @@
+use crate::datasets::schema::ColumnSchema;
+use crate::expr::{BinaryOp, Expr, Function, UnaryOp, Value};
+use anyhow::{anyhow, bail, Result};
+
+/// Предел вложенности: глубокая рекурсия не должна обрушить поток
+const MAX_DEPTH: usize = 64;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Text(String),
+    Word(String),
+    /// Колонка в квадратных скобках
+    Column(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    Comma,
+}
+
+const OPERATORS: &[&str] = &[
+    "<=", ">=", "<>", "!=", "==", "+", "-", "*", "/", "%", "^", "&", "=", "<", ">",
+];
+
+fn tokenize(source: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        match c {
+            '(' => tokens.push(Token::LParen),
+            ')' => tokens.push(Token::RParen),
+            ',' | ';' => tokens.push(Token::Comma),
+            '\'' | '"' | '[' => {
+                let close = if c == '[' { ']' } else { c };
+                let mut text = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None if c == '[' => bail!("Незакрытая скобка «[»"),
+                        None => bail!("Незакрытая кавычка"),
+                        Some(&ch) if ch == close && chars.get(i + 1) == Some(&close) => {
+                            text.push(close);
+                            i += 2;
+                        }
+                        Some(&ch) if ch == close => break,
+                        Some(&ch) => {
+                            text.push(ch);
+                            i += 1;
+                        }
+                    }
+                }
+                tokens.push(if c == '[' {
+                    Token::Column(text)
+                } else {
+                    Token::Text(text)
+                });
+            }
+            c if c.is_ascii_digit()
+                || (c == '.' && chars.get(i + 1).is_some_and(|d| d.is_ascii_digit())) =>
+            {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                if i < chars.len() && matches!(chars[i], 'e' | 'E') {
+                    let mut j = i + 1;
+                    if j < chars.len() && matches!(chars[j], '+' | '-') {
+                        j += 1;
+                    }
+                    if j < chars.len() && chars[j].is_ascii_digit() {
+                        i = j;
+                        while i < chars.len() && chars[i].is_ascii_digit() {
+                            i += 1;
+                        }
+                    }
+                }
+                let text: String = chars[start..i].iter().collect();
+                let number = text
+                    .parse::<f64>()
+                    .map_err(|_| anyhow!("Некорректное число «{}»", text))?;
+                tokens.push(Token::Number(number));
+                continue;
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                tokens.push(Token::Word(chars[start..i].iter().collect()));
+                continue;
+            }
+            _ => {
+                let rest: String = chars[i..chars.len().min(i + 2)].iter().collect();
+                let op = OPERATORS
+                    .iter()
+                    .find(|op| rest.starts_with(**op))
+                    .ok_or_else(|| anyhow!("Неожиданный символ «{}»", c))?;
+                tokens.push(Token::Op(op));
+                i += op.chars().count();
+                continue;
+            }
+        }
+        i += 1;
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,
+    schema: &'a [ColumnSchema],
+}
+
+fn is_keyword(token: Option<&Token>, keyword: &str) -> bool {
+    matches!(token, Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword))
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if is_keyword(self.peek(), keyword) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_op(&mut self, ops: &[&'static str]) -> Option<&'static str> {
+        match self.peek() {
+            Some(Token::Op(op)) if ops.contains(op) => {
+                let op = *op;
+                self.pos += 1;
+                Some(op)
+            }
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, expected: Token, label: &str) -> Result<()> {
+        if self.next() == Some(expected) {
+            Ok(())
+        } else {
+            bail!("Ожидается «{}»", label)
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<()> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            bail!("Ожидается {}", keyword)
+        }
+    }
+
+    fn column(&self, name: &str) -> Result<Expr> {
+        let found = self
+            .schema
+            .iter()
+            .position(|c| c.name == name)
+            .or_else(|| {
+                let lower = name.to_lowercase();
+                self.schema
+                    .iter()
+                    .position(|c| c.name.to_lowercase() == lower)
+            })
+            .ok_or_else(|| anyhow!("Колонка «{}» отсутствует в наборе данных", name))?;
+        Ok(Expr::Column(found, self.schema[found].column_type))
+    }
+
+    fn expression(&mut self) -> Result<Expr> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            bail!("Слишком глубокая вложенность выражения");
+        }
+        let expr = self.or();
+        self.depth -= 1;
+        expr
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        let mut left = self.and()?;
+        while self.eat_keyword("OR") {
+            left = Expr::Binary(BinaryOp::Or, Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut left = self.not()?;
+        while self.eat_keyword("AND") {
+            left = Expr::Binary(BinaryOp::And, Box::new(left), Box::new(self.not()?));
+        }
+        Ok(left)
+    }
+
+    fn not(&mut self) -> Result<Expr> {
+        if self.eat_keyword("NOT") {
+            return Ok(Expr::Unary(UnaryOp::Not, Box::new(self.not()?)));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr> {
+        let left = self.concat()?;
+        let Some(op) = self.eat_op(&["=", "==", "<>", "!=", "<", "<=", ">", ">="]) else {
+            return Ok(left);
+        };
+        let op = match op {
+            "=" | "==" => BinaryOp::Eq,
+            "<>" | "!=" => BinaryOp::Ne,
+            "<" => BinaryOp::Lt,
+            "<=" => BinaryOp::Le,
+            ">" => BinaryOp::Gt,
+            _ => BinaryOp::Ge,
+        };
+        Ok(Expr::Binary(op, Box::new(left), Box::new(self.concat()?)))
+    }
+
+    fn concat(&mut self) -> Result<Expr> {
+        let mut left = self.additive()?;
+        while self.eat_op(&["&"]).is_some() {
+            left = Expr::Binary(BinaryOp::Concat, Box::new(left), Box::new(self.additive()?));
+        }
+        Ok(left)
+    }
+
+    fn additive(&mut self) -> Result<Expr> {
+        let mut left = self.multiplicative()?;
+        while let Some(op) = self.eat_op(&["+", "-"]) {
+            let op = if op == "+" {
+                BinaryOp::Add
+            } else {
+                BinaryOp::Sub
+            };
+            left = Expr::Binary(op, Box::new(left), Box::new(self.multiplicative()?));
+        }
+        Ok(left)
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr> {
+        let mut left = self.power()?;
+        while let Some(op) = self.eat_op(&["*", "/", "%"]) {
+            let op = match op {
+                "*" => BinaryOp::Mul,
+                "/" => BinaryOp::Div,
+                _ => BinaryOp::Rem,
+            };
+            left = Expr::Binary(op, Box::new(left), Box::new(self.power()?));
+        }
+        Ok(left)
+    }
+
+    /// Степень правоассоциативна: `2^3^2` = `2^(3^2)`
+    fn power(&mut self) -> Result<Expr> {
+        let base = self.unary()?;
+        if self.eat_op(&["^"]).is_none() {
+            return Ok(base);
+        }
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            bail!("Слишком глубокая вложенность выражения");
+        }
+        let exponent = self.power()?;
+        self.depth -= 1;
+        Ok(Expr::Binary(
+            BinaryOp::Pow,
+            Box::new(base),
+            Box::new(exponent),
+        ))
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if self.eat_op(&["-"]).is_some() {
+            self.depth += 1;
+            if self.depth > MAX_DEPTH {
+                bail!("Слишком глубокая вложенность выражения");
+            }
+            let inner = self.unary()?;
+            self.depth -= 1;
+            return Ok(Expr::Unary(UnaryOp::Neg, Box::new(inner)));
+        }
+        if self.eat_op(&["+"]).is_some() {
+            return self.unary();
+        }
+        self.primary()
+    }
+
+    fn case(&mut self) -> Result<Expr> {
+        let mut branches = Vec::new();
+        while self.eat_keyword("WHEN") {
+            let condition = self.expression()?;
+            self.expect_keyword("THEN")?;
+            branches.push((condition, self.expression()?));
+        }
+        if branches.is_empty() {
+            bail!("CASE без ветвей WHEN");
+        }
+        let otherwise = if self.eat_keyword("ELSE") {
+            Some(Box::new(self.expression()?))
+        } else {
+            None
+        };
+        self.expect_keyword("END")?;
+        Ok(Expr::Case(branches, otherwise))
+    }
+
+    fn call(&mut self, name: &str) -> Result<Expr> {
+        let mut args = Vec::new();
+        if self.peek() != Some(&Token::RParen) {
+            loop {
+                args.push(self.expression()?);
+                if self.peek() == Some(&Token::Comma) {
+                    self.pos += 1;
+                } else {
+                    break;
+                }
+            }
+        }
+        self.expect(Token::RParen, ")")?;
+
+        if name.eq_ignore_ascii_case("IF") {
+            if args.len() != 3 {
+                bail!("IF ожидает 3 аргумента: условие, значение, иначе");
+            }
+            let otherwise = args.pop().map(Box::new);
+            let value = args.pop().unwrap_or(Expr::Literal(Value::Null));
+            let condition = args.pop().unwrap_or(Expr::Literal(Value::Null));
+            return Ok(Expr::Case(vec![(condition, value)], otherwise));
+        }
+        let function =
+            Function::lookup(name).ok_or_else(|| anyhow!("Неизвестная функция «{}»", name))?;
+        function.check_arity(args.len())?;
+        Ok(Expr::Call(function, args))
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        match self.next() {
+            Some(Token::Number(n)) => Ok(Expr::Literal(Value::Number(n))),
+            Some(Token::Text(text)) => Ok(Expr::Literal(Value::Text(text))),
+            Some(Token::Column(name)) => self.column(&name),
+            Some(Token::LParen) => {
+                let expr = self.expression()?;
+                self.expect(Token::RParen, ")")?;
+                Ok(expr)
+            }
+            Some(Token::Word(word)) => match word.to_ascii_uppercase().as_str() {
+                "TRUE" => Ok(Expr::Literal(Value::Bool(true))),
+                "FALSE" => Ok(Expr::Literal(Value::Bool(false))),
+                "NULL" => Ok(Expr::Literal(Value::Null)),
+                "CASE" => self.case(),
+                _ if self.peek() == Some(&Token::LParen) => {
+                    self.pos += 1;
+                    self.call(&word)
+                }
+                _ => self.column(&word),
+            },
+            Some(Token::Op(op)) => bail!("Неожиданный оператор «{}»", op),
+            Some(Token::RParen) => bail!("Лишняя закрывающая скобка"),
+            Some(Token::Comma) => bail!("Неожиданная запятая"),
+            None => bail!("Выражение оборвано"),
+        }
+    }
+}
+
+/// Разбор текста в дерево с привязкой колонок к схеме
+pub fn parse(source: &str, schema: &[ColumnSchema]) -> Result<Expr> {
+    let mut parser = Parser {
+        tokens: tokenize(source)?,
+        pos: 0,
+        depth: 0,
+        schema,
+    };
+    let expr = parser.expression()?;
+    if parser.pos < parser.tokens.len() {
+        bail!("Лишний текст после выражения");
+    }
+    Ok(expr)
+}
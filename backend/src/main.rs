@@ -1,30 +1,55 @@
 use axum::{
-    extract::{DefaultBodyLimit, Multipart},
-    http::{header, HeaderValue, Method, StatusCode},
-    response::IntoResponse,
+    body::Body,
+    extract::{DefaultBodyLimit, Multipart, Query},
+    http::{HeaderValue, Method, StatusCode},
+    response::{IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
+use futures_util::{stream, TryStreamExt};
 use serde::{Deserialize, Serialize};
 use std::{
     env,
-    io::{BufReader, Cursor},
     net::{SocketAddr, ToSocketAddrs},
     time::{Duration, Instant},
 };
-use tokio::net::TcpListener;
+use tokio::{
+    net::TcpListener,
+    sync::{mpsc, oneshot},
+};
+use tokio_util::io::{ReaderStream, StreamReader, SyncIoBridge};
 use tower_http::cors::CorsLayer;
 
 mod converter;
-use converter::{convert_csv_to_vec, convert_ods_to_vec, convert_xlsx_to_vec};
+mod download;
+use download::{attachment_headers, download_filename};
+
+use converter::convert_by_extension;
 
+mod analytics;
 mod auth;
 use auth::setup_router;
 
+mod datasets;
+
 mod exporter;
-use exporter::{build_ods, build_xlsx};
+mod expr;
+use exporter::{
+    build_delimited, build_html_report, build_html_table, build_json, build_markdown_report,
+    build_markdown_table, build_ndjson, build_ods, build_report_pdf, build_table_pdf, build_xlsx,
+    bundle::{build_dashboard_bundle, DashboardBundle},
+    delimited::{LineEnding, Quoting, TextEncoding},
+    fill_xlsx_template,
+    stream::{read_table_stream, DelimitedSink, FirstRow, NdjsonSink, XlsxSink},
+    template::TemplateError,
+    CsvOptions, ExportFormat, MarkupOptions, PdfOptions, SpreadsheetOptions, TemplateOptions,
+};
 
 mod middleware;
+mod scheduler;
+mod sql;
+mod storage;
+mod templates;
 
 #[derive(Serialize)]
 struct UploadResponse {
@@ -40,8 +65,53 @@ struct ExportRequest {
     format: Option<String>,
     #[serde(default)]
     filename: Option<String>,
+    #[serde(default)]
+    csv: CsvOptions,
+    #[serde(default)]
+    pdf: PdfOptions,
+    #[serde(default)]
+    markup: MarkupOptions,
+    #[serde(default)]
+    spreadsheet: SpreadsheetOptions,
+    #[serde(default)]
+    template: Option<TemplateOptions>,
+}
+
+#[derive(Deserialize)]
+struct ReportExportRequest {
+    text: String,
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    format: Option<String>,
+    #[serde(default)]
+    filename: Option<String>,
+    #[serde(default)]
+    pdf: PdfOptions,
+    #[serde(default)]
+    markup: MarkupOptions,
 }
 
+/// Параметры потоковой выгрузки передаются в query, чтобы не ждать их в теле
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StreamExportQuery {
+    #[serde(default)]
+    format: Option<String>,
+    #[serde(default)]
+    filename: Option<String>,
+    #[serde(default)]
+    delimiter: Option<char>,
+    #[serde(default)]
+    quoting: Quoting,
+    #[serde(default)]
+    line_ending: LineEnding,
+    #[serde(default)]
+    encoding: TextEncoding,
+}
+
+const STREAM_CHANNEL_CAPACITY: usize = 8;
+
 async fn upload(mut multipart: Multipart) -> Json<UploadResponse> {
     let mut columns = vec![];
     let mut rows = vec![];
@@ -54,27 +124,14 @@ async fn upload(mut multipart: Multipart) -> Json<UploadResponse> {
             let start = Instant::now();
             let ext = filename.to_lowercase();
 
-            let ext_clone = ext.clone();
             let data_clone = data.to_vec();
+            let name_clone = filename.clone();
 
-            let (cols, rows_data) = tokio::task::spawn_blocking(move || {
-                let reader = BufReader::new(Cursor::new(data_clone));
-                if ext_clone.ends_with(".csv") {
-                    convert_csv_to_vec(reader)
-                } else if ext_clone.ends_with(".xlsx") {
-                    convert_xlsx_to_vec(reader)
-                } else if ext_clone.ends_with(".ods") {
-                    convert_ods_to_vec(reader)
-                } else {
-                    Err(std::io::Error::new(
-                        std::io::ErrorKind::InvalidInput,
-                        "unsupported",
-                    ))
-                }
-            })
-            .await
-            .unwrap()
-            .unwrap_or((vec![], vec![]));
+            let (cols, rows_data) =
+                tokio::task::spawn_blocking(move || convert_by_extension(&name_clone, data_clone))
+                    .await
+                    .unwrap()
+                    .unwrap_or((vec![], vec![]));
 
             let duration = start.elapsed();
             let nrows = rows_data.len();
@@ -96,62 +153,275 @@ async fn upload(mut multipart: Multipart) -> Json<UploadResponse> {
 async fn export_table(
     Json(payload): Json<ExportRequest>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
-    let format = payload
-        .format
-        .as_deref()
-        .map(|f| f.to_ascii_lowercase())
-        .unwrap_or_else(|| "xlsx".to_string());
-
-    let filename = payload
-        .filename
-        .unwrap_or_else(|| format!("export.{}", format));
-
-    let (bytes, mime) = match format.as_str() {
-        "ods" => (
-            build_ods(&payload.columns, &payload.rows).map_err(|err| internal_error("ODS", err))?,
-            "application/vnd.oasis.opendocument.spreadsheet",
-        ),
-        "xlsx" | _ => (
-            build_xlsx(&payload.columns, &payload.rows)
-                .map_err(|err| internal_error("XLSX", err))?,
-            "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
-        ),
+    let format = parse_export_format(payload.format.as_deref(), ExportFormat::Xlsx)?;
+
+    let validation = match format {
+        ExportFormat::Csv | ExportFormat::Tsv => payload.csv.validate(),
+        ExportFormat::Pdf => payload.pdf.validate(),
+        ExportFormat::Xlsx | ExportFormat::Ods => payload
+            .spreadsheet
+            .validate(&payload.columns, &payload.rows),
+        _ => Ok(()),
+    };
+    validation.map_err(|err| bad_request(err.to_string()))?;
+
+    let filename = download_filename(payload.filename.as_deref(), "export", format.extension());
+
+    let columns = &payload.columns;
+    let rows = &payload.rows;
+
+    if let Some(template) = &payload.template {
+        if format != ExportFormat::Xlsx {
+            return Err(bad_request(format!(
+                "Шаблоны поддерживаются только для xlsx, а не {}",
+                format.extension()
+            )));
+        }
+        let bytes = export_with_template(columns, rows, template)?;
+        return Ok((attachment_headers(format.mime(), &filename), bytes));
+    }
+
+    let built = match format {
+        ExportFormat::Xlsx => build_xlsx(columns, rows, &payload.spreadsheet),
+        ExportFormat::Ods => build_ods(columns, rows, &payload.spreadsheet),
+        ExportFormat::Csv => build_delimited(columns, rows, b',', &payload.csv),
+        ExportFormat::Tsv => build_delimited(columns, rows, b'\t', &payload.csv),
+        ExportFormat::Json => build_json(columns, rows),
+        ExportFormat::Ndjson => build_ndjson(columns, rows),
+        ExportFormat::Pdf => build_table_pdf(columns, rows, &payload.pdf),
+        ExportFormat::Html => build_html_table(columns, rows, &payload.markup),
+        ExportFormat::Markdown => build_markdown_table(columns, rows, &payload.markup),
+    };
+    let bytes = built.map_err(|err| internal_error(format.label(), err))?;
+
+    let mime = match format {
+        ExportFormat::Csv | ExportFormat::Tsv => {
+            format!(
+                "{}; charset={}",
+                format.mime(),
+                payload.csv.encoding.charset()
+            )
+        }
+        _ => format.mime().to_string(),
+    };
+
+    Ok((attachment_headers(&mime, &filename), bytes))
+}
+
+/// Заполнение сохранённого XLSX-шаблона данными таблицы
+fn export_with_template(
+    columns: &[String],
+    rows: &[Vec<String>],
+    options: &TemplateOptions,
+) -> Result<Vec<u8>, (StatusCode, String)> {
+    let template = templates::load_template(&options.name)
+        .map_err(|err| internal_error("template", err.into()))?
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                format!("Шаблон «{}» не найден", options.name),
+            )
+        })?;
+    fill_xlsx_template(&template, columns, rows, options).map_err(|err| {
+        match err.downcast_ref::<TemplateError>() {
+            Some(template_err) => bad_request(template_err.to_string()),
+            None => internal_error("template", err),
+        }
+    })
+}
+
+/// Потоковая выгрузка больших таблиц: тело разбирается по мере поступления,
+/// CSV/TSV/NDJSON уходят клиенту порциями, XLSX собирается в режиме constant memory
+async fn export_table_stream(
+    Query(params): Query<StreamExportQuery>,
+    body: Body,
+) -> Result<Response, (StatusCode, String)> {
+    let format = parse_export_format(params.format.as_deref(), ExportFormat::Csv)?;
+    if !format.supports_streaming() {
+        return Err(bad_request(format!(
+            "Формат {} не поддерживает потоковую выгрузку",
+            format.extension()
+        )));
+    }
+
+    let csv = CsvOptions {
+        delimiter: params.delimiter,
+        quoting: params.quoting,
+        line_ending: params.line_ending,
+        encoding: params.encoding,
     };
+    csv.validate().map_err(|err| bad_request(err.to_string()))?;
 
-    let mut headers = axum::http::HeaderMap::new();
-    headers.insert(
-        header::CONTENT_TYPE,
-        HeaderValue::from_str(mime).unwrap_or(HeaderValue::from_static("application/octet-stream")),
+    let filename = download_filename(params.filename.as_deref(), "export", format.extension());
+
+    let reader = SyncIoBridge::new(StreamReader::new(
+        body.into_data_stream().map_err(std::io::Error::other),
+    ));
+    let started = Instant::now();
+
+    if format == ExportFormat::Xlsx {
+        let file = tokio::task::spawn_blocking(move || {
+            let mut sink = XlsxSink::new();
+            let rows = read_table_stream(reader, &mut sink)?;
+            let file = sink.finish()?;
+            Ok::<_, anyhow::Error>((file, rows))
+        })
+        .await
+        .map_err(|err| internal_error(format.label(), err.into()))?;
+
+        let (file, rows) = file.map_err(|err| stream_error(format, err))?;
+        log_stream_export(format, rows, started.elapsed());
+
+        let body = Body::from_stream(ReaderStream::new(tokio::fs::File::from_std(file)));
+        return Ok((attachment_headers(format.mime(), &filename), body).into_response());
+    }
+
+    let mime = match format {
+        ExportFormat::Csv | ExportFormat::Tsv => {
+            format!("{}; charset={}", format.mime(), csv.encoding.charset())
+        }
+        _ => format.mime().to_string(),
+    };
+
+    let (tx, rx) = mpsc::channel(STREAM_CHANNEL_CAPACITY);
+    let (ready_tx, ready_rx) = oneshot::channel();
+    tokio::task::spawn_blocking(move || {
+        let mut ready = Some(ready_tx);
+        let result = match format {
+            ExportFormat::Ndjson => {
+                let mut sink = NdjsonSink::new(tx.clone());
+                read_table_stream(reader, &mut FirstRow::new(&mut sink, &mut ready))
+                    .and_then(|rows| sink.finish().map(|_| rows))
+            }
+            _ => {
+                let delimiter = if format == ExportFormat::Tsv {
+                    b'\t'
+                } else {
+                    b','
+                };
+                DelimitedSink::new(delimiter, &csv, tx.clone()).and_then(|mut sink| {
+                    read_table_stream(reader, &mut FirstRow::new(&mut sink, &mut ready))
+                        .and_then(|rows| sink.finish().map(|_| rows))
+                })
+            }
+        };
+
+        match (result, ready.take()) {
+            (Ok(rows), ready) => {
+                if let Some(ready) = ready {
+                    let _ = ready.send(Ok(()));
+                }
+                log_stream_export(format, rows, started.elapsed());
+            }
+            // ответ ещё не начат — ошибка уходит статусом
+            (Err(err), Some(ready)) => {
+                let _ = ready.send(Err(err));
+            }
+            (Err(err), None) => {
+                eprintln!("❌ stream export error ({}): {:?}", format.label(), err);
+                // заголовки уже отправлены — обрываем тело, чтобы клиент не принял файл за целый
+                let _ = tx.blocking_send(Err(std::io::Error::other(err.to_string())));
+            }
+        }
+    });
+
+    // заголовки ответа — только когда разобраны `columns` и первая строка
+    match ready_rx.await {
+        Ok(Ok(())) => {}
+        Ok(Err(err)) => return Err(stream_error(format, err)),
+        Err(err) => return Err(internal_error(format.label(), err.into())),
+    }
+
+    let chunks = stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|item| (item, rx))
+    });
+    Ok((
+        attachment_headers(&mime, &filename),
+        Body::from_stream(chunks),
+    )
+        .into_response())
+}
+
+fn stream_error(format: ExportFormat, err: anyhow::Error) -> (StatusCode, String) {
+    if err.downcast_ref::<serde_json::Error>().is_some() {
+        return bad_request(format!("Некорректное тело запроса: {}", err));
+    }
+    internal_error(format.label(), err)
+}
+
+fn log_stream_export(format: ExportFormat, rows: usize, dur: Duration) {
+    println!(
+        "📤 stream export {:<6} | Rows {:>8} | {:>8} ms",
+        format.label(),
+        rows,
+        dur.as_millis()
     );
+}
 
-    let disposition = format!("attachment; filename=\"{}\"", sanitize_filename(&filename));
-    headers.insert(
-        header::CONTENT_DISPOSITION,
-        HeaderValue::from_str(&disposition)
-            .unwrap_or(HeaderValue::from_static("attachment; filename=\"export\"")),
+async fn export_report(
+    Json(payload): Json<ReportExportRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let format = parse_export_format(payload.format.as_deref(), ExportFormat::Pdf)?;
+    payload
+        .pdf
+        .validate()
+        .map_err(|err| bad_request(err.to_string()))?;
+
+    let filename = download_filename(payload.filename.as_deref(), "report", format.extension());
+
+    let title = payload.title.as_deref();
+    let built = match format {
+        ExportFormat::Pdf => build_report_pdf(title, &payload.text, &payload.pdf),
+        ExportFormat::Html => build_html_report(title, &payload.text, &payload.markup),
+        ExportFormat::Markdown => build_markdown_report(title, &payload.text, &payload.markup),
+        other => {
+            return Err(bad_request(format!(
+                "Формат {} не поддерживается для отчётов",
+                other.extension()
+            )))
+        }
+    };
+    let bytes = built.map_err(|err| internal_error(format.label(), err))?;
+
+    Ok((attachment_headers(format.mime(), &filename), bytes))
+}
+
+/// Архив дашборда: конфигурация, исходные данные и таблицы виджетов
+async fn export_dashboard(
+    Json(payload): Json<DashboardBundle>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    payload
+        .validate()
+        .map_err(|err| bad_request(err.to_string()))?;
+
+    let filename = download_filename(payload.dashboard_name(), "dashboard", "zip");
+    let bytes = build_dashboard_bundle(&payload).map_err(|err| internal_error("ZIP", err))?;
+
+    println!(
+        "📦 dashboard bundle | datasets {:>3} | tables {:>3} | {:>8.2} KB",
+        payload.datasets.len(),
+        payload.tables.len(),
+        bytes.len() as f64 / 1024.0
     );
 
-    Ok((headers, bytes))
+    Ok((attachment_headers("application/zip", &filename), bytes))
 }
 
-fn sanitize_filename(name: &str) -> String {
-    let cleaned: String = name
-        .chars()
-        .map(|c| {
-            if c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-' {
-                c
-            } else {
-                '_'
-            }
-        })
-        .collect();
-    if cleaned.is_empty() {
-        "export".to_string()
-    } else {
-        cleaned
+fn parse_export_format(
+    value: Option<&str>,
+    default: ExportFormat,
+) -> Result<ExportFormat, (StatusCode, String)> {
+    match value {
+        None => Ok(default),
+        Some(value) => ExportFormat::parse(value)
+            .ok_or_else(|| bad_request(format!("Неподдерживаемый формат экспорта: {}", value))),
     }
 }
 
+fn bad_request(message: String) -> (StatusCode, String) {
+    (StatusCode::BAD_REQUEST, message)
+}
+
 fn internal_error(label: &str, err: anyhow::Error) -> (StatusCode, String) {
     eprintln!("❌ export error ({}): {:?}", label, err);
     (
@@ -274,7 +544,13 @@ async fn main() {
     // ───────────────────────────────
     let cors = CorsLayer::new()
         .allow_origin(allowed_origins)
-        .allow_methods([Method::GET, Method::POST, Method::DELETE, Method::OPTIONS])
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::DELETE,
+            Method::OPTIONS,
+        ])
         .allow_headers([
             axum::http::header::CONTENT_TYPE,
             axum::http::header::AUTHORIZATION,
@@ -285,10 +561,20 @@ async fn main() {
     // ───────────────────────────────
     // Роутер — теперь CORS применяется ГЛОБАЛЬНО
     // ───────────────────────────────
+    let pool = storage::connect().await;
+
     let app = Router::new()
         .route("/api/upload", post(upload))
         .route("/api/export-table", post(export_table))
-        .merge(setup_router().await)
+        .route("/api/export-table/stream", post(export_table_stream))
+        .route("/api/export-report", post(export_report))
+        .route("/api/export-dashboard", post(export_dashboard))
+        .merge(setup_router(pool.clone()).await)
+        .merge(datasets::setup_router(pool.clone()).await)
+        .merge(analytics::setup_router(pool.clone()))
+        .merge(sql::setup_router(pool.clone()))
+        .merge(scheduler::setup_router(pool.clone()).await)
+        .merge(templates::setup_router(pool))
         .route("/api/ping", get(|| async { "pong" })) // тестовый endpoint
         .layer(DefaultBodyLimit::max(50 * 1024 * 1024))
         .layer(cors); // 👈 CORS добавлен последним — применяется ко всем роутам
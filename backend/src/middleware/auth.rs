@@ -12,8 +12,23 @@ use sqlx::Pool;
 use sqlx::Sqlite;
 use std::env;
 
+/// Ранг роли: User < Admin < SuperAdmin; неизвестные роли проходят только при точном совпадении
+fn role_rank(role: &str) -> u8 {
+    match role {
+        "User" => 1,
+        "Admin" => 2,
+        "SuperAdmin" => 3,
+        _ => 0,
+    }
+}
+
+/// Роль не ниже требуемой
+pub fn has_role(role: &str, required_role: &str) -> bool {
+    role == required_role || role_rank(role) >= role_rank(required_role).max(1)
+}
+
 /// Проверка роли пользователя по JWT-токену из куки.
-/// Если `required_role` не совпадает с ролью токена → 403.
+/// Если роль токена ниже `required_role` → 403.
 pub async fn require_role(
     State(_pool): State<Pool<Sqlite>>,
     jar: CookieJar,
@@ -49,7 +64,7 @@ pub async fn require_role(
     };
 
     // проверка роли
-    if claims.role != required_role && claims.role != "SuperAdmin" {
+    if !has_role(&claims.role, required_role) {
         return Err((
             StatusCode::FORBIDDEN,
             axum::Json(json!({"error": "Forbidden"})),
@@ -0,0 +1,314 @@
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, Datelike, Duration, DurationRound, TimeZone, Timelike, Utc};
+
+/// Сколько шагов поиска следующего срабатывания делается до признания
+/// расписания невыполнимым (например, `0 0 30 2 *`)
+const MAX_SEARCH_STEPS: usize = 20_000;
+
+const MONTH_NAMES: &[&str] = &[
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const DAY_NAMES: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// Расписание в формате cron из пяти полей: минута, час, день месяца,
+/// месяц, день недели. Время — UTC. Поддерживаются `*`, списки, диапазоны,
+/// шаги, имена месяцев и дней недели и сокращения `@hourly`, `@daily` и т.п.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// День месяца и день недели заданы оба — срабатывает любой из них
+    day_or_weekday: bool,
+}
+
+struct Field {
+    label: &'static str,
+    min: u32,
+    max: u32,
+    names: &'static [&'static str],
+    /// Смещение номера первого имени (январь — 1, воскресенье — 0)
+    names_from: u32,
+}
+
+const MINUTE: Field = Field {
+    label: "минута",
+    min: 0,
+    max: 59,
+    names: &[],
+    names_from: 0,
+};
+const HOUR: Field = Field {
+    label: "час",
+    min: 0,
+    max: 23,
+    names: &[],
+    names_from: 0,
+};
+const DAY: Field = Field {
+    label: "день месяца",
+    min: 1,
+    max: 31,
+    names: &[],
+    names_from: 0,
+};
+const MONTH: Field = Field {
+    label: "месяц",
+    min: 1,
+    max: 12,
+    names: MONTH_NAMES,
+    names_from: 1,
+};
+/// 7 — тоже воскресенье, как в большинстве реализаций cron
+const WEEKDAY: Field = Field {
+    label: "день недели",
+    min: 0,
+    max: 7,
+    names: DAY_NAMES,
+    names_from: 0,
+};
+
+impl Field {
+    fn value(&self, text: &str) -> Result<u32> {
+        let lower = text.to_ascii_lowercase();
+        let value = match self.names.iter().position(|name| *name == lower) {
+            Some(idx) => idx as u32 + self.names_from,
+            None => text
+                .parse::<u32>()
+                .map_err(|_| anyhow!("{}: некорректное значение «{}»", self.label, text))?,
+        };
+        if value < self.min || value > self.max {
+            bail!(
+                "{}: значение {} вне диапазона {}–{}",
+                self.label,
+                value,
+                self.min,
+                self.max
+            );
+        }
+        Ok(value)
+    }
+
+    /// Битовая маска значений поля; второй элемент — поле начинается с `*`
+    /// (`*`, `*/2`): такие дни месяца и недели не ограничивают друг друга
+    fn parse(&self, text: &str) -> Result<(u64, bool)> {
+        let mut mask = 0u64;
+        for part in text.split(',') {
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => {
+                    let step: u32 =
+                        step.parse().ok().filter(|s| *s > 0).ok_or_else(|| {
+                            anyhow!("{}: некорректный шаг «{}»", self.label, step)
+                        })?;
+                    (range, step)
+                }
+                None => (part, 1),
+            };
+            let (from, to) = if range == "*" {
+                (self.min, self.max)
+            } else if let Some((from, to)) = range.split_once('-') {
+                (self.value(from)?, self.value(to)?)
+            } else {
+                let value = self.value(range)?;
+                // `5/15` — с пятой минуты до конца диапазона
+                (value, if step > 1 { self.max } else { value })
+            };
+            if from > to {
+                bail!("{}: пустой диапазон «{}»", self.label, range);
+            }
+            let mut value = from;
+            while value <= to {
+                mask |= 1 << value;
+                value += step;
+            }
+        }
+        Ok((mask, text.starts_with('*')))
+    }
+}
+
+fn has(mask: u64, value: u32) -> bool {
+    mask & (1 << value) != 0
+}
+
+impl Schedule {
+    pub fn parse(expression: &str) -> Result<Self> {
+        let expression = expression.trim();
+        let expanded = match expression.to_ascii_lowercase().as_str() {
+            "@hourly" => "0 * * * *".to_string(),
+            "@daily" | "@midnight" => "0 0 * * *".to_string(),
+            "@weekly" => "0 0 * * 0".to_string(),
+            "@monthly" => "0 0 1 * *".to_string(),
+            "@yearly" | "@annually" => "0 0 1 1 *".to_string(),
+            _ => expression.to_string(),
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            bail!("Расписание cron состоит из пяти полей: минута час день месяц день_недели");
+        };
+        let (minutes, _) = MINUTE.parse(minute)?;
+        let (hours, _) = HOUR.parse(hour)?;
+        let (days, any_day) = DAY.parse(day)?;
+        let (months, _) = MONTH.parse(month)?;
+        let (mut weekdays, any_weekday) = WEEKDAY.parse(weekday)?;
+        if has(weekdays, 7) {
+            weekdays |= 1;
+        }
+        let schedule = Self {
+            minutes,
+            hours,
+            days,
+            months,
+            weekdays,
+            day_or_weekday: !any_day && !any_weekday,
+        };
+        if schedule.next_after(Utc::now()).is_none() {
+            bail!("Расписание «{}» никогда не срабатывает", expression);
+        }
+        Ok(schedule)
+    }
+
+    fn day_matches(&self, time: &DateTime<Utc>) -> bool {
+        let day = has(self.days, time.day());
+        let weekday = has(self.weekdays, time.weekday().num_days_from_sunday());
+        if self.day_or_weekday {
+            day || weekday
+        } else {
+            day && weekday
+        }
+    }
+
+    /// Ближайшее срабатывание строго после `after`
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut time = after.duration_trunc(Duration::minutes(1)).ok()? + Duration::minutes(1);
+        for _ in 0..MAX_SEARCH_STEPS {
+            if !has(self.months, time.month()) {
+                let (year, month) = if time.month() == 12 {
+                    (time.year() + 1, 1)
+                } else {
+                    (time.year(), time.month() + 1)
+                };
+                time = Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).single()?;
+                continue;
+            }
+            if !self.day_matches(&time) {
+                time = time
+                    .date_naive()
+                    .succ_opt()?
+                    .and_hms_opt(0, 0, 0)?
+                    .and_utc();
+                continue;
+            }
+            if !has(self.hours, time.hour()) {
+                time = time.duration_trunc(Duration::hours(1)).ok()? + Duration::hours(1);
+                continue;
+            }
+            if !has(self.minutes, time.minute()) {
+                time += Duration::minutes(1);
+                continue;
+            }
+            return Some(time);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, hour, minute, 0)
+            .single()
+            .unwrap()
+    }
+
+    /// Следующее срабатывание после субботы 1 июня 2024, 12:00
+    fn next(expression: &str) -> DateTime<Utc> {
+        Schedule::parse(expression)
+            .unwrap()
+            .next_after(at(2024, 6, 1, 12, 0))
+            .unwrap()
+    }
+
+    #[test]
+    fn steps_and_ranges() {
+        let after = at(2024, 6, 1, 10, 7) + Duration::seconds(30);
+        let every_quarter = Schedule::parse("*/15 * * * *").unwrap();
+        assert_eq!(
+            every_quarter.next_after(after),
+            Some(at(2024, 6, 1, 10, 15))
+        );
+        let from_fifth = Schedule::parse("5/20 * * * *").unwrap();
+        assert_eq!(from_fifth.next_after(after), Some(at(2024, 6, 1, 10, 25)));
+        assert_eq!(next("0 9-17 * * 1-5"), at(2024, 6, 3, 9, 0));
+        assert_eq!(next("0 10-14/2 * * *"), at(2024, 6, 1, 14, 0));
+        // срабатывание строго после заданного момента
+        assert_eq!(next("0 12 * * *"), at(2024, 6, 2, 12, 0));
+    }
+
+    #[test]
+    fn month_and_weekday_names() {
+        assert_eq!(next("0 0 1 jan,JUL *"), at(2024, 7, 1, 0, 0));
+        assert_eq!(next("0 9 * * mon-fri"), at(2024, 6, 3, 9, 0));
+        assert_eq!(
+            Schedule::parse("0 9 * * Mon-Fri").unwrap(),
+            Schedule::parse("0 9 * * 1-5").unwrap()
+        );
+    }
+
+    #[test]
+    fn seven_is_sunday() {
+        assert_eq!(next("30 8 * * 7"), at(2024, 6, 2, 8, 30));
+        assert_eq!(next("30 8 * * sun"), at(2024, 6, 2, 8, 30));
+        assert_eq!(next("0 0 * * 5-7"), at(2024, 6, 2, 0, 0));
+    }
+
+    #[test]
+    fn day_of_month_and_weekday() {
+        // заданы оба поля — подходит любое
+        assert_eq!(next("0 0 13 * fri"), at(2024, 6, 7, 0, 0));
+        // поле с `*` не ограничивает: нечётные пятницы, а не все нечётные дни
+        assert_eq!(next("0 0 */2 * fri"), at(2024, 6, 7, 0, 0));
+        assert_eq!(next("0 0 * * fri"), at(2024, 6, 7, 0, 0));
+    }
+
+    #[test]
+    fn impossible_dates() {
+        assert!(Schedule::parse("0 0 30 2 *").is_err());
+        assert!(Schedule::parse("0 0 31 4,6,9,11 *").is_err());
+        let leap = Schedule::parse("0 0 29 2 *").unwrap();
+        assert_eq!(
+            leap.next_after(at(2024, 3, 1, 0, 0)),
+            Some(at(2028, 2, 29, 0, 0))
+        );
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        for expression in [
+            "* * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * * 13 *",
+            "* * * * 8",
+            "*/0 * * * *",
+            "10-5 * * * *",
+            "* * * foo *",
+        ] {
+            assert!(Schedule::parse(expression).is_err(), "{}", expression);
+        }
+    }
+
+    #[test]
+    fn shortcuts() {
+        assert_eq!(
+            Schedule::parse("@daily").unwrap(),
+            Schedule::parse("0 0 * * *").unwrap()
+        );
+        assert_eq!(next("@weekly"), at(2024, 6, 2, 0, 0));
+        assert_eq!(next("@monthly"), at(2024, 7, 1, 0, 0));
+    }
+}
@@ -0,0 +1,230 @@
+use crate::auth::handlers::Claims;
+use crate::datasets::handlers::error;
+use crate::datasets::store::normalize_name;
+use crate::download::attachment_headers;
+use crate::scheduler::cron::Schedule;
+use crate::scheduler::jobs::JobTask;
+use crate::scheduler::store::{self, JobInfo, JobRecord, NewJob, RunInfo};
+use crate::scheduler::{is_running, next_run, spawn_run, TRIGGER_MANUAL};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sqlx::{Pool, Sqlite};
+
+type ApiResult = (StatusCode, Json<Value>);
+
+/// Предел числа повторных попыток после ошибки
+const MAX_RETRIES: i64 = 10;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobInput {
+    pub name: String,
+    pub schedule: String,
+    pub task: Value,
+    #[serde(default)]
+    pub max_retries: i64,
+    #[serde(default)]
+    pub paused: bool,
+}
+
+fn job_info(record: JobRecord) -> Value {
+    let running = is_running(&record.id);
+    let mut info = json!(JobInfo::from(record));
+    info["running"] = json!(running);
+    info
+}
+
+async fn find_job(pool: &Pool<Sqlite>, id: &str) -> Result<JobRecord, ApiResult> {
+    match store::get_job(pool, id).await {
+        Ok(Some(record)) => Ok(record),
+        Ok(None) => Err(error(StatusCode::NOT_FOUND, "Задание не найдено")),
+        Err(e) => Err(error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+}
+
+pub async fn list_jobs(State(pool): State<Pool<Sqlite>>) -> ApiResult {
+    match store::list_jobs(&pool).await {
+        Ok(records) => {
+            let jobs: Vec<Value> = records.into_iter().map(job_info).collect();
+            (StatusCode::OK, Json(json!({ "jobs": jobs })))
+        }
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Новое задание: расписание cron (UTC), задача `datasetRefresh`, `report`
+/// или `export` и число повторных попыток после ошибки
+pub async fn create_job(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(claims): Extension<Claims>,
+    Json(payload): Json<JobInput>,
+) -> ApiResult {
+    let Some(name) = normalize_name(&payload.name) else {
+        return error(StatusCode::BAD_REQUEST, "Некорректное имя задания");
+    };
+    let schedule = match Schedule::parse(&payload.schedule) {
+        Ok(schedule) => schedule,
+        Err(e) => return error(StatusCode::BAD_REQUEST, e.to_string()),
+    };
+    if !(0..=MAX_RETRIES).contains(&payload.max_retries) {
+        return error(
+            StatusCode::BAD_REQUEST,
+            format!("Число повторных попыток — от 0 до {}", MAX_RETRIES),
+        );
+    }
+    let task: JobTask = match serde_json::from_value(payload.task.clone()) {
+        Ok(task) => task,
+        Err(e) => {
+            return error(
+                StatusCode::BAD_REQUEST,
+                format!("Некорректное описание задачи: {}", e),
+            )
+        }
+    };
+    if let Err(e) = task.validate() {
+        return error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+    if let Err(e) = task.check_dataset(&pool).await {
+        return error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+
+    let next_run_at = (!payload.paused)
+        .then(|| schedule.next_after(Utc::now()).map(store::timestamp))
+        .flatten();
+    let job = NewJob {
+        name: &name,
+        schedule: payload.schedule.trim(),
+        kind: task.kind(),
+        task: &payload.task.to_string(),
+        paused: payload.paused,
+        max_retries: payload.max_retries,
+        created_by: &claims.sub,
+        next_run_at,
+    };
+    match store::create_job(&pool, job).await {
+        Ok(record) => {
+            println!(
+                "⏰ job created: {:<25} | {} | {}",
+                record.name, record.kind, record.schedule
+            );
+            (StatusCode::OK, Json(job_info(record)))
+        }
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Задание с историей запусков, новые — первыми
+pub async fn get_job(State(pool): State<Pool<Sqlite>>, Path(id): Path<String>) -> ApiResult {
+    let record = match find_job(&pool, &id).await {
+        Ok(record) => record,
+        Err(response) => return response,
+    };
+    match store::list_runs(&pool, &id).await {
+        Ok(runs) => {
+            let runs: Vec<RunInfo> = runs.into_iter().map(Into::into).collect();
+            (
+                StatusCode::OK,
+                Json(json!({ "job": job_info(record), "runs": runs })),
+            )
+        }
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+pub async fn delete_job(State(pool): State<Pool<Sqlite>>, Path(id): Path<String>) -> ApiResult {
+    match store::delete_job(&pool, &id).await {
+        Ok(true) => {
+            println!("🗑️ job deleted: {}", id);
+            (StatusCode::OK, Json(json!({ "deleted": id })))
+        }
+        Ok(false) => error(StatusCode::NOT_FOUND, "Задание не найдено"),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Запуск вне расписания; выполняется в фоне, итог — в истории запусков.
+/// Работает и для приостановленных заданий
+pub async fn trigger_job(State(pool): State<Pool<Sqlite>>, Path(id): Path<String>) -> ApiResult {
+    let record = match find_job(&pool, &id).await {
+        Ok(record) => record,
+        Err(response) => return response,
+    };
+    if !spawn_run(pool, record.clone(), TRIGGER_MANUAL, 1) {
+        return error(StatusCode::CONFLICT, "Задание уже выполняется");
+    }
+    (StatusCode::ACCEPTED, Json(job_info(record)))
+}
+
+async fn set_paused(pool: Pool<Sqlite>, id: String, paused: bool) -> ApiResult {
+    let record = match find_job(&pool, &id).await {
+        Ok(record) => record,
+        Err(response) => return response,
+    };
+    let next_run_at = if paused {
+        None
+    } else {
+        next_run(&record.schedule, Utc::now())
+    };
+    if let Err(e) = store::set_paused(&pool, &id, paused, next_run_at).await {
+        return error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
+    println!(
+        "{} job {}: {}",
+        if paused { "⏸️" } else { "▶️" },
+        if paused { "paused" } else { "resumed" },
+        record.name
+    );
+    match find_job(&pool, &id).await {
+        Ok(record) => (StatusCode::OK, Json(job_info(record))),
+        Err(response) => response,
+    }
+}
+
+/// Приостановка: задание не запускается по расписанию и не повторяется
+pub async fn pause_job(State(pool): State<Pool<Sqlite>>, Path(id): Path<String>) -> ApiResult {
+    set_paused(pool, id, true).await
+}
+
+pub async fn resume_job(State(pool): State<Pool<Sqlite>>, Path(id): Path<String>) -> ApiResult {
+    set_paused(pool, id, false).await
+}
+
+/// Файл, который сформировал запуск отчёта или выгрузки
+pub async fn run_output(
+    State(pool): State<Pool<Sqlite>>,
+    Path((id, run_id)): Path<(String, i64)>,
+) -> Result<Response, ApiResult> {
+    let run = match store::get_run(&pool, &id, run_id).await {
+        Ok(Some(run)) => run,
+        Ok(None) => return Err(error(StatusCode::NOT_FOUND, "Запуск не найден")),
+        Err(e) => return Err(error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    };
+    let Some(stored) = run.output_file else {
+        return Err(error(StatusCode::NOT_FOUND, "Запуск не сформировал файл"));
+    };
+    let path = store::output_dir(&id)
+        .map_err(|e| error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .join(&stored);
+    let bytes = match tokio::fs::read(&path).await {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(error(StatusCode::NOT_FOUND, "Файл запуска не найден"))
+        }
+        Err(e) => return Err(error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    };
+    let filename = stored
+        .split_once('-')
+        .map_or(stored.as_str(), |(_, name)| name);
+    let mime = filename
+        .rsplit_once('.')
+        .and_then(|(_, ext)| crate::exporter::ExportFormat::parse(ext))
+        .map_or("application/octet-stream", |format| format.mime());
+    Ok((attachment_headers(mime, filename), bytes).into_response())
+}
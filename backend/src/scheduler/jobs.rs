@@ -0,0 +1,278 @@
+use crate::analytics::columnar;
+use crate::analytics::format::Locale;
+use crate::analytics::report::{evaluate_report, FormatOptions, ReportConfig};
+use crate::datasets::handlers::load_table;
+use crate::datasets::models::DatasetRecord;
+use crate::datasets::{refresh, store};
+use crate::download::download_filename;
+use crate::exporter::{
+    build_delimited, build_html_report, build_html_table, build_json, build_markdown_report,
+    build_markdown_table, build_ndjson, build_ods, build_report_pdf, build_table_pdf, build_xlsx,
+    CsvOptions, ExportFormat, MarkupOptions, PdfOptions, SpreadsheetOptions,
+};
+use anyhow::{anyhow, bail, Result};
+use axum::{http::StatusCode, Json};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sqlx::{Pool, Sqlite};
+
+pub const KIND_DATASET_REFRESH: &str = "datasetRefresh";
+pub const KIND_REPORT: &str = "report";
+pub const KIND_EXPORT: &str = "export";
+
+/// Что делает задание; хранится JSON-объектом с полем `kind`
+#[derive(Deserialize, Clone, Debug)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum JobTask {
+    /// Обновление набора из его источника
+    DatasetRefresh { dataset_id: String },
+    /// Расчёт отчёта по набору; с `format` (pdf, html, md) — ещё и файл
+    Report {
+        dataset_id: String,
+        config: ReportConfig,
+        #[serde(default)]
+        locale: Option<String>,
+        #[serde(default)]
+        currency: Option<String>,
+        #[serde(default)]
+        format: Option<String>,
+        #[serde(default)]
+        filename: Option<String>,
+        #[serde(default)]
+        pdf: PdfOptions,
+        #[serde(default)]
+        markup: MarkupOptions,
+    },
+    /// Выгрузка строк набора в файл любого формата экспорта таблиц
+    Export {
+        dataset_id: String,
+        format: String,
+        #[serde(default)]
+        filename: Option<String>,
+        #[serde(default)]
+        csv: CsvOptions,
+        #[serde(default)]
+        spreadsheet: SpreadsheetOptions,
+        #[serde(default)]
+        pdf: PdfOptions,
+        #[serde(default)]
+        markup: MarkupOptions,
+    },
+}
+
+/// Результат запуска: краткая сводка для истории и, возможно, файл
+pub struct JobOutput {
+    pub summary: Value,
+    pub file: Option<(String, Vec<u8>)>,
+}
+
+fn report_format(value: &str) -> Result<ExportFormat> {
+    match ExportFormat::parse(value) {
+        Some(format @ (ExportFormat::Pdf | ExportFormat::Html | ExportFormat::Markdown)) => {
+            Ok(format)
+        }
+        _ => bail!("Формат {} не поддерживается для отчётов", value),
+    }
+}
+
+fn export_format(value: &str) -> Result<ExportFormat> {
+    ExportFormat::parse(value).ok_or_else(|| anyhow!("Неподдерживаемый формат экспорта: {}", value))
+}
+
+impl JobTask {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            JobTask::DatasetRefresh { .. } => KIND_DATASET_REFRESH,
+            JobTask::Report { .. } => KIND_REPORT,
+            JobTask::Export { .. } => KIND_EXPORT,
+        }
+    }
+
+    pub fn dataset_id(&self) -> &str {
+        match self {
+            JobTask::DatasetRefresh { dataset_id }
+            | JobTask::Report { dataset_id, .. }
+            | JobTask::Export { dataset_id, .. } => dataset_id,
+        }
+    }
+
+    /// Проверка параметров при создании задания; набор проверяется отдельно
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            JobTask::DatasetRefresh { .. } => {}
+            JobTask::Report {
+                locale,
+                format,
+                pdf,
+                ..
+            } => {
+                if let Some(locale) = locale {
+                    Locale::parse(locale)?;
+                }
+                if let Some(format) = format {
+                    report_format(format)?;
+                }
+                pdf.validate()?;
+            }
+            JobTask::Export {
+                format, csv, pdf, ..
+            } => {
+                export_format(format)?;
+                csv.validate()?;
+                pdf.validate()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Набор, с которым работает задание, и допустимость задачи для него
+    pub async fn check_dataset(&self, pool: &Pool<Sqlite>) -> Result<DatasetRecord> {
+        let record = find_dataset(pool, self.dataset_id()).await?;
+        if matches!(self, JobTask::DatasetRefresh { .. }) && record.refresh_source.is_none() {
+            bail!("У набора «{}» нет источника обновления", record.name);
+        }
+        Ok(record)
+    }
+}
+
+async fn find_dataset(pool: &Pool<Sqlite>, id: &str) -> Result<DatasetRecord> {
+    if !store::is_valid_id(id) {
+        bail!("Набор данных {} не найден", id);
+    }
+    store::get_dataset(pool, id)
+        .await?
+        .ok_or_else(|| anyhow!("Набор данных {} не найден", id))
+}
+
+/// Ошибка загрузки набора — текстом ответа API
+fn load_error((_, body): (StatusCode, Json<Value>)) -> anyhow::Error {
+    anyhow!(body.0["error"]
+        .as_str()
+        .unwrap_or("Не удалось прочитать набор данных")
+        .to_string())
+}
+
+/// Строки набора с вычисляемыми колонками
+async fn read_rows(
+    pool: &Pool<Sqlite>,
+    record: &DatasetRecord,
+) -> Result<crate::datasets::store::StoredTable> {
+    load_table(pool, record).await.map_err(load_error)
+}
+
+/// Выполнение задачи; задания создаёт администратор, поэтому права
+/// владельца набора не проверяются
+pub async fn execute(pool: &Pool<Sqlite>, task: &JobTask) -> Result<JobOutput> {
+    let record = task.check_dataset(pool).await?;
+    match task {
+        JobTask::DatasetRefresh { .. } => {
+            let refreshed = refresh::refresh_dataset(pool, &record).await?;
+            Ok(JobOutput {
+                summary: json!({
+                    "version": refreshed.record.version,
+                    "rows": refreshed.record.row_count,
+                    "warnings": refreshed.report.warnings,
+                }),
+                file: None,
+            })
+        }
+        JobTask::Report {
+            config,
+            locale,
+            currency,
+            format,
+            filename,
+            pdf,
+            markup,
+            ..
+        } => {
+            let mut options = FormatOptions::default();
+            if let Some(locale) = locale {
+                options.locale = Locale::parse(locale)?;
+            }
+            if let Some(currency) = currency.clone().filter(|c| !c.trim().is_empty()) {
+                options.currency = currency;
+            }
+            let table = columnar::load(pool, &record).await.map_err(load_error)?;
+            let (config, format, filename, pdf, markup) = (
+                config.clone(),
+                format.clone(),
+                filename.clone(),
+                pdf.clone(),
+                markup.clone(),
+            );
+            tokio::task::spawn_blocking(move || {
+                let result = evaluate_report(&table, &config, &options);
+                let file = match format.as_deref() {
+                    None => None,
+                    Some(format) => {
+                        let format = report_format(format)?;
+                        let title = result.title.as_deref();
+                        let bytes = match format {
+                            ExportFormat::Pdf => build_report_pdf(title, &result.rendered, &pdf)?,
+                            ExportFormat::Html => {
+                                build_html_report(title, &result.rendered, &markup)?
+                            }
+                            _ => build_markdown_report(title, &result.rendered, &markup)?,
+                        };
+                        let name =
+                            download_filename(filename.as_deref(), "report", format.extension());
+                        Some((name, bytes))
+                    }
+                };
+                Ok(JobOutput {
+                    summary: json!(result),
+                    file,
+                })
+            })
+            .await?
+        }
+        JobTask::Export {
+            format,
+            filename,
+            csv,
+            spreadsheet,
+            pdf,
+            markup,
+            ..
+        } => {
+            let format = export_format(format)?;
+            let table = read_rows(pool, &record).await?;
+            let (filename, csv, spreadsheet, pdf, markup) = (
+                filename.clone(),
+                csv.clone(),
+                spreadsheet.clone(),
+                pdf.clone(),
+                markup.clone(),
+            );
+            let default_stem = record.name.clone();
+            tokio::task::spawn_blocking(move || {
+                let (columns, rows) = (&table.columns, &table.rows);
+                if matches!(format, ExportFormat::Xlsx | ExportFormat::Ods) {
+                    spreadsheet.validate(columns, rows)?;
+                }
+                let bytes = match format {
+                    ExportFormat::Xlsx => build_xlsx(columns, rows, &spreadsheet)?,
+                    ExportFormat::Ods => build_ods(columns, rows, &spreadsheet)?,
+                    ExportFormat::Csv => build_delimited(columns, rows, b',', &csv)?,
+                    ExportFormat::Tsv => build_delimited(columns, rows, b'\t', &csv)?,
+                    ExportFormat::Json => build_json(columns, rows)?,
+                    ExportFormat::Ndjson => build_ndjson(columns, rows)?,
+                    ExportFormat::Pdf => build_table_pdf(columns, rows, &pdf)?,
+                    ExportFormat::Html => build_html_table(columns, rows, &markup)?,
+                    ExportFormat::Markdown => build_markdown_table(columns, rows, &markup)?,
+                };
+                let name = download_filename(filename.as_deref(), &default_stem, format.extension());
+                Ok(JobOutput {
+                    summary: json!({ "rows": rows.len(), "bytes": bytes.len(), "format": format.extension() }),
+                    file: Some((name, bytes)),
+                })
+            })
+            .await?
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use axum::routing::{get, post};
+use axum::Router;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use sqlx::{Pool, Sqlite};
+use std::collections::HashSet;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+pub mod cron;
+pub mod handlers;
+pub mod jobs;
+pub mod store;
+
+use crate::protect;
+use cron::Schedule;
+use jobs::JobTask;
+use store::{JobRecord, STATUS_ERROR, STATUS_OK};
+
+/// Как часто планировщик проверяет, не пора ли запускать задания
+const TICK: Duration = Duration::from_secs(15);
+/// Задержка первой повторной попытки; дальше она удваивается
+const RETRY_BASE_SECS: i64 = 60;
+const RETRY_MAX_SECS: i64 = 60 * 60;
+
+pub const TRIGGER_SCHEDULE: &str = "schedule";
+pub const TRIGGER_RETRY: &str = "retry";
+pub const TRIGGER_MANUAL: &str = "manual";
+
+static RUNNING: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+
+fn running() -> std::sync::MutexGuard<'static, HashSet<String>> {
+    RUNNING
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+/// Задание уже выполняется — второй экземпляр не запускается
+pub fn is_running(job_id: &str) -> bool {
+    running().contains(job_id)
+}
+
+fn claim(job_id: &str) -> bool {
+    running().insert(job_id.to_string())
+}
+
+fn release(job_id: &str) {
+    running().remove(job_id);
+}
+
+/// Следующее срабатывание расписания после `after`
+pub fn next_run(schedule: &str, after: DateTime<Utc>) -> Option<String> {
+    Schedule::parse(schedule)
+        .ok()
+        .and_then(|s| s.next_after(after))
+        .map(store::timestamp)
+}
+
+fn retry_delay(attempt: i64) -> i64 {
+    let exponent = (attempt - 1).clamp(0, 16) as u32;
+    (RETRY_BASE_SECS * 2i64.pow(exponent)).min(RETRY_MAX_SECS)
+}
+
+/// Запуск задания в фоне; `false` — задание уже выполняется
+pub fn spawn_run(pool: Pool<Sqlite>, job: JobRecord, trigger: &'static str, attempt: i64) -> bool {
+    if !claim(&job.id) {
+        return false;
+    }
+    tokio::spawn(async move {
+        let job_id = job.id.clone();
+        if let Err(e) = run(&pool, &job, trigger, attempt).await {
+            eprintln!("❌ job {}: {}", job.name, e);
+        }
+        release(&job_id);
+    });
+    true
+}
+
+async fn run(
+    pool: &Pool<Sqlite>,
+    job: &JobRecord,
+    trigger: &str,
+    attempt: i64,
+) -> anyhow::Result<()> {
+    let started = Instant::now();
+    let run_id = store::start_run(pool, &job.id, trigger, attempt).await?;
+    let result = match serde_json::from_str::<JobTask>(&job.task) {
+        Ok(task) => jobs::execute(pool, &task).await,
+        Err(e) => Err(anyhow::anyhow!("Некорректное описание задания: {}", e)),
+    };
+    // задание могли удалить, пока оно выполнялось
+    if store::get_job(pool, &job.id).await?.is_none() {
+        return Ok(());
+    }
+
+    match result {
+        Ok(output) => {
+            let output_file = match output.file {
+                Some((name, bytes)) => {
+                    let stored = format!("{}-{}", run_id, name);
+                    let dir = store::output_dir(&job.id)?;
+                    let path = dir.join(&stored);
+                    tokio::task::spawn_blocking(move || {
+                        std::fs::create_dir_all(&dir)?;
+                        std::fs::write(path, bytes)
+                    })
+                    .await??;
+                    Some(stored)
+                }
+                None => None,
+            };
+            let summary = serde_json::to_string(&output.summary)?;
+            store::finish_run(
+                pool,
+                run_id,
+                STATUS_OK,
+                None,
+                Some(&summary),
+                output_file.as_deref(),
+            )
+            .await?;
+            store::set_last_run(pool, &job.id, STATUS_OK, None, None).await?;
+            println!(
+                "⏰ job {:<25} | {:<14} | {:<8} | ok | {:>6} ms",
+                job.name,
+                job.kind,
+                trigger,
+                started.elapsed().as_millis()
+            );
+        }
+        Err(e) => {
+            let message = e.to_string();
+            let retry = (attempt <= job.max_retries).then(|| {
+                let delay = retry_delay(attempt);
+                let at = store::timestamp(Utc::now() + ChronoDuration::seconds(delay));
+                (at, attempt)
+            });
+            store::finish_run(pool, run_id, STATUS_ERROR, Some(&message), None, None).await?;
+            store::set_last_run(pool, &job.id, STATUS_ERROR, Some(&message), retry.clone()).await?;
+            match retry {
+                Some((at, _)) => eprintln!(
+                    "❌ job failed: {} — {} | повтор {} в {}",
+                    job.name,
+                    message,
+                    attempt + 1,
+                    at
+                ),
+                None => eprintln!("❌ job failed: {} — {}", job.name, message),
+            }
+        }
+    }
+    store::prune_runs(pool, &job.id).await
+}
+
+/// Один проход планировщика: запуск заданий, которым пора. Запуск по
+/// расписанию отменяет ожидающую повторную попытку
+async fn tick(pool: &Pool<Sqlite>) -> anyhow::Result<()> {
+    let now = Utc::now();
+    let now_text = store::timestamp(now);
+    for job in store::due_jobs(pool, &now_text).await? {
+        let by_schedule = job
+            .next_run_at
+            .as_deref()
+            .is_some_and(|at| at <= now_text.as_str());
+        if by_schedule {
+            // пропущенные, пока сервер стоял или задание выполнялось,
+            // срабатывания не догоняются — только одно ближайшее
+            store::set_next_run(pool, &job.id, next_run(&job.schedule, now)).await?;
+        }
+        if is_running(&job.id) {
+            continue;
+        }
+        let (trigger, attempt) = if by_schedule {
+            (TRIGGER_SCHEDULE, 1)
+        } else {
+            (TRIGGER_RETRY, job.retry_attempt + 1)
+        };
+        spawn_run(pool.clone(), job, trigger, attempt);
+    }
+    Ok(())
+}
+
+fn start(pool: Pool<Sqlite>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(TICK);
+        loop {
+            interval.tick().await;
+            if let Err(e) = tick(&pool).await {
+                eprintln!("⚠️ scheduler: {}", e);
+            }
+        }
+    });
+}
+
+/// Планировщик заданий: таблицы в SQLite, фоновый цикл в рантайме Tokio
+/// и маршруты администратора
+pub async fn setup_router(pool: Pool<Sqlite>) -> Router {
+    if let Err(e) = sqlx::query(store::JOBS_MIGRATION).execute(&pool).await {
+        panic!("❌ Jobs migration failed: {}", e);
+    }
+    match store::fail_interrupted_runs(&pool).await {
+        Ok(0) => {}
+        Ok(count) => eprintln!("⚠️ scheduler: прервано запусков при остановке: {}", count),
+        Err(e) => eprintln!("⚠️ scheduler: {}", e),
+    }
+    start(pool.clone());
+    println!("⏰ Scheduler started");
+
+    Router::new()
+        .route(
+            "/api/jobs",
+            get(handlers::list_jobs)
+                .post(handlers::create_job)
+                .route_layer(protect!(pool, "Admin")),
+        )
+        .route(
+            "/api/jobs/:id",
+            get(handlers::get_job)
+                .delete(handlers::delete_job)
+                .route_layer(protect!(pool, "Admin")),
+        )
+        .route(
+            "/api/jobs/:id/run",
+            post(handlers::trigger_job).route_layer(protect!(pool, "Admin")),
+        )
+        .route(
+            "/api/jobs/:id/pause",
+            post(handlers::pause_job).route_layer(protect!(pool, "Admin")),
+        )
+        .route(
+            "/api/jobs/:id/resume",
+            post(handlers::resume_job).route_layer(protect!(pool, "Admin")),
+        )
+        .route(
+            "/api/jobs/:id/runs/:run/output",
+            get(handlers::run_output).route_layer(protect!(pool, "Admin")),
+        )
+        .with_state(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retry_delay_doubles_up_to_the_limit() {
+        assert_eq!(retry_delay(0), RETRY_BASE_SECS);
+        assert_eq!(retry_delay(1), 60);
+        assert_eq!(retry_delay(2), 120);
+        assert_eq!(retry_delay(3), 240);
+        assert_eq!(retry_delay(6), 1920);
+        assert_eq!(retry_delay(7), RETRY_MAX_SECS);
+        assert_eq!(retry_delay(i64::MAX), RETRY_MAX_SECS);
+    }
+}
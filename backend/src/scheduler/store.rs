@@ -0,0 +1,453 @@
+use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::{FromRow, Pool, Sqlite};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::storage::data_subdir;
+
+pub const JOBS_MIGRATION: &str = r#"
+CREATE TABLE IF NOT EXISTS jobs (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    schedule TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    task TEXT NOT NULL,
+    paused INTEGER NOT NULL DEFAULT 0,
+    max_retries INTEGER NOT NULL DEFAULT 0,
+    created_by TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    next_run_at TEXT,
+    retry_at TEXT,
+    retry_attempt INTEGER NOT NULL DEFAULT 0,
+    last_run_at TEXT,
+    last_status TEXT,
+    last_error TEXT
+);
+CREATE TABLE IF NOT EXISTS job_runs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    job_id TEXT NOT NULL,
+    trigger TEXT NOT NULL,
+    attempt INTEGER NOT NULL DEFAULT 1,
+    started_at TEXT NOT NULL,
+    finished_at TEXT,
+    status TEXT NOT NULL,
+    error TEXT,
+    output TEXT,
+    output_file TEXT
+);
+CREATE INDEX IF NOT EXISTS idx_job_runs_job ON job_runs (job_id, id);
+"#;
+
+/// Сколько запусков одного задания хранится в истории
+pub const MAX_JOB_RUNS: i64 = 50;
+
+const OUTPUTS_DIR: &str = "job_outputs";
+
+const JOB_COLUMNS: &str = "id, name, schedule, kind, task, paused, max_retries, created_by, \
+    created_at, updated_at, next_run_at, retry_at, retry_attempt, last_run_at, last_status, last_error";
+const RUN_COLUMNS: &str =
+    "id, job_id, trigger, attempt, started_at, finished_at, status, error, output, output_file";
+
+pub const STATUS_RUNNING: &str = "running";
+pub const STATUS_OK: &str = "ok";
+pub const STATUS_ERROR: &str = "error";
+
+/// Строка таблицы `jobs`; описание задачи хранится JSON-объектом в `task`
+#[derive(FromRow, Debug, Clone)]
+pub struct JobRecord {
+    pub id: String,
+    pub name: String,
+    pub schedule: String,
+    pub kind: String,
+    pub task: String,
+    pub paused: bool,
+    pub max_retries: i64,
+    pub created_by: String,
+    pub created_at: String,
+    pub updated_at: String,
+    pub next_run_at: Option<String>,
+    /// Время следующей попытки после ошибки
+    pub retry_at: Option<String>,
+    pub retry_attempt: i64,
+    pub last_run_at: Option<String>,
+    pub last_status: Option<String>,
+    pub last_error: Option<String>,
+}
+
+#[derive(FromRow, Debug, Clone)]
+pub struct RunRecord {
+    pub id: i64,
+    pub job_id: String,
+    pub trigger: String,
+    pub attempt: i64,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub status: String,
+    pub error: Option<String>,
+    pub output: Option<String>,
+    pub output_file: Option<String>,
+}
+
+/// Задание в ответах API
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct JobInfo {
+    pub id: String,
+    pub name: String,
+    pub schedule: String,
+    pub kind: String,
+    pub task: Value,
+    pub paused: bool,
+    pub max_retries: i64,
+    pub created_by: String,
+    pub created_at: String,
+    pub updated_at: String,
+    pub next_run_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_at: Option<String>,
+    pub last_run_at: Option<String>,
+    pub last_status: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+impl From<JobRecord> for JobInfo {
+    fn from(record: JobRecord) -> Self {
+        Self {
+            task: serde_json::from_str(&record.task).unwrap_or(Value::Null),
+            id: record.id,
+            name: record.name,
+            schedule: record.schedule,
+            kind: record.kind,
+            paused: record.paused,
+            max_retries: record.max_retries,
+            created_by: record.created_by,
+            created_at: record.created_at,
+            updated_at: record.updated_at,
+            next_run_at: record.next_run_at,
+            retry_at: record.retry_at,
+            last_run_at: record.last_run_at,
+            last_status: record.last_status,
+            last_error: record.last_error,
+        }
+    }
+}
+
+/// Запуск задания в ответах API
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RunInfo {
+    pub id: i64,
+    pub job_id: String,
+    pub trigger: String,
+    pub attempt: i64,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_file: Option<String>,
+}
+
+impl From<RunRecord> for RunInfo {
+    fn from(record: RunRecord) -> Self {
+        Self {
+            output: record
+                .output
+                .as_deref()
+                .and_then(|o| serde_json::from_str(o).ok()),
+            id: record.id,
+            job_id: record.job_id,
+            trigger: record.trigger,
+            attempt: record.attempt,
+            started_at: record.started_at,
+            finished_at: record.finished_at,
+            status: record.status,
+            error: record.error,
+            output_file: record.output_file,
+        }
+    }
+}
+
+pub fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn now() -> String {
+    timestamp(Utc::now())
+}
+
+/// Каталог файлов, которые сформировали запуски задания
+pub fn output_dir(job_id: &str) -> io::Result<PathBuf> {
+    Ok(data_subdir(OUTPUTS_DIR)?.join(job_id))
+}
+
+fn remove_outputs(job_id: &str, files: &[String]) -> io::Result<()> {
+    let dir = output_dir(job_id)?;
+    for name in files {
+        match fs::remove_file(dir.join(name)) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+pub struct NewJob<'a> {
+    pub name: &'a str,
+    pub schedule: &'a str,
+    pub kind: &'a str,
+    pub task: &'a str,
+    pub paused: bool,
+    pub max_retries: i64,
+    pub created_by: &'a str,
+    pub next_run_at: Option<String>,
+}
+
+pub async fn create_job(pool: &Pool<Sqlite>, job: NewJob<'_>) -> Result<JobRecord> {
+    let id = uuid::Uuid::new_v4().to_string();
+    let timestamp = now();
+    sqlx::query(
+        "INSERT INTO jobs (id, name, schedule, kind, task, paused, max_retries, created_by, created_at, updated_at, next_run_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+    )
+    .bind(&id)
+    .bind(job.name)
+    .bind(job.schedule)
+    .bind(job.kind)
+    .bind(job.task)
+    .bind(job.paused)
+    .bind(job.max_retries)
+    .bind(job.created_by)
+    .bind(&timestamp)
+    .bind(&timestamp)
+    .bind(job.next_run_at)
+    .execute(pool)
+    .await?;
+    get_job(pool, &id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("задание {} не найдено после вставки", id))
+}
+
+pub async fn get_job(pool: &Pool<Sqlite>, id: &str) -> sqlx::Result<Option<JobRecord>> {
+    sqlx::query_as::<_, JobRecord>(&format!("SELECT {} FROM jobs WHERE id = ?", JOB_COLUMNS))
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn list_jobs(pool: &Pool<Sqlite>) -> sqlx::Result<Vec<JobRecord>> {
+    sqlx::query_as::<_, JobRecord>(&format!(
+        "SELECT {} FROM jobs ORDER BY created_at DESC",
+        JOB_COLUMNS
+    ))
+    .fetch_all(pool)
+    .await
+}
+
+/// Задания, которым пора запускаться по расписанию или для повторной попытки
+pub async fn due_jobs(pool: &Pool<Sqlite>, now: &str) -> sqlx::Result<Vec<JobRecord>> {
+    sqlx::query_as::<_, JobRecord>(&format!(
+        "SELECT {} FROM jobs WHERE paused = 0 AND (next_run_at <= ? OR retry_at <= ?)",
+        JOB_COLUMNS
+    ))
+    .bind(now)
+    .bind(now)
+    .fetch_all(pool)
+    .await
+}
+
+/// Пауза и возобновление; при возобновлении расписание считается заново
+pub async fn set_paused(
+    pool: &Pool<Sqlite>,
+    id: &str,
+    paused: bool,
+    next_run_at: Option<String>,
+) -> sqlx::Result<bool> {
+    let result = sqlx::query(
+        "UPDATE jobs SET paused = ?, next_run_at = ?, retry_at = NULL, retry_attempt = 0, updated_at = ?
+         WHERE id = ?",
+    )
+    .bind(paused)
+    .bind(next_run_at)
+    .bind(now())
+    .bind(id)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Следующее срабатывание по расписанию — сдвигается до запуска, чтобы
+/// задание не стартовало дважды
+pub async fn set_next_run(
+    pool: &Pool<Sqlite>,
+    id: &str,
+    next_run_at: Option<String>,
+) -> sqlx::Result<()> {
+    sqlx::query("UPDATE jobs SET next_run_at = ? WHERE id = ?")
+        .bind(next_run_at)
+        .bind(id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Итог запуска в записи задания и время повторной попытки, если она нужна
+pub async fn set_last_run(
+    pool: &Pool<Sqlite>,
+    id: &str,
+    status: &str,
+    error: Option<&str>,
+    retry: Option<(String, i64)>,
+) -> sqlx::Result<()> {
+    let (retry_at, retry_attempt) = match retry {
+        Some((at, attempt)) => (Some(at), attempt),
+        None => (None, 0),
+    };
+    sqlx::query(
+        "UPDATE jobs SET last_run_at = ?, last_status = ?, last_error = ?, retry_at = ?, retry_attempt = ?
+         WHERE id = ?",
+    )
+    .bind(now())
+    .bind(status)
+    .bind(error)
+    .bind(retry_at)
+    .bind(retry_attempt)
+    .bind(id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn start_run(
+    pool: &Pool<Sqlite>,
+    job_id: &str,
+    trigger: &str,
+    attempt: i64,
+) -> sqlx::Result<i64> {
+    let result = sqlx::query(
+        "INSERT INTO job_runs (job_id, trigger, attempt, started_at, status) VALUES (?, ?, ?, ?, ?)",
+    )
+    .bind(job_id)
+    .bind(trigger)
+    .bind(attempt)
+    .bind(now())
+    .bind(STATUS_RUNNING)
+    .execute(pool)
+    .await?;
+    Ok(result.last_insert_rowid())
+}
+
+pub async fn finish_run(
+    pool: &Pool<Sqlite>,
+    run_id: i64,
+    status: &str,
+    error: Option<&str>,
+    output: Option<&str>,
+    output_file: Option<&str>,
+) -> sqlx::Result<()> {
+    sqlx::query(
+        "UPDATE job_runs SET finished_at = ?, status = ?, error = ?, output = ?, output_file = ?
+         WHERE id = ?",
+    )
+    .bind(now())
+    .bind(status)
+    .bind(error)
+    .bind(output)
+    .bind(output_file)
+    .bind(run_id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Запуски, прерванные остановкой сервера, помечаются ошибкой при старте
+pub async fn fail_interrupted_runs(pool: &Pool<Sqlite>) -> sqlx::Result<u64> {
+    let result = sqlx::query(
+        "UPDATE job_runs SET status = ?, finished_at = ?, error = 'Прервано перезапуском сервера'
+         WHERE status = ?",
+    )
+    .bind(STATUS_ERROR)
+    .bind(now())
+    .bind(STATUS_RUNNING)
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+pub async fn list_runs(pool: &Pool<Sqlite>, job_id: &str) -> sqlx::Result<Vec<RunRecord>> {
+    sqlx::query_as::<_, RunRecord>(&format!(
+        "SELECT {} FROM job_runs WHERE job_id = ? ORDER BY id DESC",
+        RUN_COLUMNS
+    ))
+    .bind(job_id)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn get_run(
+    pool: &Pool<Sqlite>,
+    job_id: &str,
+    run_id: i64,
+) -> sqlx::Result<Option<RunRecord>> {
+    sqlx::query_as::<_, RunRecord>(&format!(
+        "SELECT {} FROM job_runs WHERE job_id = ? AND id = ?",
+        RUN_COLUMNS
+    ))
+    .bind(job_id)
+    .bind(run_id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Удаление запусков сверх `MAX_JOB_RUNS` вместе с их файлами
+pub async fn prune_runs(pool: &Pool<Sqlite>, job_id: &str) -> Result<()> {
+    let stale: Vec<(i64, Option<String>)> = sqlx::query_as(
+        "SELECT id, output_file FROM job_runs WHERE job_id = ? ORDER BY id DESC LIMIT -1 OFFSET ?",
+    )
+    .bind(job_id)
+    .bind(MAX_JOB_RUNS)
+    .fetch_all(pool)
+    .await?;
+    let Some(&(newest_stale, _)) = stale.first() else {
+        return Ok(());
+    };
+    sqlx::query("DELETE FROM job_runs WHERE job_id = ? AND id <= ?")
+        .bind(job_id)
+        .bind(newest_stale)
+        .execute(pool)
+        .await?;
+    let files: Vec<String> = stale.into_iter().filter_map(|(_, file)| file).collect();
+    remove_outputs(job_id, &files)?;
+    Ok(())
+}
+
+/// Удаление задания, его истории и сформированных файлов
+pub async fn delete_job(pool: &Pool<Sqlite>, id: &str) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM jobs WHERE id = ?")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Ok(false);
+    }
+    sqlx::query("DELETE FROM job_runs WHERE job_id = ?")
+        .bind(id)
+        .execute(pool)
+        .await?;
+    match fs::remove_dir_all(output_dir(id)?) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+    Ok(true)
+}
@@ -0,0 +1,648 @@
+use crate::analytics::filter::format_number;
+use crate::datasets::models::{DatasetRecord, KIND_FILE, KIND_SQL};
+use crate::datasets::schema::{parse_date, ColumnSchema, ColumnType};
+use crate::datasets::store::{self, StoredTable};
+use crate::exporter::parse_number;
+use anyhow::{anyhow, bail, Result};
+use chrono::Timelike;
+use futures_util::TryStreamExt;
+use libsqlite3_sys as ffi;
+use serde::Serialize;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteRow};
+use sqlx::{Column, ConnectOptions, Connection, Executor, Row, SqliteConnection, Statement};
+use sqlx::{Pool, Sqlite, TypeInfo, ValueRef};
+use std::collections::{HashMap, HashSet};
+use std::ffi::{c_char, c_int, CStr};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_ROW_LIMIT: usize = 10_000;
+pub const MAX_ROW_LIMIT: usize = 100_000;
+/// Предел времени запроса вместе с загрузкой упомянутых в нём таблиц
+pub const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Через сколько инструкций SQLite проверяется предел времени
+const PROGRESS_STEP: i32 = 10_000;
+
+/// Набор данных пользователя, доступный в запросах как таблица
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SqlTable {
+    pub table: String,
+    pub dataset_id: String,
+    pub dataset_name: String,
+    pub columns: Vec<ColumnSchema>,
+}
+
+/// Результат запроса в формате ответа `/api/upload`
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryOutput {
+    #[serde(flatten)]
+    pub table: StoredTable,
+    /// Строк было больше, чем `limit`; лишние отброшены
+    pub truncated: bool,
+    pub elapsed_ms: u128,
+}
+
+/// Ошибка в самом запросе: разбор, проверка, выполнение, предел времени —
+/// отдаётся клиенту как 400. Остальные ошибки — сбои хранилища
+#[derive(Debug)]
+pub struct QueryError(pub String);
+
+impl std::fmt::Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+fn rejected(message: impl Into<String>) -> anyhow::Error {
+    QueryError(message.into()).into()
+}
+
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Имя таблицы из имени набора: без расширения файла, буквы и цифры,
+/// остальное — `_`; «Продажи 2024.xlsx» → `продажи_2024`
+fn table_name(dataset_name: &str) -> String {
+    let stem = match dataset_name.rsplit_once('.') {
+        Some((stem, ext))
+            if !stem.is_empty()
+                && matches!(
+                    ext.to_ascii_lowercase().as_str(),
+                    "csv" | "tsv" | "xlsx" | "xls" | "ods" | "json"
+                ) =>
+        {
+            stem
+        }
+        _ => dataset_name,
+    };
+    let mut name = String::new();
+    for c in stem.chars() {
+        if c.is_alphanumeric() {
+            name.extend(c.to_lowercase());
+        } else if !name.ends_with('_') {
+            name.push('_');
+        }
+    }
+    let name = name.trim_matches('_');
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("t_{}", name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// Разбор текста запроса без выполнения: ровно один оператор. Возвращает все
+/// идентификаторы — по ним выбираются таблицы. Что оператор только читает,
+/// проверяет сама SQLite (`ensure_read_only`), а не поиск ключевых слов
+fn scan(sql: &str) -> Result<HashSet<String>> {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut identifiers = HashSet::new();
+    let mut empty = true;
+    let mut finished = false;
+    let mut i = 0;
+
+    let read_quoted = |start: usize, close: char| -> Result<(String, usize)> {
+        let mut text = String::new();
+        let mut j = start + 1;
+        while j < chars.len() {
+            if chars[j] == close {
+                if close != ']' && chars.get(j + 1) == Some(&close) {
+                    text.push(close);
+                    j += 2;
+                    continue;
+                }
+                return Ok((text, j + 1));
+            }
+            text.push(chars[j]);
+            j += 1;
+        }
+        Err(rejected("Незакрытая кавычка в запросе"))
+    };
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c == '-' && next == Some('-') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        }
+        if c == '/' && next == Some('*') {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                i += 1;
+            }
+            i += 2;
+            continue;
+        }
+        // после `;` допустимы только пустые операторы
+        if finished && c != ';' {
+            return Err(rejected("Допускается только один запрос"));
+        }
+        if c != ';' {
+            empty = false;
+        }
+        match c {
+            '\'' => i = read_quoted(i, '\'')?.1,
+            '"' | '`' | '[' => {
+                let close = if c == '[' { ']' } else { c };
+                let (name, end) = read_quoted(i, close)?;
+                identifiers.insert(name.to_ascii_lowercase());
+                i = end;
+            }
+            ';' => {
+                finished = true;
+                i += 1;
+            }
+            c if c.is_ascii_digit() => {
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '.') {
+                    i += 1;
+                }
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let start = i;
+                while i < chars.len()
+                    && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$')
+                {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                identifiers.insert(word.to_ascii_lowercase());
+            }
+            _ => i += 1,
+        }
+    }
+
+    if empty {
+        return Err(rejected("Пустой запрос"));
+    }
+    Ok(identifiers)
+}
+
+/// Оператор разбирается самой SQLite без выполнения: запрос должен только читать
+/// (`sqlite3_stmt_readonly`) и возвращать строки, что отсекает и запись, и
+/// `ATTACH`, `PRAGMA` с присваиванием, управление транзакциями
+async fn ensure_read_only(conn: &mut SqliteConnection, sql: &str) -> Result<()> {
+    const READ_ONLY: &str = "Разрешены только запросы на чтение (SELECT)";
+    let len = c_int::try_from(sql.len()).map_err(|_| rejected("Слишком длинный запрос"))?;
+    let mut handle = conn.lock_handle().await?;
+    let db = handle.as_raw_handle().as_ptr();
+    let mut stmt: *mut ffi::sqlite3_stmt = std::ptr::null_mut();
+    // SAFETY: `db` — открытое соединение под блокировкой `handle`; текст
+    // передаётся с длиной, без завершающего нуля; оператор финализируется ниже
+    unsafe {
+        let rc = ffi::sqlite3_prepare_v2(
+            db,
+            sql.as_ptr() as *const c_char,
+            len,
+            &mut stmt,
+            std::ptr::null_mut(),
+        );
+        if rc != ffi::SQLITE_OK {
+            let message = CStr::from_ptr(ffi::sqlite3_errmsg(db)).to_string_lossy();
+            return Err(rejected(format!("Ошибка SQL: {}", message)));
+        }
+        if stmt.is_null() {
+            return Err(rejected("Пустой запрос"));
+        }
+        let read_only =
+            ffi::sqlite3_stmt_readonly(stmt) != 0 && ffi::sqlite3_column_count(stmt) > 0;
+        ffi::sqlite3_finalize(stmt);
+        if !read_only {
+            return Err(rejected(READ_ONLY));
+        }
+    }
+    Ok(())
+}
+
+/// Загруженные из файлов наборы владельца с именами таблиц; при совпадении
+/// имён более поздний набор получает суффикс `_2`, `_3`…
+async fn owner_tables(pool: &Pool<Sqlite>, owner: &str) -> Result<Vec<(String, DatasetRecord)>> {
+    Ok(assign_tables(
+        store::list_datasets(pool, Some(owner)).await?,
+    ))
+}
+
+fn assign_tables(records: Vec<DatasetRecord>) -> Vec<(String, DatasetRecord)> {
+    let mut records: Vec<DatasetRecord> = records
+        .into_iter()
+        .filter(|record| record.kind == KIND_FILE)
+        .collect();
+    records.sort_by(|a, b| (&a.uploaded_at, &a.id).cmp(&(&b.uploaded_at, &b.id)));
+
+    let mut used = HashSet::new();
+    let mut tables = Vec::with_capacity(records.len());
+    for record in records {
+        let base = table_name(&record.name);
+        let mut name = base.clone();
+        let mut suffix = 2;
+        while !used.insert(name.to_ascii_lowercase()) {
+            name = format!("{}_{}", base, suffix);
+            suffix += 1;
+        }
+        tables.push((name, record));
+    }
+    tables
+}
+
+/// Имена таблиц (в нижнем регистре) и наборы за ними
+fn table_ids(tables: Vec<(String, DatasetRecord)>) -> HashMap<String, String> {
+    tables
+        .into_iter()
+        .map(|(name, record)| (name.to_ascii_lowercase(), record.id))
+        .collect()
+}
+
+/// Сохранённые запросы владельца, которые после удаления `record` не найдут
+/// свою таблицу или прочтут другой набор: удаление освобождает имя, и
+/// суффиксы `_2`, `_3`… у одноимённых наборов сдвигаются
+pub async fn dependent_queries(pool: &Pool<Sqlite>, record: &DatasetRecord) -> Result<Vec<String>> {
+    if record.kind != KIND_FILE {
+        return Ok(Vec::new());
+    }
+    let records = store::list_datasets(pool, Some(&record.owner)).await?;
+    let before = table_ids(assign_tables(records.clone()));
+    let remaining = records.iter().filter(|r| r.id != record.id).cloned();
+    let after = table_ids(assign_tables(remaining.collect()));
+    let changed: HashSet<String> = before
+        .into_iter()
+        .filter(|(name, id)| after.get(name) != Some(id))
+        .map(|(name, _)| name)
+        .collect();
+    Ok(records
+        .iter()
+        .filter(|r| r.kind == KIND_SQL)
+        .filter(|r| {
+            saved_sql(r)
+                .and_then(|sql| scan(&sql).ok())
+                .is_some_and(|identifiers| !identifiers.is_disjoint(&changed))
+        })
+        .map(|r| r.id.clone())
+        .collect())
+}
+
+pub async fn list_tables(pool: &Pool<Sqlite>, owner: &str) -> Result<Vec<SqlTable>> {
+    Ok(owner_tables(pool, owner)
+        .await?
+        .into_iter()
+        .map(|(table, record)| SqlTable {
+            table,
+            columns: serde_json::from_str(&record.schema).unwrap_or_default(),
+            dataset_id: record.id,
+            dataset_name: record.name,
+        })
+        .collect())
+}
+
+/// Имена колонок таблицы SQLite: пустые заменяются на `column_N`,
+/// повторы (без учёта регистра, как в SQLite) получают суффикс
+fn sql_columns(columns: &[String]) -> Vec<String> {
+    let mut used = HashSet::new();
+    columns
+        .iter()
+        .enumerate()
+        .map(|(idx, name)| {
+            let base = if name.trim().is_empty() {
+                format!("column_{}", idx + 1)
+            } else {
+                name.clone()
+            };
+            let mut candidate = base.clone();
+            let mut suffix = 2;
+            while !used.insert(candidate.to_ascii_lowercase()) {
+                candidate = format!("{}_{}", base, suffix);
+                suffix += 1;
+            }
+            candidate
+        })
+        .collect()
+}
+
+/// Числа — `REAL`, даты — ISO-строки, понятные функциям `date()`/`strftime()`,
+/// пустые значения — `NULL`. После `deadline` загрузка прерывается
+async fn load_table(
+    conn: &mut SqliteConnection,
+    name: &str,
+    schema: &[ColumnSchema],
+    table: &StoredTable,
+    deadline: Instant,
+) -> Result<()> {
+    let columns = sql_columns(&table.columns);
+    let types: Vec<ColumnType> = table
+        .columns
+        .iter()
+        .map(|column| {
+            schema
+                .iter()
+                .find(|s| &s.name == column)
+                .map_or(ColumnType::String, |s| s.column_type)
+        })
+        .collect();
+
+    let definitions: Vec<String> = columns
+        .iter()
+        .zip(&types)
+        .map(|(column, column_type)| {
+            let affinity = if *column_type == ColumnType::Number {
+                "REAL"
+            } else {
+                "TEXT"
+            };
+            format!("{} {}", quote_ident(column), affinity)
+        })
+        .collect();
+    conn.execute(
+        format!(
+            "CREATE TABLE {} ({})",
+            quote_ident(name),
+            definitions.join(", ")
+        )
+        .as_str(),
+    )
+    .await?;
+
+    let insert = format!(
+        "INSERT INTO {} VALUES ({})",
+        quote_ident(name),
+        vec!["?"; columns.len()].join(", ")
+    );
+    let mut tx = conn.begin().await?;
+    for row in &table.rows {
+        if Instant::now() >= deadline {
+            return Err(timed_out());
+        }
+        let mut query = sqlx::query(&insert);
+        for (idx, column_type) in types.iter().enumerate() {
+            let value = row.get(idx).map(String::as_str).unwrap_or("");
+            if value.trim().is_empty() {
+                query = query.bind(None::<String>);
+                continue;
+            }
+            query = match column_type {
+                ColumnType::Number => match parse_number(value) {
+                    Some(number) => query.bind(number),
+                    None => query.bind(value),
+                },
+                ColumnType::Date => match parse_date(value) {
+                    Some(date) if date.num_seconds_from_midnight() == 0 => {
+                        query.bind(date.format("%Y-%m-%d").to_string())
+                    }
+                    Some(date) => query.bind(date.format("%Y-%m-%d %H:%M:%S").to_string()),
+                    None => query.bind(value),
+                },
+                _ => query.bind(value),
+            };
+        }
+        query.execute(&mut *tx).await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+fn timed_out() -> anyhow::Error {
+    rejected(format!(
+        "Превышено время выполнения запроса ({} с)",
+        QUERY_TIMEOUT.as_secs()
+    ))
+}
+
+fn cell_text(row: &SqliteRow, idx: usize) -> Result<String> {
+    let raw = row.try_get_raw(idx)?;
+    if raw.is_null() {
+        return Ok(String::new());
+    }
+    let type_name = raw.type_info().name().to_string();
+    Ok(match type_name.as_str() {
+        "INTEGER" => row.try_get_unchecked::<i64, _>(idx)?.to_string(),
+        "REAL" => format_number(row.try_get_unchecked::<f64, _>(idx)?),
+        "BLOB" => String::from_utf8_lossy(&row.try_get_unchecked::<Vec<u8>, _>(idx)?).into_owned(),
+        _ => row.try_get_unchecked::<String, _>(idx)?,
+    })
+}
+
+/// Выполнение запроса только на чтение над наборами владельца: упомянутые
+/// в запросе наборы копируются во временную базу в памяти
+pub async fn run_query(
+    pool: &Pool<Sqlite>,
+    owner: &str,
+    sql: &str,
+    limit: usize,
+) -> Result<QueryOutput> {
+    if limit == 0 || limit > MAX_ROW_LIMIT {
+        return Err(rejected(format!(
+            "Лимит строк должен быть от 1 до {}",
+            MAX_ROW_LIMIT
+        )));
+    }
+    let identifiers = scan(sql)?;
+    let started = Instant::now();
+    let deadline = started + QUERY_TIMEOUT;
+
+    let mut conn = SqliteConnectOptions::from_str("sqlite::memory:")?
+        .connect()
+        .await?;
+    conn.lock_handle()
+        .await?
+        .set_progress_handler(PROGRESS_STEP, move || Instant::now() < deadline);
+    let interrupted = |e: sqlx::Error| {
+        if Instant::now() >= deadline {
+            timed_out()
+        } else {
+            rejected(format!("Ошибка SQL: {}", e))
+        }
+    };
+
+    for (name, record) in owner_tables(pool, owner).await? {
+        if !identifiers.contains(&name.to_ascii_lowercase()) {
+            continue;
+        }
+        let file_id = record.id.clone();
+        let read = tokio::task::spawn_blocking(move || store::read_table(&file_id));
+        let table = tokio::time::timeout_at(deadline.into(), read)
+            .await
+            .map_err(|_| timed_out())???
+            .ok_or_else(|| anyhow!("Файл набора «{}» не найден", record.name))?;
+        let schema: Vec<ColumnSchema> = serde_json::from_str(&record.schema).unwrap_or_default();
+        load_table(&mut conn, &name, &schema, &table, deadline)
+            .await
+            .map_err(|err| match err.downcast::<sqlx::Error>() {
+                Ok(_) if Instant::now() >= deadline => timed_out(),
+                Ok(e) => e.into(),
+                Err(err) => err,
+            })?;
+    }
+    conn.execute("PRAGMA query_only = ON").await?;
+    ensure_read_only(&mut conn, sql).await?;
+
+    let statement = (&mut conn).prepare(sql).await.map_err(interrupted)?;
+    let columns: Vec<String> = statement
+        .columns()
+        .iter()
+        .map(|c| c.name().to_string())
+        .collect();
+
+    let mut rows = Vec::new();
+    let mut truncated = false;
+    {
+        let mut stream = statement.query().fetch(&mut conn);
+        while let Some(row) = stream.try_next().await.map_err(interrupted)? {
+            if rows.len() == limit {
+                truncated = true;
+                break;
+            }
+            rows.push(
+                (0..columns.len())
+                    .map(|idx| cell_text(&row, idx))
+                    .collect::<Result<Vec<_>>>()?,
+            );
+        }
+    }
+    conn.close().await?;
+
+    Ok(QueryOutput {
+        table: StoredTable { columns, rows },
+        truncated,
+        elapsed_ms: started.elapsed().as_millis(),
+    })
+}
+
+/// Текст запроса из описания виртуального набора
+fn saved_sql(record: &DatasetRecord) -> Option<String> {
+    record
+        .definition
+        .as_deref()
+        .and_then(|d| serde_json::from_str::<serde_json::Value>(d).ok())
+        .and_then(|d| d.get("sql").and_then(|s| s.as_str()).map(str::to_string))
+}
+
+/// Строки виртуального набора: сохранённый запрос выполняется от имени владельца
+pub async fn materialize(pool: &Pool<Sqlite>, record: &DatasetRecord) -> Result<StoredTable> {
+    let sql = saved_sql(record)
+        .ok_or_else(|| anyhow!("У набора «{}» нет сохранённого запроса", record.name))?;
+    let output = run_query(pool, &record.owner, &sql, MAX_ROW_LIMIT).await?;
+    if output.truncated {
+        bail!("Запрос вернул больше {} строк", MAX_ROW_LIMIT);
+    }
+    Ok(output.table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(sql: &str) -> Vec<String> {
+        let mut words: Vec<String> = scan(sql).unwrap().into_iter().collect();
+        words.sort();
+        words
+    }
+
+    #[test]
+    fn scan_collects_identifiers() {
+        assert_eq!(
+            words("SELECT Delete_Flag, \"Сумма \"\"итого\"\"\" FROM [Продажи 2024] JOIN `b`"),
+            vec![
+                "b",
+                "delete_flag",
+                "from",
+                "join",
+                "select",
+                // как в SQLite: без учёта регистра только латиница
+                "Продажи 2024",
+                "Сумма \"итого\""
+            ]
+        );
+        // строки и числа — не идентификаторы
+        assert_eq!(
+            words("SELECT 'orders', 1.5e3 FROM t"),
+            vec!["from", "select", "t"]
+        );
+    }
+
+    #[test]
+    fn scan_skips_comments() {
+        assert_eq!(
+            words("-- UPDATE t\nSELECT a /* DROP TABLE t; */ FROM t; -- конец"),
+            vec!["a", "from", "select", "t"]
+        );
+        assert_eq!(scan("SELECT 1;;").unwrap().len(), 1);
+        assert!(scan("SELECT 1 /* не закрыт").is_ok());
+    }
+
+    #[test]
+    fn scan_allows_one_statement() {
+        let err = scan("SELECT 1; SELECT 2").unwrap_err();
+        assert_eq!(err.to_string(), "Допускается только один запрос");
+        assert!(scan("SELECT 1; DELETE FROM t").is_err());
+        assert!(scan("SELECT ';'; ").is_ok());
+        assert!(scan("SELECT \"a;b\" FROM t").is_ok());
+    }
+
+    #[test]
+    fn scan_rejects_empty_and_unclosed() {
+        assert_eq!(
+            scan("  -- пусто\n").unwrap_err().to_string(),
+            "Пустой запрос"
+        );
+        assert_eq!(scan(";").unwrap_err().to_string(), "Пустой запрос");
+        assert_eq!(
+            scan("SELECT 'abc").unwrap_err().to_string(),
+            "Незакрытая кавычка в запросе"
+        );
+        assert!(scan("SELECT 'abc").unwrap_err().is::<QueryError>());
+    }
+
+    #[tokio::test]
+    async fn only_reading_statements_run() {
+        let mut conn = SqliteConnectOptions::from_str("sqlite::memory:")
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        conn.execute("CREATE TABLE t (\"Update\" TEXT, \"Delete\" REAL, replace TEXT)")
+            .await
+            .unwrap();
+        conn.execute("PRAGMA query_only = ON").await.unwrap();
+
+        for sql in [
+            "SELECT \"Update\", \"Delete\", replace FROM t",
+            "WITH x AS (SELECT 1) SELECT * FROM x",
+            "VALUES (1)",
+            "select * from t where replace = 'DELETE FROM t'",
+        ] {
+            assert!(ensure_read_only(&mut conn, sql).await.is_ok(), "{}", sql);
+        }
+        for sql in [
+            "INSERT INTO t VALUES (1, 2, 3)",
+            "UPDATE t SET replace = 1",
+            "WITH x AS (SELECT 1) DELETE FROM t",
+            "DROP TABLE t",
+            "ATTACH 'other.db' AS other",
+            "PRAGMA query_only = OFF",
+            "BEGIN",
+            "VACUUM",
+        ] {
+            let err = ensure_read_only(&mut conn, sql).await.unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "Разрешены только запросы на чтение (SELECT)",
+                "{}",
+                sql
+            );
+        }
+        let err = ensure_read_only(&mut conn, "SELECT * FROM missing")
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Ошибка SQL: no such table: missing");
+    }
+}
@@ -0,0 +1,108 @@
+use crate::auth::handlers::Claims;
+use crate::datasets::handlers::error;
+use crate::datasets::models::{DatasetInfo, KIND_SQL};
+use crate::datasets::store;
+use crate::sql::engine::{self, QueryError, QueryOutput, DEFAULT_ROW_LIMIT, MAX_ROW_LIMIT};
+use axum::{extract::State, http::StatusCode, Extension, Json};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::{Pool, Sqlite};
+
+type ApiResult = (StatusCode, Json<serde_json::Value>);
+
+#[derive(Deserialize)]
+pub struct QueryInput {
+    pub sql: String,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct SaveQueryInput {
+    pub name: String,
+    pub sql: String,
+}
+
+/// Таблицы, доступные пользователю в запросах
+pub async fn list_tables(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(claims): Extension<Claims>,
+) -> ApiResult {
+    match engine::list_tables(&pool, &claims.sub).await {
+        Ok(tables) => (StatusCode::OK, Json(json!({ "tables": tables }))),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Ошибки запроса — 400, сбои чтения наборов — 500
+fn query_failed(e: anyhow::Error) -> ApiResult {
+    let status = if e.is::<QueryError>() {
+        StatusCode::BAD_REQUEST
+    } else {
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+    error(status, e.to_string())
+}
+
+pub async fn run_query(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(claims): Extension<Claims>,
+    Json(input): Json<QueryInput>,
+) -> Result<Json<QueryOutput>, ApiResult> {
+    let limit = input.limit.unwrap_or(DEFAULT_ROW_LIMIT);
+    let output = engine::run_query(&pool, &claims.sub, &input.sql, limit)
+        .await
+        .map_err(query_failed)?;
+
+    println!(
+        "🧮 sql query by {:<10} | Rows {:>6}{} | {:>6} ms",
+        claims.sub,
+        output.table.rows.len(),
+        if output.truncated { "+" } else { " " },
+        output.elapsed_ms
+    );
+    Ok(Json(output))
+}
+
+/// Сохранение запроса виртуальным набором; запрос выполняется сразу,
+/// чтобы проверить его и определить схему
+pub async fn save_query(
+    State(pool): State<Pool<Sqlite>>,
+    Extension(claims): Extension<Claims>,
+    Json(input): Json<SaveQueryInput>,
+) -> ApiResult {
+    let Some(name) = store::normalize_name(&input.name) else {
+        return error(StatusCode::BAD_REQUEST, "Некорректное имя набора данных");
+    };
+    let output = match engine::run_query(&pool, &claims.sub, &input.sql, MAX_ROW_LIMIT).await {
+        Ok(output) if output.truncated => {
+            return error(
+                StatusCode::BAD_REQUEST,
+                format!("Запрос вернул больше {} строк", MAX_ROW_LIMIT),
+            )
+        }
+        Ok(output) => output,
+        Err(e) => return query_failed(e),
+    };
+
+    let definition = json!({ "sql": input.sql });
+    let created = store::create_virtual_dataset(
+        &pool,
+        &name,
+        &claims.sub,
+        KIND_SQL,
+        &definition,
+        &output.table,
+    )
+    .await;
+    match created {
+        Ok(record) => {
+            println!(
+                "💾 sql dataset saved: {:<25} | Rows {:>6}",
+                record.name, record.row_count
+            );
+            (StatusCode::OK, Json(json!(DatasetInfo::from(record))))
+        }
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
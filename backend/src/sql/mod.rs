@@ -0,0 +1,27 @@
+use axum::routing::{get, post};
+use axum::Router;
+use sqlx::{Pool, Sqlite};
+
+pub mod engine;
+pub mod handlers;
+
+use crate::protect;
+
+/// SQL-запросы только на чтение над наборами пользователя;
+/// сохранённый запрос становится виртуальным набором данных
+pub fn setup_router(pool: Pool<Sqlite>) -> Router {
+    Router::new()
+        .route(
+            "/api/sql/tables",
+            get(handlers::list_tables).route_layer(protect!(pool, "User")),
+        )
+        .route(
+            "/api/sql/query",
+            post(handlers::run_query).route_layer(protect!(pool, "User")),
+        )
+        .route(
+            "/api/sql/datasets",
+            post(handlers::save_query).route_layer(protect!(pool, "User")),
+        )
+        .with_state(pool)
+}
@@ -0,0 +1,87 @@
+use sqlx::sqlite::SqlitePoolOptions;
+use sqlx::{Pool, Sqlite};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::{env, fs};
+
+static DATA_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// 🗂️ Универсальный путь для данных
+/// 1. Если есть /app/data → используем его (Docker)
+/// 2. Иначе — backend/data (локальная разработка)
+pub fn data_dir() -> &'static Path {
+    DATA_DIR.get_or_init(|| {
+        let cwd = env::current_dir().expect("❌ Cannot get current dir");
+        println!("🔍 Current working dir: {}", cwd.display());
+
+        let dir = if PathBuf::from("/app/data").exists() {
+            PathBuf::from("/app/data")
+        } else if cwd.join("data").exists() {
+            cwd.join("data")
+        } else {
+            cwd.join("backend/data")
+        };
+
+        // ✅ Создаём директорию, если её нет
+        if let Err(e) = fs::create_dir_all(&dir) {
+            eprintln!("⚠️ Failed to ensure data dir: {} — {:?}", dir.display(), e);
+        }
+        dir
+    })
+}
+
+/// Подкаталог каталога данных, создаётся при первом обращении
+pub fn data_subdir(name: &str) -> std::io::Result<PathBuf> {
+    let dir = data_dir().join(name);
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// Подключение к общей SQLite-базе в каталоге данных
+pub async fn connect() -> Pool<Sqlite> {
+    let db_path = data_dir().join("users.db");
+
+    // ✅ Создаём файл, если он не существует
+    if !db_path.exists() {
+        match fs::File::create(&db_path) {
+            Ok(_) => println!("🆕 Created empty database file at {}", db_path.display()),
+            Err(e) => eprintln!("⚠️ Failed to create database file: {}", e),
+        }
+    }
+
+    // 🧩 Формируем URL для SQLite
+    let db_url = format!("sqlite://{}", db_path.display());
+    println!("📦 Using database at: {}", db_url);
+
+    // 🗄️ Подключаемся
+    SqlitePoolOptions::new()
+        .max_connections(5)
+        .connect(&db_url)
+        .await
+        .unwrap_or_else(|e| panic!("❌ Cannot connect to users.db: {e:?}"))
+}
+
+/// Добавление колонки в существующую таблицу: `CREATE TABLE IF NOT EXISTS`
+/// не меняет таблицы, созданные прежними версиями
+pub async fn ensure_column(
+    pool: &Pool<Sqlite>,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> sqlx::Result<()> {
+    let exists: Option<(String,)> =
+        sqlx::query_as("SELECT name FROM pragma_table_info(?) WHERE name = ?")
+            .bind(table)
+            .bind(column)
+            .fetch_optional(pool)
+            .await?;
+    if exists.is_none() {
+        sqlx::query(&format!(
+            "ALTER TABLE {} ADD COLUMN {} {}",
+            table, column, definition
+        ))
+        .execute(pool)
+        .await?;
+    }
+    Ok(())
+}
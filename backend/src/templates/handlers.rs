@@ -0,0 +1,81 @@
+use crate::exporter::template::validate_xlsx_template;
+use crate::templates::store;
+use axum::{
+    extract::{Multipart, Path},
+    http::StatusCode,
+    Json,
+};
+use serde_json::json;
+
+fn error(status: StatusCode, message: impl Into<String>) -> (StatusCode, Json<serde_json::Value>) {
+    (status, Json(json!({ "error": message.into() })))
+}
+
+pub async fn list_templates() -> (StatusCode, Json<serde_json::Value>) {
+    match store::list_templates() {
+        Ok(templates) => (StatusCode::OK, Json(json!({ "templates": templates }))),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+/// Загрузка шаблона: поле `file` (XLSX) и необязательное поле `name`
+pub async fn upload_template(mut multipart: Multipart) -> (StatusCode, Json<serde_json::Value>) {
+    let mut name: Option<String> = None;
+    let mut file: Option<(String, Vec<u8>)> = None;
+
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => return error(StatusCode::BAD_REQUEST, e.to_string()),
+        };
+        match field.name().unwrap_or("") {
+            "name" => match field.text().await {
+                Ok(text) => name = Some(text),
+                Err(e) => return error(StatusCode::BAD_REQUEST, e.to_string()),
+            },
+            "file" => {
+                let filename = field.file_name().unwrap_or("template.xlsx").to_string();
+                match field.bytes().await {
+                    Ok(bytes) => file = Some((filename, bytes.to_vec())),
+                    Err(e) => return error(StatusCode::BAD_REQUEST, e.to_string()),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let Some((filename, bytes)) = file else {
+        return error(StatusCode::BAD_REQUEST, "Файл шаблона не передан");
+    };
+    let raw_name = name
+        .filter(|n| !n.trim().is_empty())
+        .unwrap_or_else(|| filename.clone());
+    let Some(name) = store::normalize_name(&raw_name) else {
+        return error(StatusCode::BAD_REQUEST, "Некорректное имя шаблона");
+    };
+
+    if let Err(e) = validate_xlsx_template(&bytes) {
+        return error(StatusCode::BAD_REQUEST, e.to_string());
+    }
+    if let Err(e) = store::save_template(&name, &bytes) {
+        return error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+    }
+
+    println!("📄 Template saved: {} ({} bytes)", name, bytes.len());
+    (
+        StatusCode::OK,
+        Json(json!({ "name": name, "size": bytes.len() })),
+    )
+}
+
+pub async fn delete_template(Path(name): Path<String>) -> (StatusCode, Json<serde_json::Value>) {
+    let Some(name) = store::normalize_name(&name) else {
+        return error(StatusCode::BAD_REQUEST, "Некорректное имя шаблона");
+    };
+    match store::remove_template(&name) {
+        Ok(true) => (StatusCode::OK, Json(json!({ "deleted": name }))),
+        Ok(false) => error(StatusCode::NOT_FOUND, "Шаблон не найден"),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
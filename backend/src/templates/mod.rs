@@ -0,0 +1,26 @@
+use axum::routing::{delete, get};
+use axum::Router;
+use sqlx::{Pool, Sqlite};
+
+pub mod handlers;
+mod store;
+
+pub use store::load_template;
+
+use crate::protect;
+
+/// Маршруты управления XLSX-шаблонами выгрузки
+pub fn setup_router(pool: Pool<Sqlite>) -> Router {
+    Router::new()
+        .route(
+            "/api/export-templates",
+            get(handlers::list_templates)
+                .post(handlers::upload_template)
+                .route_layer(protect!(pool, "Admin")),
+        )
+        .route(
+            "/api/export-templates/:name",
+            delete(handlers::delete_template).route_layer(protect!(pool, "Admin")),
+        )
+        .with_state(pool)
+}
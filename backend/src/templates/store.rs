@@ -0,0 +1,99 @@
+use crate::storage::data_subdir;
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+
+const TEMPLATES_DIR: &str = "templates";
+const EXTENSION: &str = "xlsx";
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateInfo {
+    pub name: String,
+    pub size: u64,
+    pub modified: i64,
+}
+
+/// Имя шаблона → безопасное имя файла: буквы, цифры, `-` и `_`
+pub fn normalize_name(name: &str) -> Option<String> {
+    let trimmed = name.trim();
+    let stem = trimmed
+        .strip_suffix(".xlsx")
+        .or_else(|| trimmed.strip_suffix(".XLSX"))
+        .unwrap_or(trimmed);
+    let cleaned: String = stem
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let cleaned = cleaned.trim_matches('_').to_string();
+    if cleaned.is_empty() || cleaned.chars().count() > 100 {
+        None
+    } else {
+        Some(cleaned)
+    }
+}
+
+fn template_path(name: &str) -> io::Result<PathBuf> {
+    Ok(data_subdir(TEMPLATES_DIR)?.join(format!("{}.{}", name, EXTENSION)))
+}
+
+pub fn save_template(name: &str, bytes: &[u8]) -> io::Result<()> {
+    fs::write(template_path(name)?, bytes)
+}
+
+/// Содержимое шаблона или `None`, если такого нет
+pub fn load_template(name: &str) -> io::Result<Option<Vec<u8>>> {
+    let Some(name) = normalize_name(name) else {
+        return Ok(None);
+    };
+    match fs::read(template_path(&name)?) {
+        Ok(bytes) => Ok(Some(bytes)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// `false`, если шаблона не было
+pub fn remove_template(name: &str) -> io::Result<bool> {
+    match fs::remove_file(template_path(name)?) {
+        Ok(()) => Ok(true),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+pub fn list_templates() -> io::Result<Vec<TemplateInfo>> {
+    let mut templates = Vec::new();
+    for entry in fs::read_dir(data_subdir(TEMPLATES_DIR)?)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some(EXTENSION) {
+            continue;
+        }
+        let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+        let meta = entry.metadata()?;
+        let modified = meta
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|dur| dur.as_secs() as i64)
+            .unwrap_or(0);
+        templates.push(TemplateInfo {
+            name: name.to_string(),
+            size: meta.len(),
+            modified,
+        });
+    }
+    templates.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(templates)
+}
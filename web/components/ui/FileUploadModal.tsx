@@ -347,7 +347,7 @@ export default function FileUploadModal({
       >
         <input
           type="file"
-          accept=".csv,.xlsx,.ods"
+          accept=".csv,.xlsx,.ods,.json"
           id="fileInput"
           className="hidden"
           onChange={(e) => {
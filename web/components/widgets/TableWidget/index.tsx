@@ -142,7 +142,7 @@ export default function TableWidget({
     const sanitized = base
       .split("")
       .map((char) =>
-        /[0-9A-Za-z_\-]/.test(char) ? char : "_",
+        /[\p{L}\p{N}_\-]/u.test(char) ? char : "_",
       )
       .join("")
       .replace(/_+/g, "_")
@@ -1,3 +1,6 @@
+import type { PivotApplyResult } from "@/lib/pivot";
+import type { PivotConfig, PivotFilter, PivotSort, ReportConfig } from "@/lib/types";
+
 export async function uploadFile(
   file: File
 ): Promise<{ columns: string[]; rows: string[][] }> {
@@ -14,16 +17,79 @@ export async function uploadFile(
   return res.json();
 }
 
+export type ExportFormat =
+  | "xlsx"
+  | "ods"
+  | "csv"
+  | "tsv"
+  | "json"
+  | "ndjson"
+  | "pdf"
+  | "html"
+  | "md";
+
+export type HighlightStyle = {
+  background?: string;
+  color?: string;
+  bold?: boolean;
+};
+
+export type ConditionalFormatRule = { column: string } & (
+  | {
+      type: "cell_value";
+      operator:
+        | "equal"
+        | "not_equal"
+        | "greater_than"
+        | "greater_than_or_equal"
+        | "less_than"
+        | "less_than_or_equal"
+        | "between"
+        | "not_between";
+      value: number | string;
+      value2?: number | string;
+      style?: HighlightStyle;
+    }
+  | { type: "color_scale"; minColor?: string; midColor?: string; maxColor?: string }
+  | { type: "data_bar"; color?: string }
+  | {
+      type: "top_n";
+      count: number;
+      bottom?: boolean;
+      percent?: boolean;
+      style?: HighlightStyle;
+    }
+);
+
+export type FormulaColumn = {
+  name: string;
+  /** Колонки указываются в квадратных скобках: "[Цена] * [Количество]" */
+  formula: string;
+};
+
+export type TotalFunction = "sum" | "average" | "count";
+
+export type SpreadsheetOptions = {
+  conditionalFormats?: ConditionalFormatRule[];
+  formulaColumns?: FormulaColumn[];
+  totals?: {
+    label?: string;
+    columns: Record<string, TotalFunction>;
+  };
+};
+
 export async function exportTable({
   columns,
   rows,
   format,
   filename,
+  spreadsheet,
 }: {
   columns: string[];
   rows: string[][];
-  format: "xlsx" | "ods";
+  format: ExportFormat;
   filename?: string;
+  spreadsheet?: SpreadsheetOptions;
 }): Promise<Blob> {
   const res = await fetch("/api/export-table", {
     method: "POST",
@@ -35,6 +101,7 @@ export async function exportTable({
       rows,
       format,
       filename,
+      spreadsheet,
     }),
   });
 
@@ -44,3 +111,897 @@ export async function exportTable({
 
   return await res.blob();
 }
+
+export type BundleDataset = {
+  name: string;
+  columns: string[];
+  rows: string[][];
+};
+
+export type BundleTable = {
+  id?: string;
+  title?: string;
+  columns: string[];
+  rows: string[][];
+  spreadsheet?: SpreadsheetOptions;
+};
+
+export async function exportDashboardBundle({
+  dashboard,
+  datasets,
+  tables,
+}: {
+  dashboard: Record<string, unknown>;
+  datasets: BundleDataset[];
+  tables: BundleTable[];
+}): Promise<Blob> {
+  const res = await fetch("/api/export-dashboard", {
+    method: "POST",
+    headers: {
+      "Content-Type": "application/json",
+    },
+    body: JSON.stringify({ dashboard, datasets, tables }),
+  });
+
+  if (!res.ok) {
+    throw new Error("Failed to generate dashboard bundle");
+  }
+
+  return await res.blob();
+}
+
+export type DatasetColumnType = "string" | "number" | "date" | "boolean";
+
+export type DatasetColumn = {
+  name: string;
+  type: DatasetColumnType;
+  nullable: boolean;
+};
+
+export type DatasetInfo = {
+  id: string;
+  name: string;
+  owner: string;
+  sourceFilename?: string | null;
+  schema: DatasetColumn[];
+  rowCount: number;
+  sizeBytes: number;
+  uploadedAt: string;
+  updatedAt: string;
+  kind: "file" | "sql" | "derived";
+  definition?: ({ sql?: string } & Partial<DerivedDefinition>) | null;
+  computed?: ComputedColumn[];
+  cleaning?: CleaningStep[];
+  version: number;
+  append?: AppendConfig;
+  refreshSource?: RefreshSource;
+  lastRefresh?: RefreshStatus;
+};
+
+export type CleaningStep =
+  | { op: "trim"; columns?: string[] }
+  | { op: "dropEmptyRows" }
+  | { op: "dropEmptyColumns" }
+  | { op: "deduplicate"; columns?: string[] }
+  | { op: "rename"; from: string; to: string }
+  | { op: "removeColumns"; columns: string[] }
+  | { op: "reorder"; columns: string[] }
+  | {
+      op: "split";
+      column: string;
+      separator: string;
+      into: string[];
+      keepOriginal?: boolean;
+    }
+  | {
+      op: "merge";
+      columns: string[];
+      separator?: string;
+      into: string;
+      keepOriginal?: boolean;
+    }
+  | { op: "fillDown"; columns?: string[] }
+  | {
+      op: "replace";
+      columns?: string[];
+      from: string;
+      to?: string;
+      wholeCell?: boolean;
+    }
+  | { op: "changeType"; column: string; type: DatasetColumn["type"] };
+
+export type CleaningReport = {
+  rowsBefore: number;
+  rowsAfter: number;
+  columnsBefore: number;
+  columnsAfter: number;
+  steps: { op: CleaningStep["op"]; changed: number; skipped: boolean }[];
+  warnings: string[];
+};
+
+export type ComputedColumn = {
+  name: string;
+  expression: string;
+  type?: DatasetColumn["type"];
+};
+
+export async function listDatasets(): Promise<DatasetInfo[]> {
+  const res = await fetch("/api/datasets", { credentials: "include" });
+  if (!res.ok) throw new Error("Failed to load datasets");
+  const data = await res.json();
+  return data.datasets;
+}
+
+export async function uploadDataset(
+  file: File,
+  name?: string,
+  cleaning?: CleaningStep[]
+): Promise<DatasetInfo & { cleaningReport?: CleaningReport }> {
+  const formData = new FormData();
+  formData.append("file", file);
+  if (name) formData.append("name", name);
+  if (cleaning?.length) formData.append("cleaning", JSON.stringify(cleaning));
+
+  const res = await fetch("/api/datasets", {
+    method: "POST",
+    body: formData,
+    credentials: "include",
+  });
+
+  if (!res.ok) throw new Error("Failed to upload dataset");
+
+  return res.json();
+}
+
+export async function getDataset(id: string): Promise<DatasetInfo> {
+  const res = await fetch(`/api/datasets/${encodeURIComponent(id)}`, {
+    credentials: "include",
+  });
+  if (!res.ok) throw new Error("Failed to load dataset");
+  return res.json();
+}
+
+export async function fetchDatasetData(
+  id: string
+): Promise<{ columns: string[]; rows: string[][] }> {
+  const res = await fetch(`/api/datasets/${encodeURIComponent(id)}/data`, {
+    credentials: "include",
+  });
+  if (!res.ok) throw new Error("Failed to load dataset rows");
+  return res.json();
+}
+
+export async function renameDataset(id: string, name: string): Promise<DatasetInfo> {
+  const res = await fetch(`/api/datasets/${encodeURIComponent(id)}/rename`, {
+    method: "POST",
+    headers: {
+      "Content-Type": "application/json",
+    },
+    body: JSON.stringify({ name }),
+    credentials: "include",
+  });
+  if (!res.ok) throw new Error("Failed to rename dataset");
+  return res.json();
+}
+
+export async function deleteDataset(id: string): Promise<void> {
+  const res = await fetch(`/api/datasets/${encodeURIComponent(id)}`, {
+    method: "DELETE",
+    credentials: "include",
+  });
+  if (!res.ok) {
+    const data = await res.json().catch(() => null);
+    throw new Error(data?.error ?? "Failed to delete dataset");
+  }
+}
+
+export async function queryPivot(
+  datasetId: string,
+  config: PivotConfig
+): Promise<PivotApplyResult> {
+  const res = await fetch(`/api/datasets/${encodeURIComponent(datasetId)}/pivot`, {
+    method: "POST",
+    headers: {
+      "Content-Type": "application/json",
+    },
+    body: JSON.stringify(config),
+    credentials: "include",
+  });
+  if (!res.ok) throw new Error("Failed to run pivot query");
+  return res.json();
+}
+
+export type ReportQueryResult = {
+  values: Record<string, string>;
+  rendered: string;
+  title?: string;
+  warnings?: string[];
+};
+
+export async function queryReport(
+  datasetId: string,
+  config: ReportConfig,
+  options: { locale?: string; currency?: string } = {}
+): Promise<ReportQueryResult> {
+  const res = await fetch(`/api/datasets/${encodeURIComponent(datasetId)}/report`, {
+    method: "POST",
+    headers: {
+      "Content-Type": "application/json",
+    },
+    body: JSON.stringify({ ...config, ...options }),
+    credentials: "include",
+  });
+  if (!res.ok) throw new Error("Failed to evaluate report");
+  return res.json();
+}
+
+export type RowsQuery = {
+  offset?: number;
+  limit?: number;
+  cursor?: string | null;
+  sort?: PivotSort[];
+  filters?: PivotFilter[];
+  columns?: string[];
+};
+
+export type RowsPage = {
+  columns: string[];
+  rows: string[][];
+  total: number;
+  offset: number;
+  nextCursor: string | null;
+};
+
+export async function fetchDatasetRows(datasetId: string, query: RowsQuery): Promise<RowsPage> {
+  const res = await fetch(`/api/datasets/${encodeURIComponent(datasetId)}/rows`, {
+    method: "POST",
+    headers: {
+      "Content-Type": "application/json",
+    },
+    body: JSON.stringify(query),
+    credentials: "include",
+  });
+  if (!res.ok) throw new Error("Failed to load dataset rows");
+  return res.json();
+}
+
+export type ChartQuery = {
+  type?: "bar" | "line" | "pie";
+  xAxis: string;
+  yAxis: { field: string; agg?: "sum" | "count" | "avg" | "min" | "max" }[];
+  filters?: { label: string; column: string; operator: string; value: string }[];
+  includeOthers?: boolean;
+  bucket?: "day" | "week" | "month" | "quarter" | "year";
+  fillGaps?: boolean;
+  topN?: number;
+  otherLabel?: string;
+};
+
+export type ChartQueryResult = {
+  xKey: "category" | "name";
+  series: { key: string; field: string; agg: string }[];
+  data: Record<string, string | number | null>[];
+  skipped: number;
+};
+
+export async function queryChart(datasetId: string, query: ChartQuery): Promise<ChartQueryResult> {
+  const res = await fetch(`/api/datasets/${encodeURIComponent(datasetId)}/chart`, {
+    method: "POST",
+    headers: {
+      "Content-Type": "application/json",
+    },
+    body: JSON.stringify(query),
+    credentials: "include",
+  });
+  if (!res.ok) throw new Error("Failed to load chart data");
+  return res.json();
+}
+
+export type ColumnValueType = "string" | "number" | "date" | "boolean";
+
+export type ColumnStats = {
+  name: string;
+  type: ColumnValueType;
+  detectedType: ColumnValueType | null;
+  filled: number;
+  nulls: number;
+  distinct: number;
+  valueTypes: Record<ColumnValueType, number>;
+  numbers?: {
+    count: number;
+    min: number;
+    max: number;
+    mean: number;
+    median: number;
+    quantiles: { p5: number; p25: number; p75: number; p95: number };
+  };
+  dates?: { count: number; min: string; max: string; days: number };
+  lengths?: {
+    min: number;
+    max: number;
+    mean: number;
+    buckets: { from: number; to: number; count: number }[];
+  };
+  top: { value: string; count: number }[];
+};
+
+export type DataQualityWarning = {
+  column: string;
+  kind: "mixedTypes" | "outliers" | "whitespace" | "dateNumberCoercion";
+  count: number;
+  message: string;
+  examples: string[];
+};
+
+export type DatasetStats = {
+  rowCount: number;
+  columns: ColumnStats[];
+  warnings: DataQualityWarning[];
+};
+
+export async function getDatasetStats(datasetId: string, top?: number): Promise<DatasetStats> {
+  const query = top === undefined ? "" : `?top=${top}`;
+  const res = await fetch(`/api/datasets/${encodeURIComponent(datasetId)}/stats${query}`, {
+    credentials: "include",
+  });
+  if (!res.ok) {
+    const data = await res.json().catch(() => null);
+    throw new Error(data?.error ?? "Failed to load dataset statistics");
+  }
+  return res.json();
+}
+
+export type SqlTable = {
+  table: string;
+  datasetId: string;
+  datasetName: string;
+  columns: DatasetColumn[];
+};
+
+export type SqlQueryResult = {
+  columns: string[];
+  rows: string[][];
+  truncated: boolean;
+  elapsedMs: number;
+};
+
+export async function listSqlTables(): Promise<SqlTable[]> {
+  const res = await fetch("/api/sql/tables", { credentials: "include" });
+  if (!res.ok) throw new Error("Failed to load SQL tables");
+  const data = await res.json();
+  return data.tables ?? [];
+}
+
+export async function runSqlQuery(sql: string, limit?: number): Promise<SqlQueryResult> {
+  const res = await fetch("/api/sql/query", {
+    method: "POST",
+    headers: {
+      "Content-Type": "application/json",
+    },
+    body: JSON.stringify({ sql, limit }),
+    credentials: "include",
+  });
+  if (!res.ok) {
+    const data = await res.json().catch(() => null);
+    throw new Error(data?.error ?? "Failed to run SQL query");
+  }
+  return res.json();
+}
+
+export async function saveSqlDataset(name: string, sql: string): Promise<DatasetInfo> {
+  const res = await fetch("/api/sql/datasets", {
+    method: "POST",
+    headers: {
+      "Content-Type": "application/json",
+    },
+    body: JSON.stringify({ name, sql }),
+    credentials: "include",
+  });
+  if (!res.ok) {
+    const data = await res.json().catch(() => null);
+    throw new Error(data?.error ?? "Failed to save SQL dataset");
+  }
+  return res.json();
+}
+
+export async function updateComputedColumns(
+  datasetId: string,
+  columns: Pick<ComputedColumn, "name" | "expression">[]
+): Promise<DatasetInfo> {
+  const res = await fetch(`/api/datasets/${encodeURIComponent(datasetId)}/computed`, {
+    method: "PUT",
+    headers: {
+      "Content-Type": "application/json",
+    },
+    body: JSON.stringify({ columns }),
+    credentials: "include",
+  });
+  if (!res.ok) {
+    const data = await res.json().catch(() => null);
+    throw new Error(data?.error ?? "Failed to save computed columns");
+  }
+  return res.json();
+}
+
+export async function validateExpression(
+  datasetId: string,
+  expression: string
+): Promise<{ type: DatasetColumn["type"]; preview: string[] }> {
+  const res = await fetch(`/api/datasets/${encodeURIComponent(datasetId)}/computed/validate`, {
+    method: "POST",
+    headers: {
+      "Content-Type": "application/json",
+    },
+    body: JSON.stringify({ expression }),
+    credentials: "include",
+  });
+  if (!res.ok) {
+    const data = await res.json().catch(() => null);
+    throw new Error(data?.error ?? "Invalid expression");
+  }
+  return res.json();
+}
+
+export type DerivedSpec =
+  | {
+      type: "join";
+      left: string;
+      right: string;
+      on: { left: string; right: string }[];
+      how?: "inner" | "left" | "full";
+    }
+  | {
+      type: "union";
+      datasets: string[];
+      sourceColumn?: string | null;
+    };
+
+export type DerivedDefinition = DerivedSpec & { materialized?: boolean };
+
+export type DerivedReport = {
+  rows: number;
+  matchedLeft?: number;
+  unmatchedLeft?: number;
+  unmatchedRight?: number;
+  duplicateLeftKeys?: number;
+  duplicateRightKeys?: number;
+  emptyKeys?: number;
+  warnings: string[];
+};
+
+export async function previewDerivedDataset(
+  spec: DerivedSpec
+): Promise<{ columns: string[]; rows: string[][]; report: DerivedReport }> {
+  const res = await fetch("/api/datasets/derived/preview", {
+    method: "POST",
+    headers: {
+      "Content-Type": "application/json",
+    },
+    body: JSON.stringify(spec),
+    credentials: "include",
+  });
+  if (!res.ok) {
+    const data = await res.json().catch(() => null);
+    throw new Error(data?.error ?? "Failed to preview derived dataset");
+  }
+  return res.json();
+}
+
+export async function createDerivedDataset(
+  name: string,
+  definition: DerivedDefinition
+): Promise<{ dataset: DatasetInfo; report: DerivedReport }> {
+  const res = await fetch("/api/datasets/derived", {
+    method: "POST",
+    headers: {
+      "Content-Type": "application/json",
+    },
+    body: JSON.stringify({ name, ...definition }),
+    credentials: "include",
+  });
+  if (!res.ok) {
+    const data = await res.json().catch(() => null);
+    throw new Error(data?.error ?? "Failed to create derived dataset");
+  }
+  return res.json();
+}
+
+export async function rebuildDerivedDataset(
+  datasetId: string
+): Promise<{ dataset: DatasetInfo; report: DerivedReport }> {
+  const res = await fetch(`/api/datasets/${encodeURIComponent(datasetId)}/rebuild`, {
+    method: "POST",
+    credentials: "include",
+  });
+  if (!res.ok) throw new Error("Failed to rebuild dataset");
+  return res.json();
+}
+
+export type SchemaDiff = {
+  added: DatasetColumn[];
+  removed: DatasetColumn[];
+  retyped: { name: string; from: DatasetColumn["type"]; to: DatasetColumn["type"] }[];
+  warnings: string[];
+};
+
+export type WidgetColumns = { id: string; title?: string; columns: string[] };
+
+export async function replaceDatasetFile(
+  datasetId: string,
+  file: File,
+  options: { widgets?: WidgetColumns[]; dryRun?: boolean } = {}
+): Promise<{
+  dataset?: DatasetInfo;
+  dryRun?: boolean;
+  cleaningReport: CleaningReport;
+  diff: SchemaDiff;
+}> {
+  const formData = new FormData();
+  formData.append("file", file);
+  if (options.widgets?.length) formData.append("widgets", JSON.stringify(options.widgets));
+  if (options.dryRun) formData.append("dryRun", "true");
+
+  const res = await fetch(`/api/datasets/${encodeURIComponent(datasetId)}/upload`, {
+    method: "POST",
+    body: formData,
+    credentials: "include",
+  });
+  if (!res.ok) {
+    const data = await res.json().catch(() => null);
+    throw new Error(data?.error ?? "Failed to replace dataset file");
+  }
+  return res.json();
+}
+
+export type AppendMode = "insert" | "upsert";
+
+export type AppendConfig = {
+  keys: string[];
+  mode: AppendMode;
+};
+
+export type AppendReport = {
+  inserted: number;
+  updated: number;
+  skipped: number;
+  rows: number;
+  warnings: string[];
+};
+
+export async function appendDataset(
+  datasetId: string,
+  file: File,
+  options: { keys?: string[]; mode?: AppendMode; dryRun?: boolean } = {}
+): Promise<{
+  dataset?: DatasetInfo;
+  dryRun?: boolean;
+  append: AppendReport;
+  cleaningReport: CleaningReport;
+}> {
+  const formData = new FormData();
+  formData.append("file", file);
+  if (options.keys) formData.append("keys", JSON.stringify(options.keys));
+  if (options.mode) formData.append("mode", options.mode);
+  if (options.dryRun) formData.append("dryRun", "true");
+
+  const res = await fetch(`/api/datasets/${encodeURIComponent(datasetId)}/append`, {
+    method: "POST",
+    body: formData,
+    credentials: "include",
+  });
+  if (!res.ok) {
+    const data = await res.json().catch(() => null);
+    throw new Error(data?.error ?? "Failed to append to dataset");
+  }
+  return res.json();
+}
+
+export type RefreshSource =
+  | { type: "path"; path: string }
+  | { type: "url"; url: string; format?: "csv" | "xlsx" | "ods" | "json" };
+
+export type RefreshStatus = {
+  at: string;
+  ok: boolean;
+  error?: string;
+  version?: number;
+  rows?: number;
+  durationMs: number;
+};
+
+export async function importDataset(
+  source: RefreshSource,
+  name?: string,
+  cleaning?: CleaningStep[]
+): Promise<{ dataset: DatasetInfo; cleaningReport: CleaningReport }> {
+  const res = await fetch("/api/datasets/import", {
+    method: "POST",
+    headers: { "Content-Type": "application/json" },
+    body: JSON.stringify({ name, source, cleaning }),
+    credentials: "include",
+  });
+  if (!res.ok) {
+    const data = await res.json().catch(() => null);
+    throw new Error(data?.error ?? "Failed to import dataset");
+  }
+  return res.json();
+}
+
+export async function setDatasetSource(
+  datasetId: string,
+  source: RefreshSource | null
+): Promise<DatasetInfo> {
+  const res = await fetch(`/api/datasets/${encodeURIComponent(datasetId)}/source`, {
+    method: "PUT",
+    headers: { "Content-Type": "application/json" },
+    body: JSON.stringify({ source }),
+    credentials: "include",
+  });
+  if (!res.ok) {
+    const data = await res.json().catch(() => null);
+    throw new Error(data?.error ?? "Failed to update dataset source");
+  }
+  return res.json();
+}
+
+export async function refreshDataset(
+  datasetId: string
+): Promise<{ dataset: DatasetInfo; cleaningReport: CleaningReport; diff: SchemaDiff }> {
+  const res = await fetch(`/api/datasets/${encodeURIComponent(datasetId)}/refresh`, {
+    method: "POST",
+    credentials: "include",
+  });
+  if (!res.ok) {
+    const data = await res.json().catch(() => null);
+    throw new Error(data?.error ?? "Failed to refresh dataset");
+  }
+  return res.json();
+}
+
+export async function updateCleaningRecipe(
+  datasetId: string,
+  steps: CleaningStep[]
+): Promise<{ dataset: DatasetInfo; cleaningReport: CleaningReport }> {
+  const res = await fetch(`/api/datasets/${encodeURIComponent(datasetId)}/cleaning`, {
+    method: "PUT",
+    headers: {
+      "Content-Type": "application/json",
+    },
+    body: JSON.stringify({ steps }),
+    credentials: "include",
+  });
+  if (!res.ok) {
+    const data = await res.json().catch(() => null);
+    throw new Error(data?.error ?? "Failed to save cleaning recipe");
+  }
+  return res.json();
+}
+
+export async function previewCleaningRecipe(
+  datasetId: string,
+  steps: CleaningStep[]
+): Promise<{
+  columns: string[];
+  schema: DatasetColumn[];
+  rows: string[][];
+  report: CleaningReport;
+}> {
+  const res = await fetch(
+    `/api/datasets/${encodeURIComponent(datasetId)}/cleaning/preview`,
+    {
+      method: "POST",
+      headers: {
+        "Content-Type": "application/json",
+      },
+      body: JSON.stringify({ steps }),
+      credentials: "include",
+    }
+  );
+  if (!res.ok) {
+    const data = await res.json().catch(() => null);
+    throw new Error(data?.error ?? "Failed to preview cleaning recipe");
+  }
+  return res.json();
+}
+
+export type DatasetVersion = {
+  version: number;
+  createdAt: string;
+  sourceFilename?: string | null;
+  schema: DatasetColumn[];
+  rowCount: number;
+  sizeBytes: number;
+  restoredFrom?: number;
+  current: boolean;
+};
+
+export async function listDatasetVersions(
+  datasetId: string
+): Promise<{ current: number; versions: DatasetVersion[] }> {
+  const res = await fetch(`/api/datasets/${encodeURIComponent(datasetId)}/versions`, {
+    credentials: "include",
+  });
+  if (!res.ok) throw new Error("Failed to load dataset versions");
+  return res.json();
+}
+
+export async function getVersionDiff(datasetId: string, version: number): Promise<SchemaDiff> {
+  const res = await fetch(
+    `/api/datasets/${encodeURIComponent(datasetId)}/versions/${version}/diff`,
+    { credentials: "include" }
+  );
+  if (!res.ok) throw new Error("Failed to load version diff");
+  const data = await res.json();
+  return data.diff;
+}
+
+export async function rollbackDatasetVersion(
+  datasetId: string,
+  version: number
+): Promise<{ dataset: DatasetInfo; diff: SchemaDiff }> {
+  const res = await fetch(
+    `/api/datasets/${encodeURIComponent(datasetId)}/versions/${version}/rollback`,
+    {
+      method: "POST",
+      credentials: "include",
+    }
+  );
+  if (!res.ok) {
+    const data = await res.json().catch(() => null);
+    throw new Error(data?.error ?? "Failed to roll back dataset");
+  }
+  return res.json();
+}
+
+export type JobTask =
+  | { kind: "datasetRefresh"; datasetId: string }
+  | {
+      kind: "report";
+      datasetId: string;
+      config: ReportConfig;
+      locale?: string;
+      currency?: string;
+      format?: "pdf" | "html" | "md";
+      filename?: string;
+    }
+  | {
+      kind: "export";
+      datasetId: string;
+      format: string;
+      filename?: string;
+    };
+
+export type JobInfo = {
+  id: string;
+  name: string;
+  schedule: string;
+  kind: JobTask["kind"];
+  task: JobTask;
+  paused: boolean;
+  running: boolean;
+  maxRetries: number;
+  createdBy: string;
+  createdAt: string;
+  updatedAt: string;
+  nextRunAt?: string | null;
+  retryAt?: string;
+  lastRunAt?: string | null;
+  lastStatus?: "running" | "ok" | "error" | null;
+  lastError?: string;
+};
+
+export type JobRun = {
+  id: number;
+  jobId: string;
+  trigger: "schedule" | "retry" | "manual";
+  attempt: number;
+  startedAt: string;
+  finishedAt?: string | null;
+  status: "running" | "ok" | "error";
+  error?: string;
+  output?: unknown;
+  outputFile?: string;
+};
+
+export type JobInput = {
+  name: string;
+  schedule: string;
+  task: JobTask;
+  maxRetries?: number;
+  paused?: boolean;
+};
+
+export async function listJobs(): Promise<JobInfo[]> {
+  const res = await fetch(`/api/jobs`, { credentials: "include" });
+  if (!res.ok) throw new Error("Failed to load jobs");
+  const data = await res.json();
+  return data.jobs;
+}
+
+export async function createJob(input: JobInput): Promise<JobInfo> {
+  const res = await fetch(`/api/jobs`, {
+    method: "POST",
+    headers: {
+      "Content-Type": "application/json",
+    },
+    body: JSON.stringify(input),
+    credentials: "include",
+  });
+  if (!res.ok) {
+    const data = await res.json().catch(() => null);
+    throw new Error(data?.error ?? "Failed to create job");
+  }
+  return res.json();
+}
+
+export async function getJob(jobId: string): Promise<{ job: JobInfo; runs: JobRun[] }> {
+  const res = await fetch(`/api/jobs/${encodeURIComponent(jobId)}`, {
+    credentials: "include",
+  });
+  if (!res.ok) throw new Error("Failed to load job");
+  return res.json();
+}
+
+async function jobAction(jobId: string, action: "run" | "pause" | "resume"): Promise<JobInfo> {
+  const res = await fetch(`/api/jobs/${encodeURIComponent(jobId)}/${action}`, {
+    method: "POST",
+    credentials: "include",
+  });
+  if (!res.ok) {
+    const data = await res.json().catch(() => null);
+    throw new Error(data?.error ?? `Failed to ${action} job`);
+  }
+  return res.json();
+}
+
+export const runJob = (jobId: string) => jobAction(jobId, "run");
+export const pauseJob = (jobId: string) => jobAction(jobId, "pause");
+export const resumeJob = (jobId: string) => jobAction(jobId, "resume");
+
+export async function deleteJob(jobId: string): Promise<void> {
+  const res = await fetch(`/api/jobs/${encodeURIComponent(jobId)}`, {
+    method: "DELETE",
+    credentials: "include",
+  });
+  if (!res.ok) {
+    const data = await res.json().catch(() => null);
+    throw new Error(data?.error ?? "Failed to delete job");
+  }
+}
+
+export function jobRunOutputUrl(jobId: string, runId: number): string {
+  return `/api/jobs/${encodeURIComponent(jobId)}/runs/${runId}/output`;
+}
+
+export type QueryCacheStats = {
+  enabled: boolean;
+  capacityBytes: number;
+  sizeBytes: number;
+  entries: number;
+  hits: number;
+  misses: number;
+  hitRate: number;
+  evictions: number;
+  invalidations: number;
+  oversized: number;
+  byKind: Record<"pivot" | "chart" | "report" | "stats", { hits: number; misses: number }>;
+};
+
+export async function getQueryCacheStats(): Promise<QueryCacheStats> {
+  const res = await fetch(`/api/cache/stats`, { credentials: "include" });
+  if (!res.ok) throw new Error("Failed to load cache stats");
+  return res.json();
+}
+
+export async function clearQueryCache(): Promise<QueryCacheStats> {
+  const res = await fetch(`/api/cache/clear`, {
+    method: "POST",
+    credentials: "include",
+  });
+  if (!res.ok) {
+    const data = await res.json().catch(() => null);
+    throw new Error(data?.error ?? "Failed to clear cache");
+  }
+  const data = await res.json();
+  return data.stats;
+}
@@ -45,6 +45,50 @@ const backendRewrites =
           source: "/api/export-table",
           destination: withInternal("/api/export-table"),
         },
+        {
+          source: "/api/export-table/stream",
+          destination: withInternal("/api/export-table/stream"),
+        },
+        {
+          source: "/api/export-report",
+          destination: withInternal("/api/export-report"),
+        },
+        {
+          source: "/api/export-dashboard",
+          destination: withInternal("/api/export-dashboard"),
+        },
+        {
+          source: "/api/export-templates",
+          destination: withInternal("/api/export-templates"),
+        },
+        {
+          source: "/api/export-templates/:path*",
+          destination: withInternal("/api/export-templates/:path*"),
+        },
+        {
+          source: "/api/datasets",
+          destination: withInternal("/api/datasets"),
+        },
+        {
+          source: "/api/datasets/:path*",
+          destination: withInternal("/api/datasets/:path*"),
+        },
+        {
+          source: "/api/sql/:path*",
+          destination: withInternal("/api/sql/:path*"),
+        },
+        {
+          source: "/api/jobs",
+          destination: withInternal("/api/jobs"),
+        },
+        {
+          source: "/api/jobs/:path*",
+          destination: withInternal("/api/jobs/:path*"),
+        },
+        {
+          source: "/api/cache/:path*",
+          destination: withInternal("/api/cache/:path*"),
+        },
         {
           source: "/api/logout",
           destination: withInternal("/api/logout"),